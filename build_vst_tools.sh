@@ -1,55 +1,31 @@
-
 mkdir ./ffx.tools.DawProject.xrnx/bin
 
-# build vst2info-tool
-cd vst2info-tool
+# the helpers are built from the cargo workspace in this folder, the
+# binaries end up in ./target
+for tool in vst2info-tool vst3info-tool plugininfo; do
 
 if [[ "$OSTYPE" == "darwin"* ]]; then
-cargo b -r  --target=x86_64-apple-darwin --target=aarch64-apple-darwin
-#lipo -create \
-#  -output target/vst2info-tool \
-#  target/x86_64-apple-darwin/release/vst2info-tool \
-#  target/aarch64-apple-darwin/release/vst2info-tool
-#cp ./target/vst2info-tool ../ffx.tools.DawProject.xrnx/bin/vst2info-tool-mac
-cp ./target/x86_64-apple-darwin/release/vst2info-tool ../ffx.tools.DawProject.xrnx/bin/vst2info-tool-mac-x64
-cp ./target/aarch64-apple-darwin/release/vst2info-tool ../ffx.tools.DawProject.xrnx/bin/vst2info-tool-mac-arm
+cargo b -r -p $tool --target=x86_64-apple-darwin --target=aarch64-apple-darwin
+cp ./target/x86_64-apple-darwin/release/$tool ./ffx.tools.DawProject.xrnx/bin/$tool-mac-x64
+cp ./target/aarch64-apple-darwin/release/$tool ./ffx.tools.DawProject.xrnx/bin/$tool-mac-arm
 
-cargo b -r  --target=x86_64-pc-windows-gnu
-cp ./target/x86_64-pc-windows-gnu/release/vst2info-tool.exe ../ffx.tools.DawProject.xrnx/bin/vst2info-tool-win.exe
+# the bundled SQLite of the library is C code, cross compiles it in the
+# Docker images of Cross.toml
+cross build --release -p $tool --target=x86_64-pc-windows-gnu
+cp ./target/x86_64-pc-windows-gnu/release/$tool.exe ./ffx.tools.DawProject.xrnx/bin/$tool-win.exe
 
-CARGO_TARGET_X86_64_UNKNOWN_LINUX_GNU_LINKER=x86_64-unknown-linux-gnu-gcc \
-cargo b -r --target=x86_64-unknown-linux-gnu
-cp ./target/x86_64-unknown-linux-gnu/release/vst2info-tool ../ffx.tools.DawProject.xrnx/bin/vst2info-tool-linux
+cross build --release -p $tool --target=x86_64-unknown-linux-gnu
+cp ./target/x86_64-unknown-linux-gnu/release/$tool ./ffx.tools.DawProject.xrnx/bin/$tool-linux
 fi
 
 if [[ "$OSTYPE" == "win32"* ]]; then
-cargo b -r
-cp ./target/release/vst2info-tool ../ffx.tools.DawProject.xrnx/bin/vst2info-tool-win.exe
+cargo b -r -p $tool
+cp ./target/release/$tool.exe ./ffx.tools.DawProject.xrnx/bin/$tool-win.exe
 fi
 
 if [[ "$OSTYPE" == "linux-gnu"* ]]; then
-cargo b -r
-cp ./target/release/vst2info-tool ../ffx.tools.DawProject.xrnx/bin/vst2info-tool-linux
-fi
-
-cd ..
-
-
-
-# build vst3info-tool
-cd vst3info-tool
-
-if [[ "$OSTYPE" == "darwin"* ]]; then
-cargo b -r  --target=x86_64-apple-darwin --target=aarch64-apple-darwin
-cp ./target/x86_64-apple-darwin/release/vst3info-tool ../ffx.tools.DawProject.xrnx/bin/vst3info-tool-mac-x64
-cp ./target/aarch64-apple-darwin/release/vst3info-tool ../ffx.tools.DawProject.xrnx/bin/vst3info-tool-mac-arm
-
-cross build --release --target=x86_64-pc-windows-gnu
-cp ./target/x86_64-pc-windows-gnu/release/vst3info-tool.exe ../ffx.tools.DawProject.xrnx/bin/vst3info-tool-win.exe
-
-cross build --release --target=x86_64-unknown-linux-gnu
-cp ./target/x86_64-unknown-linux-gnu/release/vst3info-tool ../ffx.tools.DawProject.xrnx/bin/vst3info-tool-linux
-
+cargo b -r -p $tool
+cp ./target/release/$tool ./ffx.tools.DawProject.xrnx/bin/$tool-linux
 fi
 
-cd ..
\ No newline at end of file
+done
@@ -0,0 +1,539 @@
+/*!
+ * ------------------------------------------------------------------------
+ * CLAP test plugin
+ * by Jurek Raben
+ *
+ * A CLAP plugin for the tests of the helper tools, it doesn't process any
+ * audio. The file contains a gain effect and a synth, both with the same
+ * three parameters. The effect has stereo audio ports and a latency, the
+ * synth a note input. The state is the magic "DPTS" followed by the
+ * parameter count and the IDs and values, little endian.
+ *
+ * Licensed under CC Attribution-NonCommercial-ShareAlike 4.0 International
+ * Info here: https://creativecommons.org/licenses/by-nc-sa/4.0/
+ * ------------------------------------------------------------------------
+ */
+
+#![allow(non_upper_case_globals)]
+
+use std::ffi::{CStr, c_char, c_void};
+use std::ptr;
+
+use clap_sys::entry::clap_plugin_entry;
+use clap_sys::ext::audio_ports::{
+    CLAP_AUDIO_PORT_IS_MAIN, CLAP_EXT_AUDIO_PORTS, CLAP_PORT_STEREO, clap_audio_port_info,
+    clap_plugin_audio_ports,
+};
+use clap_sys::ext::latency::{CLAP_EXT_LATENCY, clap_plugin_latency};
+use clap_sys::ext::note_ports::{
+    CLAP_EXT_NOTE_PORTS, CLAP_NOTE_DIALECT_CLAP, CLAP_NOTE_DIALECT_MIDI, clap_note_port_info,
+    clap_plugin_note_ports,
+};
+use clap_sys::ext::params::*;
+use clap_sys::ext::state::{CLAP_EXT_STATE, clap_plugin_state};
+use clap_sys::factory::plugin_factory::{CLAP_PLUGIN_FACTORY_ID, clap_plugin_factory};
+use clap_sys::host::clap_host;
+use clap_sys::id::{CLAP_INVALID_ID, clap_id};
+use clap_sys::plugin::{clap_plugin, clap_plugin_descriptor};
+use clap_sys::process::{CLAP_PROCESS_CONTINUE, clap_process, clap_process_status};
+use clap_sys::stream::{clap_istream, clap_ostream};
+use clap_sys::version::CLAP_VERSION;
+
+const STATE_MAGIC: &[u8; 4] = b"DPTS";
+const LATENCY: u32 = 64;
+const MODES: [&str; 4] = ["Clean", "Warm", "Hot", "Broken"];
+
+struct Param {
+    id: clap_id,
+    name: &'static str,
+    module: &'static str,
+    min: f64,
+    max: f64,
+    default: f64,
+    flags: u32,
+}
+
+const PARAMS: [Param; 3] = [
+    Param {
+        id: 10,
+        name: "Gain",
+        module: "Main",
+        min: -60.0,
+        max: 12.0,
+        default: 0.0,
+        flags: CLAP_PARAM_IS_AUTOMATABLE | CLAP_PARAM_IS_MODULATABLE,
+    },
+    Param {
+        id: 20,
+        name: "Mode",
+        module: "Main",
+        min: 0.0,
+        max: 3.0,
+        default: 1.0,
+        flags: CLAP_PARAM_IS_STEPPED | CLAP_PARAM_IS_ENUM | CLAP_PARAM_IS_AUTOMATABLE,
+    },
+    Param {
+        id: 30,
+        name: "Bypass",
+        module: "",
+        min: 0.0,
+        max: 1.0,
+        default: 0.0,
+        flags: CLAP_PARAM_IS_STEPPED | CLAP_PARAM_IS_BYPASS | CLAP_PARAM_IS_AUTOMATABLE,
+    },
+];
+
+// Pointer arrays are not Sync by themselves
+struct Features([*const c_char; 3]);
+unsafe impl Sync for Features {}
+
+static GAIN_FEATURES: Features =
+    Features([c"audio-effect".as_ptr(), c"stereo".as_ptr(), ptr::null()]);
+static SYNTH_FEATURES: Features =
+    Features([c"instrument".as_ptr(), c"synthesizer".as_ptr(), ptr::null()]);
+
+static GAIN_DESCRIPTOR: clap_plugin_descriptor = clap_plugin_descriptor {
+    clap_version: CLAP_VERSION,
+    id: c"com.dawproject.test-gain".as_ptr(),
+    name: c"Test Gain".as_ptr(),
+    vendor: c"DawProject".as_ptr(),
+    url: c"https://github.com/Jurek-Raben/DawProject-Tool".as_ptr(),
+    manual_url: ptr::null(),
+    support_url: ptr::null(),
+    version: c"1.2.3".as_ptr(),
+    description: c"Gain effect for tests".as_ptr(),
+    features: GAIN_FEATURES.0.as_ptr(),
+};
+
+static SYNTH_DESCRIPTOR: clap_plugin_descriptor = clap_plugin_descriptor {
+    clap_version: CLAP_VERSION,
+    id: c"com.dawproject.test-synth".as_ptr(),
+    name: c"Test Synth".as_ptr(),
+    vendor: c"DawProject".as_ptr(),
+    url: ptr::null(),
+    manual_url: ptr::null(),
+    support_url: ptr::null(),
+    version: c"0.9.0".as_ptr(),
+    description: c"Synth for tests".as_ptr(),
+    features: SYNTH_FEATURES.0.as_ptr(),
+};
+
+static DESCRIPTORS: [&clap_plugin_descriptor; 2] = [&GAIN_DESCRIPTOR, &SYNTH_DESCRIPTOR];
+
+#[repr(C)]
+struct Instance {
+    // First field, so the clap_plugin pointer is the instance pointer
+    plugin: clap_plugin,
+    is_synth: bool,
+    values: [f64; 3],
+}
+
+unsafe fn instance<'a>(plugin: *const clap_plugin) -> &'a mut Instance {
+    unsafe { &mut *(plugin as *mut Instance) }
+}
+
+fn param_index(param_id: clap_id) -> Option<usize> {
+    PARAMS.iter().position(|param| param.id == param_id)
+}
+
+// Entry and factory
+
+#[unsafe(no_mangle)]
+pub static clap_entry: clap_plugin_entry = clap_plugin_entry {
+    clap_version: CLAP_VERSION,
+    init: Some(entry_init),
+    deinit: Some(entry_deinit),
+    get_factory: Some(entry_get_factory),
+};
+
+static FACTORY: clap_plugin_factory = clap_plugin_factory {
+    get_plugin_count: Some(factory_get_plugin_count),
+    get_plugin_descriptor: Some(factory_get_plugin_descriptor),
+    create_plugin: Some(factory_create_plugin),
+};
+
+unsafe extern "C" fn entry_init(_plugin_path: *const c_char) -> bool {
+    true
+}
+
+unsafe extern "C" fn entry_deinit() {}
+
+unsafe extern "C" fn entry_get_factory(factory_id: *const c_char) -> *const c_void {
+    if unsafe { CStr::from_ptr(factory_id) } == CLAP_PLUGIN_FACTORY_ID {
+        &FACTORY as *const clap_plugin_factory as *const c_void
+    } else {
+        ptr::null()
+    }
+}
+
+unsafe extern "C" fn factory_get_plugin_count(_factory: *const clap_plugin_factory) -> u32 {
+    DESCRIPTORS.len() as u32
+}
+
+unsafe extern "C" fn factory_get_plugin_descriptor(
+    _factory: *const clap_plugin_factory,
+    index: u32,
+) -> *const clap_plugin_descriptor {
+    DESCRIPTORS
+        .get(index as usize)
+        .map_or(ptr::null(), |descriptor| *descriptor)
+}
+
+unsafe extern "C" fn factory_create_plugin(
+    _factory: *const clap_plugin_factory,
+    _host: *const clap_host,
+    plugin_id: *const c_char,
+) -> *const clap_plugin {
+    let plugin_id = unsafe { CStr::from_ptr(plugin_id) };
+    let Some(descriptor) = DESCRIPTORS
+        .iter()
+        .find(|descriptor| unsafe { CStr::from_ptr(descriptor.id) } == plugin_id)
+    else {
+        return ptr::null();
+    };
+
+    let instance = Box::new(Instance {
+        plugin: clap_plugin {
+            desc: *descriptor,
+            plugin_data: ptr::null_mut(),
+            init: Some(plugin_init),
+            destroy: Some(plugin_destroy),
+            activate: Some(plugin_activate),
+            deactivate: Some(plugin_deactivate),
+            start_processing: Some(plugin_start_processing),
+            stop_processing: Some(plugin_stop_processing),
+            reset: Some(plugin_reset),
+            process: Some(plugin_process),
+            get_extension: Some(plugin_get_extension),
+            on_main_thread: Some(plugin_on_main_thread),
+        },
+        is_synth: ptr::eq(*descriptor, &SYNTH_DESCRIPTOR),
+        values: PARAMS.map(|param| param.default),
+    });
+    Box::into_raw(instance) as *const clap_plugin
+}
+
+// Plugin
+
+unsafe extern "C" fn plugin_init(_plugin: *const clap_plugin) -> bool {
+    true
+}
+
+unsafe extern "C" fn plugin_destroy(plugin: *const clap_plugin) {
+    drop(unsafe { Box::from_raw(plugin as *mut Instance) });
+}
+
+unsafe extern "C" fn plugin_activate(
+    _plugin: *const clap_plugin,
+    _sample_rate: f64,
+    _min_frames_count: u32,
+    _max_frames_count: u32,
+) -> bool {
+    true
+}
+
+unsafe extern "C" fn plugin_deactivate(_plugin: *const clap_plugin) {}
+
+unsafe extern "C" fn plugin_start_processing(_plugin: *const clap_plugin) -> bool {
+    true
+}
+
+unsafe extern "C" fn plugin_stop_processing(_plugin: *const clap_plugin) {}
+
+unsafe extern "C" fn plugin_reset(_plugin: *const clap_plugin) {}
+
+unsafe extern "C" fn plugin_process(
+    _plugin: *const clap_plugin,
+    _process: *const clap_process,
+) -> clap_process_status {
+    CLAP_PROCESS_CONTINUE
+}
+
+unsafe extern "C" fn plugin_on_main_thread(_plugin: *const clap_plugin) {}
+
+unsafe extern "C" fn plugin_get_extension(
+    plugin: *const clap_plugin,
+    id: *const c_char,
+) -> *const c_void {
+    let id = unsafe { CStr::from_ptr(id) };
+    let is_synth = unsafe { instance(plugin) }.is_synth;
+    if id == CLAP_EXT_PARAMS {
+        &PARAMS_EXTENSION as *const _ as *const c_void
+    } else if id == CLAP_EXT_AUDIO_PORTS {
+        &AUDIO_PORTS_EXTENSION as *const _ as *const c_void
+    } else if id == CLAP_EXT_NOTE_PORTS {
+        &NOTE_PORTS_EXTENSION as *const _ as *const c_void
+    } else if id == CLAP_EXT_LATENCY && !is_synth {
+        &LATENCY_EXTENSION as *const _ as *const c_void
+    } else if id == CLAP_EXT_STATE {
+        &STATE_EXTENSION as *const _ as *const c_void
+    } else {
+        ptr::null()
+    }
+}
+
+// clap.params
+
+static PARAMS_EXTENSION: clap_plugin_params = clap_plugin_params {
+    count: Some(params_count),
+    get_info: Some(params_get_info),
+    get_value: Some(params_get_value),
+    value_to_text: Some(params_value_to_text),
+    text_to_value: Some(params_text_to_value),
+    flush: Some(params_flush),
+};
+
+unsafe extern "C" fn params_count(_plugin: *const clap_plugin) -> u32 {
+    PARAMS.len() as u32
+}
+
+unsafe extern "C" fn params_get_info(
+    _plugin: *const clap_plugin,
+    param_index: u32,
+    param_info: *mut clap_param_info,
+) -> bool {
+    let Some(param) = PARAMS.get(param_index as usize) else {
+        return false;
+    };
+    let info = unsafe { &mut *param_info };
+    info.id = param.id;
+    info.flags = param.flags;
+    info.cookie = ptr::null_mut();
+    copy_str(&mut info.name, param.name);
+    copy_str(&mut info.module, param.module);
+    info.min_value = param.min;
+    info.max_value = param.max;
+    info.default_value = param.default;
+    true
+}
+
+unsafe extern "C" fn params_get_value(
+    plugin: *const clap_plugin,
+    param_id: clap_id,
+    out_value: *mut f64,
+) -> bool {
+    let Some(index) = param_index(param_id) else {
+        return false;
+    };
+    unsafe { *out_value = instance(plugin).values[index] };
+    true
+}
+
+unsafe extern "C" fn params_value_to_text(
+    _plugin: *const clap_plugin,
+    param_id: clap_id,
+    value: f64,
+    out_buffer: *mut c_char,
+    out_buffer_capacity: u32,
+) -> bool {
+    let text = match param_id {
+        10 => format!("{:.1} dB", value),
+        20 => MODES.get(value.round() as usize).unwrap_or(&"").to_string(),
+        30 => if value >= 0.5 { "On" } else { "Off" }.to_string(),
+        _ => return false,
+    };
+    let buffer =
+        unsafe { std::slice::from_raw_parts_mut(out_buffer, out_buffer_capacity as usize) };
+    copy_str(buffer, &text);
+    true
+}
+
+unsafe extern "C" fn params_text_to_value(
+    _plugin: *const clap_plugin,
+    _param_id: clap_id,
+    _param_value_text: *const c_char,
+    _out_value: *mut f64,
+) -> bool {
+    false
+}
+
+unsafe extern "C" fn params_flush(
+    _plugin: *const clap_plugin,
+    _in: *const clap_sys::events::clap_input_events,
+    _out: *const clap_sys::events::clap_output_events,
+) {
+}
+
+// clap.audio-ports
+
+static AUDIO_PORTS_EXTENSION: clap_plugin_audio_ports = clap_plugin_audio_ports {
+    count: Some(audio_ports_count),
+    get: Some(audio_ports_get),
+};
+
+unsafe extern "C" fn audio_ports_count(plugin: *const clap_plugin, is_input: bool) -> u32 {
+    if is_input && unsafe { instance(plugin) }.is_synth {
+        0
+    } else {
+        1
+    }
+}
+
+unsafe extern "C" fn audio_ports_get(
+    plugin: *const clap_plugin,
+    index: u32,
+    is_input: bool,
+    info: *mut clap_audio_port_info,
+) -> bool {
+    if index >= unsafe { audio_ports_count(plugin, is_input) } {
+        return false;
+    }
+    let is_synth = unsafe { instance(plugin) }.is_synth;
+    let info = unsafe { &mut *info };
+    info.id = if is_input { 0 } else { 1 };
+    copy_str(
+        &mut info.name,
+        if is_input { "Main In" } else { "Main Out" },
+    );
+    info.flags = CLAP_AUDIO_PORT_IS_MAIN;
+    info.channel_count = 2;
+    info.port_type = CLAP_PORT_STEREO.as_ptr();
+    info.in_place_pair = match (is_synth, is_input) {
+        (true, _) => CLAP_INVALID_ID,
+        (false, true) => 1,
+        (false, false) => 0,
+    };
+    true
+}
+
+// clap.note-ports
+
+static NOTE_PORTS_EXTENSION: clap_plugin_note_ports = clap_plugin_note_ports {
+    count: Some(note_ports_count),
+    get: Some(note_ports_get),
+};
+
+unsafe extern "C" fn note_ports_count(plugin: *const clap_plugin, is_input: bool) -> u32 {
+    if is_input && unsafe { instance(plugin) }.is_synth {
+        1
+    } else {
+        0
+    }
+}
+
+unsafe extern "C" fn note_ports_get(
+    plugin: *const clap_plugin,
+    index: u32,
+    is_input: bool,
+    info: *mut clap_note_port_info,
+) -> bool {
+    if index >= unsafe { note_ports_count(plugin, is_input) } {
+        return false;
+    }
+    let info = unsafe { &mut *info };
+    info.id = 0;
+    info.supported_dialects = CLAP_NOTE_DIALECT_CLAP | CLAP_NOTE_DIALECT_MIDI;
+    info.preferred_dialect = CLAP_NOTE_DIALECT_CLAP;
+    copy_str(&mut info.name, "Notes");
+    true
+}
+
+// clap.latency
+
+static LATENCY_EXTENSION: clap_plugin_latency = clap_plugin_latency {
+    get: Some(latency_get),
+};
+
+unsafe extern "C" fn latency_get(_plugin: *const clap_plugin) -> u32 {
+    LATENCY
+}
+
+// clap.state
+
+static STATE_EXTENSION: clap_plugin_state = clap_plugin_state {
+    save: Some(state_save),
+    load: Some(state_load),
+};
+
+unsafe extern "C" fn state_save(plugin: *const clap_plugin, stream: *const clap_ostream) -> bool {
+    let instance = unsafe { instance(plugin) };
+    let mut state = STATE_MAGIC.to_vec();
+    state.extend((PARAMS.len() as u32).to_le_bytes());
+    for (param, value) in PARAMS.iter().zip(instance.values) {
+        state.extend(param.id.to_le_bytes());
+        state.extend(value.to_le_bytes());
+    }
+
+    let stream = unsafe { &*stream };
+    let Some(write) = stream.write else {
+        return false;
+    };
+    let mut written = 0;
+    while written < state.len() {
+        let remaining = &state[written..];
+        let count = unsafe {
+            write(
+                stream,
+                remaining.as_ptr() as *const c_void,
+                remaining.len() as u64,
+            )
+        };
+        if count <= 0 {
+            return false;
+        }
+        written += count as usize;
+    }
+    true
+}
+
+unsafe extern "C" fn state_load(plugin: *const clap_plugin, stream: *const clap_istream) -> bool {
+    let stream = unsafe { &*stream };
+    let Some(read) = stream.read else {
+        return false;
+    };
+    let mut state = Vec::new();
+    let mut buffer = [0u8; 64];
+    loop {
+        let count = unsafe { read(stream, buffer.as_mut_ptr() as *mut c_void, 64) };
+        if count < 0 {
+            return false;
+        }
+        if count == 0 {
+            break;
+        }
+        state.extend_from_slice(&buffer[..count as usize]);
+    }
+
+    let Some(values) = parse_state(&state) else {
+        return false;
+    };
+    let instance = unsafe { instance(plugin) };
+    for (param_id, value) in values {
+        if let Some(index) = param_index(param_id) {
+            let param = &PARAMS[index];
+            instance.values[index] = value.clamp(param.min, param.max);
+        }
+    }
+    true
+}
+
+fn parse_state(state: &[u8]) -> Option<Vec<(clap_id, f64)>> {
+    let rest = state.strip_prefix(STATE_MAGIC)?;
+    let count = u32::from_le_bytes(rest.get(0..4)?.try_into().ok()?) as usize;
+    let entries = rest.get(4..)?;
+    if entries.len() != count * 12 {
+        return None;
+    }
+    Some(
+        entries
+            .chunks(12)
+            .map(|entry| {
+                let param_id = u32::from_le_bytes(entry[0..4].try_into().unwrap());
+                let value = f64::from_le_bytes(entry[4..12].try_into().unwrap());
+                (param_id, value)
+            })
+            .collect(),
+    )
+}
+
+// Copies a string into a C char array, cut to its size and zero terminated
+fn copy_str(target: &mut [c_char], source: &str) {
+    let Some(max_len) = target.len().checked_sub(1) else {
+        return;
+    };
+    let bytes = &source.as_bytes()[..source.len().min(max_len)];
+    for (target, byte) in target.iter_mut().zip(bytes) {
+        *target = *byte as c_char;
+    }
+    target[bytes.len()] = 0;
+}
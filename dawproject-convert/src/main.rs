@@ -0,0 +1,274 @@
+/*!
+ * ------------------------------------------------------------------------
+ * DAWproject Convert Tool
+ * by Jurek Raben
+ *
+ * Converts saved Renoise songs to .dawproject files without Renoise, with
+ * the same rules as the export of the Lua tool, e.g. for batch conversions
+ * of song archives on a server, and .dawproject files to import plans for
+ * the Lua tool to apply to a new song. Prints one JSON line per file,
+ * followed by a summary line.
+ *
+ * Licensed under CC Attribution-NonCommercial-ShareAlike 4.0 International
+ * Info here: https://creativecommons.org/licenses/by-nc-sa/4.0/
+ * ------------------------------------------------------------------------
+ */
+
+use std::collections::HashMap;
+use std::ffi::OsString;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::Instant;
+
+use clap::{Parser, Subcommand};
+use serde_json::{Map, Value, json};
+
+use dawproject_plugin_info::dawproject::DawProject;
+use dawproject_plugin_info::dawproject::export::{self, ExportOptions, Vst2Plugin};
+use dawproject_plugin_info::dawproject::import::{
+    self, DEFAULT_LINES_PER_BEAT, DEFAULT_PATTERN_LINES, ImportOptions,
+};
+use dawproject_plugin_info::output::{OS, error_exit, print_json};
+use dawproject_plugin_info::vst2::resolve::parse_unique_id;
+use dawproject_plugin_info::xrns;
+
+const DAWPROJECT_EXTENSION: &str = "dawproject";
+const PLAN_EXTENSION: &str = "json";
+const DEFAULT_VST2_VERSION: i32 = 1;
+
+#[derive(Parser)]
+#[command(
+    name = "dawproject-convert",
+    version,
+    about = "Converts between Renoise songs and DAWproject files without Renoise"
+)]
+struct Cli {
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Writes a .dawproject for every song
+    Export {
+        /// .xrns songs or extracted Song.xml files
+        #[arg(required = true)]
+        songs: Vec<PathBuf>,
+
+        /// Folder of the written files, the folder of each song by default
+        #[arg(long, value_name = "DIR")]
+        output_dir: Option<PathBuf>,
+
+        /// Keeps the clips at the pattern start instead of moving them by the
+        /// output delay of their track
+        #[arg(long)]
+        ignore_track_delay: bool,
+
+        /// Unique ID and version of a VST2 plugin by its Renoise
+        /// PluginIdentifier, VST2 devices without are skipped
+        #[arg(long, value_name = "IDENTIFIER=ID[:VERSION]")]
+        vst2_id: Vec<String>,
+    },
+    /// Writes a JSON import plan for every .dawproject, for the Lua tool to
+    /// apply to a new song
+    Import {
+        /// .dawproject files
+        #[arg(required = true)]
+        projects: Vec<PathBuf>,
+
+        /// Folder of the written files, the folder of each project by default
+        #[arg(long, value_name = "DIR")]
+        output_dir: Option<PathBuf>,
+
+        /// Lines per beat of the song, up to 256
+        #[arg(long, value_name = "LINES", default_value_t = DEFAULT_LINES_PER_BEAT)]
+        lines_per_beat: u32,
+
+        /// Longest pattern, up to 512 lines, patterns are also cut at the
+        /// markers
+        #[arg(long, value_name = "LINES", default_value_t = DEFAULT_PATTERN_LINES)]
+        pattern_lines: usize,
+    },
+}
+
+fn main() {
+    let cli = Cli::parse();
+
+    match cli.command {
+        Command::Export {
+            songs,
+            output_dir,
+            ignore_track_delay,
+            vst2_id,
+        } => {
+            let options = ExportOptions {
+                add_track_delay: !ignore_track_delay,
+                vst2_plugins: parse_vst2_plugins(&vst2_id),
+            };
+            convert_files(
+                "song",
+                &songs,
+                output_dir.as_deref(),
+                DAWPROJECT_EXTENSION,
+                |song, output_path| export_song(song, output_path, &options),
+            );
+        }
+        Command::Import {
+            projects,
+            output_dir,
+            lines_per_beat,
+            pattern_lines,
+        } => {
+            let options = ImportOptions {
+                lines_per_beat,
+                pattern_lines,
+            };
+            convert_files(
+                "project",
+                &projects,
+                output_dir.as_deref(),
+                PLAN_EXTENSION,
+                |project, output_path| import_project(project, output_path, &options),
+            );
+        }
+    }
+}
+
+// One line per file, named by the key, and the summary. A file whose output
+// path was already written in this run fails instead of overwriting it.
+fn convert_files(
+    key: &str,
+    files: &[PathBuf],
+    output_dir: Option<&Path>,
+    extension: &str,
+    convert: impl Fn(&Path, &Path) -> Result<Map<String, Value>, String>,
+) {
+    let started = Instant::now();
+    let mut count_ok = 0;
+    let mut count_failed = 0;
+    let mut written: HashMap<PathBuf, &Path> = HashMap::new();
+
+    for file in files {
+        let mut line = Map::new();
+        line.insert(key.to_string(), json!(file.display().to_string()));
+
+        let output_path = output_path(file, output_dir, extension);
+        let result = match written.get(&output_path) {
+            Some(earlier) => Err(format!(
+                "{} was already written for {}",
+                output_path.display(),
+                earlier.display()
+            )),
+            None => convert(file, &output_path),
+        };
+        if result.is_ok() {
+            written.insert(output_path, file);
+        }
+        match result {
+            Ok(output) => {
+                count_ok += 1;
+                line.insert("ok".to_string(), json!(true));
+                line.extend(output);
+            }
+            Err(error) => {
+                count_failed += 1;
+                line.insert("ok".to_string(), json!(false));
+                line.insert("error".to_string(), json!(error));
+            }
+        }
+        print_json(&line);
+    }
+
+    print_json(&json!({
+        "summary": {
+            "total": count_ok + count_failed,
+            "ok": count_ok,
+            "failed": count_failed,
+            "seconds": started.elapsed().as_secs_f64()
+        },
+        "os": OS
+    }));
+}
+
+fn export_song(
+    song: &Path,
+    output_path: &Path,
+    options: &ExportOptions,
+) -> Result<Map<String, Value>, String> {
+    let song_xml = xrns::read_song(song)?;
+    let file_name = song
+        .file_name()
+        .unwrap_or_default()
+        .to_string_lossy()
+        .to_string();
+    let export = export::export_song(&song_xml, &file_name, options);
+
+    export.project.write(output_path)?;
+
+    let output = json!({
+        "file": output_path.display().to_string(),
+        "countTracks": export.count_tracks,
+        "countNotes": export.count_notes,
+        "countAutomationPoints": export.count_automation_points,
+        "countPlugins": export.count_plugins,
+        "skipped": export.skipped
+    });
+    Ok(output.as_object().cloned().unwrap_or_default())
+}
+
+fn import_project(
+    project: &Path,
+    output_path: &Path,
+    options: &ImportOptions,
+) -> Result<Map<String, Value>, String> {
+    let dawproject = DawProject::read(project)?;
+    let import = import::import_project(&dawproject, options);
+
+    let plan = serde_json::to_string_pretty(&import.plan).map_err(|e| e.to_string())?;
+    fs::write(output_path, plan)
+        .map_err(|e| format!("Failed to write {}: {}", output_path.display(), e))?;
+
+    let output = json!({
+        "file": output_path.display().to_string(),
+        "countTracks": import.count_tracks,
+        "countPatterns": import.count_patterns,
+        "countNotes": import.count_notes,
+        "countDroppedNotes": import.count_dropped_notes,
+        "countAutomationPoints": import.count_automation_points,
+        "countPlugins": import.count_plugins,
+        "skipped": import.skipped
+    });
+    Ok(output.as_object().cloned().unwrap_or_default())
+}
+
+// Next to the input file unless an output folder is given, the extension
+// appended to the stem as it may contain dots
+fn output_path(input: &Path, output_dir: Option<&Path>, extension: &str) -> PathBuf {
+    let output_dir = output_dir
+        .or_else(|| input.parent())
+        .unwrap_or(Path::new("."));
+    let mut file_name = OsString::from(input.file_stem().unwrap_or_default());
+    file_name.push(".");
+    file_name.push(extension);
+    output_dir.join(file_name)
+}
+
+// "IDENTIFIER=ID[:VERSION]" pairs, the ID as number or four characters.
+// Without the version, the .fxp files get version 1.
+fn parse_vst2_plugins(pairs: &[String]) -> HashMap<String, Vst2Plugin> {
+    pairs
+        .iter()
+        .map(|pair| {
+            let (identifier, id) = pair
+                .rsplit_once('=')
+                .unwrap_or_else(|| error_exit(format!("{} is no IDENTIFIER=ID pair", pair)));
+            let (id, version) = id
+                .rsplit_once(':')
+                .and_then(|(id, version)| Some((id, version.parse().ok()?)))
+                .unwrap_or((id, DEFAULT_VST2_VERSION));
+            let unique_id = parse_unique_id(id)
+                .unwrap_or_else(|| error_exit(format!("{} is no VST2 unique ID", id)));
+            (identifier.to_string(), Vst2Plugin { unique_id, version })
+        })
+        .collect()
+}
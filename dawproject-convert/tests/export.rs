@@ -0,0 +1,409 @@
+// Export of a Renoise song fixture with patterns, sequence, automation,
+// sends and plugin devices of every type
+
+mod common;
+
+use std::env;
+use std::fs::{self, File};
+use std::io::{Read, Write};
+use std::path::Path;
+
+use serde_json::{Value, json};
+use zip::ZipArchive;
+use zip::write::SimpleFileOptions;
+
+use dawproject_plugin_info::xml::{self, Element};
+
+use common::{fixture, output_dir, run};
+
+// Time, value and interpolation
+type AutomationPoint<'a> = (&'a str, &'a str, &'a str);
+
+fn export(dir: &Path, extra_args: &[&str]) -> Vec<Value> {
+    let song = fixture("Song.xml");
+    let mut args = vec!["export", &song, "--output-dir", dir.to_str().unwrap()];
+    args.extend(extra_args);
+    run(&args)
+}
+
+fn archive_file(archive: &Path, name: &str) -> Vec<u8> {
+    let mut archive = ZipArchive::new(File::open(archive).unwrap()).unwrap();
+    let mut data = Vec::new();
+    archive
+        .by_name(name)
+        .unwrap()
+        .read_to_end(&mut data)
+        .unwrap();
+    data
+}
+
+fn project(archive: &Path) -> Element {
+    xml::parse(&String::from_utf8(archive_file(archive, "project.xml")).unwrap()).unwrap()
+}
+
+fn attribute<'a>(element: &'a Element, name: &str) -> &'a str {
+    element.attribute(name).unwrap()
+}
+
+#[test]
+fn exports_structure_and_transport() {
+    let dir = output_dir("structure");
+    let lines = export(&dir, &[]);
+    let archive = dir.join("Song.dawproject");
+
+    assert_eq!(lines[0]["ok"], true);
+    assert_eq!(lines[0]["file"], archive.display().to_string());
+    assert_eq!(lines[0]["countTracks"], 5);
+    assert_eq!(lines[0]["countNotes"], 9);
+    assert_eq!(lines[0]["countAutomationPoints"], 10);
+    assert_eq!(
+        lines[1]["summary"]["ok"], 1,
+        "{}",
+        lines[1]["summary"]["failed"]
+    );
+    assert_eq!(lines[1]["os"], env::consts::OS);
+
+    let project = project(&archive);
+    let transport = project.child("Transport").unwrap();
+    assert_eq!(attribute(transport.child("Tempo").unwrap(), "value"), "120");
+    let signature = transport.child("TimeSignature").unwrap();
+    assert_eq!(attribute(signature, "numerator"), "3");
+    assert_eq!(attribute(signature, "denominator"), "4");
+
+    // Tracks nest by routing, sends and master come last
+    let tracks: Vec<&Element> = project
+        .child("Structure")
+        .unwrap()
+        .children_named("Track")
+        .collect();
+    let ids: Vec<&str> = tracks.iter().map(|track| attribute(track, "id")).collect();
+    assert_eq!(ids, ["track1", "track3", "track5", "track4"]);
+
+    let lead = tracks[0];
+    assert_eq!(attribute(lead, "color"), "#ff8000");
+    assert_eq!(attribute(lead, "comment"), "Delay: 50");
+    let channel = lead.child("Channel").unwrap();
+    assert_eq!(attribute(channel.child("Volume").unwrap(), "value"), "0.5");
+    assert_eq!(attribute(channel.child("Pan").unwrap(), "value"), "0.25");
+    let send = channel.find(&["Sends", "Send"]).unwrap();
+    assert_eq!(attribute(send, "destination"), "track5");
+
+    let drums = tracks[1].child("Track").unwrap();
+    assert_eq!(attribute(drums, "name"), "Beats Drums");
+    assert_eq!(
+        attribute(drums.find(&["Channel", "Mute"]).unwrap(), "value"),
+        "true"
+    );
+    assert_eq!(attribute(tracks[1], "contentType"), "tracks");
+
+    let markers: Vec<(&str, &str)> = project
+        .find(&["Arrangement", "Markers"])
+        .unwrap()
+        .children_named("Marker")
+        .map(|marker| (attribute(marker, "time"), attribute(marker, "name")))
+        .collect();
+    assert_eq!(markers, [("0", "Intro"), ("4", "Part B")]);
+
+    let metadata = String::from_utf8(archive_file(&archive, "metadata.xml")).unwrap();
+    let metadata = xml::parse(&metadata).unwrap();
+    assert_eq!(metadata.child_text("Title"), Some("Convert Test"));
+    assert_eq!(metadata.child_text("Artist"), Some("Jurek Raben"));
+}
+
+#[test]
+fn exports_notes_in_clips_per_sequence_slot() {
+    let dir = output_dir("notes");
+    export(&dir, &[]);
+    let project = project(&dir.join("Song.dawproject"));
+
+    let lanes: Vec<&Element> = project
+        .find(&["Arrangement", "Lanes"])
+        .unwrap()
+        .children_named("Lanes")
+        .collect();
+    assert_eq!(attribute(lanes[0], "track"), "track1");
+    let clips: Vec<&Element> = lanes[0]
+        .child("Clips")
+        .unwrap()
+        .children_named("Clip")
+        .collect();
+    let times: Vec<&str> = clips.iter().map(|clip| attribute(clip, "time")).collect();
+    // 50 ms track delay at 120 BPM
+    assert_eq!(times, ["0.1", "4.1", "6.1"]);
+
+    let notes: Vec<&Element> = clips[0]
+        .find(&["Lanes", "Notes"])
+        .unwrap()
+        .children_named("Note")
+        .collect();
+    let note = |index: usize| -> Vec<(&str, &str)> {
+        notes[index]
+            .attributes
+            .iter()
+            .map(|(name, value)| (name.as_str(), value.as_str()))
+            .collect()
+    };
+    assert_eq!(
+        note(0),
+        [
+            ("time", "0"),
+            ("duration", "1"),
+            ("channel", "0"),
+            ("key", "48"),
+            ("vel", "0.5039370078740157"),
+            ("rel", "1")
+        ]
+    );
+    // A note-off in the next pattern ends the second column
+    assert_eq!(note(1)[1], ("duration", "4"));
+    // Delay 80 is half a line, the velocity is the last one of the column
+    assert_eq!(note(2)[0], ("time", "2.125"));
+    assert_eq!(note(2)[4], ("vel", "0.5039370078740157"));
+
+    let pressure = notes[0].find(&["Points", "RealPoint"]).unwrap();
+    assert_eq!(attribute(pressure, "value"), "0.251968");
+    assert_eq!(attribute(pressure, "time"), "0.5");
+
+    // The muted slot gives a disabled clip, the mute change ends the note
+    let drum_clips: Vec<&Element> = lanes[1]
+        .child("Clips")
+        .unwrap()
+        .children_named("Clip")
+        .collect();
+    assert_eq!(attribute(drum_clips[0], "enable"), "true");
+    assert_eq!(
+        attribute(
+            drum_clips[0].find(&["Lanes", "Notes", "Note"]).unwrap(),
+            "duration"
+        ),
+        "6"
+    );
+    assert_eq!(attribute(drum_clips[1], "enable"), "false");
+}
+
+#[test]
+fn ignores_track_delay() {
+    let dir = output_dir("delay");
+    export(&dir, &["--ignore-track-delay"]);
+    let project = project(&dir.join("Song.dawproject"));
+
+    let clip = project
+        .find(&["Arrangement", "Lanes", "Lanes", "Clips", "Clip"])
+        .unwrap();
+    assert_eq!(attribute(clip, "time"), "0");
+}
+
+#[test]
+fn exports_automation_lanes_and_parameters() {
+    let dir = output_dir("automation");
+    export(&dir, &[]);
+    let project = project(&dir.join("Song.dawproject"));
+
+    let lanes = project.find(&["Arrangement", "Lanes", "Lanes"]).unwrap();
+    let points: Vec<(&str, Vec<AutomationPoint>)> = lanes
+        .children_named("Points")
+        .map(|points| {
+            (
+                attribute(points.child("Target").unwrap(), "parameter"),
+                points
+                    .children_named("RealPoint")
+                    .map(|point| {
+                        (
+                            attribute(point, "time"),
+                            attribute(point, "value"),
+                            attribute(point, "interpolation"),
+                        )
+                    })
+                    .collect(),
+            )
+        })
+        .collect();
+    assert_eq!(
+        points,
+        [
+            (
+                "paramid-1-2-2",
+                vec![
+                    ("0", "0", "linear"),
+                    ("2", "1", "linear"),
+                    ("6", "0", "linear"),
+                    ("8", "1", "linear")
+                ]
+            ),
+            // The Instr. Automation device, repeated values are left out
+            (
+                "paramid-1-i1-3",
+                vec![
+                    ("0", "0.5", "hold"),
+                    ("3", "0.25", "hold"),
+                    ("6", "0.5", "hold"),
+                    ("9", "0.25", "hold")
+                ]
+            ),
+            (
+                "paramid-1-i1-7",
+                vec![("3", "1", "linear"), ("9", "1", "linear")]
+            )
+        ]
+    );
+    let cc_target = lanes
+        .children_named("Points")
+        .nth(2)
+        .unwrap()
+        .child("Target")
+        .unwrap();
+    assert_eq!(attribute(cc_target, "expression"), "channelController");
+    assert_eq!(attribute(cc_target, "controller"), "7");
+
+    let instrument = project
+        .find(&["Structure", "Track", "Channel", "Devices", "Vst3Plugin"])
+        .unwrap();
+    assert_eq!(attribute(instrument, "deviceRole"), "instrument");
+    let parameters: Vec<&str> = instrument
+        .child("Parameters")
+        .unwrap()
+        .children_named("RealParameter")
+        .map(|parameter| attribute(parameter, "id"))
+        .collect();
+    assert_eq!(parameters, ["paramid-1-i1-3", "paramid-1-i1-7"]);
+}
+
+#[test]
+fn writes_plugin_states() {
+    let dir = output_dir("plugins");
+    let lines = export(&dir, &["--vst2-id", "Test Chunks=TstC:1100"]);
+    let archive = dir.join("Song.dawproject");
+
+    assert_eq!(lines[0]["countPlugins"], 4);
+    assert_eq!(
+        lines[0]["skipped"],
+        json!([{
+            "track": 2,
+            "device": "Drums",
+            "reason": "Sample instrument, the conversion to Redux needs Renoise"
+        }])
+    );
+
+    assert_eq!(
+        archive_file(&archive, "plugins/instr-tr1-no1-testsynth.vstpreset"),
+        b"VST3Instrument"
+    );
+    let fxp = archive_file(&archive, "plugins/fx-tr2-no2-testchunks.fxp");
+    assert_eq!(&fxp[0..4], b"CcnK");
+    assert_eq!(&fxp[8..12], b"FPCh");
+    assert_eq!(&fxp[16..20], b"TstC");
+    assert_eq!(&fxp[20..24], 1100i32.to_be_bytes());
+    // The active preset, cut to 27 bytes and a null
+    assert_eq!(&fxp[28..56], b"Bright Pads With A Long Pre\0");
+    let aupreset = archive_file(&archive, "plugins/fx-tr5-no2-testreverb.aupreset");
+    assert!(aupreset.starts_with(b"<?xml"));
+
+    let project = project(&archive);
+    let tracks: Vec<&Element> = project
+        .child("Structure")
+        .unwrap()
+        .children_named("Track")
+        .collect();
+    let drums = tracks[1].child("Track").unwrap();
+    let vst2 = drums.find(&["Channel", "Devices", "Vst2Plugin"]).unwrap();
+    assert_eq!(
+        attribute(vst2, "deviceID"),
+        i32::from_be_bytes(*b"TstC").to_string()
+    );
+    let au = tracks[2].find(&["Channel", "Devices", "AuPlugin"]).unwrap();
+    assert_eq!(
+        attribute(au, "deviceID"),
+        "6175667849644D79756E614D42414C4B"
+    );
+}
+
+#[test]
+fn skips_vst2_plugins_without_unique_id() {
+    let dir = output_dir("vst2");
+    let lines = export(&dir, &[]);
+
+    assert_eq!(lines[0]["countPlugins"], 3);
+    assert_eq!(
+        lines[0]["skipped"][1],
+        json!({
+            "track": 2,
+            "device": "Test Chunks",
+            "reason": "Unknown VST2 unique ID"
+        })
+    );
+}
+
+#[test]
+fn converts_xrns_archives_and_reports_failures() {
+    let dir = output_dir("batch");
+    let song = dir.join("Archived.xrns");
+    let mut archive = zip::ZipWriter::new(File::create(&song).unwrap());
+    archive
+        .start_file("Song.xml", SimpleFileOptions::default())
+        .unwrap();
+    archive
+        .write_all(&fs::read(fixture("Song.xml")).unwrap())
+        .unwrap();
+    archive.finish().unwrap();
+    let missing = dir.join("Missing.xrns");
+
+    let lines = run(&["export", song.to_str().unwrap(), missing.to_str().unwrap()]);
+
+    assert_eq!(lines[0]["ok"], true);
+    assert_eq!(
+        lines[0]["file"],
+        dir.join("Archived.dawproject").display().to_string()
+    );
+    assert!(dir.join("Archived.dawproject").is_file());
+    assert_eq!(lines[1]["ok"], false);
+    assert!(
+        lines[1]["error"]
+            .as_str()
+            .unwrap()
+            .starts_with(&format!("Failed to read {}", missing.display()))
+    );
+    assert_eq!(lines[2]["summary"]["total"], 2);
+    assert_eq!(lines[2]["summary"]["failed"], 1);
+}
+
+#[test]
+fn keeps_dots_in_names_and_reports_output_collisions() {
+    let dir = output_dir("collisions");
+    let mut songs = Vec::new();
+    for name in ["My.Song.v1", "My.Song.v2", "Other"] {
+        let song_dir = dir.join(name);
+        fs::create_dir_all(&song_dir).unwrap();
+        let song = match name {
+            "Other" => song_dir.join("Song.xml"),
+            name => song_dir.join(format!("{}.xrns", name)),
+        };
+        fs::copy(fixture("Song.xml"), &song).unwrap();
+        songs.push(song.display().to_string());
+    }
+    songs.push(fixture("Song.xml"));
+    let output = dir.join("out");
+    fs::create_dir_all(&output).unwrap();
+
+    let mut args = vec!["export", "--output-dir", output.to_str().unwrap()];
+    args.extend(songs.iter().map(String::as_str));
+    let lines = run(&args);
+
+    for (line, name) in lines.iter().zip(["My.Song.v1", "My.Song.v2", "Song"]) {
+        assert_eq!(line["ok"], true, "{}", line);
+        let file = output.join(format!("{}.dawproject", name));
+        assert_eq!(line["file"], file.display().to_string());
+        assert!(file.is_file());
+    }
+    // A second Song.xml would overwrite the first one
+    assert_eq!(lines[3]["ok"], false);
+    assert_eq!(
+        lines[3]["error"],
+        format!(
+            "{} was already written for {}",
+            output.join("Song.dawproject").display(),
+            songs[2]
+        )
+    );
+    assert_eq!(lines[4]["summary"]["total"], 4);
+    assert_eq!(lines[4]["summary"]["failed"], 1);
+}
@@ -0,0 +1,427 @@
+// Import plans of the exported song fixture and of a project as other
+// applications write it, with groups, seconds, VST2 programs and more
+// notes than note columns
+
+mod common;
+
+use std::env;
+use std::fs::{self, File};
+use std::io::Write;
+use std::path::Path;
+
+use serde_json::{Value, json};
+use zip::write::SimpleFileOptions;
+
+use common::{fixture, output_dir, run};
+
+fn plan(path: &Path) -> Value {
+    serde_json::from_str(&fs::read_to_string(path).unwrap()).unwrap()
+}
+
+// The song fixture exported and imported again
+fn round_trip(dir: &Path) -> (Vec<Value>, Value) {
+    let song = fixture("Song.xml");
+    let dir_arg = dir.to_str().unwrap();
+    run(&[
+        "export",
+        &song,
+        "--output-dir",
+        dir_arg,
+        "--ignore-track-delay",
+        "--vst2-id",
+        "Test Chunks=TstC",
+    ]);
+    let project = dir.join("Song.dawproject");
+    let lines = run(&[
+        "import",
+        project.to_str().unwrap(),
+        "--output-dir",
+        dir_arg,
+        "--pattern-lines",
+        "32",
+    ]);
+    let plan = plan(&dir.join("Song.json"));
+    (lines, plan)
+}
+
+fn write_project(path: &Path, project_xml: &str, files: &[(&str, Vec<u8>)]) {
+    let mut archive = zip::ZipWriter::new(File::create(path).unwrap());
+    archive
+        .start_file("project.xml", SimpleFileOptions::default())
+        .unwrap();
+    archive.write_all(project_xml.as_bytes()).unwrap();
+    for (name, data) in files {
+        archive
+            .start_file(*name, SimpleFileOptions::default())
+            .unwrap();
+        archive.write_all(data).unwrap();
+    }
+    archive.finish().unwrap();
+}
+
+// A VST2 program with parameter values instead of a chunk
+fn fxp_program(unique_id: &[u8; 4], parameters: &[f32]) -> Vec<u8> {
+    let mut content = b"FxCk".to_vec();
+    content.extend(1i32.to_be_bytes());
+    content.extend(unique_id);
+    content.extend(1i32.to_be_bytes());
+    content.extend((parameters.len() as i32).to_be_bytes());
+    content.extend([0u8; 28]);
+    for parameter in parameters {
+        content.extend(parameter.to_be_bytes());
+    }
+    let mut data = b"CcnK".to_vec();
+    data.extend((content.len() as i32).to_be_bytes());
+    data.extend(content);
+    data
+}
+
+// Keys in a bus, without master. 90 BPM, so two seconds are three beats.
+fn foreign_project() -> String {
+    let chord: String = (60..73)
+        .map(|key| format!(r#"<Note time="0" duration="1" key="{}" vel="0.5"/>"#, key))
+        .collect();
+    format!(
+        r##"<?xml version="1.0" encoding="UTF-8"?>
+<Project version="1.0">
+  <Application name="Other DAW" version="1.0"/>
+  <Transport>
+    <Tempo unit="bpm" value="90"/>
+    <TimeSignature numerator="7" denominator="8"/>
+  </Transport>
+  <Structure>
+    <Track id="bus" name="Bus" contentType="tracks">
+      <Channel id="bus-channel" role="submix"/>
+      <Track id="keys" name="Keys" contentType="notes" color="#102030">
+        <Channel id="keys-channel" role="regular">
+          <Devices>
+            <Vst2Plugin id="synth" deviceID="1416918083" deviceName="Test Params" name="Keys Synth" deviceRole="instrument">
+              <State path="plugins/synth.fxp"/>
+              <Parameters>
+                <RealParameter id="cutoff" name="Cutoff" parameterID="4" unit="linear" min="20" max="220"/>
+              </Parameters>
+            </Vst2Plugin>
+            <Equalizer id="eq" name="EQ" deviceRole="audioFX"/>
+            <ClapPlugin id="delay" deviceID="org.example.delay" name="Clap Delay" deviceRole="audioFX">
+              <State path="plugins/missing.clap-preset"/>
+            </ClapPlugin>
+          </Devices>
+          <Volume unit="linear" value="0.5"/>
+          <Pan unit="linear" min="-1" max="1" value="-0.5"/>
+        </Channel>
+      </Track>
+    </Track>
+  </Structure>
+  <Arrangement>
+    <Lanes timeUnit="seconds">
+      <Lanes track="keys">
+        <Clips>
+          <Clip time="2" duration="8" contentTimeUnit="beats">
+            <Notes>
+              {}
+              <Note time="1" duration="1" key="60" vel="1"/>
+              <Note time="2" duration="1" key="125" vel="1"/>
+            </Notes>
+          </Clip>
+          <Clip time="8" duration="2" enable="false">
+            <Notes>
+              <Note time="0" duration="1" key="48" vel="1"/>
+            </Notes>
+          </Clip>
+        </Clips>
+        <Points>
+          <Target parameter="cutoff"/>
+          <RealPoint time="2" value="120" interpolation="linear"/>
+          <RealPoint time="4" value="220" interpolation="linear"/>
+        </Points>
+      </Lanes>
+    </Lanes>
+    <Markers timeUnit="beats">
+      <Marker time="8" name="Drop"/>
+    </Markers>
+  </Arrangement>
+</Project>
+"##,
+        chord
+    )
+}
+
+#[test]
+fn imports_tracks_of_an_exported_song() {
+    let dir = output_dir("tracks");
+    let (lines, plan) = round_trip(&dir);
+
+    assert_eq!(lines[0]["ok"], true);
+    assert_eq!(
+        lines[0]["file"],
+        dir.join("Song.json").display().to_string()
+    );
+    assert_eq!(lines[0]["countTracks"], 5);
+    assert_eq!(lines[0]["countNotes"], 9);
+    assert_eq!(lines[0]["countPlugins"], 4);
+    assert_eq!(
+        lines[0]["skipped"],
+        json!([{
+            "track": 1,
+            "element": "channelController",
+            "reason": "Expression lanes are not imported"
+        }])
+    );
+    assert_eq!(lines[1]["summary"]["ok"], 1);
+    assert_eq!(lines[1]["os"], env::consts::OS);
+
+    assert_eq!(plan["name"], "Convert Test");
+    assert_eq!(plan["bpm"], 120.0);
+    assert_eq!(plan["signatureNumerator"], 3);
+
+    // Group members before their group, the master before the sends
+    let tracks: Vec<(&str, &str)> = plan["tracks"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|track| {
+            (
+                track["name"].as_str().unwrap(),
+                track["type"].as_str().unwrap(),
+            )
+        })
+        .collect();
+    assert_eq!(
+        tracks,
+        [
+            ("Lead", "sequencer"),
+            ("Beats Drums", "sequencer"),
+            ("Group", "group"),
+            ("Master", "master"),
+            ("Reverb", "send")
+        ]
+    );
+    let lead = &plan["tracks"][0];
+    assert_eq!(lead["color"], json!([255, 128, 0]));
+    assert_eq!(lead["volume"], 0.5);
+    assert_eq!(lead["panning"], 0.25);
+    assert_eq!(lead["noteColumns"], 2);
+    assert_eq!(lead["instrument"], 1);
+    assert_eq!(
+        lead["sends"],
+        json!([{"track": 5, "amount": 0.5, "panning": 0.5, "enabled": true}])
+    );
+    assert_eq!(plan["tracks"][1]["group"], 3);
+    assert_eq!(plan["tracks"][1]["muted"], true);
+}
+
+#[test]
+fn imports_patterns_and_sequence() {
+    let dir = output_dir("patterns");
+    let (_, plan) = round_trip(&dir);
+
+    // Cut at the marker of "Part B", the rest fits into one pattern
+    let lengths: Vec<u64> = plan["patterns"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|pattern| pattern["numberOfLines"].as_u64().unwrap())
+        .collect();
+    assert_eq!(lengths, [16, 32]);
+    assert_eq!(
+        plan["sequence"],
+        json!([
+            {"pattern": 1, "sectionName": "Intro", "mutedTracks": []},
+            {"pattern": 2, "sectionName": "Part B", "mutedTracks": [2]}
+        ])
+    );
+
+    let lead_lines = &plan["patterns"][0]["tracks"][0]["lines"];
+    assert_eq!(
+        lead_lines[0]["noteColumns"],
+        json!([
+            {"column": 1, "note": "C-4", "instrument": "00", "volume": "40"},
+            {"column": 2, "note": "E-4", "instrument": "00", "volume": "7F"}
+        ])
+    );
+    // Poly pressure, the note-off with the release velocity and the delay
+    assert_eq!(
+        lead_lines[1],
+        json!({"index": 3, "noteColumns": [{"column": 1, "volume": "20"}]})
+    );
+    assert_eq!(
+        lead_lines[2],
+        json!({"index": 5, "noteColumns": [{"column": 1, "note": "OFF", "volume": "7F"}]})
+    );
+    assert_eq!(lead_lines[3]["noteColumns"][0]["delay"], "80");
+}
+
+#[test]
+fn imports_automation_per_sequence_slot() {
+    let dir = output_dir("automation");
+    let (lines, plan) = round_trip(&dir);
+
+    assert_eq!(lines[0]["countAutomationPoints"], 11);
+    let delay = &plan["automation"][0];
+    assert_eq!(delay["track"], 1);
+    assert_eq!(delay["device"], 2);
+    assert_eq!(delay["parameterId"], 2);
+    assert_eq!(delay["playMode"], "lines");
+    // The ramp through the end of the first pattern
+    assert_eq!(
+        delay["points"][2],
+        json!({"sequence": 1, "time": 16.99609375, "value": 0.500244140625})
+    );
+    assert_eq!(
+        delay["points"][3],
+        json!({"sequence": 2, "time": 1.0, "value": 0.5})
+    );
+
+    let instrument = &plan["automation"][1];
+    assert_eq!(instrument["device"], Value::Null);
+    assert_eq!(instrument["instrument"], 1);
+    assert_eq!(instrument["playMode"], "points");
+    assert_eq!(
+        instrument["points"][2],
+        json!({"sequence": 2, "time": 1.0, "value": 0.25})
+    );
+}
+
+#[test]
+fn writes_plugin_states_as_parameter_chunks() {
+    let dir = output_dir("plugins");
+    let (_, plan) = round_trip(&dir);
+
+    let synth = &plan["instruments"][0];
+    assert_eq!(synth["track"], 1);
+    assert_eq!(synth["plugin"]["pluginType"], "VST3");
+    assert_eq!(synth["plugin"]["parameterChunk"], "VlNUM0luc3RydW1lbnQ=");
+
+    // The chunk of the .fxp is the ParameterChunk of Song.xml
+    let vst2 = &plan["tracks"][1]["devices"][0];
+    assert_eq!(vst2["pluginType"], "VST");
+    assert_eq!(vst2["identifier"], "Test Chunks");
+    assert_eq!(vst2["parameterChunk"], "RFBQMgAAAD8AAAAAAACAPw==");
+
+    // A binary plist, as Renoise saves it
+    let au = &plan["tracks"][4]["devices"][0];
+    assert_eq!(au["identifier"], "aufx:MyId:Manu");
+    assert!(
+        au["parameterChunk"]
+            .as_str()
+            .unwrap()
+            .starts_with("YnBsaXN0MD")
+    );
+}
+
+#[test]
+fn imports_projects_of_other_applications() {
+    let dir = output_dir("foreign");
+    let project = dir.join("Foreign.dawproject");
+    write_project(
+        &project,
+        &foreign_project(),
+        &[("plugins/synth.fxp", fxp_program(b"TstP", &[0.25, 0.75]))],
+    );
+
+    let lines = run(&["import", project.to_str().unwrap(), "--pattern-lines", "16"]);
+    let plan = plan(&dir.join("Foreign.json"));
+
+    assert_eq!(lines[0]["ok"], true);
+    // 13 notes of a chord for 12 note columns and a key above B-9
+    assert_eq!(lines[0]["countNotes"], 14);
+    assert_eq!(lines[0]["countDroppedNotes"], 2);
+    assert_eq!(
+        lines[0]["skipped"],
+        json!([
+            {"track": 1, "element": "EQ", "reason": "Built-in device of another application"},
+            {"track": 1, "element": "Clap Delay", "reason": "The state is not in the archive"},
+            {"track": 1, "element": "Notes", "reason": "Notes beyond 12 note columns are dropped"},
+            {"track": 1, "element": "Notes", "reason": "Keys above B-9 are not imported"}
+        ])
+    );
+    assert_eq!(plan["bpm"], 90.0);
+    assert_eq!(plan["signatureDenominator"], 8);
+
+    // A master is added after the bus
+    let keys = &plan["tracks"][0];
+    assert_eq!(keys["group"], 2);
+    assert_eq!(keys["color"], json!([16, 32, 48]));
+    assert_eq!(keys["panning"], 0.25);
+    assert_eq!(keys["noteColumns"], 12);
+    assert_eq!(plan["tracks"][2]["type"], "master");
+    assert_eq!(plan["tracks"][2]["name"], "Master");
+
+    let synth = &plan["instruments"][0]["plugin"];
+    assert_eq!(synth["identifier"], "Test Params");
+    assert_eq!(synth["parameterChunk"], Value::Null);
+    assert_eq!(synth["parameters"], json!([0.25, 0.75]));
+    assert_eq!(keys["devices"][0]["pluginType"], "CLAP");
+
+    // The clip starts at line 13, the patterns are cut at the marker
+    let sequence = plan["sequence"].as_array().unwrap();
+    assert_eq!(sequence.len(), 4);
+    assert_eq!(sequence[2]["sectionName"], "Drop");
+    assert_eq!(sequence[3]["mutedTracks"], json!([1]));
+    let chord = &plan["patterns"][0]["tracks"][0]["lines"][0];
+    assert_eq!(chord["index"], 13);
+    assert_eq!(chord["noteColumns"].as_array().unwrap().len(), 12);
+    // The next note of the first column needs no note-off
+    let next = &plan["patterns"][1]["tracks"][0]["lines"][0]["noteColumns"];
+    assert_eq!(next[0]["note"], "C-5");
+    assert_eq!(next[1]["note"], "OFF");
+
+    let cutoff = &plan["automation"][0];
+    assert_eq!(cutoff["instrument"], 1);
+    assert_eq!(cutoff["parameterId"], 4);
+    assert_eq!(
+        cutoff["points"][0],
+        json!({"sequence": 1, "time": 13.0, "value": 0.5})
+    );
+}
+
+#[test]
+fn caps_the_sequence_at_1000_slots() {
+    let dir = output_dir("long");
+    let project = dir.join("Long.dawproject");
+    write_project(
+        &project,
+        r#"<?xml version="1.0" encoding="UTF-8"?>
+<Project version="1.0">
+  <Structure>
+    <Track id="keys" name="Keys" contentType="notes">
+      <Channel id="keys-channel" role="regular"/>
+    </Track>
+  </Structure>
+  <Arrangement>
+    <Markers timeUnit="beats">
+      <Marker time="1e9" name="Far away"/>
+    </Markers>
+  </Arrangement>
+</Project>
+"#,
+        &[],
+    );
+
+    let lines = run(&["import", project.to_str().unwrap()]);
+    let plan = plan(&dir.join("Long.json"));
+
+    assert_eq!(lines[0]["ok"], true);
+    assert_eq!(lines[0]["countPatterns"], 1000);
+    assert_eq!(
+        lines[0]["skipped"],
+        json!([{
+            "track": null,
+            "element": "Arrangement",
+            "reason": "Content beyond 1000 sequence slots is not imported"
+        }])
+    );
+    assert_eq!(plan["sequence"].as_array().unwrap().len(), 1000);
+}
+
+#[test]
+fn reports_files_which_are_no_dawproject() {
+    let dir = output_dir("batch");
+    let song = fixture("Song.xml");
+
+    let lines = run(&["import", &song, "--output-dir", dir.to_str().unwrap()]);
+
+    assert_eq!(lines[0]["ok"], false);
+    assert_eq!(lines[0]["error"], format!("{} is no DAWproject", song));
+    assert_eq!(lines[1]["summary"]["failed"], 1);
+}
@@ -0,0 +1,202 @@
+/*!
+ * ------------------------------------------------------------------------
+ * AudioUnit identifier codec
+ * by Jurek Raben
+ *
+ * An AudioUnit is identified by the type, subtype and manufacturer
+ * four-char codes of its component description. The characters are Mac
+ * Roman, codes shorter than four characters are padded with spaces. The
+ * DAWproject deviceID of an AuPlugin differs by DAW:
+ *
+ * - plain: the three codes separated by colons, e.g. "aufx:MyId:Manu",
+ *   as the PluginIdentifier of Renoise and the arguments of auval
+ * - bitwig: the three codes in one, e.g. "aufxMyIdManu"
+ * - studio-one: 16 bytes as hex, the type, the two halves of the subtype
+ *   swapped, the reversed manufacturer and "BALK"
+ *
+ * Licensed under CC Attribution-NonCommercial-ShareAlike 4.0 International
+ * Info here: https://creativecommons.org/licenses/by-nc-sa/4.0/
+ * ------------------------------------------------------------------------
+ */
+
+use std::fmt;
+
+use serde::Serialize;
+
+const CODE_LEN: usize = 4;
+const SEPARATOR: char = ':';
+
+// Appended by Studio One to every AudioUnit ID
+const STUDIO_ONE_SUFFIX: &[u8; 4] = b"BALK";
+const STUDIO_ONE_LEN: usize = 32;
+
+// Mac Roman characters 0x80 to 0xFF
+const MAC_ROMAN_HIGH: &str = concat!(
+    "ÄÅÇÉÑÖÜáàâäãåçéè",
+    "êëíìîïñóòôöõúùûü",
+    "†°¢£§•¶ß®©™´¨≠ÆØ",
+    "∞±≤≥¥µ∂∑∏π∫ªºΩæø",
+    "¿¡¬√ƒ≈∆«»…\u{A0}ÀÃÕŒœ",
+    "–—“”‘’÷◊ÿŸ⁄€‹›ﬁﬂ",
+    "‡·‚„‰ÂÊÁËÈÍÎÏÌÓÔ",
+    "\u{F8FF}ÒÚÛÙıˆ˜¯˘˙˚¸˝˛ˇ",
+);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct FourCharCode(pub u32);
+
+impl FourCharCode {
+    // One to four Mac Roman characters, padded with spaces
+    pub fn parse(code: &str) -> Option<FourCharCode> {
+        let mut bytes = code
+            .chars()
+            .map(mac_roman_byte)
+            .collect::<Option<Vec<u8>>>()?;
+        if bytes.is_empty() || bytes.len() > CODE_LEN {
+            return None;
+        }
+        bytes.resize(CODE_LEN, b' ');
+        Some(FourCharCode(u32::from_be_bytes(bytes.try_into().ok()?)))
+    }
+
+    pub fn bytes(&self) -> [u8; 4] {
+        self.0.to_be_bytes()
+    }
+}
+
+impl fmt::Display for FourCharCode {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let code: String = self.bytes().into_iter().map(mac_roman_char).collect();
+        f.write_str(&code)
+    }
+}
+
+impl Serialize for FourCharCode {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AuComponent {
+    #[serde(rename = "type")]
+    pub component_type: FourCharCode,
+    pub subtype: FourCharCode,
+    pub manufacturer: FourCharCode,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum DeviceIdForm {
+    Plain,
+    Bitwig,
+    StudioOne,
+}
+
+impl AuComponent {
+    pub fn device_id(&self, form: DeviceIdForm) -> String {
+        match form {
+            DeviceIdForm::Plain => format!(
+                "{}{sep}{}{sep}{}",
+                self.component_type,
+                self.subtype,
+                self.manufacturer,
+                sep = SEPARATOR
+            ),
+            DeviceIdForm::Bitwig => {
+                format!(
+                    "{}{}{}",
+                    self.component_type, self.subtype, self.manufacturer
+                )
+            }
+            DeviceIdForm::StudioOne => {
+                let subtype = self.subtype.bytes();
+                let mut manufacturer = self.manufacturer.bytes();
+                manufacturer.reverse();
+
+                let mut bytes = self.component_type.bytes().to_vec();
+                bytes.extend(&subtype[2..]);
+                bytes.extend(&subtype[..2]);
+                bytes.extend(manufacturer);
+                bytes.extend(STUDIO_ONE_SUFFIX);
+                bytes.iter().map(|byte| format!("{:02X}", byte)).collect()
+            }
+        }
+    }
+}
+
+// Detects the form of a deviceID and decodes it
+pub fn parse_device_id(device_id: &str) -> Option<(AuComponent, DeviceIdForm)> {
+    let chars: Vec<char> = device_id.chars().collect();
+
+    if chars.len() == STUDIO_ONE_LEN && chars.iter().all(char::is_ascii_hexdigit) {
+        return parse_studio_one(device_id).map(|component| (component, DeviceIdForm::StudioOne));
+    }
+    // Codes at fixed positions first, as codes may contain the separator
+    if chars.len() == 3 * CODE_LEN + 2 && chars[4] == SEPARATOR && chars[9] == SEPARATOR {
+        let codes = [&chars[0..4], &chars[5..9], &chars[10..14]];
+        return component_of(codes).map(|component| (component, DeviceIdForm::Plain));
+    }
+    // Plain form with the padding trimmed, before the Bitwig form as
+    // "aumu:Syn:Man" has the length of a Bitwig ID as well
+    let codes: Vec<Vec<char>> = device_id
+        .split(SEPARATOR)
+        .map(|code| code.chars().collect())
+        .collect();
+    if let [component_type, subtype, manufacturer] = codes.as_slice()
+        && let Some(component) = component_of([component_type, subtype, manufacturer])
+    {
+        return Some((component, DeviceIdForm::Plain));
+    }
+    if chars.len() == 3 * CODE_LEN {
+        let codes = [&chars[0..4], &chars[4..8], &chars[8..12]];
+        return component_of(codes).map(|component| (component, DeviceIdForm::Bitwig));
+    }
+    None
+}
+
+fn component_of(codes: [&[char]; 3]) -> Option<AuComponent> {
+    let code = |chars: &[char]| FourCharCode::parse(&chars.iter().collect::<String>());
+    Some(AuComponent {
+        component_type: code(codes[0])?,
+        subtype: code(codes[1])?,
+        manufacturer: code(codes[2])?,
+    })
+}
+
+fn parse_studio_one(device_id: &str) -> Option<AuComponent> {
+    let bytes = (0..device_id.len())
+        .step_by(2)
+        .map(|start| u8::from_str_radix(&device_id[start..start + 2], 16).ok())
+        .collect::<Option<Vec<u8>>>()?;
+    if &bytes[12..16] != STUDIO_ONE_SUFFIX {
+        return None;
+    }
+    let code = |bytes: [u8; 4]| FourCharCode(u32::from_be_bytes(bytes));
+    Some(AuComponent {
+        component_type: code([bytes[0], bytes[1], bytes[2], bytes[3]]),
+        subtype: code([bytes[6], bytes[7], bytes[4], bytes[5]]),
+        manufacturer: code([bytes[11], bytes[10], bytes[9], bytes[8]]),
+    })
+}
+
+fn mac_roman_char(byte: u8) -> char {
+    match byte {
+        0..0x80 => byte as char,
+        _ => MAC_ROMAN_HIGH
+            .chars()
+            .nth(byte as usize - 0x80)
+            .unwrap_or(char::REPLACEMENT_CHARACTER),
+    }
+}
+
+fn mac_roman_byte(c: char) -> Option<u8> {
+    if c.is_ascii() {
+        return Some(c as u8);
+    }
+    MAC_ROMAN_HIGH
+        .chars()
+        .position(|high| high == c)
+        .map(|index| (index + 0x80) as u8)
+}
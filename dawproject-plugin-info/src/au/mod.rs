@@ -0,0 +1,15 @@
+/*!
+ * ------------------------------------------------------------------------
+ * AudioUnit support
+ * by Jurek Raben
+ *
+ * AudioUnits are not loaded, only their .aupreset files are read and
+ * written, so this works on every platform.
+ *
+ * Licensed under CC Attribution-NonCommercial-ShareAlike 4.0 International
+ * Info here: https://creativecommons.org/licenses/by-nc-sa/4.0/
+ * ------------------------------------------------------------------------
+ */
+
+pub mod identifier;
+pub mod preset;
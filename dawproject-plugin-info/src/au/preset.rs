@@ -0,0 +1,157 @@
+/*!
+ * ------------------------------------------------------------------------
+ * AudioUnit .aupreset reader and writer
+ * by Jurek Raben
+ *
+ * An .aupreset is a property list, binary ("bplist00") as saved by Renoise
+ * or XML. The plugin state is the "data" key, the component is described
+ * by the "type", "subtype" and "manufacturer" four-char codes, stored as
+ * signed 32 bit integers. Written back as XML plist, like by
+ * `plutil -convert xml1`, without needing macOS.
+ *
+ * Licensed under CC Attribution-NonCommercial-ShareAlike 4.0 International
+ * Info here: https://creativecommons.org/licenses/by-nc-sa/4.0/
+ * ------------------------------------------------------------------------
+ */
+
+use std::fs;
+use std::io::Cursor;
+use std::path::Path;
+
+use plist::{Dictionary, Integer, Value};
+use serde::Serialize;
+
+use super::identifier::FourCharCode;
+
+pub const BINARY_MAGIC: &[u8; 8] = b"bplist00";
+
+pub const DATA_KEY: &str = "data";
+pub const NAME_KEY: &str = "name";
+
+// Keys of the component description, in the order of the Renoise device ID
+pub const TYPE_KEY: &str = "type";
+pub const SUBTYPE_KEY: &str = "subtype";
+pub const MANUFACTURER_KEY: &str = "manufacturer";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PlistFormat {
+    Binary,
+    Xml,
+}
+
+#[derive(Debug, Clone)]
+pub struct AuPreset {
+    // Format of the file read
+    pub format: PlistFormat,
+    dictionary: Dictionary,
+}
+
+pub fn read_preset(path: &Path) -> Result<AuPreset, String> {
+    let data = fs::read(path).map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+    parse_preset(&data)
+}
+
+pub fn parse_preset(data: &[u8]) -> Result<AuPreset, String> {
+    let (format, value) = parse_plist(data)?;
+    let dictionary = value
+        .into_dictionary()
+        .ok_or("Not an .aupreset file, the plist is no dictionary")?;
+    Ok(AuPreset { format, dictionary })
+}
+
+// Any plist, binary or XML, converted to XML
+pub fn plist_to_xml(data: &[u8]) -> Result<Vec<u8>, String> {
+    let (_, value) = parse_plist(data)?;
+    write_xml(&value)
+}
+
+// Any plist converted to binary, as Renoise saves the AudioUnit states
+pub fn plist_to_binary(data: &[u8]) -> Result<Vec<u8>, String> {
+    let (_, value) = parse_plist(data)?;
+    let mut binary = Vec::new();
+    value
+        .to_writer_binary(&mut binary)
+        .map_err(|e| format!("Failed to write plist: {}", e))?;
+    Ok(binary)
+}
+
+fn parse_plist(data: &[u8]) -> Result<(PlistFormat, Value), String> {
+    let format = if data.starts_with(BINARY_MAGIC) {
+        PlistFormat::Binary
+    } else {
+        PlistFormat::Xml
+    };
+    let value =
+        Value::from_reader(Cursor::new(data)).map_err(|e| format!("Not a plist file: {}", e))?;
+    Ok((format, value))
+}
+
+fn write_xml(value: &Value) -> Result<Vec<u8>, String> {
+    let mut xml = Vec::new();
+    value
+        .to_writer_xml(&mut xml)
+        .map_err(|e| format!("Failed to write plist: {}", e))?;
+    // plutil ends the file with a line break
+    xml.push(b'\n');
+    Ok(xml)
+}
+
+impl AuPreset {
+    pub fn data(&self) -> Option<&[u8]> {
+        self.dictionary.get(DATA_KEY)?.as_data()
+    }
+
+    pub fn name(&self) -> Option<&str> {
+        self.dictionary.get(NAME_KEY)?.as_string()
+    }
+
+    // type, subtype or manufacturer, negative values are codes with the
+    // high bit set
+    pub fn code(&self, key: &str) -> Option<FourCharCode> {
+        let code = match self.dictionary.get(key)? {
+            Value::Integer(integer) => integer
+                .as_signed()
+                .map(|code| code as u32)
+                .or_else(|| integer.as_unsigned().map(|code| code as u32)),
+            _ => None,
+        }?;
+        Some(FourCharCode(code))
+    }
+
+    pub fn set_data(&mut self, data: Vec<u8>) {
+        self.dictionary
+            .insert(DATA_KEY.to_string(), Value::Data(data));
+    }
+
+    pub fn set_name(&mut self, name: &str) {
+        self.dictionary
+            .insert(NAME_KEY.to_string(), Value::String(name.to_string()));
+    }
+
+    pub fn set_code(&mut self, key: &str, code: FourCharCode) {
+        self.dictionary.insert(
+            key.to_string(),
+            Value::Integer(Integer::from(code.0 as i32)),
+        );
+    }
+
+    pub fn to_xml(&self) -> Result<Vec<u8>, String> {
+        write_xml(&Value::Dictionary(self.dictionary.clone()))
+    }
+}
+
+// A four-char code like "aufx", or the code as decimal or 0x hex number
+pub fn parse_four_char_code(code: &str) -> Option<FourCharCode> {
+    if code.chars().count() == 4 {
+        return FourCharCode::parse(code);
+    }
+    let number = match code.strip_prefix("0x") {
+        Some(hex) => u32::from_str_radix(hex, 16).ok(),
+        None => code
+            .parse::<u32>()
+            .ok()
+            .or_else(|| code.parse::<i32>().ok().map(|code| code as u32)),
+    };
+    number.map(FourCharCode)
+}
@@ -0,0 +1,171 @@
+/*!
+ * ------------------------------------------------------------------------
+ * Persistent plugin info cache
+ * by Jurek Raben
+ *
+ * Stores the info output per plugin binary in a JSON file. An entry is
+ * only used while the binary's modification time and size and the tool
+ * version are unchanged, so updated plugins are inspected again. Several
+ * processes may use the same file, e.g. a scan next to single info calls,
+ * every save merges the own changes into the current file.
+ *
+ * Licensed under CC Attribution-NonCommercial-ShareAlike 4.0 International
+ * Info here: https://creativecommons.org/licenses/by-nc-sa/4.0/
+ * ------------------------------------------------------------------------
+ */
+
+use std::collections::HashSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process;
+use std::time::UNIX_EPOCH;
+
+use serde_json::{Map, Value, json};
+
+const TOOL_VERSION: &str = env!("CARGO_PKG_VERSION");
+
+pub struct PluginCache {
+    file: PathBuf,
+    entries: Map<String, Value>,
+    // Keys changed since opening, only these are written over the file
+    inserted: HashSet<String>,
+    removed: HashSet<String>,
+}
+
+// A missing or unreadable cache file counts as empty
+fn read_entries(file: &Path) -> Map<String, Value> {
+    fs::read_to_string(file)
+        .ok()
+        .and_then(|content| serde_json::from_str(&content).ok())
+        .unwrap_or_default()
+}
+
+// Modification time in milliseconds and size of a binary
+fn file_stamp(binary: &Path) -> Option<(u64, u64)> {
+    let metadata = fs::metadata(binary).ok()?;
+    let modified = metadata
+        .modified()
+        .ok()?
+        .duration_since(UNIX_EPOCH)
+        .ok()?
+        .as_millis() as u64;
+    Some((modified, metadata.len()))
+}
+
+fn cache_key(binary: &Path) -> String {
+    fs::canonicalize(binary)
+        .unwrap_or_else(|_| binary.to_path_buf())
+        .display()
+        .to_string()
+}
+
+fn is_valid(key: &str, entry: &Value) -> bool {
+    let Some((modified, size)) = file_stamp(Path::new(key)) else {
+        return false;
+    };
+    entry["modified"].as_u64() == Some(modified)
+        && entry["size"].as_u64() == Some(size)
+        && entry["toolVersion"].as_str() == Some(TOOL_VERSION)
+}
+
+impl PluginCache {
+    // Each tool keeps its own file in the folder, e.g. vst2info-cache.json.
+    // A missing or unreadable cache file starts an empty cache.
+    pub fn open(dir: &Path, file_name: &str) -> Result<PluginCache, String> {
+        fs::create_dir_all(dir)
+            .map_err(|e| format!("Failed to create cache folder {}: {}", dir.display(), e))?;
+        let file = dir.join(file_name);
+        Ok(PluginCache {
+            entries: read_entries(&file),
+            file,
+            inserted: HashSet::new(),
+            removed: HashSet::new(),
+        })
+    }
+
+    pub fn get(&self, binary: &Path) -> Option<Value> {
+        let key = cache_key(binary);
+        let entry = self.entries.get(&key)?;
+        if !is_valid(&key, entry) {
+            return None;
+        }
+        Some(entry["info"].clone())
+    }
+
+    pub fn insert(&mut self, binary: &Path, info: Value) {
+        let key = cache_key(binary);
+        let Some((modified, size)) = file_stamp(Path::new(&key)) else {
+            return;
+        };
+        self.removed.remove(&key);
+        self.inserted.insert(key.clone());
+        self.entries.insert(
+            key,
+            json!({
+                "modified": modified,
+                "size": size,
+                "toolVersion": TOOL_VERSION,
+                "info": info
+            }),
+        );
+    }
+
+    // The own changes are merged into the file as it is now, so entries
+    // saved by other processes meanwhile are kept. Written to a temporary
+    // file of this process first, an interrupted write keeps the previous
+    // cache.
+    pub fn save(&self) -> Result<(), String> {
+        let mut entries = read_entries(&self.file);
+        for key in &self.removed {
+            entries.shift_remove(key);
+        }
+        for key in &self.inserted {
+            if let Some(entry) = self.entries.get(key) {
+                entries.insert(key.clone(), entry.clone());
+            }
+        }
+
+        let temp_file = self
+            .file
+            .with_extension(format!("json.{}.tmp", process::id()));
+        fs::write(&temp_file, Value::Object(entries).to_string())
+            .and_then(|_| fs::rename(&temp_file, &self.file))
+            .map_err(|e| {
+                let _ = fs::remove_file(&temp_file);
+                format!("Failed to write {}: {}", self.file.display(), e)
+            })
+    }
+
+    pub fn list(&self) -> Vec<Value> {
+        self.entries
+            .iter()
+            .map(|(key, entry)| {
+                json!({
+                    "path": key,
+                    "name": entry["info"]["name"],
+                    "toolVersion": entry["toolVersion"],
+                    "modified": entry["modified"],
+                    "size": entry["size"],
+                    "valid": is_valid(key, entry)
+                })
+            })
+            .collect()
+    }
+
+    // Removes entries of deleted or updated binaries and of other tool
+    // versions, returns the removed paths
+    pub fn prune(&mut self) -> Vec<String> {
+        let stale: Vec<String> = self
+            .entries
+            .iter()
+            .filter(|(key, entry)| !is_valid(key, entry))
+            .map(|(key, _)| key.to_string())
+            .collect();
+        for key in &stale {
+            self.entries.shift_remove(key);
+            self.inserted.remove(key);
+            self.removed.insert(key.clone());
+        }
+        stale
+    }
+}
@@ -0,0 +1,90 @@
+/*!
+ * ------------------------------------------------------------------------
+ * Child processes of the helper tools
+ * by Jurek Raben
+ *
+ * Plugins are loaded in child processes of the tool itself, so a crashing
+ * or hanging plugin does not take the caller down with it.
+ *
+ * Licensed under CC Attribution-NonCommercial-ShareAlike 4.0 International
+ * Info here: https://creativecommons.org/licenses/by-nc-sa/4.0/
+ * ------------------------------------------------------------------------
+ */
+
+use std::env;
+use std::ffi::OsStr;
+use std::io::Read;
+use std::process::{Child, Command, ExitStatus, Stdio};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+use serde_json::Value;
+
+const POLL_INTERVAL: Duration = Duration::from_millis(20);
+
+// Runs the tool again with the given arguments and returns the JSON it
+// printed. The child is killed if it does not finish within the timeout.
+pub fn run_child(args: &[&OsStr], timeout: Duration) -> Result<Value, String> {
+    let exe = env::current_exe().map_err(|e| format!("Failed to find the tool itself: {}", e))?;
+    let mut child = Command::new(exe)
+        .args(args)
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|e| format!("Failed to start the inspection: {}", e))?;
+
+    // Read both pipes while waiting, a full pipe would block the child
+    let stdout = read_in_background(child.stdout.take());
+    let stderr = read_in_background(child.stderr.take());
+
+    let status = wait_with_timeout(&mut child, timeout);
+    let stdout = stdout.join().unwrap_or_default();
+    let stderr = stderr.join().unwrap_or_default();
+
+    let status = status?;
+    if status.success() {
+        return find_json(&stdout).ok_or_else(|| "No plugin info in the output".to_string());
+    }
+
+    match find_json(&stderr).and_then(|error| error["error"].as_str().map(String::from)) {
+        Some(error) => Err(error),
+        None => Err(format!("Plugin process failed ({})", status)),
+    }
+}
+
+fn read_in_background<R: Read + Send + 'static>(pipe: Option<R>) -> JoinHandle<String> {
+    thread::spawn(move || {
+        let mut output = String::new();
+        if let Some(mut pipe) = pipe {
+            let mut bytes = Vec::new();
+            let _ = pipe.read_to_end(&mut bytes);
+            output = String::from_utf8_lossy(&bytes).to_string();
+        }
+        output
+    })
+}
+
+pub fn wait_with_timeout(child: &mut Child, timeout: Duration) -> Result<ExitStatus, String> {
+    let started = Instant::now();
+    loop {
+        match child.try_wait() {
+            Ok(Some(status)) => return Ok(status),
+            Ok(None) if started.elapsed() >= timeout => {
+                let _ = child.kill();
+                let _ = child.wait();
+                return Err(format!("Timed out after {} seconds", timeout.as_secs()));
+            }
+            Ok(None) => thread::sleep(POLL_INTERVAL),
+            Err(e) => return Err(format!("Failed to wait for the inspection: {}", e)),
+        }
+    }
+}
+
+// The tool prints its JSON as one line, plugins might print around it
+pub fn find_json(output: &str) -> Option<Value> {
+    output.lines().rev().find_map(|line| {
+        let start = line.find("{\"")?;
+        serde_json::from_str(&line[start..]).ok()
+    })
+}
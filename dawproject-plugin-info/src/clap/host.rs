@@ -0,0 +1,42 @@
+/*!
+ * ------------------------------------------------------------------------
+ * CLAP host context
+ * by Jurek Raben
+ *
+ * The clap_host handed to create_plugin. It only reports a name, no
+ * host extensions are offered and all requests of the plugin are
+ * ignored, the plugin is never processed anyway.
+ *
+ * Licensed under CC Attribution-NonCommercial-ShareAlike 4.0 International
+ * Info here: https://creativecommons.org/licenses/by-nc-sa/4.0/
+ * ------------------------------------------------------------------------
+ */
+
+use std::ffi::{c_char, c_void};
+use std::ptr;
+
+use clap_sys::host::clap_host;
+use clap_sys::version::CLAP_VERSION;
+
+// Lives as long as the process, so it outlasts every plugin instance
+pub static HOST: clap_host = clap_host {
+    clap_version: CLAP_VERSION,
+    host_data: ptr::null_mut(),
+    name: c"DawProject Plugin Info".as_ptr(),
+    vendor: c"Jurek Raben".as_ptr(),
+    url: c"https://github.com/Jurek-Raben/DawProject-Tool".as_ptr(),
+    version: concat!(env!("CARGO_PKG_VERSION"), "\0").as_ptr() as *const c_char,
+    get_extension: Some(get_extension),
+    request_restart: Some(ignore_request),
+    request_process: Some(ignore_request),
+    request_callback: Some(ignore_request),
+};
+
+unsafe extern "C" fn get_extension(
+    _host: *const clap_host,
+    _extension_id: *const c_char,
+) -> *const c_void {
+    ptr::null()
+}
+
+unsafe extern "C" fn ignore_request(_host: *const clap_host) {}
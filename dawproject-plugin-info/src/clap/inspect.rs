@@ -0,0 +1,115 @@
+/*!
+ * ------------------------------------------------------------------------
+ * CLAP plugin inspection
+ * by Jurek Raben
+ *
+ * Creates a plugin of a .clap file, reads its descriptor and the
+ * clap.params, clap.audio-ports, clap.note-ports and clap.latency
+ * extensions, and destroys it again.
+ *
+ * Licensed under CC Attribution-NonCommercial-ShareAlike 4.0 International
+ * Info here: https://creativecommons.org/licenses/by-nc-sa/4.0/
+ * ------------------------------------------------------------------------
+ */
+
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+
+use super::plugin::{ClapAudioPort, ClapDescriptor, ClapLibrary, ClapNotePort, ClapParameter};
+use crate::vst3::stdio;
+use crate::{Format, ParameterInfo, PluginError, PluginInfo};
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ClapDetails {
+    // All plugins of the file
+    pub plugins: Vec<ClapDescriptor>,
+    // The inspected one of them
+    pub plugin: ClapDescriptor,
+    pub audio_inputs: Vec<ClapAudioPort>,
+    pub audio_outputs: Vec<ClapAudioPort>,
+    pub note_inputs: Vec<ClapNotePort>,
+    pub note_outputs: Vec<ClapNotePort>,
+    pub parameters: Vec<ClapParameter>,
+    // In samples, null without clap.latency
+    pub latency: Option<u32>,
+}
+
+// The plugin with the given ID, or the first plugin of the file
+pub fn inspect_plugin(binary: &Path, plugin_id: Option<&str>) -> Result<PluginInfo, PluginError> {
+    inspect_details(binary, plugin_id).map(plugin_info)
+}
+
+pub fn inspect_details(binary: &Path, plugin_id: Option<&str>) -> Result<ClapDetails, PluginError> {
+    stdio::suppress_output(|| {
+        let library = ClapLibrary::load(binary)?;
+        let plugins = library.descriptors();
+        let plugin = library.create_plugin(plugin_id)?;
+        let descriptor = plugin
+            .descriptor()
+            .ok_or_else(|| PluginError::ComponentError("Plugin has no descriptor".to_string()))?;
+
+        Ok(ClapDetails {
+            plugins,
+            plugin: descriptor,
+            audio_inputs: plugin.audio_ports(true),
+            audio_outputs: plugin.audio_ports(false),
+            note_inputs: plugin.note_ports(true),
+            note_outputs: plugin.note_ports(false),
+            parameters: plugin.parameters(),
+            latency: plugin.latency(),
+        })
+    })
+}
+
+// The format independent info, with normalized parameter values. Ports
+// are counted like the buses of VST3, audio and note ports together.
+pub fn plugin_info(details: ClapDetails) -> PluginInfo {
+    let parameters = details
+        .parameters
+        .iter()
+        .enumerate()
+        .map(|(index, param)| ParameterInfo {
+            index: index as i32,
+            id: param.id,
+            name: param.name.clone(),
+            units: String::new(),
+            display: param.display.clone(),
+            step_count: if has_flag(param, "stepped") {
+                (param.max_value - param.min_value).round() as i32
+            } else {
+                0
+            },
+            value: normalize(param, param.value),
+            default_value: normalize(param, param.default_value),
+            automatable: has_flag(param, "automatable"),
+        })
+        .collect();
+
+    PluginInfo {
+        format: Format::Clap,
+        name: details.plugin.name,
+        vendor: details.plugin.vendor,
+        version: details.plugin.version,
+        id: details.plugin.id,
+        category: details.plugin.features.join("|"),
+        count_inputs: (details.audio_inputs.len() + details.note_inputs.len()) as i32,
+        count_outputs: (details.audio_outputs.len() + details.note_outputs.len()) as i32,
+        count_presets: 0,
+        parameters,
+    }
+}
+
+fn has_flag(param: &ClapParameter, flag: &str) -> bool {
+    param.flags.iter().any(|name| name == flag)
+}
+
+fn normalize(param: &ClapParameter, value: f64) -> f64 {
+    let range = param.max_value - param.min_value;
+    if range > 0.0 {
+        ((value - param.min_value) / range).clamp(0.0, 1.0)
+    } else {
+        0.0
+    }
+}
@@ -0,0 +1,122 @@
+/*!
+ * ------------------------------------------------------------------------
+ * CLAP backend
+ * by Jurek Raben
+ *
+ * A .clap file may contain several plugins, the PluginFormat functions
+ * report the first one. inspect::inspect_details lists all descriptors
+ * and the ports, parameter ranges and latency of a chosen plugin,
+ * state::apply_state loads and saves its state.
+ *
+ * Licensed under CC Attribution-NonCommercial-ShareAlike 4.0 International
+ * Info here: https://creativecommons.org/licenses/by-nc-sa/4.0/
+ * ------------------------------------------------------------------------
+ */
+
+pub mod host;
+pub mod inspect;
+pub mod plugin;
+pub mod state;
+
+use std::env;
+use std::path::{Path, PathBuf};
+
+use crate::search;
+use crate::{Format, PluginError, PluginFormat, PluginInfo};
+
+pub struct Clap;
+
+impl PluginFormat for Clap {
+    fn format(&self) -> Format {
+        Format::Clap
+    }
+
+    fn search_paths(&self) -> Vec<PathBuf> {
+        search::env_search_paths("CLAP_PATH", default_search_paths())
+    }
+
+    fn is_plugin(&self, path: &Path) -> bool {
+        path.exists() && search::has_extension(path, "clap")
+    }
+
+    fn resolve_binary(&self, path: &Path) -> Result<PathBuf, PluginError> {
+        binary_path(path)
+    }
+
+    fn inspect(&self, binary: &Path) -> Result<PluginInfo, PluginError> {
+        inspect::inspect_plugin(binary, None)
+    }
+}
+
+// The binary inside a macOS .clap bundle, or the given path if it is a
+// file, as on Linux and Windows
+pub fn binary_path(path: &Path) -> Result<PathBuf, PluginError> {
+    if path.is_file() {
+        return Ok(path.to_path_buf());
+    }
+    if !path.is_dir() || !search::has_extension(path, "clap") {
+        return Err(PluginError::PathError("Invalid CLAP path".to_string()));
+    }
+
+    let executable = search::bundle_executable(path)
+        .or_else(|| {
+            path.file_stem()
+                .map(|stem| stem.to_string_lossy().to_string())
+        })
+        .ok_or_else(|| PluginError::PathError("No binary found".to_string()))?;
+    let binary = path.join("Contents").join("MacOS").join(executable);
+    if binary.is_file() {
+        Ok(binary)
+    } else {
+        Err(PluginError::PathError("No binary found".to_string()))
+    }
+}
+
+// clap_entry.init wants the path of the bundle, not of the binary inside
+pub fn plugin_path(binary: &Path) -> PathBuf {
+    let bundle = binary
+        .parent()
+        .filter(|dir| dir.ends_with("Contents/MacOS"))
+        .and_then(|dir| dir.parent()?.parent());
+    match bundle {
+        Some(bundle) if search::has_extension(bundle, "clap") => bundle.to_path_buf(),
+        _ => binary.to_path_buf(),
+    }
+}
+
+#[cfg(target_os = "macos")]
+fn default_search_paths() -> Vec<PathBuf> {
+    let mut paths = Vec::new();
+    if let Some(home) = env::var_os("HOME").map(PathBuf::from) {
+        paths.push(home.join("Library/Audio/Plug-Ins/CLAP"));
+    }
+    paths.push(PathBuf::from("/Library/Audio/Plug-Ins/CLAP"));
+    paths
+}
+
+#[cfg(target_os = "linux")]
+fn default_search_paths() -> Vec<PathBuf> {
+    let mut paths = Vec::new();
+    if let Some(home) = env::var_os("HOME").map(PathBuf::from) {
+        paths.push(home.join(".clap"));
+    }
+    paths.push(PathBuf::from("/usr/lib/clap"));
+    paths
+}
+
+#[cfg(target_os = "windows")]
+fn default_search_paths() -> Vec<PathBuf> {
+    let mut paths = Vec::new();
+    if let Some(base) = env::var_os("LOCALAPPDATA").map(PathBuf::from) {
+        paths.push(base.join("Programs").join("Common").join("CLAP"));
+    }
+    if let Some(base) = env::var_os("CommonProgramFiles").map(PathBuf::from) {
+        paths.push(base.join("CLAP"));
+    }
+    paths
+}
+
+#[cfg(not(any(target_os = "macos", target_os = "linux", target_os = "windows")))]
+fn default_search_paths() -> Vec<PathBuf> {
+    Vec::new()
+}
@@ -0,0 +1,543 @@
+/*!
+ * ------------------------------------------------------------------------
+ * CLAP plugin loading
+ * by Jurek Raben
+ *
+ * ClapLibrary holds the loaded binary with its initialized clap_entry and
+ * plugin factory, ClapPlugin an initialized instance created from it.
+ * Dropping them destroys the instance, calls deinit and unloads the
+ * binary, in this order. The clap.state streams read from and write to
+ * memory.
+ *
+ * Licensed under CC Attribution-NonCommercial-ShareAlike 4.0 International
+ * Info here: https://creativecommons.org/licenses/by-nc-sa/4.0/
+ * ------------------------------------------------------------------------
+ */
+
+use std::ffi::{CStr, CString, c_char, c_void};
+use std::marker::PhantomData;
+use std::path::Path;
+use std::ptr;
+
+use clap_sys::entry::clap_plugin_entry;
+use clap_sys::ext::audio_ports::{
+    CLAP_AUDIO_PORT_IS_MAIN, CLAP_AUDIO_PORT_PREFERS_64BITS,
+    CLAP_AUDIO_PORT_REQUIRES_COMMON_SAMPLE_SIZE, CLAP_AUDIO_PORT_SUPPORTS_64BITS,
+    CLAP_EXT_AUDIO_PORTS, clap_plugin_audio_ports,
+};
+use clap_sys::ext::latency::{CLAP_EXT_LATENCY, clap_plugin_latency};
+use clap_sys::ext::note_ports::{
+    CLAP_EXT_NOTE_PORTS, CLAP_NOTE_DIALECT_CLAP, CLAP_NOTE_DIALECT_MIDI,
+    CLAP_NOTE_DIALECT_MIDI_MPE, CLAP_NOTE_DIALECT_MIDI2, clap_plugin_note_ports,
+};
+use clap_sys::ext::params::*;
+use clap_sys::ext::state::{CLAP_EXT_STATE, clap_plugin_state};
+use clap_sys::factory::plugin_factory::{CLAP_PLUGIN_FACTORY_ID, clap_plugin_factory};
+use clap_sys::id::CLAP_INVALID_ID;
+use clap_sys::plugin::{clap_plugin, clap_plugin_descriptor};
+use clap_sys::stream::{clap_istream, clap_ostream};
+use clap_sys::version::clap_version_is_compatible;
+use libloading::Library;
+use serde::{Deserialize, Serialize};
+
+use super::host::HOST;
+use crate::PluginError;
+
+// Used to activate the plugin for reading its latency
+const SAMPLE_RATE: f64 = 48000.0;
+const MAX_FRAMES: u32 = 1024;
+
+const DISPLAY_SIZE: usize = 256;
+
+const PARAM_FLAGS: [(u32, &str); 17] = [
+    (CLAP_PARAM_IS_STEPPED, "stepped"),
+    (CLAP_PARAM_IS_PERIODIC, "periodic"),
+    (CLAP_PARAM_IS_HIDDEN, "hidden"),
+    (CLAP_PARAM_IS_READONLY, "readonly"),
+    (CLAP_PARAM_IS_BYPASS, "bypass"),
+    (CLAP_PARAM_IS_AUTOMATABLE, "automatable"),
+    (
+        CLAP_PARAM_IS_AUTOMATABLE_PER_NOTE_ID,
+        "automatablePerNoteId",
+    ),
+    (CLAP_PARAM_IS_AUTOMATABLE_PER_KEY, "automatablePerKey"),
+    (
+        CLAP_PARAM_IS_AUTOMATABLE_PER_CHANNEL,
+        "automatablePerChannel",
+    ),
+    (CLAP_PARAM_IS_AUTOMATABLE_PER_PORT, "automatablePerPort"),
+    (CLAP_PARAM_IS_MODULATABLE, "modulatable"),
+    (
+        CLAP_PARAM_IS_MODULATABLE_PER_NOTE_ID,
+        "modulatablePerNoteId",
+    ),
+    (CLAP_PARAM_IS_MODULATABLE_PER_KEY, "modulatablePerKey"),
+    (
+        CLAP_PARAM_IS_MODULATABLE_PER_CHANNEL,
+        "modulatablePerChannel",
+    ),
+    (CLAP_PARAM_IS_MODULATABLE_PER_PORT, "modulatablePerPort"),
+    (CLAP_PARAM_REQUIRES_PROCESS, "requiresProcess"),
+    (CLAP_PARAM_IS_ENUM, "enum"),
+];
+
+const AUDIO_PORT_FLAGS: [(u32, &str); 4] = [
+    (CLAP_AUDIO_PORT_IS_MAIN, "main"),
+    (CLAP_AUDIO_PORT_SUPPORTS_64BITS, "supports64Bits"),
+    (CLAP_AUDIO_PORT_PREFERS_64BITS, "prefers64Bits"),
+    (
+        CLAP_AUDIO_PORT_REQUIRES_COMMON_SAMPLE_SIZE,
+        "requiresCommonSampleSize",
+    ),
+];
+
+const NOTE_DIALECTS: [(u32, &str); 4] = [
+    (CLAP_NOTE_DIALECT_CLAP, "clap"),
+    (CLAP_NOTE_DIALECT_MIDI, "midi"),
+    (CLAP_NOTE_DIALECT_MIDI_MPE, "midiMpe"),
+    (CLAP_NOTE_DIALECT_MIDI2, "midi2"),
+];
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ClapDescriptor {
+    pub id: String,
+    pub name: String,
+    pub vendor: String,
+    pub version: String,
+    pub url: String,
+    pub description: String,
+    pub features: Vec<String>,
+}
+
+// Values are plain, within min and max value
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ClapParameter {
+    pub id: u32,
+    pub name: String,
+    pub module: String,
+    pub display: String,
+    pub min_value: f64,
+    pub max_value: f64,
+    pub default_value: f64,
+    pub value: f64,
+    pub flags: Vec<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ClapAudioPort {
+    pub id: u32,
+    pub name: String,
+    // "mono", "stereo" or another port type, null if unspecified
+    pub port_type: Option<String>,
+    pub channel_count: u32,
+    pub in_place_pair: Option<u32>,
+    pub flags: Vec<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ClapNotePort {
+    pub id: u32,
+    pub name: String,
+    pub preferred_dialect: String,
+    pub supported_dialects: Vec<String>,
+}
+
+pub struct ClapLibrary {
+    entry: *const clap_plugin_entry,
+    factory: *const clap_plugin_factory,
+    // Dropped last, after deinit
+    _library: Library,
+}
+
+impl ClapLibrary {
+    pub fn load(binary: &Path) -> Result<ClapLibrary, PluginError> {
+        let library = unsafe { Library::new(binary) }
+            .map_err(|e| PluginError::LoadError(format!("Failed to load library: {}", e)))?;
+
+        // clap_entry is a struct, not a function
+        let entry = *unsafe { library.get::<*const clap_plugin_entry>(b"clap_entry") }
+            .map_err(|e| PluginError::LoadError(format!("clap_entry not found: {}", e)))?;
+        let entry_ref = unsafe { entry.as_ref() }
+            .ok_or_else(|| PluginError::LoadError("clap_entry is NULL".to_string()))?;
+        if !clap_version_is_compatible(entry_ref.clap_version) {
+            return Err(PluginError::LoadError(format!(
+                "Unsupported CLAP version {}.{}.{}",
+                entry_ref.clap_version.major,
+                entry_ref.clap_version.minor,
+                entry_ref.clap_version.revision
+            )));
+        }
+
+        let plugin_path = path_to_c_string(&super::plugin_path(binary))?;
+        let (Some(init), Some(get_factory)) = (entry_ref.init, entry_ref.get_factory) else {
+            return Err(PluginError::InitError(
+                "clap_entry is incomplete".to_string(),
+            ));
+        };
+        if !unsafe { init(plugin_path.as_ptr()) } {
+            return Err(PluginError::InitError(
+                "Failed to initialize clap_entry".to_string(),
+            ));
+        }
+
+        // deinit is called on drop from now on
+        let mut library = ClapLibrary {
+            entry,
+            factory: ptr::null(),
+            _library: library,
+        };
+        let factory = unsafe { get_factory(CLAP_PLUGIN_FACTORY_ID.as_ptr()) };
+        if factory.is_null() {
+            return Err(PluginError::FactoryError(
+                "No plugin factory found".to_string(),
+            ));
+        }
+        library.factory = factory as *const clap_plugin_factory;
+        Ok(library)
+    }
+
+    pub fn descriptors(&self) -> Vec<ClapDescriptor> {
+        let factory = unsafe { &*self.factory };
+        let count = factory
+            .get_plugin_count
+            .map_or(0, |get_count| unsafe { get_count(self.factory) });
+
+        (0..count)
+            .filter_map(|index| {
+                let get_descriptor = factory.get_plugin_descriptor?;
+                let descriptor = unsafe { get_descriptor(self.factory, index).as_ref() }?;
+                Some(read_descriptor(descriptor))
+            })
+            .collect()
+    }
+
+    // Creates and initializes the plugin with the given ID, or the first
+    // plugin of the factory
+    pub fn create_plugin(&self, plugin_id: Option<&str>) -> Result<ClapPlugin<'_>, PluginError> {
+        let plugin_id = match plugin_id {
+            Some(plugin_id) => plugin_id.to_string(),
+            None => self
+                .descriptors()
+                .into_iter()
+                .next()
+                .map(|descriptor| descriptor.id)
+                .ok_or_else(|| PluginError::FactoryError("No plugin found".to_string()))?,
+        };
+        let c_plugin_id = CString::new(plugin_id.clone())
+            .map_err(|_| PluginError::FactoryError("Invalid plugin ID".to_string()))?;
+
+        let create = unsafe { &*self.factory }
+            .create_plugin
+            .ok_or_else(|| PluginError::FactoryError("Factory can't create plugins".to_string()))?;
+        let plugin = unsafe { create(self.factory, &HOST, c_plugin_id.as_ptr()) };
+        let Some(plugin_ref) = (unsafe { plugin.as_ref() }) else {
+            return Err(PluginError::ComponentError(format!(
+                "Failed to create plugin {}",
+                plugin_id
+            )));
+        };
+
+        // Destroyed on drop from now on, even if init fails
+        let instance = ClapPlugin {
+            plugin,
+            _library: PhantomData,
+        };
+        let initialized = plugin_ref.init.is_some_and(|init| unsafe { init(plugin) });
+        if !initialized {
+            return Err(PluginError::InitError(format!(
+                "Failed to initialize plugin {}",
+                plugin_id
+            )));
+        }
+        Ok(instance)
+    }
+}
+
+impl Drop for ClapLibrary {
+    fn drop(&mut self) {
+        if let Some(deinit) = unsafe { (*self.entry).deinit } {
+            unsafe { deinit() };
+        }
+    }
+}
+
+pub struct ClapPlugin<'a> {
+    plugin: *const clap_plugin,
+    _library: PhantomData<&'a ClapLibrary>,
+}
+
+impl ClapPlugin<'_> {
+    pub fn descriptor(&self) -> Option<ClapDescriptor> {
+        let descriptor = unsafe { (*self.plugin).desc.as_ref() }?;
+        Some(read_descriptor(descriptor))
+    }
+
+    // The plugin's implementation of an extension like clap.params
+    pub fn extension<T>(&self, id: &CStr) -> Option<&T> {
+        let get_extension = unsafe { (*self.plugin).get_extension }?;
+        unsafe { (get_extension(self.plugin, id.as_ptr()) as *const T).as_ref() }
+    }
+
+    pub fn parameters(&self) -> Vec<ClapParameter> {
+        let Some(params) = self.extension::<clap_plugin_params>(CLAP_EXT_PARAMS) else {
+            return Vec::new();
+        };
+        let (Some(count), Some(get_info)) = (params.count, params.get_info) else {
+            return Vec::new();
+        };
+
+        (0..unsafe { count(self.plugin) })
+            .filter_map(|index| {
+                let mut info: clap_param_info = unsafe { std::mem::zeroed() };
+                if !unsafe { get_info(self.plugin, index, &mut info) } {
+                    return None;
+                }
+                let value = self.parameter_value(info.id).unwrap_or(info.default_value);
+                Some(ClapParameter {
+                    id: info.id,
+                    name: c_str_to_string(&info.name),
+                    module: c_str_to_string(&info.module),
+                    display: self.value_to_text(info.id, value).unwrap_or_default(),
+                    min_value: info.min_value,
+                    max_value: info.max_value,
+                    default_value: info.default_value,
+                    value,
+                    flags: flag_names(info.flags, &PARAM_FLAGS),
+                })
+            })
+            .collect()
+    }
+
+    pub fn parameter_value(&self, param_id: u32) -> Option<f64> {
+        let params = self.extension::<clap_plugin_params>(CLAP_EXT_PARAMS)?;
+        let get_value = params.get_value?;
+        let mut value = 0.0;
+        unsafe { get_value(self.plugin, param_id, &mut value) }.then_some(value)
+    }
+
+    fn value_to_text(&self, param_id: u32, value: f64) -> Option<String> {
+        let params = self.extension::<clap_plugin_params>(CLAP_EXT_PARAMS)?;
+        let value_to_text = params.value_to_text?;
+        let mut display: [c_char; DISPLAY_SIZE] = [0; DISPLAY_SIZE];
+        unsafe {
+            value_to_text(
+                self.plugin,
+                param_id,
+                value,
+                display.as_mut_ptr(),
+                DISPLAY_SIZE as u32,
+            )
+        }
+        .then(|| c_str_to_string(&display))
+    }
+
+    pub fn audio_ports(&self, is_input: bool) -> Vec<ClapAudioPort> {
+        let Some(ports) = self.extension::<clap_plugin_audio_ports>(CLAP_EXT_AUDIO_PORTS) else {
+            return Vec::new();
+        };
+        let (Some(count), Some(get)) = (ports.count, ports.get) else {
+            return Vec::new();
+        };
+
+        (0..unsafe { count(self.plugin, is_input) })
+            .filter_map(|index| {
+                let mut info = unsafe { std::mem::zeroed() };
+                if !unsafe { get(self.plugin, index, is_input, &mut info) } {
+                    return None;
+                }
+                Some(ClapAudioPort {
+                    id: info.id,
+                    name: c_str_to_string(&info.name),
+                    port_type: (!info.port_type.is_null())
+                        .then(|| unsafe { ptr_to_string(info.port_type) }),
+                    channel_count: info.channel_count,
+                    in_place_pair: (info.in_place_pair != CLAP_INVALID_ID)
+                        .then_some(info.in_place_pair),
+                    flags: flag_names(info.flags, &AUDIO_PORT_FLAGS),
+                })
+            })
+            .collect()
+    }
+
+    pub fn note_ports(&self, is_input: bool) -> Vec<ClapNotePort> {
+        let Some(ports) = self.extension::<clap_plugin_note_ports>(CLAP_EXT_NOTE_PORTS) else {
+            return Vec::new();
+        };
+        let (Some(count), Some(get)) = (ports.count, ports.get) else {
+            return Vec::new();
+        };
+
+        (0..unsafe { count(self.plugin, is_input) })
+            .filter_map(|index| {
+                let mut info = unsafe { std::mem::zeroed() };
+                if !unsafe { get(self.plugin, index, is_input, &mut info) } {
+                    return None;
+                }
+                Some(ClapNotePort {
+                    id: info.id,
+                    name: c_str_to_string(&info.name),
+                    preferred_dialect: flag_names(info.preferred_dialect, &NOTE_DIALECTS)
+                        .pop()
+                        .unwrap_or_default(),
+                    supported_dialects: flag_names(info.supported_dialects, &NOTE_DIALECTS),
+                })
+            })
+            .collect()
+    }
+
+    // Loads a state as written by save_state, false if the plugin rejects it
+    pub fn load_state(&self, state: &[u8]) -> Result<bool, PluginError> {
+        let load = self
+            .state_extension()?
+            .load
+            .ok_or_else(no_state_extension)?;
+        let mut reader = StateReader {
+            data: state,
+            position: 0,
+        };
+        let stream = clap_istream {
+            ctx: &mut reader as *mut StateReader as *mut c_void,
+            read: Some(read_state),
+        };
+        Ok(unsafe { load(self.plugin, &stream) })
+    }
+
+    pub fn save_state(&self) -> Result<Vec<u8>, PluginError> {
+        let save = self
+            .state_extension()?
+            .save
+            .ok_or_else(no_state_extension)?;
+        let mut state: Vec<u8> = Vec::new();
+        let stream = clap_ostream {
+            ctx: &mut state as *mut Vec<u8> as *mut c_void,
+            write: Some(write_state),
+        };
+        if unsafe { save(self.plugin, &stream) } {
+            Ok(state)
+        } else {
+            Err(PluginError::ComponentError(
+                "Failed to save plugin state".to_string(),
+            ))
+        }
+    }
+
+    fn state_extension(&self) -> Result<&clap_plugin_state, PluginError> {
+        self.extension::<clap_plugin_state>(CLAP_EXT_STATE)
+            .ok_or_else(no_state_extension)
+    }
+
+    // Only valid while the plugin is active, so it is activated for it
+    pub fn latency(&self) -> Option<u32> {
+        let latency = self.extension::<clap_plugin_latency>(CLAP_EXT_LATENCY)?;
+        let get = latency.get?;
+        let plugin = unsafe { &*self.plugin };
+        let (Some(activate), Some(deactivate)) = (plugin.activate, plugin.deactivate) else {
+            return None;
+        };
+
+        if !unsafe { activate(self.plugin, SAMPLE_RATE, 1, MAX_FRAMES) } {
+            return None;
+        }
+        let samples = unsafe { get(self.plugin) };
+        unsafe { deactivate(self.plugin) };
+        Some(samples)
+    }
+}
+
+impl Drop for ClapPlugin<'_> {
+    fn drop(&mut self) {
+        if let Some(destroy) = unsafe { (*self.plugin).destroy } {
+            unsafe { destroy(self.plugin) };
+        }
+    }
+}
+
+fn no_state_extension() -> PluginError {
+    PluginError::ComponentError("Plugin has no clap.state extension".to_string())
+}
+
+struct StateReader<'a> {
+    data: &'a [u8],
+    position: usize,
+}
+
+unsafe extern "C" fn read_state(
+    stream: *const clap_istream,
+    buffer: *mut c_void,
+    size: u64,
+) -> i64 {
+    let reader = unsafe { &mut *((*stream).ctx as *mut StateReader) };
+    let remaining = &reader.data[reader.position..];
+    let count = remaining.len().min(size as usize);
+    unsafe { ptr::copy_nonoverlapping(remaining.as_ptr(), buffer as *mut u8, count) };
+    reader.position += count;
+    count as i64
+}
+
+unsafe extern "C" fn write_state(
+    stream: *const clap_ostream,
+    buffer: *const c_void,
+    size: u64,
+) -> i64 {
+    let state = unsafe { &mut *((*stream).ctx as *mut Vec<u8>) };
+    let data = unsafe { std::slice::from_raw_parts(buffer as *const u8, size as usize) };
+    state.extend_from_slice(data);
+    size as i64
+}
+
+fn read_descriptor(descriptor: &clap_plugin_descriptor) -> ClapDescriptor {
+    let mut features = Vec::new();
+    if !descriptor.features.is_null() {
+        let mut feature = descriptor.features;
+        while !unsafe { *feature }.is_null() {
+            features.push(unsafe { ptr_to_string(*feature) });
+            feature = unsafe { feature.add(1) };
+        }
+    }
+
+    ClapDescriptor {
+        id: unsafe { ptr_to_string(descriptor.id) },
+        name: unsafe { ptr_to_string(descriptor.name) },
+        vendor: unsafe { ptr_to_string(descriptor.vendor) },
+        version: unsafe { ptr_to_string(descriptor.version) },
+        url: unsafe { ptr_to_string(descriptor.url) },
+        description: unsafe { ptr_to_string(descriptor.description) },
+        features,
+    }
+}
+
+fn flag_names(flags: u32, names: &[(u32, &str)]) -> Vec<String> {
+    names
+        .iter()
+        .filter(|(flag, _)| flags & flag != 0)
+        .map(|(_, name)| name.to_string())
+        .collect()
+}
+
+fn path_to_c_string(path: &Path) -> Result<CString, PluginError> {
+    CString::new(path.to_string_lossy().as_bytes())
+        .map_err(|_| PluginError::PathError("Invalid plugin path".to_string()))
+}
+
+// Empty for NULL, plugins may leave optional descriptor fields out
+unsafe fn ptr_to_string(chars: *const c_char) -> String {
+    if chars.is_null() {
+        return String::new();
+    }
+    unsafe { CStr::from_ptr(chars) }
+        .to_string_lossy()
+        .to_string()
+}
+
+fn c_str_to_string(chars: &[c_char]) -> String {
+    let bytes: Vec<u8> = chars
+        .iter()
+        .take_while(|&&c| c != 0)
+        .map(|&c| c as u8)
+        .collect();
+    String::from_utf8_lossy(&bytes).to_string()
+}
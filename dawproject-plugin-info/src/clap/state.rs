@@ -0,0 +1,57 @@
+/*!
+ * ------------------------------------------------------------------------
+ * CLAP plugin state
+ * by Jurek Raben
+ *
+ * Loads a state blob into a plugin through clap.state, e.g. the state
+ * Renoise stored for the device, and saves the plugin's own state again.
+ * The saved state is what the State element of a DawProject ClapPlugin
+ * points to, like the .vstpreset of a Vst3Plugin.
+ *
+ * Licensed under CC Attribution-NonCommercial-ShareAlike 4.0 International
+ * Info here: https://creativecommons.org/licenses/by-nc-sa/4.0/
+ * ------------------------------------------------------------------------
+ */
+
+use std::path::Path;
+
+use super::plugin::{ClapDescriptor, ClapLibrary, ClapParameter};
+use crate::PluginError;
+use crate::vst3::stdio;
+
+pub struct ClapState {
+    pub plugin: ClapDescriptor,
+    // Whether the plugin accepted the given state, true without one
+    pub applied: bool,
+    // The state saved after loading
+    pub data: Vec<u8>,
+    // Read back after loading
+    pub parameters: Vec<ClapParameter>,
+}
+
+// Loads the given state, if any, into the plugin with the given ID or the
+// first plugin of the file, and saves it again
+pub fn apply_state(
+    binary: &Path,
+    plugin_id: Option<&str>,
+    state: Option<&[u8]>,
+) -> Result<ClapState, PluginError> {
+    stdio::suppress_output(|| {
+        let library = ClapLibrary::load(binary)?;
+        let plugin = library.create_plugin(plugin_id)?;
+        let descriptor = plugin
+            .descriptor()
+            .ok_or_else(|| PluginError::ComponentError("Plugin has no descriptor".to_string()))?;
+
+        let applied = match state {
+            Some(state) => plugin.load_state(state)?,
+            None => true,
+        };
+        Ok(ClapState {
+            plugin: descriptor,
+            applied,
+            data: plugin.save_state()?,
+            parameters: plugin.parameters(),
+        })
+    })
+}
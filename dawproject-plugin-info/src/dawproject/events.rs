@@ -0,0 +1,448 @@
+/*!
+ * ------------------------------------------------------------------------
+ * Linear note and automation events of a Renoise song
+ * by Jurek Raben
+ *
+ * The NoteAbstraction of the Lua tool, without the Renoise API: walks the
+ * pattern sequence and turns the note columns into notes with exact start,
+ * duration, velocity and poly pressure, takes care of missing note-offs and
+ * muted sequence slots, and collects the graphical automation and the MIDI
+ * commands of the patterns. Times are in 1/256 lines, tracks are zero
+ * based, device and instrument references one based as in the IDs of the
+ * DAWproject.
+ *
+ * Licensed under CC Attribution-NonCommercial-ShareAlike 4.0 International
+ * Info here: https://creativecommons.org/licenses/by-nc-sa/4.0/
+ * ------------------------------------------------------------------------
+ */
+
+use std::collections::HashMap;
+
+use crate::xml::Element;
+use crate::xrns::pattern::{EffectColumn, Envelope, Note, NoteColumn, Pattern, SequenceEntry};
+use crate::xrns::song;
+
+pub const TICKS_PER_LINE: i64 = 256;
+
+// Fake parameter indices of the MIDI commands without a controller number
+pub const PITCH_BEND_INDEX: i64 = 200000;
+pub const CHANNEL_PRESSURE_INDEX: i64 = 200001;
+pub const PROGRAM_CHANGE_INDEX: i64 = 200002;
+
+// Automation types besides plain parameter automation, as named by the
+// Instr. MIDI Control device
+pub const AUTOMATION_TYPE: &str = "automation";
+pub const CONTROL_CHANGE_TYPE: &str = "CC";
+pub const PITCH_BEND_TYPE: &str = "PB";
+pub const CHANNEL_PRESSURE_TYPE: &str = "CP";
+pub const PROGRAM_CHANGE_TYPE: &str = "Prg";
+
+const AUTOMATION_DEVICE: &str = "InstrumentAutomationDevice";
+const MIDI_CONTROL_DEVICE: &str = "InstrumentMidiControlDevice";
+
+// Automation values are compared with five decimals
+const VALUE_PRECISION: f64 = 100000.0;
+
+#[derive(Debug, Clone)]
+pub struct NoteEvent {
+    pub track: usize,
+    pub column: usize,
+    // One based, as in the clip IDs
+    pub sequence: usize,
+    pub pattern: usize,
+    pub key: u8,
+    pub velocity: f64,
+    pub release_velocity: Option<f64>,
+    pub timestamp: i64,
+    pub duration: i64,
+    // Start and length of the pattern in the song
+    pub pattern_timestamp: i64,
+    pub pattern_duration: i64,
+    pub enabled: bool,
+    // Times relative to the note start
+    pub pressure: Vec<PressurePoint>,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct PressurePoint {
+    pub timestamp: i64,
+    pub value: f64,
+}
+
+#[derive(Debug, Clone)]
+pub struct AutomationEvent {
+    pub track: usize,
+    // Device index like "3", or instrument index like "i2"
+    pub device: String,
+    pub parameter: i64,
+    // "automation" or a MIDI type like "CC"
+    pub automation_type: String,
+    pub parameter_name: String,
+    pub timestamp: f64,
+    pub value: f64,
+    // "hold" or "linear"
+    pub interpolation: &'static str,
+}
+
+// A track of the song with the instrument played on it
+struct SongTrack<'a> {
+    index: usize,
+    element: &'a Element,
+    instrument: Option<usize>,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct SongEvents {
+    pub notes: Vec<NoteEvent>,
+    pub automation: Vec<AutomationEvent>,
+    // End of the song
+    pub duration: i64,
+}
+
+impl NoteEvent {
+    pub fn pattern_relative_timestamp(&self) -> i64 {
+        self.timestamp - self.pattern_timestamp
+    }
+}
+
+// Events of the whole song, sorted by track and time
+pub fn song_events(song: &Element, patterns: &[Pattern], sequence: &[SequenceEntry]) -> SongEvents {
+    let instruments: Vec<&Element> = song
+        .find(&["Instruments"])
+        .map(|instruments| instruments.children_named("Instrument").collect())
+        .unwrap_or_default();
+    let tracks: Vec<SongTrack> = song::tracks(song)
+        .enumerate()
+        .map(|(index, element)| SongTrack {
+            index,
+            element,
+            instrument: track_instrument(patterns, index, visible_note_columns(element)),
+        })
+        .collect();
+
+    let mut events = SongEvents::default();
+    let mut active_notes: HashMap<(usize, usize), NoteEvent> = HashMap::new();
+    let mut last_velocities: HashMap<(usize, usize), f64> = HashMap::new();
+    let mut last_point_values: HashMap<(usize, String, i64), f64> = HashMap::new();
+    let mut muted_tracks: Vec<bool> = vec![false; tracks.len()];
+    let mut line_offset: usize = 0;
+
+    for (sequence_index, entry) in sequence.iter().enumerate() {
+        let Some(pattern) = patterns.get(entry.pattern) else {
+            continue;
+        };
+
+        for track in &tracks {
+            let track_index = track.index;
+            // Notes end at a change of the slot mute state
+            let muted = entry.muted_tracks.contains(&track_index);
+            if muted_tracks[track_index] != muted {
+                muted_tracks[track_index] = muted;
+                let keys: Vec<(usize, usize)> = active_notes
+                    .keys()
+                    .filter(|(track, _)| *track == track_index)
+                    .copied()
+                    .collect();
+                for key in keys {
+                    if let Some(note) = active_notes.remove(&key) {
+                        events.notes.push(end_note(
+                            note,
+                            line_offset as i64 * TICKS_PER_LINE,
+                            None,
+                        ));
+                    }
+                }
+            }
+
+            if let Some(pattern_track) = pattern.tracks.get(track_index) {
+                add_graphical_automation(
+                    &mut events.automation,
+                    &mut last_point_values,
+                    track,
+                    &pattern_track.envelopes,
+                    line_offset,
+                    &instruments,
+                );
+            }
+
+            let columns = visible_note_columns(track.element);
+            for line in pattern
+                .tracks
+                .get(track_index)
+                .map(|pattern_track| pattern_track.lines.as_slice())
+                .unwrap_or_default()
+                .iter()
+                .filter(|line| line.index < pattern.number_of_lines)
+            {
+                let line_timestamp = (line_offset + line.index) as i64 * TICKS_PER_LINE;
+
+                for (column_index, column) in line.note_columns.iter().take(columns).enumerate() {
+                    let key = (track_index, column_index);
+                    let timestamp = line_timestamp + column.delay as i64;
+
+                    add_midi_command(
+                        &mut events.automation,
+                        track_index,
+                        column,
+                        line.effect_columns.first(),
+                        timestamp,
+                    );
+
+                    let velocity = column
+                        .volume
+                        .filter(|volume| *volume < 128)
+                        .map(|volume| volume as f64 / 127.0);
+
+                    match column.note {
+                        None => {
+                            if let (Some(note), Some(value)) =
+                                (active_notes.get_mut(&key), velocity)
+                            {
+                                note.pressure.push(PressurePoint {
+                                    timestamp: timestamp - note.timestamp,
+                                    value,
+                                });
+                            }
+                        }
+                        Some(Note::Off) => {
+                            if let Some(note) = active_notes.remove(&key) {
+                                events.notes.push(end_note(note, timestamp, velocity));
+                            }
+                        }
+                        Some(Note::On(key_value)) => {
+                            if let Some(note) = active_notes.remove(&key) {
+                                events.notes.push(end_note(note, timestamp, velocity));
+                            }
+                            if let Some(velocity) = velocity {
+                                last_velocities.insert(key, velocity);
+                            }
+                            active_notes.insert(
+                                key,
+                                NoteEvent {
+                                    track: track_index,
+                                    column: column_index,
+                                    sequence: sequence_index + 1,
+                                    pattern: entry.pattern + 1,
+                                    key: key_value,
+                                    velocity: velocity
+                                        .or_else(|| last_velocities.get(&key).copied())
+                                        .unwrap_or(1.0),
+                                    release_velocity: None,
+                                    timestamp,
+                                    duration: 0,
+                                    pattern_timestamp: line_offset as i64 * TICKS_PER_LINE,
+                                    pattern_duration: pattern.number_of_lines as i64
+                                        * TICKS_PER_LINE,
+                                    enabled: !muted,
+                                    pressure: Vec::new(),
+                                },
+                            );
+                        }
+                    }
+                }
+            }
+        }
+
+        line_offset += pattern.number_of_lines;
+    }
+
+    // Notes still playing end with the song
+    events.duration = line_offset as i64 * TICKS_PER_LINE;
+    for (_, note) in active_notes.drain() {
+        events.notes.push(end_note(note, events.duration, None));
+    }
+
+    events
+        .notes
+        .sort_by_key(|note| (note.track, note.timestamp, note.column));
+    events.automation.sort_by(|a, b| {
+        (a.track, &a.device, a.parameter)
+            .cmp(&(b.track, &b.device, b.parameter))
+            .then(a.timestamp.total_cmp(&b.timestamp))
+    });
+    events
+}
+
+// The first instrument played on the track, in pattern pool order
+pub fn track_instrument(patterns: &[Pattern], track: usize, columns: usize) -> Option<usize> {
+    patterns.iter().find_map(|pattern| {
+        pattern.tracks.get(track)?.lines.iter().find_map(|line| {
+            line.note_columns
+                .iter()
+                .take(columns)
+                .find_map(|column| column.instrument)
+                .map(usize::from)
+        })
+    })
+}
+
+pub fn visible_note_columns(track: &Element) -> usize {
+    track
+        .child_text("NumberOfVisibleNoteColumns")
+        .and_then(|columns| columns.parse().ok())
+        .unwrap_or(usize::MAX)
+}
+
+fn end_note(mut note: NoteEvent, timestamp: i64, release_velocity: Option<f64>) -> NoteEvent {
+    note.duration = timestamp - note.timestamp;
+    note.release_velocity = release_velocity;
+    note
+}
+
+// MIDI commands in the panning column, the values are in the first effect
+// column of the line
+fn add_midi_command(
+    automation: &mut Vec<AutomationEvent>,
+    track: usize,
+    column: &NoteColumn,
+    effect_column: Option<&EffectColumn>,
+    timestamp: i64,
+) {
+    let (Some(instrument), Some(effect_column)) = (column.instrument, effect_column) else {
+        return;
+    };
+    let hex = |value: &str| i64::from_str_radix(value, 16).ok();
+    let number = hex(&effect_column.number);
+    let both = hex(&format!("{}{}", effect_column.number, effect_column.amount));
+
+    let (parameter, automation_type, parameter_name, value) = match column.panning_string.as_str() {
+        "M0" => (
+            number,
+            CONTROL_CHANGE_TYPE,
+            format!("CC #{}", effect_column.number),
+            hex(&effect_column.amount).map(|amount| amount as f64 / 127.0),
+        ),
+        "M1" => (
+            Some(PITCH_BEND_INDEX),
+            PITCH_BEND_TYPE,
+            "Pitchbend".to_string(),
+            both.map(|value| value as f64 / 32767.0),
+        ),
+        "M2" => (
+            Some(PROGRAM_CHANGE_INDEX),
+            PROGRAM_CHANGE_TYPE,
+            "Program Change".to_string(),
+            both.map(|value| value as f64 / 127.0),
+        ),
+        "M3" => (
+            Some(CHANNEL_PRESSURE_INDEX),
+            CHANNEL_PRESSURE_TYPE,
+            "Channel Pressure".to_string(),
+            both.map(|value| value as f64 / 127.0),
+        ),
+        _ => return,
+    };
+    let (Some(parameter), Some(value)) = (parameter, value) else {
+        return;
+    };
+
+    automation.push(AutomationEvent {
+        track,
+        device: format!("i{}", instrument as usize + 1),
+        parameter,
+        automation_type: automation_type.to_string(),
+        parameter_name,
+        timestamp: timestamp as f64,
+        value,
+        interpolation: "linear",
+    });
+}
+
+// Envelopes of plugin devices, and of the Instr. Automation and Instr. MIDI
+// Control devices, which automate the plugin of the track's instrument.
+// Points repeating the last value of the lane are left out.
+fn add_graphical_automation(
+    automation: &mut Vec<AutomationEvent>,
+    last_point_values: &mut HashMap<(usize, String, i64), f64>,
+    track: &SongTrack,
+    envelopes: &[Envelope],
+    line_offset: usize,
+    instruments: &[&Element],
+) {
+    let track_index = track.index;
+    let devices: Vec<&Element> = song::devices(track.element).collect();
+
+    for envelope in envelopes {
+        // The pre-mixer is not exported
+        if envelope.device_index == 0 {
+            continue;
+        }
+        let Some(device) = devices.get(envelope.device_index) else {
+            continue;
+        };
+        let Some((device_ref, parameter, automation_type)) = envelope_target(
+            device,
+            envelope.device_index,
+            envelope.parameter_index,
+            track.instrument,
+            instruments,
+        ) else {
+            continue;
+        };
+        let interpolation = if envelope.play_mode == "Points" {
+            "hold"
+        } else {
+            "linear"
+        };
+
+        for point in &envelope.points {
+            let value = (point.value * VALUE_PRECISION).round() / VALUE_PRECISION;
+            let lane = (track_index, device_ref.clone(), parameter);
+            if last_point_values.get(&lane) == Some(&value) {
+                continue;
+            }
+            last_point_values.insert(lane, value);
+
+            automation.push(AutomationEvent {
+                track: track_index,
+                device: device_ref.clone(),
+                parameter,
+                automation_type: automation_type.clone(),
+                parameter_name: format!("Parameter {}", parameter),
+                timestamp: (line_offset as f64 + point.time) * TICKS_PER_LINE as f64,
+                value,
+                interpolation,
+            });
+        }
+    }
+}
+
+// Device reference, parameter index and automation type of an envelope
+fn envelope_target(
+    device: &Element,
+    device_index: usize,
+    parameter_index: usize,
+    track_instrument: Option<usize>,
+    instruments: &[&Element],
+) -> Option<(String, i64, String)> {
+    let device_type = song::device_type(device);
+    if device_type != AUTOMATION_DEVICE && device_type != MIDI_CONTROL_DEVICE {
+        song::plugin_device(device)?;
+        return Some((
+            (device_index + 1).to_string(),
+            parameter_index as i64,
+            AUTOMATION_TYPE.to_string(),
+        ));
+    }
+
+    let instrument = device
+        .child_text("LinkedInstrument")
+        .and_then(|instrument| instrument.parse::<usize>().ok())
+        .or(track_instrument)?;
+    song::instrument_plugin(instruments.get(instrument)?)?;
+    let device_ref = format!("i{}", instrument + 1);
+
+    if device_type == AUTOMATION_DEVICE {
+        let parameter = device
+            .child_text(&format!("ParameterNumber{}", parameter_index))?
+            .parse()
+            .ok()?;
+        return Some((device_ref, parameter, AUTOMATION_TYPE.to_string()));
+    }
+    let parameter = device
+        .child_text(&format!("ControllerNumber{}", parameter_index))?
+        .parse()
+        .ok()?;
+    let automation_type = device.child_text(&format!("ControllerType{}", parameter_index))?;
+    Some((device_ref, parameter, automation_type.to_string()))
+}
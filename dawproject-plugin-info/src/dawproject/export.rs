@@ -0,0 +1,807 @@
+/*!
+ * ------------------------------------------------------------------------
+ * Renoise song to DAWproject export
+ * by Jurek Raben
+ *
+ * Builds project.xml and metadata.xml from a saved song with the rules of
+ * the Lua tool: tracks nested by their output routing with sends and the
+ * master last, one clip per track and sequence slot, automation lanes and
+ * the automated parameters of the plugin devices, markers at the section
+ * starts. Plugin states are taken from the ParameterChunk of the devices,
+ * devices which need Renoise or a plugin helper tool are reported as
+ * skipped.
+ *
+ * Licensed under CC Attribution-NonCommercial-ShareAlike 4.0 International
+ * Info here: https://creativecommons.org/licenses/by-nc-sa/4.0/
+ * ------------------------------------------------------------------------
+ */
+
+use std::collections::HashMap;
+
+use base64::prelude::*;
+use serde::Serialize;
+
+use super::events::{self, AutomationEvent, NoteEvent, SongEvents, TICKS_PER_LINE};
+use super::{DawProject, PLUGINS_DIR};
+use crate::au::identifier::{DeviceIdForm, parse_device_id};
+use crate::au::preset::plist_to_xml;
+use crate::vst2::fxp;
+use crate::xml::Element;
+use crate::xrns::pattern::{self, SequenceEntry};
+use crate::xrns::song::{self, PluginDevice};
+
+pub const DAWPROJECT_VERSION: &str = "1.0";
+pub const APPLICATION_NAME: &str = "Renoise";
+
+const DEFAULT_BPM: f64 = 125.0;
+const DEFAULT_LPB: f64 = 4.0;
+const DEFAULT_SIGNATURE: u32 = 4;
+const MASTER_ROUTING: &str = "Master";
+const SEND_DEVICE: &str = "SendDevice";
+const ACTIVE_STATE: &str = "Active";
+
+#[derive(Debug, Clone)]
+pub struct ExportOptions {
+    // Moves the clips by the output delay of their track
+    pub add_track_delay: bool,
+    // VST2 plugins by PluginIdentifier, Song.xml lacks their unique IDs
+    pub vst2_plugins: HashMap<String, Vst2Plugin>,
+}
+
+// What the .fxp header of a VST2 plugin needs besides the Song.xml
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Vst2Plugin {
+    pub unique_id: i32,
+    // The plugin's version as fxVersion, some plugins check it
+    pub version: i32,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SkippedDevice {
+    // One based, as in the track IDs
+    pub track: usize,
+    pub device: String,
+    pub reason: String,
+}
+
+#[derive(Debug, Clone)]
+pub struct Export {
+    pub project: DawProject,
+    pub count_tracks: usize,
+    pub count_notes: usize,
+    pub count_automation_points: usize,
+    pub count_plugins: usize,
+    pub skipped: Vec<SkippedDevice>,
+}
+
+impl Default for ExportOptions {
+    fn default() -> Self {
+        ExportOptions {
+            add_track_delay: true,
+            vst2_plugins: HashMap::new(),
+        }
+    }
+}
+
+// A track of the song, the index is one based
+struct Track<'a> {
+    index: usize,
+    element: &'a Element,
+    track_type: &'static str,
+    instrument: Option<usize>,
+}
+
+// Where a plugin device goes in the project
+struct DeviceSlot {
+    // Below plugins/, without extension
+    save_path: String,
+    parameter_id_prefix: String,
+    role: &'static str,
+    enabled: bool,
+}
+
+struct Exporter<'a> {
+    options: &'a ExportOptions,
+    bpm: f64,
+    lpb: f64,
+    tracks: Vec<Track<'a>>,
+    instruments: Vec<&'a Element>,
+    events: SongEvents,
+    files: Vec<(String, Vec<u8>)>,
+    count_plugins: usize,
+    skipped: Vec<SkippedDevice>,
+}
+
+pub fn export_song(song: &Element, file_name: &str, options: &ExportOptions) -> Export {
+    let global = song.child("GlobalSongData");
+    let global_text = |name| {
+        global
+            .and_then(|global| global.child_text(name))
+            .unwrap_or_default()
+    };
+    let global_number = |name| global.and_then(|global| global.child_number(name));
+
+    let patterns = pattern::patterns(song);
+    let sequence = pattern::sequence(song);
+    let events = events::song_events(song, &patterns, &sequence);
+
+    let mut exporter = Exporter {
+        options,
+        bpm: global_number("BeatsPerMin").unwrap_or(DEFAULT_BPM),
+        lpb: global_number("LinesPerBeat")
+            .filter(|lpb| *lpb > 0.0)
+            .unwrap_or(DEFAULT_LPB),
+        tracks: song::tracks(song)
+            .enumerate()
+            .map(|(index, element)| Track {
+                index: index + 1,
+                element,
+                track_type: song::track_type(element).unwrap_or_default(),
+                instrument: events::track_instrument(
+                    &patterns,
+                    index,
+                    events::visible_note_columns(element),
+                ),
+            })
+            .collect(),
+        instruments: song
+            .find(&["Instruments"])
+            .map(|instruments| instruments.children_named("Instrument").collect())
+            .unwrap_or_default(),
+        events,
+        files: Vec::new(),
+        count_plugins: 0,
+        skipped: Vec::new(),
+    };
+
+    let numerator = global_number("SignatureNumerator").map_or(DEFAULT_SIGNATURE, |n| n as u32);
+    let denominator = global_number("SignatureDenominator").map_or(DEFAULT_SIGNATURE, |n| n as u32);
+    let application_version = song.attribute("doc_version").unwrap_or_default();
+
+    let project = Element::new("Project")
+        .with_attribute("version", DAWPROJECT_VERSION)
+        .with_child(
+            Element::new("Application")
+                .with_attribute("name", APPLICATION_NAME)
+                .with_attribute("version", application_version),
+        )
+        .with_child(
+            Element::new("Transport")
+                .with_child(
+                    Element::new("Tempo")
+                        .with_attribute("min", 20)
+                        .with_attribute("max", 999)
+                        .with_attribute("unit", "bpm")
+                        .with_attribute("name", "Tempo")
+                        .with_attribute("value", exporter.bpm),
+                )
+                .with_child(
+                    Element::new("TimeSignature")
+                        .with_attribute("numerator", numerator)
+                        .with_attribute("denominator", denominator),
+                ),
+        )
+        .with_child(Element::new("Structure").with_children(exporter.structure()))
+        .with_child(
+            Element::new("Arrangement")
+                .with_child(
+                    Element::new("Lanes")
+                        .with_attribute("timeUnit", "beats")
+                        .with_children(exporter.track_lanes()),
+                )
+                .with_child(exporter.markers(&patterns, &sequence)),
+        );
+
+    let metadata = Element::new("MetaData")
+        .with_child(Element::new("Artist").with_text(global_text("Artist")))
+        .with_child(Element::new("Title").with_text(global_text("SongName")))
+        .with_child(Element::new("Comment").with_text(file_name));
+
+    Export {
+        count_tracks: exporter.tracks.len(),
+        count_notes: exporter.events.notes.len(),
+        count_automation_points: exporter.events.automation.len(),
+        count_plugins: exporter.count_plugins,
+        skipped: exporter.skipped,
+        project: DawProject {
+            project,
+            metadata,
+            files: exporter.files,
+        },
+    }
+}
+
+// Names without punctuation and control characters
+pub fn prepare_name(name: &str) -> String {
+    name.chars()
+        .filter(|c| !c.is_ascii_punctuation() && !c.is_control())
+        .collect()
+}
+
+// File names are also lower case and without spaces
+pub fn prepare_file_name(name: &str) -> String {
+    prepare_name(name)
+        .chars()
+        .filter(|c| !c.is_whitespace())
+        .collect::<String>()
+        .to_lowercase()
+}
+
+// Times are cut after six decimals
+fn round(value: f64) -> f64 {
+    (value * 1e6).floor() / 1e6
+}
+
+impl Exporter<'_> {
+    fn beats(&self, ticks: f64) -> f64 {
+        ticks / TICKS_PER_LINE as f64 / self.lpb
+    }
+
+    fn sequencer_track_count(&self) -> usize {
+        self.tracks
+            .iter()
+            .filter(|track| matches!(track.track_type, "sequencer" | "group"))
+            .count()
+    }
+
+    // Tracks and groups nested by routing, followed by the sends and the
+    // master
+    fn structure(&mut self) -> Vec<Element> {
+        let mut elements = self.routed_tracks(MASTER_ROUTING);
+        for index in 0..self.tracks.len() {
+            if self.tracks[index].track_type == "send" {
+                elements.push(self.track_element(index));
+            }
+        }
+        if let Some(master) = self
+            .tracks
+            .iter()
+            .position(|track| track.track_type == "master")
+        {
+            elements.push(self.track_element(master));
+        }
+        elements
+    }
+
+    fn routed_tracks(&mut self, routing: &str) -> Vec<Element> {
+        let mut elements = Vec::new();
+        for index in 0..self.tracks.len() {
+            let track = &self.tracks[index];
+            if !matches!(track.track_type, "sequencer" | "group")
+                || output_routing(track.element) != routing
+            {
+                continue;
+            }
+            let mut element = self.track_element(index);
+            let track = &self.tracks[index];
+            if track.track_type == "group" {
+                let name = track.element.child_text("Name").unwrap_or_default();
+                element = element.with_children(self.routed_tracks(name));
+            }
+            elements.push(element);
+        }
+        elements
+    }
+
+    fn track_element(&mut self, index: usize) -> Element {
+        let devices = self.devices(index);
+        let track = &self.tracks[index];
+        let track_index = track.index;
+        let name = prepare_name(track.element.child_text("Name").unwrap_or_default());
+        let delay = output_delay(track.element);
+        let (content_type, role) = match track.track_type {
+            "group" => ("tracks", "master"),
+            "send" => ("audio", "effect"),
+            "master" => ("audio notes", "master"),
+            _ => ("notes", "regular"),
+        };
+        let muted = track
+            .element
+            .child_text("State")
+            .is_some_and(|state| state != ACTIVE_STATE);
+        let solo = track.element.child_text("SoloState") == Some("true");
+
+        let mut channel = Element::new("Channel")
+            .with_attribute("id", format!("channel{}", track_index))
+            .with_attribute("name", &name)
+            .with_attribute("audioChannels", 2)
+            .with_attribute("solo", solo)
+            .with_attribute("role", role);
+        if !devices.is_empty() {
+            channel = channel.with_child(Element::new("Devices").with_children(devices));
+        }
+        channel = channel
+            .with_child(
+                Element::new("Mute")
+                    .with_attribute("id", format!("mute{}", track_index))
+                    .with_attribute("value", muted),
+            )
+            .with_child(
+                Element::new("Pan")
+                    .with_attribute("id", format!("pan{}", track_index))
+                    .with_attribute(
+                        "value",
+                        mixer_value(track.element, "PostPanning").unwrap_or(0.5),
+                    )
+                    .with_attribute("unit", "normalized")
+                    .with_attribute("min", 0)
+                    .with_attribute("max", 1),
+            );
+        let sends = self.sends(index);
+        if !sends.is_empty() {
+            channel = channel.with_child(Element::new("Sends").with_children(sends));
+        }
+        channel = channel.with_child(
+            Element::new("Volume")
+                .with_attribute("id", format!("vol{}", track_index))
+                .with_attribute("min", 0)
+                .with_attribute("max", 2)
+                .with_attribute("unit", "linear")
+                .with_attribute(
+                    "value",
+                    mixer_value(track.element, "PostVolume").unwrap_or(1.0),
+                ),
+        );
+
+        Element::new("Track")
+            .with_attribute("id", format!("track{}", track_index))
+            .with_attribute("name", &name)
+            .with_attribute("color", format!("#{}", color(track.element)))
+            .with_attribute(
+                "comment",
+                match delay {
+                    0.0 => String::new(),
+                    delay => format!("Delay: {}", delay),
+                },
+            )
+            .with_attribute("contentType", content_type)
+            .with_child(channel)
+    }
+
+    // Send devices of the track, the receiver is counted from the first
+    // send track
+    fn sends(&self, index: usize) -> Vec<Element> {
+        let track = &self.tracks[index];
+        let send_offset = self.sequencer_track_count() + 2;
+
+        song::devices(track.element)
+            .enumerate()
+            .filter(|(_, device)| song::device_type(device) == SEND_DEVICE)
+            .map(|(device_index, device)| {
+                let receiver = device.child_value("DestSendTrack").unwrap_or(0.0) as usize;
+                let parameter = |element: &str, name: &str, unit: &str| {
+                    Element::new(element)
+                        .with_attribute("max", 1)
+                        .with_attribute("min", 0)
+                        .with_attribute("unit", unit)
+                        .with_attribute("value", device.child_value(name).unwrap_or(0.0))
+                };
+                Element::new("Send")
+                    .with_attribute("destination", format!("track{}", receiver + send_offset))
+                    .with_attribute("type", "post")
+                    .with_attribute("id", format!("send{}-{}", track.index, device_index + 1))
+                    .with_child(Element::new("Enable").with_attribute("value", is_active(device)))
+                    .with_child(parameter("Volume", "SendAmount", "linear"))
+                    .with_child(parameter("Pan", "SendPan", "normalized"))
+            })
+            .collect()
+    }
+
+    // The plugin of the track's instrument, followed by the plugin devices
+    // after the pre-mixer
+    fn devices(&mut self, index: usize) -> Vec<Element> {
+        let track = &self.tracks[index];
+        let track_index = track.index;
+        let element = track.element;
+        let mut devices = Vec::new();
+
+        if let Some(instrument_index) = track.instrument
+            && let Some(instrument) = self.instruments.get(instrument_index).copied()
+        {
+            let instrument_name = instrument.child_text("Name").unwrap_or_default();
+            match song::instrument_plugin(instrument).and_then(song::plugin_device) {
+                Some(plugin) => self.add_device(
+                    &mut devices,
+                    track_index,
+                    &plugin,
+                    DeviceSlot {
+                        save_path: format!(
+                            "instr-tr{}-no{}-{}",
+                            track_index,
+                            instrument_index + 1,
+                            prepare_file_name(instrument_name)
+                        ),
+                        parameter_id_prefix: format!(
+                            "paramid-{}-i{}",
+                            track_index,
+                            instrument_index + 1
+                        ),
+                        role: "instrument",
+                        enabled: true,
+                    },
+                ),
+                None => self.skip(
+                    track_index,
+                    instrument_name,
+                    "Sample instrument, the conversion to Redux needs Renoise",
+                ),
+            }
+        }
+
+        for (device_index, device) in song::devices(element).enumerate().skip(1) {
+            let Some(plugin) = song::plugin_device(device) else {
+                continue;
+            };
+            self.add_device(
+                &mut devices,
+                track_index,
+                &plugin,
+                DeviceSlot {
+                    save_path: format!(
+                        "fx-tr{}-no{}-{}",
+                        track_index,
+                        device_index + 1,
+                        prepare_file_name(&plugin.name)
+                    ),
+                    parameter_id_prefix: format!("paramid-{}-{}", track_index, device_index + 1),
+                    role: "audioFX",
+                    enabled: is_active(device),
+                },
+            );
+        }
+        devices
+    }
+
+    fn add_device(
+        &mut self,
+        devices: &mut Vec<Element>,
+        track_index: usize,
+        plugin: &PluginDevice,
+        slot: DeviceSlot,
+    ) {
+        let Some(chunk) = plugin
+            .parameter_chunk
+            .as_deref()
+            .and_then(|chunk| BASE64_STANDARD.decode(strip_whitespace(chunk)).ok())
+        else {
+            return self.skip(track_index, &plugin.name, "No ParameterChunk");
+        };
+
+        let (element_name, device_id, extension, state) = match plugin.plugin_type.as_str() {
+            "VST3" => ("Vst3Plugin", plugin.identifier.clone(), "vstpreset", chunk),
+            "VST" => {
+                let Some(vst2) = self.options.vst2_plugins.get(&plugin.identifier) else {
+                    return self.skip(track_index, &plugin.name, "Unknown VST2 unique ID");
+                };
+                // Named after the active preset as in the Lua export, the
+                // writer cuts it to 27 bytes
+                let state = fxp::write_chunk_program(
+                    vst2.unique_id,
+                    vst2.version,
+                    plugin.parameters.len() as i32,
+                    plugin.preset_name.as_deref().unwrap_or(&plugin.name),
+                    &chunk,
+                );
+                ("Vst2Plugin", vst2.unique_id.to_string(), "fxp", state)
+            }
+            "AU" => {
+                let Some((component, _)) = parse_device_id(&plugin.identifier) else {
+                    return self.skip(track_index, &plugin.name, "Invalid AudioUnit identifier");
+                };
+                let state = match plist_to_xml(&chunk) {
+                    Ok(state) => state,
+                    Err(e) => return self.skip(track_index, &plugin.name, &e),
+                };
+                let device_id = component.device_id(DeviceIdForm::StudioOne);
+                ("AuPlugin", device_id, "aupreset", state)
+            }
+            other => {
+                let reason = format!("Unsupported plugin type {}", other);
+                return self.skip(track_index, &plugin.name, &reason);
+            }
+        };
+
+        let state_path = format!("{}/{}.{}", PLUGINS_DIR, slot.save_path, extension);
+        self.files.push((state_path.clone(), state));
+        self.count_plugins += 1;
+
+        let mut device = Element::new(element_name)
+            .with_attribute("id", format!("plugin{}-{}", track_index, devices.len() + 1))
+            .with_attribute("deviceID", device_id)
+            .with_attribute("deviceRole", slot.role)
+            .with_attribute("deviceName", prepare_name(&plugin.short_name))
+            .with_attribute("name", prepare_name(&plugin.name))
+            .with_attribute("loaded", true)
+            .with_child(
+                Element::new("Enabled")
+                    .with_attribute("id", format!("enabled{}-{}", track_index, devices.len()))
+                    .with_attribute("value", slot.enabled),
+            )
+            .with_child(
+                Element::new("State")
+                    .with_attribute("path", state_path)
+                    .with_attribute("external", false),
+            );
+        let parameters = self.automated_parameters(&slot.parameter_id_prefix);
+        if !parameters.is_empty() {
+            device = device.with_child(Element::new("Parameters").with_children(parameters));
+        }
+        devices.push(device);
+    }
+
+    fn skip(&mut self, track: usize, device: &str, reason: &str) {
+        self.skipped.push(SkippedDevice {
+            track,
+            device: device.to_string(),
+            reason: reason.to_string(),
+        });
+    }
+
+    // The automated parameters of a device, by the prefix of their IDs
+    fn automated_parameters(&self, parameter_id_prefix: &str) -> Vec<Element> {
+        let mut parameters: Vec<Element> = Vec::new();
+        let mut seen: Vec<i64> = Vec::new();
+        for event in &self.events.automation {
+            if parameter_id(event).0 != parameter_id_prefix || seen.contains(&event.parameter) {
+                continue;
+            }
+            seen.push(event.parameter);
+            parameters.push(
+                Element::new("RealParameter")
+                    .with_attribute("id", parameter_id(event).1)
+                    .with_attribute("name", &event.parameter_name)
+                    .with_attribute("parameterID", event.parameter)
+                    .with_attribute("unit", "normalized")
+                    .with_attribute("min", 0)
+                    .with_attribute("max", 1),
+            );
+        }
+        parameters
+    }
+
+    // Per track the clips with the notes and the automation lanes
+    fn track_lanes(&self) -> Vec<Element> {
+        self.tracks
+            .iter()
+            .filter_map(|track| {
+                let track_index = track.index - 1;
+                let notes: Vec<&NoteEvent> = self
+                    .events
+                    .notes
+                    .iter()
+                    .filter(|note| note.track == track_index)
+                    .collect();
+                let automation = self.automation_lanes(track_index);
+                if notes.is_empty() && automation.is_empty() {
+                    return None;
+                }
+
+                let suffix = match notes.first() {
+                    Some(note) => format!("{}-{}", track.index, note.sequence),
+                    None => track.index.to_string(),
+                };
+                let mut lanes = Element::new("Lanes")
+                    .with_attribute("id", format!("lanes{}", suffix))
+                    .with_attribute("track", format!("track{}", track.index));
+                if !notes.is_empty() {
+                    lanes = lanes.with_child(
+                        Element::new("Clips")
+                            .with_attribute("id", format!("clips{}", suffix))
+                            .with_children(self.clips(track, &notes)),
+                    );
+                }
+                Some(lanes.with_children(automation))
+            })
+            .collect()
+    }
+
+    // One clip per sequence slot, at the pattern start
+    fn clips(&self, track: &Track, notes: &[&NoteEvent]) -> Vec<Element> {
+        let track_delay = if self.options.add_track_delay {
+            output_delay(track.element) / (60000.0 / self.bpm)
+        } else {
+            0.0
+        };
+
+        let mut clips: Vec<Element> = Vec::new();
+        let mut sequence = 0;
+        for note in notes {
+            if note.sequence != sequence || clips.is_empty() {
+                sequence = note.sequence;
+                let time = (self.beats(note.pattern_timestamp as f64) + track_delay).max(0.0);
+                clips.push(
+                    Element::new("Clip")
+                        .with_attribute("time", time)
+                        .with_attribute("duration", self.beats(note.pattern_duration as f64))
+                        .with_attribute("playStart", 0)
+                        .with_attribute("enable", note.enabled)
+                        .with_attribute(
+                            "name",
+                            format!("track{} pattern{}", track.index, note.pattern),
+                        )
+                        .with_child(
+                            Element::new("Lanes")
+                                .with_attribute(
+                                    "id",
+                                    format!("sublanes{}-{}", track.index, sequence),
+                                )
+                                .with_child(Element::new("Notes").with_attribute(
+                                    "id",
+                                    format!("notes{}-{}", track.index, sequence),
+                                )),
+                        ),
+                );
+            }
+            let Some(notes_element) = clips
+                .last_mut()
+                .and_then(|clip| clip.children.first_mut())
+                .and_then(|lanes| lanes.children.first_mut())
+            else {
+                continue;
+            };
+            let number = notes_element.children.len() + 1;
+            notes_element
+                .children
+                .push(self.note(track.index, note, number));
+        }
+        clips
+    }
+
+    fn note(&self, track_index: usize, note: &NoteEvent, number: usize) -> Element {
+        let mut element = Element::new("Note")
+            .with_attribute(
+                "time",
+                round(self.beats(note.pattern_relative_timestamp() as f64)),
+            )
+            .with_attribute("duration", round(self.beats(note.duration as f64)))
+            .with_attribute("channel", 0)
+            .with_attribute("key", note.key)
+            .with_attribute("vel", note.velocity);
+        if let Some(release_velocity) = note.release_velocity {
+            element = element.with_attribute("rel", release_velocity);
+        }
+        if note.pressure.is_empty() {
+            return element;
+        }
+
+        element.with_child(
+            Element::new("Points")
+                .with_attribute(
+                    "id",
+                    format!("polypressure{}-{}-{}", track_index, note.sequence, number),
+                )
+                .with_attribute("unit", "linear")
+                .with_child(Element::new("Target").with_attribute("expression", "pressure"))
+                .with_children(note.pressure.iter().map(|point| {
+                    Element::new("RealPoint")
+                        .with_attribute("value", round(point.value))
+                        .with_attribute("interpolation", "linear")
+                        .with_attribute("time", round(self.beats(point.timestamp as f64)))
+                })),
+        )
+    }
+
+    // One Points lane per automated parameter of the track
+    fn automation_lanes(&self, track_index: usize) -> Vec<Element> {
+        let mut lanes: Vec<(String, Element)> = Vec::new();
+        for event in self
+            .events
+            .automation
+            .iter()
+            .filter(|event| event.track == track_index)
+        {
+            let (_, id) = parameter_id(event);
+            let point = Element::new("RealPoint")
+                .with_attribute("time", round(self.beats(event.timestamp)))
+                .with_attribute("value", round(event.value))
+                .with_attribute("interpolation", event.interpolation);
+
+            if let Some((_, lane)) = lanes.iter_mut().find(|(lane_id, _)| *lane_id == id) {
+                lane.children.push(point);
+                continue;
+            }
+            let mut target = Element::new("Target").with_attribute("parameter", &id);
+            if let Some(expression) = expression(&event.automation_type) {
+                target = target.with_attribute("expression", expression);
+            }
+            if event.automation_type == events::CONTROL_CHANGE_TYPE {
+                target = target.with_attribute("controller", event.parameter);
+            }
+            target = target.with_attribute("channel", 0);
+
+            let lane = Element::new("Points")
+                .with_attribute("name", &event.parameter_name)
+                .with_attribute("unit", "normalized")
+                .with_child(target)
+                .with_child(point);
+            lanes.push((id, lane));
+        }
+        lanes.into_iter().map(|(_, lane)| lane).collect()
+    }
+
+    // A marker at every section start
+    fn markers(&self, patterns: &[pattern::Pattern], sequence: &[SequenceEntry]) -> Element {
+        let mut markers = Element::new("Markers").with_attribute("timeUnit", "beats");
+        let mut line_offset = 0;
+        for entry in sequence {
+            if entry.section_start {
+                markers = markers.with_child(
+                    Element::new("Marker")
+                        .with_attribute(
+                            "time",
+                            round(self.beats((line_offset * TICKS_PER_LINE) as f64)),
+                        )
+                        .with_attribute("name", prepare_name(&entry.section_name)),
+                );
+            }
+            line_offset += patterns
+                .get(entry.pattern)
+                .map_or(0, |pattern| pattern.number_of_lines as i64);
+        }
+        markers
+    }
+}
+
+// Prefix and full ID of the automated parameter
+fn parameter_id(event: &AutomationEvent) -> (String, String) {
+    let prefix = format!("paramid-{}-{}", event.track + 1, event.device);
+    let id = format!("{}-{}", prefix, event.parameter);
+    (prefix, id)
+}
+
+fn expression(automation_type: &str) -> Option<&'static str> {
+    match automation_type {
+        events::PITCH_BEND_TYPE => Some("pitchBend"),
+        events::CONTROL_CHANGE_TYPE => Some("channelController"),
+        events::CHANNEL_PRESSURE_TYPE => Some("channelPressure"),
+        events::PROGRAM_CHANGE_TYPE => Some("programChange"),
+        _ => None,
+    }
+}
+
+// "r,g,b" as lower case hex
+fn color(track: &Element) -> String {
+    let rgb: Vec<u8> = track
+        .child_text("Color")
+        .unwrap_or_default()
+        .split(',')
+        .filter_map(|part| part.trim().parse().ok())
+        .collect();
+    match rgb[..] {
+        [r, g, b] => format!("{:02x}{:02x}{:02x}", r, g, b),
+        _ => "000000".to_string(),
+    }
+}
+
+// Mixer values are saved in the track mixer device, the first device
+fn mixer_value(track: &Element, name: &str) -> Option<f64> {
+    let mixer = song::devices(track).next();
+    mixer
+        .and_then(|mixer| mixer.child_value(name).or_else(|| mixer.child_number(name)))
+        .or_else(|| track.child_number(name))
+}
+
+// In ms
+fn output_delay(track: &Element) -> f64 {
+    mixer_value(track, "OutputDelay").unwrap_or(0.0)
+}
+
+fn output_routing(track: &Element) -> &str {
+    song::devices(track)
+        .next()
+        .and_then(|mixer| mixer.child_text("OutputRouting"))
+        .or_else(|| track.child_text("OutputRouting"))
+        .filter(|routing| !routing.is_empty())
+        .unwrap_or(MASTER_ROUTING)
+}
+
+fn is_active(device: &Element) -> bool {
+    device
+        .child_value("IsActive")
+        .is_none_or(|active| active != 0.0)
+}
+
+fn strip_whitespace(text: &str) -> String {
+    text.chars().filter(|c| !c.is_whitespace()).collect()
+}
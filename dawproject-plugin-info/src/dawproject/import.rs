@@ -0,0 +1,1395 @@
+/*!
+ * ------------------------------------------------------------------------
+ * DAWproject to Renoise import
+ * by Jurek Raben
+ *
+ * Maps a .dawproject onto a Renoise song and returns it as an import plan
+ * for the Lua tool to apply: the tracks flattened with the group members
+ * before their group, the master and the sends last, the plugin devices
+ * with their states as ParameterChunk, patterns cut at the markers and
+ * every pattern length, notes in note columns and automation per sequence
+ * slot.
+ * Indices are one based and note column values are the strings of the
+ * pattern editor, as in the Lua API.
+ *
+ * Licensed under CC Attribution-NonCommercial-ShareAlike 4.0 International
+ * Info here: https://creativecommons.org/licenses/by-nc-sa/4.0/
+ * ------------------------------------------------------------------------
+ */
+
+use std::collections::{BTreeMap, HashMap};
+
+use base64::prelude::*;
+use serde::Serialize;
+
+use super::DawProject;
+use super::events::TICKS_PER_LINE;
+use crate::au::identifier::{DeviceIdForm, parse_device_id};
+use crate::au::preset::plist_to_binary;
+use crate::vst2::fxp::{self, FxContent};
+use crate::xml::Element;
+use crate::xrns::pattern::{self, NOTE_OFF};
+
+pub const DEFAULT_LINES_PER_BEAT: u32 = 4;
+pub const MAX_LINES_PER_BEAT: u32 = 256;
+pub const DEFAULT_PATTERN_LINES: usize = 64;
+pub const MAX_PATTERN_LINES: usize = 512;
+pub const MAX_NOTE_COLUMNS: usize = 12;
+pub const MAX_SEQUENCE_SLOTS: usize = 1000;
+
+const DEFAULT_BPM: f64 = 120.0;
+const DEFAULT_SIGNATURE: u32 = 4;
+const MAX_VELOCITY: f64 = 127.0;
+const MASTER_NAME: &str = "Master";
+
+// Point elements of automation lanes, BoolPoints always hold
+const POINT_ELEMENTS: [&str; 4] = ["RealPoint", "IntegerPoint", "EnumPoint", "BoolPoint"];
+
+// Parameter elements of plugin devices which automation can target
+const PARAMETER_ELEMENTS: [&str; 4] = [
+    "RealParameter",
+    "IntegerParameter",
+    "EnumParameter",
+    "BoolParameter",
+];
+
+#[derive(Debug, Clone)]
+pub struct ImportOptions {
+    pub lines_per_beat: u32,
+    // Longest pattern, patterns are also cut at markers
+    pub pattern_lines: usize,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ImportPlan {
+    pub name: String,
+    pub artist: String,
+    pub comment: String,
+    pub bpm: f64,
+    pub lines_per_beat: u32,
+    pub signature_numerator: u32,
+    pub signature_denominator: u32,
+    pub tracks: Vec<ImportTrack>,
+    pub instruments: Vec<ImportInstrument>,
+    pub patterns: Vec<ImportPattern>,
+    pub sequence: Vec<ImportSequenceEntry>,
+    pub automation: Vec<ImportAutomation>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ImportTrack {
+    pub index: usize,
+    // "sequencer", "group", "master" or "send"
+    #[serde(rename = "type")]
+    pub track_type: &'static str,
+    pub name: String,
+    pub color: Option<[u8; 3]>,
+    // Index of the group track the track is a member of
+    pub group: Option<usize>,
+    pub note_columns: usize,
+    pub muted: bool,
+    pub solo: bool,
+    // Linear
+    pub volume: f64,
+    // Normalized, 0.5 is center
+    pub panning: f64,
+    pub instrument: Option<usize>,
+    // The chain after the track mixer, the first device has the index 2
+    pub devices: Vec<ImportDevice>,
+    pub sends: Vec<ImportSend>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ImportDevice {
+    // "VST3", "VST", "AU" or "CLAP", as the plugin types of Renoise
+    pub plugin_type: &'static str,
+    // PluginIdentifier of Renoise where the deviceID tells it, the plain
+    // type:subtype:manufacturer form for AudioUnits
+    pub identifier: String,
+    pub device_id: String,
+    pub name: String,
+    pub enabled: bool,
+    // Base64, None if the state is missing or only has parameter values
+    pub parameter_chunk: Option<String>,
+    // Values of VST2 programs without chunk
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub parameters: Vec<f32>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ImportInstrument {
+    pub index: usize,
+    pub name: String,
+    // The sequencer track playing it
+    pub track: usize,
+    pub plugin: ImportDevice,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ImportSend {
+    // Index of the send track
+    pub track: usize,
+    // Linear
+    pub amount: f64,
+    pub panning: f64,
+    pub enabled: bool,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ImportPattern {
+    pub index: usize,
+    pub number_of_lines: usize,
+    pub tracks: Vec<ImportPatternTrack>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ImportPatternTrack {
+    pub track: usize,
+    // Only lines with notes
+    pub lines: Vec<ImportLine>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ImportLine {
+    pub index: usize,
+    pub note_columns: Vec<ImportNoteColumn>,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ImportNoteColumn {
+    pub column: usize,
+    // e.g. "C-4" or "OFF", None for volume changes
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub note: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub instrument: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub volume: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub delay: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ImportSequenceEntry {
+    pub pattern: usize,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub section_name: Option<String>,
+    // Tracks with disabled clips in the slot
+    pub muted_tracks: Vec<usize>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ImportAutomation {
+    pub track: usize,
+    // Index in the device chain, None for instrument plugins
+    pub device: Option<usize>,
+    pub instrument: Option<usize>,
+    pub parameter_id: i64,
+    pub name: String,
+    // "points" or "lines"
+    pub play_mode: &'static str,
+    pub points: Vec<ImportAutomationPoint>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ImportAutomationPoint {
+    pub sequence: usize,
+    // In lines, 1 is the start of the pattern
+    pub time: f64,
+    // Normalized
+    pub value: f64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SkippedElement {
+    pub track: Option<usize>,
+    pub element: String,
+    pub reason: String,
+}
+
+#[derive(Debug, Clone)]
+pub struct Import {
+    pub plan: ImportPlan,
+    pub count_tracks: usize,
+    pub count_patterns: usize,
+    pub count_notes: usize,
+    // Notes beyond the note columns or outside of the note range
+    pub count_dropped_notes: usize,
+    pub count_automation_points: usize,
+    pub count_plugins: usize,
+    pub skipped: Vec<SkippedElement>,
+}
+
+impl Default for ImportOptions {
+    fn default() -> Self {
+        ImportOptions {
+            lines_per_beat: DEFAULT_LINES_PER_BEAT,
+            pattern_lines: DEFAULT_PATTERN_LINES,
+        }
+    }
+}
+
+// A Track of the structure with the id of its group track
+struct SourceTrack<'a> {
+    element: &'a Element,
+    track_type: &'static str,
+    group: Option<&'a str>,
+}
+
+// What a parameter id of the project automates
+struct ParameterTarget {
+    track: usize,
+    device: Option<usize>,
+    instrument: Option<usize>,
+    parameter_id: i64,
+    name: String,
+    range: Option<(f64, f64)>,
+}
+
+// Times are in ticks from the song start
+struct SourceNote {
+    track: usize,
+    start: i64,
+    end: i64,
+    key: i64,
+    velocity: f64,
+    release_velocity: Option<f64>,
+    pressure: Vec<(i64, f64)>,
+}
+
+struct Lane {
+    parameter: String,
+    hold: bool,
+    points: Vec<(i64, f64)>,
+}
+
+struct Slot {
+    start_line: usize,
+    lines: usize,
+    section_name: Option<String>,
+}
+
+// Position in the timeline while walking the lanes and clips
+#[derive(Clone, Copy)]
+struct Context {
+    track: Option<usize>,
+    // Beats from the song start to the content start
+    offset: f64,
+    window: (f64, f64),
+    enabled: bool,
+    seconds: bool,
+}
+
+// A note placed in a column, ends with a note-off unless the next note
+// starts at its end
+struct PlacedNote {
+    end: i64,
+    start_line: i64,
+    release_velocity: Option<f64>,
+}
+
+type Entries = BTreeMap<(usize, usize, usize, usize), ImportNoteColumn>;
+
+struct Importer<'a> {
+    project: &'a DawProject,
+    bpm: f64,
+    lines_per_beat: u32,
+    tracks: Vec<ImportTrack>,
+    // Track and channel ids
+    track_ids: HashMap<&'a str, usize>,
+    instruments: Vec<ImportInstrument>,
+    parameters: HashMap<&'a str, ParameterTarget>,
+    notes: Vec<SourceNote>,
+    lanes: Vec<Lane>,
+    disabled_clips: Vec<(usize, i64, i64)>,
+    count_plugins: usize,
+    count_dropped_notes: usize,
+    skipped: Vec<SkippedElement>,
+}
+
+pub fn import_project(project: &DawProject, options: &ImportOptions) -> Import {
+    let root = &project.project;
+    let transport = root.child("Transport");
+    let tempo = transport
+        .and_then(|transport| transport.child("Tempo"))
+        .and_then(|tempo| number(tempo, "value"))
+        .filter(|bpm| *bpm > 0.0)
+        .unwrap_or(DEFAULT_BPM);
+    let signature = transport.and_then(|transport| transport.child("TimeSignature"));
+    let signature_value = |name| {
+        signature
+            .and_then(|signature| number(signature, name))
+            .map_or(DEFAULT_SIGNATURE, |value| value as u32)
+    };
+
+    let mut importer = Importer {
+        project,
+        bpm: tempo,
+        lines_per_beat: options.lines_per_beat.clamp(1, MAX_LINES_PER_BEAT),
+        tracks: Vec::new(),
+        track_ids: HashMap::new(),
+        instruments: Vec::new(),
+        parameters: HashMap::new(),
+        notes: Vec::new(),
+        lanes: Vec::new(),
+        disabled_clips: Vec::new(),
+        count_plugins: 0,
+        count_dropped_notes: 0,
+        skipped: Vec::new(),
+    };
+    importer.add_tracks();
+
+    let arrangement = root.child("Arrangement");
+    if let Some(lanes) = arrangement.and_then(|arrangement| arrangement.child("Lanes")) {
+        let context = Context {
+            track: None,
+            offset: 0.0,
+            window: (f64::NEG_INFINITY, f64::INFINITY),
+            enabled: true,
+            seconds: false,
+        };
+        importer.walk(lanes, context);
+    }
+    for name in ["TempoAutomation", "TimeSignatureAutomation"] {
+        if arrangement
+            .and_then(|arrangement| arrangement.child(name))
+            .is_some()
+        {
+            importer.skip(None, name, "Tempo and signature changes are not imported");
+        }
+    }
+
+    let markers = arrangement
+        .and_then(|arrangement| arrangement.child("Markers"))
+        .map(|markers| importer.markers(markers))
+        .unwrap_or_default();
+    let slots = importer.slots(&markers, options.pattern_lines.clamp(1, MAX_PATTERN_LINES));
+    let (patterns, count_notes) = importer.patterns(&slots);
+    let automation = importer.automation(&slots);
+    let sequence = importer.sequence(&slots);
+
+    let metadata_text = |name| {
+        project
+            .metadata
+            .child_text(name)
+            .unwrap_or_default()
+            .to_string()
+    };
+    let plan = ImportPlan {
+        name: metadata_text("Title"),
+        artist: metadata_text("Artist"),
+        comment: metadata_text("Comment"),
+        bpm: importer.bpm,
+        lines_per_beat: importer.lines_per_beat,
+        signature_numerator: signature_value("numerator"),
+        signature_denominator: signature_value("denominator"),
+        tracks: importer.tracks,
+        instruments: importer.instruments,
+        patterns,
+        sequence,
+        automation,
+    };
+
+    Import {
+        count_tracks: plan.tracks.len(),
+        count_patterns: plan.patterns.len(),
+        count_notes,
+        count_dropped_notes: importer.count_dropped_notes,
+        count_automation_points: plan
+            .automation
+            .iter()
+            .map(|automation| automation.points.len())
+            .sum(),
+        count_plugins: importer.count_plugins,
+        skipped: importer.skipped,
+        plan,
+    }
+}
+
+// Renoise needs the group members before the group, the master and the
+// sends come after the sequencer tracks
+fn collect_tracks<'a>(
+    elements: impl Iterator<Item = &'a Element>,
+    group: Option<&'a str>,
+    regular: &mut Vec<SourceTrack<'a>>,
+    others: &mut Vec<SourceTrack<'a>>,
+) {
+    for element in elements {
+        let members: Vec<&Element> = element.children_named("Track").collect();
+        let role = element
+            .child("Channel")
+            .and_then(|channel| channel.attribute("role"));
+        let content_type = element.attribute("contentType").unwrap_or_default();
+
+        if !members.is_empty() || content_type.split(' ').any(|content| content == "tracks") {
+            collect_tracks(
+                members.into_iter(),
+                element.attribute("id"),
+                regular,
+                others,
+            );
+            regular.push(SourceTrack {
+                element,
+                track_type: "group",
+                group,
+            });
+        } else if group.is_none() && matches!(role, Some("master" | "effect")) {
+            let track_type = if role == Some("master") {
+                "master"
+            } else {
+                "send"
+            };
+            others.push(SourceTrack {
+                element,
+                track_type,
+                group,
+            });
+        } else {
+            regular.push(SourceTrack {
+                element,
+                track_type: "sequencer",
+                group,
+            });
+        }
+    }
+}
+
+fn number(element: &Element, name: &str) -> Option<f64> {
+    element.attribute(name)?.trim().parse().ok()
+}
+
+fn is_true(element: Option<&Element>, name: &str) -> bool {
+    element.and_then(|element| element.attribute(name)) == Some("true")
+}
+
+// Volumes are linear or in dB
+fn volume(element: &Element) -> Option<f64> {
+    let value = number(element, "value")?;
+    Some(match element.attribute("unit") {
+        Some("decibel") => 10f64.powf(value / 20.0),
+        _ => value,
+    })
+}
+
+// Pan values are normalized to the Renoise panning, e.g. from -1 to 1
+fn panning(element: &Element) -> Option<f64> {
+    Some(normalize(
+        number(element, "value")?,
+        parameter_range(element),
+    ))
+}
+
+fn parameter_range(element: &Element) -> Option<(f64, f64)> {
+    if element.attribute("unit") == Some("normalized") {
+        return None;
+    }
+    let (min, max) = (number(element, "min")?, number(element, "max")?);
+    (max > min).then_some((min, max))
+}
+
+fn normalize(value: f64, range: Option<(f64, f64)>) -> f64 {
+    match range {
+        Some((min, max)) => ((value - min) / (max - min)).clamp(0.0, 1.0),
+        None => value,
+    }
+}
+
+// "#rrggbb"
+fn parse_color(color: &str) -> Option<[u8; 3]> {
+    let hex = color.strip_prefix('#')?;
+    if hex.len() != 6 {
+        return None;
+    }
+    let channel = |index: usize| u8::from_str_radix(hex.get(index..index + 2)?, 16).ok();
+    Some([channel(0)?, channel(2)?, channel(4)?])
+}
+
+fn hex(value: f64) -> String {
+    format!(
+        "{:02X}",
+        (value * MAX_VELOCITY).round().clamp(0.0, MAX_VELOCITY) as u8
+    )
+}
+
+// The Renoise state of a plugin and the parameter values of VST2 programs
+// without chunk
+fn plugin_state(plugin_type: &str, data: &[u8]) -> Result<(Option<Vec<u8>>, Vec<f32>), String> {
+    match plugin_type {
+        "VST" => match fxp::parse_fx_file(data)?.content {
+            FxContent::Program(program) if program.is_chunk => {
+                Ok((Some(program.chunk), Vec::new()))
+            }
+            FxContent::Program(program) => Ok((None, program.parameters)),
+            FxContent::Bank(bank) if bank.is_chunk => Ok((Some(bank.chunk), Vec::new())),
+            FxContent::Bank(bank) => {
+                let current = bank.current_program.unwrap_or(0).max(0) as usize;
+                let parameters = bank
+                    .programs
+                    .into_iter()
+                    .nth(current)
+                    .map(|program| program.parameters)
+                    .unwrap_or_default();
+                Ok((None, parameters))
+            }
+        },
+        "AU" => Ok((Some(plist_to_binary(data)?), Vec::new())),
+        _ => Ok((Some(data.to_vec()), Vec::new())),
+    }
+}
+
+impl<'a> Importer<'a> {
+    fn ticks(&self, beats: f64) -> i64 {
+        (beats * self.lines_per_beat as f64 * TICKS_PER_LINE as f64).round() as i64
+    }
+
+    fn beats(&self, value: f64, seconds: bool) -> f64 {
+        if seconds {
+            value * self.bpm / 60.0
+        } else {
+            value
+        }
+    }
+
+    fn skip(&mut self, track: Option<usize>, element: &str, reason: &str) {
+        let skipped = SkippedElement {
+            track,
+            element: element.to_string(),
+            reason: reason.to_string(),
+        };
+        // Lanes and clips report the same issue over and over
+        if !self.skipped.iter().any(|other| {
+            other.track == skipped.track
+                && other.element == skipped.element
+                && other.reason == skipped.reason
+        }) {
+            self.skipped.push(skipped);
+        }
+    }
+
+    fn add_tracks(&mut self) {
+        let project = self.project;
+        let mut regular = Vec::new();
+        let mut others = Vec::new();
+        if let Some(structure) = project.project.child("Structure") {
+            collect_tracks(
+                structure.children_named("Track"),
+                None,
+                &mut regular,
+                &mut others,
+            );
+        }
+
+        // One master, further ones become sends. Without master one is
+        // added.
+        let mut sources: Vec<Option<SourceTrack>> = regular.into_iter().map(Some).collect();
+        let master = others
+            .iter()
+            .position(|track| track.track_type == "master")
+            .map(|master| others.remove(master));
+        sources.push(master);
+        sources.extend(others.into_iter().map(|mut track| {
+            track.track_type = "send";
+            Some(track)
+        }));
+
+        for (position, source) in sources.iter().enumerate() {
+            let Some(source) = source else {
+                continue;
+            };
+            let channel = source.element.child("Channel");
+            let ids = [
+                source.element.attribute("id"),
+                channel.and_then(|channel| channel.attribute("id")),
+            ];
+            for id in ids.into_iter().flatten() {
+                self.track_ids.insert(id, position + 1);
+            }
+        }
+
+        for (position, source) in sources.iter().enumerate() {
+            let track = match source {
+                Some(source) => self.track(source, position + 1),
+                None => ImportTrack {
+                    index: position + 1,
+                    track_type: "master",
+                    name: MASTER_NAME.to_string(),
+                    color: None,
+                    group: None,
+                    note_columns: 0,
+                    muted: false,
+                    solo: false,
+                    volume: 1.0,
+                    panning: 0.5,
+                    instrument: None,
+                    devices: Vec::new(),
+                    sends: Vec::new(),
+                },
+            };
+            self.tracks.push(track);
+        }
+        // The send tracks are known now
+        for (position, source) in sources.iter().enumerate() {
+            if let Some(source) = source {
+                self.add_sends(source.element, position);
+            }
+        }
+    }
+
+    fn add_sends(&mut self, element: &Element, position: usize) {
+        let sends = element
+            .child("Channel")
+            .and_then(|channel| channel.child("Sends"));
+        for send in sends.iter().flat_map(|sends| sends.children_named("Send")) {
+            let destination = send
+                .attribute("destination")
+                .and_then(|destination| self.track_ids.get(destination).copied())
+                .filter(|destination| self.tracks[destination - 1].track_type == "send");
+            let Some(destination) = destination else {
+                self.skip(
+                    Some(position + 1),
+                    "Send",
+                    "The destination is no send track",
+                );
+                continue;
+            };
+            self.tracks[position].sends.push(ImportSend {
+                track: destination,
+                amount: send.child("Volume").and_then(volume).unwrap_or(1.0),
+                panning: send.child("Pan").and_then(panning).unwrap_or(0.5),
+                enabled: !matches!(
+                    send.child("Enable")
+                        .and_then(|enable| enable.attribute("value")),
+                    Some("false")
+                ),
+            });
+        }
+    }
+
+    fn track(&mut self, source: &SourceTrack<'a>, index: usize) -> ImportTrack {
+        let element = source.element;
+        let channel = element.child("Channel");
+        let channel_child = |name| channel.and_then(|channel| channel.child(name));
+        let name = element
+            .attribute("name")
+            .or_else(|| channel.and_then(|channel| channel.attribute("name")))
+            .unwrap_or_default();
+
+        let mut track = ImportTrack {
+            index,
+            track_type: source.track_type,
+            name: name.to_string(),
+            color: element.attribute("color").and_then(parse_color),
+            group: source
+                .group
+                .and_then(|group| self.track_ids.get(group).copied()),
+            note_columns: usize::from(source.track_type == "sequencer"),
+            muted: is_true(channel_child("Mute"), "value"),
+            solo: is_true(channel, "solo"),
+            volume: channel_child("Volume").and_then(volume).unwrap_or(1.0),
+            panning: channel_child("Pan").and_then(panning).unwrap_or(0.5),
+            instrument: None,
+            devices: Vec::new(),
+            sends: Vec::new(),
+        };
+
+        let devices = channel_child("Devices");
+        for device in devices.iter().flat_map(|devices| devices.children.iter()) {
+            self.add_device(&mut track, device);
+        }
+        track
+    }
+
+    fn add_device(&mut self, track: &mut ImportTrack, element: &'a Element) {
+        let name = element
+            .attribute("name")
+            .or_else(|| element.attribute("deviceName"))
+            .unwrap_or(&element.name);
+        let plugin_type = match element.name.as_str() {
+            "Vst3Plugin" => "VST3",
+            "Vst2Plugin" => "VST",
+            "AuPlugin" => "AU",
+            "ClapPlugin" => "CLAP",
+            _ => {
+                return self.skip(
+                    Some(track.index),
+                    name,
+                    "Built-in device of another application",
+                );
+            }
+        };
+        let is_instrument = element.attribute("deviceRole") == Some("instrument");
+        if is_instrument && (track.track_type != "sequencer" || track.instrument.is_some()) {
+            return self.skip(
+                Some(track.index),
+                name,
+                "Only one instrument per sequencer track",
+            );
+        }
+
+        let device_id = element.attribute("deviceID").unwrap_or_default();
+        let identifier = match plugin_type {
+            "AU" => match parse_device_id(device_id) {
+                Some((component, _)) => component.device_id(DeviceIdForm::Plain),
+                None => {
+                    return self.skip(Some(track.index), name, "Invalid AudioUnit identifier");
+                }
+            },
+            "VST" => element.attribute("deviceName").unwrap_or(name).to_string(),
+            _ => device_id.to_string(),
+        };
+
+        let state_path = element
+            .child("State")
+            .and_then(|state| state.attribute("path"))
+            .unwrap_or_default();
+        let (chunk, parameters) = match self.project.file(state_path) {
+            Some(data) => match plugin_state(plugin_type, data) {
+                Ok(state) => state,
+                Err(e) => {
+                    self.skip(Some(track.index), name, &e);
+                    (None, Vec::new())
+                }
+            },
+            None => {
+                self.skip(Some(track.index), name, "The state is not in the archive");
+                (None, Vec::new())
+            }
+        };
+        self.count_plugins += 1;
+
+        let device = ImportDevice {
+            plugin_type,
+            identifier,
+            device_id: device_id.to_string(),
+            name: name.to_string(),
+            enabled: !matches!(
+                element
+                    .child("Enabled")
+                    .and_then(|enabled| enabled.attribute("value")),
+                Some("false")
+            ),
+            parameter_chunk: chunk.map(|chunk| BASE64_STANDARD.encode(chunk)),
+            parameters,
+        };
+        let (device_index, instrument_index) = if is_instrument {
+            let instrument = self.instruments.len() + 1;
+            self.instruments.push(ImportInstrument {
+                index: instrument,
+                name: name.to_string(),
+                track: track.index,
+                plugin: device,
+            });
+            track.instrument = Some(instrument);
+            (None, Some(instrument))
+        } else {
+            track.devices.push(device);
+            (Some(track.devices.len() + 1), None)
+        };
+
+        let parameters = element.child("Parameters");
+        for parameter in parameters
+            .iter()
+            .flat_map(|parameters| parameters.children.iter())
+        {
+            let (Some(id), Some(parameter_id)) =
+                (parameter.attribute("id"), number(parameter, "parameterID"))
+            else {
+                continue;
+            };
+            if !PARAMETER_ELEMENTS.contains(&parameter.name.as_str()) {
+                continue;
+            }
+            self.parameters.insert(
+                id,
+                ParameterTarget {
+                    track: track.index,
+                    device: device_index,
+                    instrument: instrument_index,
+                    parameter_id: parameter_id as i64,
+                    name: parameter.attribute("name").unwrap_or_default().to_string(),
+                    range: parameter_range(parameter),
+                },
+            );
+        }
+    }
+
+    // Lanes, clips and their content, other elements are looked into for
+    // nested lanes
+    fn walk(&mut self, element: &'a Element, mut context: Context) {
+        if let Some(time_unit) = element.attribute("timeUnit") {
+            context.seconds = time_unit == "seconds";
+        }
+        if let Some(track) = element
+            .attribute("track")
+            .and_then(|track| self.track_ids.get(track))
+        {
+            context.track = Some(*track);
+        }
+
+        match element.name.as_str() {
+            "Clip" => self.walk_clip(element, context),
+            "Notes" => self.add_notes(element, context),
+            "Points" => self.add_points(element, context),
+            "Audio" | "Warps" | "Video" => {
+                self.skip(context.track, &element.name, "Audio clips are not imported");
+            }
+            _ => {
+                for child in &element.children {
+                    self.walk(child, context);
+                }
+            }
+        }
+    }
+
+    fn walk_clip(&mut self, clip: &'a Element, context: Context) {
+        let time = self.beats(number(clip, "time").unwrap_or(0.0), context.seconds);
+        let duration = number(clip, "duration").map_or(f64::INFINITY, |duration| {
+            self.beats(duration, context.seconds)
+        });
+        let content_seconds = clip
+            .attribute("contentTimeUnit")
+            .map_or(context.seconds, |time_unit| time_unit == "seconds");
+        let play_start = self.beats(number(clip, "playStart").unwrap_or(0.0), content_seconds);
+
+        let start = context.offset + time;
+        let window = (
+            start.max(context.window.0),
+            (start + duration).min(context.window.1),
+        );
+        let enabled = context.enabled && clip.attribute("enable") != Some("false");
+        if let Some(track) = context.track.filter(|_| !enabled) {
+            self.disabled_clips
+                .push((track, self.ticks(window.0), self.ticks(window.1)));
+        }
+        if clip.attribute("reference").is_some() {
+            return self.skip(
+                context.track,
+                clip.attribute("name").unwrap_or("Clip"),
+                "Clips referencing other content are not imported",
+            );
+        }
+
+        let content = Context {
+            offset: start - play_start,
+            window,
+            enabled,
+            seconds: content_seconds,
+            ..context
+        };
+        for child in &clip.children {
+            self.walk(child, content);
+        }
+    }
+
+    fn add_notes(&mut self, notes: &'a Element, context: Context) {
+        let Some(track) = context.track else {
+            return;
+        };
+        let is_sequencer = self
+            .tracks
+            .iter()
+            .any(|other| other.index == track && other.track_type == "sequencer");
+
+        for note in notes.children_named("Note") {
+            let start =
+                context.offset + self.beats(number(note, "time").unwrap_or(0.0), context.seconds);
+            if start < context.window.0 || start >= context.window.1 {
+                continue;
+            }
+            if !is_sequencer {
+                self.count_dropped_notes += 1;
+                self.skip(
+                    Some(track),
+                    "Notes",
+                    "Only sequencer tracks have note columns",
+                );
+                continue;
+            }
+            let duration = self.beats(number(note, "duration").unwrap_or(0.0), context.seconds);
+            let end = (start + duration).min(context.window.1);
+
+            let pressure = note
+                .children_named("Points")
+                .filter(|points| {
+                    points
+                        .child("Target")
+                        .and_then(|target| target.attribute("expression"))
+                        == Some("pressure")
+                })
+                .flat_map(|points| points.children.iter())
+                .filter_map(|point| {
+                    let time = start + self.beats(number(point, "time")?, context.seconds);
+                    Some((self.ticks(time), number(point, "value")?))
+                })
+                .collect();
+
+            let start_tick = self.ticks(start);
+            self.notes.push(SourceNote {
+                track,
+                start: start_tick,
+                end: self.ticks(end).max(start_tick + 1),
+                key: number(note, "key").unwrap_or(0.0) as i64,
+                velocity: number(note, "vel").unwrap_or(1.0),
+                release_velocity: number(note, "rel"),
+                pressure,
+            });
+        }
+    }
+
+    fn add_points(&mut self, points: &'a Element, context: Context) {
+        let target = points.child("Target");
+        if let Some(expression) = target.and_then(|target| target.attribute("expression")) {
+            return self.skip(
+                context.track,
+                expression,
+                "Expression lanes are not imported",
+            );
+        }
+        let Some(parameter) = target
+            .and_then(|target| target.attribute("parameter"))
+            .filter(|parameter| self.parameters.contains_key(*parameter))
+        else {
+            let name = points.attribute("name").unwrap_or("Points");
+            return self.skip(
+                context.track,
+                name,
+                "Only plugin parameters are imported as automation",
+            );
+        };
+
+        let lane_index = match self
+            .lanes
+            .iter()
+            .position(|lane| lane.parameter == parameter)
+        {
+            Some(index) => index,
+            None => {
+                self.lanes.push(Lane {
+                    parameter: parameter.to_string(),
+                    hold: true,
+                    points: Vec::new(),
+                });
+                self.lanes.len() - 1
+            }
+        };
+        for point in points
+            .children
+            .iter()
+            .filter(|point| POINT_ELEMENTS.contains(&point.name.as_str()))
+        {
+            let Some(time) = number(point, "time") else {
+                continue;
+            };
+            let time = context.offset + self.beats(time, context.seconds);
+            if time < context.window.0 || time > context.window.1 {
+                continue;
+            }
+            let value = match point.attribute("value") {
+                Some("true") => 1.0,
+                Some("false") => 0.0,
+                _ => number(point, "value").unwrap_or(0.0),
+            };
+            let tick = self.ticks(time);
+            let lane = &mut self.lanes[lane_index];
+            if point.name != "BoolPoint" && point.attribute("interpolation") != Some("hold") {
+                lane.hold = false;
+            }
+            lane.points.push((tick, value));
+        }
+    }
+
+    // Lines and names, one marker per line
+    fn markers(&self, markers: &Element) -> Vec<(usize, String)> {
+        let seconds = markers.attribute("timeUnit") == Some("seconds");
+        let mut lines: Vec<(usize, String)> = markers
+            .children_named("Marker")
+            .filter_map(|marker| {
+                let time = self.beats(number(marker, "time")?, seconds);
+                let line = self.ticks(time).max(0) / TICKS_PER_LINE;
+                let name = marker.attribute("name").unwrap_or_default().to_string();
+                Some((line as usize, name))
+            })
+            .collect();
+        lines.sort_by_key(|(line, _)| *line);
+        lines.dedup_by_key(|(line, _)| *line);
+        lines
+    }
+
+    // One sequence slot per pattern, cut at the markers, up to the length of
+    // the Renoise sequence
+    fn slots(&mut self, markers: &[(usize, String)], pattern_lines: usize) -> Vec<Slot> {
+        let end = self
+            .notes
+            .iter()
+            .map(|note| note.end)
+            .chain(
+                self.lanes
+                    .iter()
+                    .flat_map(|lane| lane.points.iter().map(|(tick, _)| tick + 1)),
+            )
+            .chain(
+                markers
+                    .iter()
+                    .map(|(line, _)| *line as i64 * TICKS_PER_LINE + 1),
+            )
+            .max()
+            .unwrap_or(0);
+        let count_lines = (end.max(0) as usize).div_ceil(TICKS_PER_LINE as usize);
+
+        let mut slots: Vec<Slot> = Vec::new();
+        let mut start_line = 0;
+        while start_line < count_lines || slots.is_empty() {
+            if slots.len() == MAX_SEQUENCE_SLOTS {
+                self.skip(
+                    None,
+                    "Arrangement",
+                    "Content beyond 1000 sequence slots is not imported",
+                );
+                break;
+            }
+            let next_marker = markers
+                .iter()
+                .map(|(line, _)| *line)
+                .find(|line| *line > start_line)
+                .unwrap_or(usize::MAX);
+            let lines = pattern_lines.min(next_marker - start_line);
+            slots.push(Slot {
+                start_line,
+                lines,
+                section_name: markers
+                    .iter()
+                    .find(|(line, _)| *line == start_line)
+                    .map(|(_, name)| name.clone()),
+            });
+            start_line += lines;
+        }
+        slots
+    }
+
+    // Slot and line in the slot of a tick
+    fn position(slots: &[Slot], tick: i64) -> Option<(usize, usize)> {
+        let line = (tick.max(0) / TICKS_PER_LINE) as usize;
+        let slot = slots
+            .partition_point(|slot| slot.start_line <= line)
+            .checked_sub(1)?;
+        let line_in_slot = line - slots[slot].start_line;
+        (line_in_slot < slots[slot].lines).then_some((slot, line_in_slot))
+    }
+
+    fn patterns(&mut self, slots: &[Slot]) -> (Vec<ImportPattern>, usize) {
+        let mut entries = Entries::new();
+        let mut count_notes = 0;
+        let end = slots.last().map_or(0, |slot| {
+            (slot.start_line + slot.lines) as i64 * TICKS_PER_LINE
+        });
+
+        let mut notes = std::mem::take(&mut self.notes);
+        notes.sort_by_key(|note| (note.track, note.start, note.key));
+        for track_notes in notes.chunk_by(|a, b| a.track == b.track) {
+            count_notes += self.place_notes(track_notes, slots, end, &mut entries);
+        }
+
+        let mut patterns: Vec<ImportPattern> = slots
+            .iter()
+            .enumerate()
+            .map(|(index, slot)| ImportPattern {
+                index: index + 1,
+                number_of_lines: slot.lines,
+                tracks: Vec::new(),
+            })
+            .collect();
+        for ((slot, track, line, _), column) in entries {
+            let tracks = &mut patterns[slot].tracks;
+            if tracks.last().is_none_or(|last| last.track != track) {
+                tracks.push(ImportPatternTrack {
+                    track,
+                    lines: Vec::new(),
+                });
+            }
+            let Some(pattern_track) = tracks.last_mut() else {
+                continue;
+            };
+            if pattern_track
+                .lines
+                .last()
+                .is_none_or(|last| last.index != line + 1)
+            {
+                pattern_track.lines.push(ImportLine {
+                    index: line + 1,
+                    note_columns: Vec::new(),
+                });
+            }
+            if let Some(last) = pattern_track.lines.last_mut() {
+                last.note_columns.push(column);
+            }
+        }
+        (patterns, count_notes)
+    }
+
+    // Notes of one track, each in the first column free at its start.
+    // Returns the count of placed notes.
+    fn place_notes(
+        &mut self,
+        notes: &[SourceNote],
+        slots: &[Slot],
+        end: i64,
+        entries: &mut Entries,
+    ) -> usize {
+        let Some(track) = notes.first().map(|note| note.track) else {
+            return 0;
+        };
+        let instrument = self
+            .tracks
+            .iter()
+            .find(|other| other.index == track)
+            .and_then(|other| other.instrument)
+            .map(|instrument| format!("{:02X}", instrument - 1));
+        let mut columns: Vec<PlacedNote> = Vec::new();
+        let mut pressure = Vec::new();
+        let mut count_placed = 0;
+
+        let insert = |entries: &mut Entries, column: usize, tick: i64, entry: ImportNoteColumn| {
+            let Some((slot, line)) = Self::position(slots, tick) else {
+                return;
+            };
+            let delay = tick % TICKS_PER_LINE;
+            entries
+                .entry((slot, track, line, column))
+                .or_insert(ImportNoteColumn {
+                    column: column + 1,
+                    delay: (delay > 0 && entry.note.is_some()).then(|| format!("{:02X}", delay)),
+                    ..entry
+                });
+        };
+        let note_off = |placed: &PlacedNote| ImportNoteColumn {
+            note: Some(NOTE_OFF.to_string()),
+            volume: placed.release_velocity.map(hex),
+            ..Default::default()
+        };
+
+        for note in notes {
+            let Some(note_string) = u8::try_from(note.key).ok().and_then(pattern::note_string)
+            else {
+                self.count_dropped_notes += 1;
+                self.skip(Some(track), "Notes", "Keys above B-9 are not imported");
+                continue;
+            };
+            let line = note.start / TICKS_PER_LINE;
+            let free = columns.iter().position(|placed| {
+                placed.end <= note.start
+                    && placed.start_line < line
+                    && (placed.end == note.start || placed.end / TICKS_PER_LINE < line)
+            });
+            let column = match free {
+                Some(column) => {
+                    if columns[column].end < note.start {
+                        insert(
+                            entries,
+                            column,
+                            columns[column].end,
+                            note_off(&columns[column]),
+                        );
+                    }
+                    column
+                }
+                None if columns.len() < MAX_NOTE_COLUMNS => {
+                    columns.push(PlacedNote {
+                        end: note.start,
+                        start_line: line,
+                        release_velocity: None,
+                    });
+                    columns.len() - 1
+                }
+                None => {
+                    self.count_dropped_notes += 1;
+                    self.skip(
+                        Some(track),
+                        "Notes",
+                        "Notes beyond 12 note columns are dropped",
+                    );
+                    continue;
+                }
+            };
+
+            insert(
+                entries,
+                column,
+                note.start,
+                ImportNoteColumn {
+                    note: Some(note_string),
+                    instrument: instrument.clone(),
+                    volume: Some(hex(note.velocity)),
+                    ..Default::default()
+                },
+            );
+            columns[column] = PlacedNote {
+                end: note.end,
+                start_line: line,
+                release_velocity: note.release_velocity,
+            };
+            // Between the lines of the note and its note-off
+            let (first_line, last_line) = (line, note.end / TICKS_PER_LINE);
+            pressure.extend(
+                note.pressure
+                    .iter()
+                    .filter(|(tick, _)| {
+                        let pressure_line = tick / TICKS_PER_LINE;
+                        pressure_line > first_line && pressure_line < last_line
+                    })
+                    .map(|(tick, value)| (column, tick / TICKS_PER_LINE * TICKS_PER_LINE, *value)),
+            );
+            count_placed += 1;
+        }
+
+        // Notes playing until the song end need no note-off
+        for (column, placed) in columns.iter().enumerate() {
+            if placed.end < end {
+                insert(entries, column, placed.end, note_off(placed));
+            }
+        }
+        for (column, tick, value) in pressure {
+            insert(
+                entries,
+                column,
+                tick,
+                ImportNoteColumn {
+                    volume: Some(hex(value)),
+                    ..Default::default()
+                },
+            );
+        }
+
+        if let Some(import_track) = self.tracks.iter_mut().find(|other| other.index == track) {
+            import_track.note_columns = columns.len().max(1);
+        }
+        count_placed
+    }
+
+    // Pattern automation per slot. The value at the start of every slot
+    // after the first point is added, ramps through the slot end also get
+    // the value at the last tick of the slot.
+    fn automation(&self, slots: &[Slot]) -> Vec<ImportAutomation> {
+        let mut automation = Vec::new();
+        for lane in &self.lanes {
+            let Some(target) = self.parameters.get(lane.parameter.as_str()) else {
+                continue;
+            };
+            let mut points: Vec<(i64, f64)> = lane
+                .points
+                .iter()
+                .map(|(tick, value)| (*tick, normalize(*value, target.range)))
+                .collect();
+            // The last of several points at one tick wins
+            points.sort_by_key(|(tick, _)| *tick);
+            points.reverse();
+            points.dedup_by_key(|(tick, _)| *tick);
+            points.reverse();
+
+            let (Some(first), Some(last)) = (points.first(), points.last()) else {
+                continue;
+            };
+            let (Some((first_slot, _)), Some((last_slot, _))) = (
+                Self::position(slots, first.0),
+                Self::position(slots, last.0),
+            ) else {
+                continue;
+            };
+            let value_at = |tick: i64| {
+                let next = points.partition_point(|(point_tick, _)| *point_tick <= tick);
+                match (
+                    next.checked_sub(1).map(|previous| points[previous]),
+                    points.get(next),
+                ) {
+                    (Some((from_tick, from)), Some((to_tick, to))) if !lane.hold => {
+                        from + (to - from) * (tick - from_tick) as f64
+                            / (to_tick - from_tick) as f64
+                    }
+                    (Some((_, from)), _) => from,
+                    (None, _) => first.1,
+                }
+            };
+
+            let mut import_points = Vec::new();
+            for (slot_index, slot) in slots
+                .iter()
+                .enumerate()
+                .take(last_slot + 1)
+                .skip(first_slot)
+            {
+                let start = slot.start_line as i64 * TICKS_PER_LINE;
+                let end = (slot.start_line + slot.lines) as i64 * TICKS_PER_LINE;
+                let mut slot_points: Vec<(i64, f64)> = points
+                    .iter()
+                    .filter(|(tick, _)| *tick >= start && *tick < end)
+                    .copied()
+                    .collect();
+                if slot_index > first_slot
+                    && slot_points.first().is_none_or(|(tick, _)| *tick != start)
+                {
+                    slot_points.insert(0, (start, value_at(start)));
+                }
+                if !lane.hold
+                    && slot_index < last_slot
+                    && slot_points.last().is_some_and(|(tick, _)| *tick < end - 1)
+                {
+                    slot_points.push((end - 1, value_at(end - 1)));
+                }
+                import_points.extend(slot_points.into_iter().map(|(tick, value)| {
+                    ImportAutomationPoint {
+                        sequence: slot_index + 1,
+                        time: 1.0 + (tick - start) as f64 / TICKS_PER_LINE as f64,
+                        value,
+                    }
+                }));
+            }
+
+            automation.push(ImportAutomation {
+                track: target.track,
+                device: target.device,
+                instrument: target.instrument,
+                parameter_id: target.parameter_id,
+                name: target.name.clone(),
+                play_mode: if lane.hold { "points" } else { "lines" },
+                points: import_points,
+            });
+        }
+        automation
+    }
+
+    fn sequence(&self, slots: &[Slot]) -> Vec<ImportSequenceEntry> {
+        slots
+            .iter()
+            .enumerate()
+            .map(|(index, slot)| {
+                let start = slot.start_line as i64 * TICKS_PER_LINE;
+                let end = (slot.start_line + slot.lines) as i64 * TICKS_PER_LINE;
+                let mut muted_tracks: Vec<usize> = self
+                    .disabled_clips
+                    .iter()
+                    .filter(|(_, clip_start, clip_end)| *clip_start < end && *clip_end > start)
+                    .map(|(track, _, _)| *track)
+                    .collect();
+                muted_tracks.sort_unstable();
+                muted_tracks.dedup();
+                ImportSequenceEntry {
+                    pattern: index + 1,
+                    section_name: slot.section_name.clone(),
+                    muted_tracks,
+                }
+            })
+            .collect()
+    }
+}
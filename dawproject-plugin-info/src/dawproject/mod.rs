@@ -0,0 +1,112 @@
+/*!
+ * ------------------------------------------------------------------------
+ * DAWproject archives
+ * by Jurek Raben
+ *
+ * A .dawproject is a zip archive with project.xml, metadata.xml and the
+ * plugin states below plugins/, which the State elements of the devices
+ * point to.
+ *
+ * Licensed under CC Attribution-NonCommercial-ShareAlike 4.0 International
+ * Info here: https://creativecommons.org/licenses/by-nc-sa/4.0/
+ * ------------------------------------------------------------------------
+ */
+
+pub mod events;
+pub mod export;
+pub mod import;
+
+use std::fs::File;
+use std::io::{Read, Write};
+use std::path::Path;
+
+use zip::write::SimpleFileOptions;
+use zip::{ZipArchive, ZipWriter};
+
+use crate::xml::{self, Element};
+
+pub const PROJECT_XML: &str = "project.xml";
+pub const METADATA_XML: &str = "metadata.xml";
+pub const PLUGINS_DIR: &str = "plugins";
+
+#[derive(Debug, Clone)]
+pub struct DawProject {
+    pub project: Element,
+    pub metadata: Element,
+    // Further files by archive path, e.g. "plugins/fx-tr1-no2-delay.vstpreset"
+    pub files: Vec<(String, Vec<u8>)>,
+}
+
+impl DawProject {
+    // The metadata may be missing, as it is optional
+    pub fn read(path: &Path) -> Result<DawProject, String> {
+        let read_error =
+            |e: &dyn std::fmt::Display| format!("Failed to read {}: {}", path.display(), e);
+
+        let file = File::open(path).map_err(|e| read_error(&e))?;
+        let mut archive =
+            ZipArchive::new(file).map_err(|_| format!("{} is no DAWproject", path.display()))?;
+        let mut project = None;
+        let mut metadata = None;
+        let mut files = Vec::new();
+
+        for index in 0..archive.len() {
+            let mut entry = archive.by_index(index).map_err(|e| read_error(&e))?;
+            if entry.is_dir() {
+                continue;
+            }
+            let name = entry.name().to_string();
+            let mut data = Vec::new();
+            entry.read_to_end(&mut data).map_err(|e| read_error(&e))?;
+
+            match name.as_str() {
+                PROJECT_XML => project = Some(parse_xml(&name, &data)?),
+                METADATA_XML => metadata = Some(parse_xml(&name, &data)?),
+                _ => files.push((name, data)),
+            }
+        }
+
+        Ok(DawProject {
+            project: project.ok_or_else(|| format!("{} is no DAWproject", path.display()))?,
+            metadata: metadata.unwrap_or_else(|| Element::new("MetaData")),
+            files,
+        })
+    }
+
+    pub fn file(&self, name: &str) -> Option<&[u8]> {
+        self.files
+            .iter()
+            .find(|(file_name, _)| file_name == name)
+            .map(|(_, data)| data.as_slice())
+    }
+
+    pub fn write(&self, path: &Path) -> Result<(), String> {
+        let write_error =
+            |e: &dyn std::fmt::Display| format!("Failed to write {}: {}", path.display(), e);
+
+        let file = File::create(path).map_err(|e| write_error(&e))?;
+        let mut archive = ZipWriter::new(file);
+        let entries = [
+            (PROJECT_XML, self.project.to_xml().into_bytes()),
+            (METADATA_XML, self.metadata.to_xml().into_bytes()),
+        ];
+        let files = self
+            .files
+            .iter()
+            .map(|(name, data)| (name.as_str(), data.clone()));
+
+        for (name, data) in entries.into_iter().chain(files) {
+            archive
+                .start_file(name, SimpleFileOptions::default())
+                .map_err(|e| write_error(&e))?;
+            archive.write_all(&data).map_err(|e| write_error(&e))?;
+        }
+        archive.finish().map_err(|e| write_error(&e))?;
+        Ok(())
+    }
+}
+
+fn parse_xml(name: &str, data: &[u8]) -> Result<Element, String> {
+    let text = String::from_utf8_lossy(data);
+    xml::parse(&text).map_err(|e| format!("{}: {}", name, e))
+}
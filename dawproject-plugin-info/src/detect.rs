@@ -0,0 +1,112 @@
+/*!
+ * ------------------------------------------------------------------------
+ * Plugin format detection
+ * by Jurek Raben
+ *
+ * Tells the format of a plugin path from its bundle structure, or for
+ * plain binaries from the entry point they export: GetPluginFactory for
+ * VST3, clap_entry for CLAP and VSTPluginMain for VST2. The exports are
+ * read from the ELF, Mach-O or PE export table, the binary is not loaded.
+ *
+ * Licensed under CC Attribution-NonCommercial-ShareAlike 4.0 International
+ * Info here: https://creativecommons.org/licenses/by-nc-sa/4.0/
+ * ------------------------------------------------------------------------
+ */
+
+use std::fs;
+use std::path::Path;
+
+use object::read::macho::{FatArch, MachOFatFile, MachOFatFile32, MachOFatFile64};
+use object::{BinaryFormat, FileKind, Object};
+
+use crate::clap::Clap;
+use crate::search;
+use crate::vst2::Vst2;
+use crate::vst3::Vst3;
+use crate::{Format, PluginError, PluginFormat};
+
+// Checked in this order, VST2 last because of the generic "main"
+const ENTRY_POINTS: [(&[u8], Format); 5] = [
+    (b"GetPluginFactory", Format::Vst3),
+    (b"clap_entry", Format::Clap),
+    (b"VSTPluginMain", Format::Vst2),
+    (b"main_macho", Format::Vst2),
+    (b"main", Format::Vst2),
+];
+
+pub fn detect_format(path: &Path) -> Result<Format, PluginError> {
+    if !path.exists() {
+        return Err(PluginError::PathError(
+            path.display().to_string() + " does not exist",
+        ));
+    }
+
+    if path.is_dir() {
+        return bundle_format(path).ok_or_else(|| {
+            PluginError::PathError(path.display().to_string() + " is no plugin bundle")
+        });
+    }
+
+    let data = fs::read(path)
+        .map_err(|e| PluginError::LoadError(format!("Failed to read {}: {}", path.display(), e)))?;
+    let exports = exported_symbols(&data)
+        .map_err(|e| PluginError::LoadError(format!("Failed to load library: {}", e)))?;
+    ENTRY_POINTS
+        .iter()
+        .find(|(symbol, _)| exports.contains(symbol))
+        .map(|(_, format)| *format)
+        .ok_or_else(|| PluginError::LoadError("No plugin entry point found".to_string()))
+}
+
+// Names of the exported symbols, of the first architecture of a universal
+// binary. Mach-O prefixes C symbols with an underscore.
+fn exported_symbols(data: &[u8]) -> Result<Vec<&[u8]>, String> {
+    let data = match FileKind::parse(data).map_err(|e| e.to_string())? {
+        FileKind::MachOFat32 => first_arch(MachOFatFile32::parse(data), data)?,
+        FileKind::MachOFat64 => first_arch(MachOFatFile64::parse(data), data)?,
+        _ => data,
+    };
+    let file = object::File::parse(data).map_err(|e| e.to_string())?;
+    let is_macho = file.format() == BinaryFormat::MachO;
+    Ok(file
+        .exports()
+        .map_err(|e| e.to_string())?
+        .iter()
+        .map(|export| match export.name() {
+            [b'_', name @ ..] if is_macho => name,
+            name => name,
+        })
+        .collect())
+}
+
+fn first_arch<'data, Fat: FatArch>(
+    fat_file: object::Result<MachOFatFile<'data, Fat>>,
+    data: &'data [u8],
+) -> Result<&'data [u8], String> {
+    fat_file
+        .map_err(|e| e.to_string())?
+        .arches()
+        .first()
+        .ok_or("Universal binary without architectures")?
+        .data(data)
+        .map_err(|e| e.to_string())
+}
+
+fn bundle_format(path: &Path) -> Option<Format> {
+    [
+        ("vst3", Format::Vst3),
+        ("clap", Format::Clap),
+        ("vst", Format::Vst2),
+    ]
+    .into_iter()
+    .find(|(extension, _)| search::has_extension(path, extension))
+    .map(|(_, format)| format)
+}
+
+pub fn backend(format: Format) -> Result<Box<dyn PluginFormat>, PluginError> {
+    match format {
+        Format::Vst2 => Ok(Box::new(Vst2)),
+        Format::Vst3 => Ok(Box::new(Vst3)),
+        Format::Clap => Ok(Box::new(Clap)),
+    }
+}
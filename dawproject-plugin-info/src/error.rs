@@ -0,0 +1,45 @@
+/*!
+ * ------------------------------------------------------------------------
+ * Plugin error type
+ * by Jurek Raben
+ *
+ * Licensed under CC Attribution-NonCommercial-ShareAlike 4.0 International
+ * Info here: https://creativecommons.org/licenses/by-nc-sa/4.0/
+ * ------------------------------------------------------------------------
+ */
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum PluginError {
+    LoadError(String),
+    InitError(String),
+    FactoryError(String),
+    ComponentError(String),
+    PathError(String),
+}
+
+impl std::fmt::Display for PluginError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            PluginError::LoadError(msg) => write!(f, "Load error: {}", msg),
+            PluginError::InitError(msg) => write!(f, "Initialization error: {}", msg),
+            PluginError::FactoryError(msg) => write!(f, "Factory error: {}", msg),
+            PluginError::ComponentError(msg) => write!(f, "Component error: {}", msg),
+            PluginError::PathError(msg) => write!(f, "Path error: {}", msg),
+        }
+    }
+}
+
+impl std::error::Error for PluginError {}
+
+impl PluginError {
+    // The message without the kind prefix, as printed by the helper tools
+    pub fn message(&self) -> &str {
+        match self {
+            PluginError::LoadError(msg)
+            | PluginError::InitError(msg)
+            | PluginError::FactoryError(msg)
+            | PluginError::ComponentError(msg)
+            | PluginError::PathError(msg) => msg,
+        }
+    }
+}
@@ -0,0 +1,55 @@
+/*!
+ * ------------------------------------------------------------------------
+ * Format independent plugin info
+ * by Jurek Raben
+ *
+ * Licensed under CC Attribution-NonCommercial-ShareAlike 4.0 International
+ * Info here: https://creativecommons.org/licenses/by-nc-sa/4.0/
+ * ------------------------------------------------------------------------
+ */
+
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Format {
+    Vst2,
+    Vst3,
+    Clap,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PluginInfo {
+    pub format: Format,
+    pub name: String,
+    pub vendor: String,
+    pub version: String,
+    // VST2 unique ID as decimal number, VST3 class ID as hex string, CLAP
+    // plugin ID
+    pub id: String,
+    pub category: String,
+    // Channels for VST2, audio and event buses for VST3, audio and note
+    // ports for CLAP
+    pub count_inputs: i32,
+    pub count_outputs: i32,
+    pub count_presets: i32,
+    pub parameters: Vec<ParameterInfo>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ParameterInfo {
+    pub index: i32,
+    // VST3 parameter ID or CLAP param ID, the index for VST2
+    pub id: u32,
+    pub name: String,
+    pub units: String,
+    pub display: String,
+    // 0 for continuous parameters
+    pub step_count: i32,
+    // Normalized between 0 and 1
+    pub value: f64,
+    pub default_value: f64,
+    pub automatable: bool,
+}
@@ -0,0 +1,57 @@
+/*!
+ * ------------------------------------------------------------------------
+ * DawProject plugin info library
+ * by Jurek Raben
+ *
+ * Loads audio plugins and reads the information the DawProject export
+ * needs, but the Renoise API does not provide: plugin IDs, parameter IDs
+ * and preset data. Shared by the vst2info-tool, vst3info-tool and plugininfo
+ * helpers, and by dawproject-convert, which exports Renoise songs without
+ * Renoise.
+ *
+ * Licensed under CC Attribution-NonCommercial-ShareAlike 4.0 International
+ * Info here: https://creativecommons.org/licenses/by-nc-sa/4.0/
+ * ------------------------------------------------------------------------
+ */
+
+pub mod au;
+pub mod cache;
+pub mod child;
+pub mod clap;
+pub mod dawproject;
+pub mod detect;
+pub mod error;
+pub mod info;
+pub mod output;
+pub mod renoise_db;
+pub mod scan;
+pub mod search;
+pub mod serve;
+pub mod state_diff;
+pub mod vst2;
+pub mod vst3;
+pub mod xml;
+pub mod xrns;
+
+use std::path::{Path, PathBuf};
+
+pub use error::PluginError;
+pub use info::{Format, ParameterInfo, PluginInfo};
+
+// A plugin format backend, e.g. vst2::Vst2, vst3::Vst3 or clap::Clap, see also
+// detect::backend
+pub trait PluginFormat {
+    fn format(&self) -> Format;
+
+    // Folders to search for plugins, the format's environment variable first
+    fn search_paths(&self) -> Vec<PathBuf>;
+
+    // Whether the path is an existing bundle or binary of this format
+    fn is_plugin(&self, path: &Path) -> bool;
+
+    // The binary inside a bundle, or the path itself for plain binaries
+    fn resolve_binary(&self, path: &Path) -> Result<PathBuf, PluginError>;
+
+    // Loads the binary and reads the plugin info
+    fn inspect(&self, binary: &Path) -> Result<PluginInfo, PluginError>;
+}
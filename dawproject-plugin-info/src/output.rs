@@ -0,0 +1,43 @@
+/*!
+ * ------------------------------------------------------------------------
+ * Console output of the helper tools
+ * by Jurek Raben
+ *
+ * Results are printed as JSON on stdout, errors as {"error": ...} on
+ * stderr. The Lua side cuts the JSON at the first quote followed by a
+ * closing brace, so nested objects keep a non string last field and
+ * "os" comes last.
+ *
+ * Licensed under CC Attribution-NonCommercial-ShareAlike 4.0 International
+ * Info here: https://creativecommons.org/licenses/by-nc-sa/4.0/
+ * ------------------------------------------------------------------------
+ */
+
+use std::fmt::Display;
+use std::io::{self, Write};
+use std::process::exit;
+
+use serde::Serialize;
+use serde_json::{Value, json};
+
+pub const OS: &str = std::env::consts::OS;
+
+pub fn error_exit(error_message: impl Display) -> ! {
+    eprintln!("{}", json!({ "error": error_message.to_string() }));
+    exit(1);
+}
+
+pub fn print_json<T: Serialize>(value: &T) {
+    let line = serde_json::to_string(value).unwrap_or_else(|e| error_exit(e));
+    let mut stdout = io::stdout().lock();
+    let _ = writeln!(stdout, "{}", line);
+    let _ = stdout.flush();
+}
+
+// Appends "os" as last field of a result object
+pub fn with_os(mut output: Value) -> Value {
+    if let Some(object) = output.as_object_mut() {
+        object.insert("os".to_string(), json!(OS));
+    }
+    output
+}
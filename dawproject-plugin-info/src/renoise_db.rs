@@ -0,0 +1,82 @@
+/*!
+ * ------------------------------------------------------------------------
+ * Renoise plugin database access
+ * by Jurek Raben
+ *
+ * Reads the CachedVSTs_<arch>.db plugin database of Renoise, which maps
+ * the DocumentIdentifier of a device to the plugin file. The database is
+ * opened read only, Renoise might be running.
+ *
+ * Licensed under CC Attribution-NonCommercial-ShareAlike 4.0 International
+ * Info here: https://creativecommons.org/licenses/by-nc-sa/4.0/
+ * ------------------------------------------------------------------------
+ */
+
+use std::path::{Path, PathBuf};
+
+use rusqlite::{Connection, OpenFlags, OptionalExtension};
+use serde::Serialize;
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CachedPlugin {
+    pub document_id: String,
+    // None for plugins Renoise keeps without a file
+    pub path: Option<String>,
+    pub exists: bool,
+}
+
+fn open(db: &Path) -> Result<Connection, String> {
+    Connection::open_with_flags(db, OpenFlags::SQLITE_OPEN_READ_ONLY)
+        .map_err(|e| format!("Failed to open {}: {}", db.display(), e))
+}
+
+// LocalFilePath of the plugin with the given DocumentIdentifier
+pub fn plugin_path(db: &Path, document_id: &str) -> Result<PathBuf, String> {
+    let connection = open(db)?;
+    let path: Option<Option<String>> = connection
+        .query_row(
+            "SELECT LocalFilePath FROM CachedPlugins WHERE DocumentIdentifier = ?1",
+            [document_id],
+            |row| row.get(0),
+        )
+        .optional()
+        .map_err(|e| format!("Failed to read {}: {}", db.display(), e))?;
+
+    match path {
+        Some(Some(path)) => Ok(PathBuf::from(path)),
+        Some(None) => {
+            Err(document_id.to_string() + " has no plugin file in " + &db.display().to_string())
+        }
+        None => Err(document_id.to_string() + " not found in " + &db.display().to_string()),
+    }
+}
+
+// All plugins of the database and whether their files still exist
+pub fn list_plugins(db: &Path) -> Result<Vec<CachedPlugin>, String> {
+    let connection = open(db)?;
+    let read_error = |e: rusqlite::Error| format!("Failed to read {}: {}", db.display(), e);
+
+    let mut statement = connection
+        .prepare(
+            "SELECT DocumentIdentifier, LocalFilePath FROM CachedPlugins \
+             ORDER BY DocumentIdentifier",
+        )
+        .map_err(read_error)?;
+    let rows = statement
+        .query_map([], |row| {
+            Ok((row.get::<_, String>(0)?, row.get::<_, Option<String>>(1)?))
+        })
+        .map_err(read_error)?;
+
+    let mut plugins = Vec::new();
+    for row in rows {
+        let (document_id, path) = row.map_err(read_error)?;
+        plugins.push(CachedPlugin {
+            exists: path.as_ref().is_some_and(|path| Path::new(path).exists()),
+            document_id,
+            path,
+        });
+    }
+    Ok(plugins)
+}
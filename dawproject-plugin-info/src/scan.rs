@@ -0,0 +1,111 @@
+/*!
+ * ------------------------------------------------------------------------
+ * Batch scan
+ * by Jurek Raben
+ *
+ * Inspects every plugin of a format below the given folders, each one in
+ * its own child process, and prints one JSON object per line. A crashing
+ * or hanging plugin only produces a failure line, the scan goes on. With
+ * a cache, only new or updated plugins are inspected.
+ *
+ * Licensed under CC Attribution-NonCommercial-ShareAlike 4.0 International
+ * Info here: https://creativecommons.org/licenses/by-nc-sa/4.0/
+ * ------------------------------------------------------------------------
+ */
+
+use std::ffi::OsStr;
+use std::path::PathBuf;
+use std::time::{Duration, Instant};
+
+use serde_json::{Map, json};
+
+use crate::PluginFormat;
+use crate::cache::PluginCache;
+use crate::child;
+use crate::output::{OS, print_json};
+use crate::search;
+
+// New cache entries written at once, an aborted scan keeps its progress
+const CACHE_SAVE_INTERVAL: usize = 25;
+
+// The child is this tool again, called as `<tool> info <plugin>`
+pub fn run_scan(
+    format: &dyn PluginFormat,
+    paths: Vec<PathBuf>,
+    timeout: Duration,
+    mut cache: Option<&mut PluginCache>,
+    refresh: bool,
+) {
+    let paths = if paths.is_empty() {
+        format.search_paths()
+    } else {
+        paths
+    };
+
+    let started = Instant::now();
+    let mut count_ok = 0;
+    let mut count_failed = 0;
+    let mut count_cached = 0;
+    let mut count_unsaved = 0;
+    let mut cache_error = None;
+
+    for plugin in search::find_plugins(format, &paths) {
+        let mut line = Map::new();
+        line.insert("path".to_string(), json!(plugin.display().to_string()));
+        let binary = format.resolve_binary(&plugin).ok();
+
+        let cached = match (&cache, &binary) {
+            (Some(cache), Some(binary)) if !refresh => cache.get(binary),
+            _ => None,
+        };
+        if let Some(info) = cached {
+            count_ok += 1;
+            count_cached += 1;
+            line.insert("ok".to_string(), json!(true));
+            line.insert("cached".to_string(), json!(true));
+            line.insert("info".to_string(), info);
+            print_json(&line);
+            continue;
+        }
+
+        match child::run_child(&[OsStr::new("info"), plugin.as_os_str()], timeout) {
+            Ok(info) => {
+                count_ok += 1;
+                if let (Some(cache), Some(binary)) = (cache.as_deref_mut(), &binary) {
+                    cache.insert(binary, info.clone());
+                    count_unsaved += 1;
+                    if count_unsaved == CACHE_SAVE_INTERVAL {
+                        count_unsaved = 0;
+                        cache_error = cache.save().err().or(cache_error);
+                    }
+                }
+                line.insert("ok".to_string(), json!(true));
+                line.insert("cached".to_string(), json!(false));
+                line.insert("info".to_string(), info);
+            }
+            Err(error) => {
+                count_failed += 1;
+                line.insert("ok".to_string(), json!(false));
+                line.insert("error".to_string(), json!(error));
+            }
+        }
+        print_json(&line);
+    }
+    if let Some(cache) = cache
+        && count_unsaved > 0
+    {
+        cache_error = cache.save().err().or(cache_error);
+    }
+
+    let mut summary = json!({
+        "total": count_ok + count_failed,
+        "ok": count_ok,
+        "failed": count_failed,
+        "cached": count_cached,
+        "seconds": started.elapsed().as_secs_f64()
+    });
+    if let Some(error) = cache_error {
+        summary["cacheError"] = json!(error);
+    }
+    print_json(&json!({ "summary": summary, "os": OS }));
+}
@@ -0,0 +1,83 @@
+/*!
+ * ------------------------------------------------------------------------
+ * Plugin search
+ * by Jurek Raben
+ *
+ * Finds the bundles and binaries of a plugin format below folders, e.g.
+ * the format's search paths.
+ *
+ * Licensed under CC Attribution-NonCommercial-ShareAlike 4.0 International
+ * Info here: https://creativecommons.org/licenses/by-nc-sa/4.0/
+ * ------------------------------------------------------------------------
+ */
+
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::PluginFormat;
+
+const MAX_SEARCH_DEPTH: usize = 5;
+
+// Folders of a search path variable like VST_PATH, followed by the
+// format's default folders
+pub fn env_search_paths(variable: &str, default_paths: Vec<PathBuf>) -> Vec<PathBuf> {
+    let mut paths: Vec<PathBuf> = env::var_os(variable)
+        .map(|value| env::split_paths(&value).collect())
+        .unwrap_or_default();
+    paths.extend(default_paths);
+    paths
+}
+
+// All plugin binaries and bundles below the given folders, paths that
+// are plugins themselves are taken as they are
+pub fn find_plugins(format: &dyn PluginFormat, paths: &[PathBuf]) -> Vec<PathBuf> {
+    let mut plugins = Vec::new();
+    for path in paths {
+        if format.is_plugin(path) {
+            plugins.push(path.clone());
+        } else {
+            collect_plugins(format, path, 0, &mut plugins);
+        }
+    }
+    plugins
+}
+
+fn collect_plugins(
+    format: &dyn PluginFormat,
+    dir: &Path,
+    depth: usize,
+    plugins: &mut Vec<PathBuf>,
+) {
+    if depth > MAX_SEARCH_DEPTH {
+        return;
+    }
+    let Ok(entries) = fs::read_dir(dir) else {
+        return;
+    };
+
+    let mut entries: Vec<PathBuf> = entries.flatten().map(|entry| entry.path()).collect();
+    entries.sort();
+
+    for path in entries {
+        if format.is_plugin(&path) {
+            plugins.push(path);
+        } else if path.is_dir() {
+            collect_plugins(format, &path, depth + 1, plugins);
+        }
+    }
+}
+
+pub fn has_extension(path: &Path, expected: &str) -> bool {
+    path.extension()
+        .is_some_and(|extension| extension.eq_ignore_ascii_case(expected))
+}
+
+// CFBundleExecutable of a macOS bundle's Info.plist, XML or binary
+pub fn bundle_executable(bundle: &Path) -> Option<String> {
+    let info = plist::Value::from_file(bundle.join("Contents").join("Info.plist")).ok()?;
+    info.as_dictionary()?
+        .get("CFBundleExecutable")?
+        .as_string()
+        .map(|executable| executable.to_string())
+}
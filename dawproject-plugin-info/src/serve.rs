@@ -0,0 +1,322 @@
+/*!
+ * ------------------------------------------------------------------------
+ * Server mode
+ * by Jurek Raben
+ *
+ * Answers newline delimited JSON-RPC 2.0 requests on stdin/stdout. Every
+ * plugin is loaded once by a worker child process, which is kept running
+ * for further requests. A crashing or hanging plugin only takes its own
+ * worker down, it is started again on the next request.
+ *
+ * Requests have named params, e.g.
+ * {"jsonrpc":"2.0","id":1,"method":"inspect","params":{"plugin":"..."}}
+ * Instead of "plugin", "renoiseDb" and "documentId" can be given. The
+ * methods besides shutdown are up to the tool.
+ *
+ * Licensed under CC Attribution-NonCommercial-ShareAlike 4.0 International
+ * Info here: https://creativecommons.org/licenses/by-nc-sa/4.0/
+ * ------------------------------------------------------------------------
+ */
+
+use std::collections::HashMap;
+use std::env;
+use std::ffi::OsString;
+use std::io::{self, BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+use std::process::{Child, ChildStdin, Command, Stdio};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use serde_json::{Value, json};
+
+use crate::child;
+use crate::output::print_json;
+use crate::renoise_db;
+
+pub const PARSE_ERROR: i32 = -32700;
+pub const INVALID_REQUEST: i32 = -32600;
+pub const METHOD_NOT_FOUND: i32 = -32601;
+pub const INVALID_PARAMS: i32 = -32602;
+pub const PLUGIN_ERROR: i32 = -32000;
+
+const WORKER_EXIT_TIMEOUT: Duration = Duration::from_secs(5);
+
+pub type RpcError = (i32, String);
+
+// Arguments of the tool for a worker, e.g. ["worker", <binary>]. Requests
+// with equal arguments share a worker.
+type WorkerArgs = Vec<OsString>;
+
+struct Worker {
+    child: Child,
+    stdin: Option<ChildStdin>,
+    messages: Receiver<Value>,
+    last_id: u64,
+}
+
+impl Worker {
+    fn start(args: &[OsString], timeout: Duration) -> Result<Worker, String> {
+        let exe =
+            env::current_exe().map_err(|e| format!("Failed to find the tool itself: {}", e))?;
+        let mut child = Command::new(exe)
+            .args(args)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .spawn()
+            .map_err(|e| format!("Failed to start the plugin worker: {}", e))?;
+
+        // Only complete JSON lines are passed on, plugins might print to
+        // stdout by themselves
+        let (sender, messages) = mpsc::channel();
+        let stdout = child.stdout.take();
+        thread::spawn(move || {
+            let Some(stdout) = stdout else {
+                return;
+            };
+            for line in BufReader::new(stdout).lines() {
+                let Ok(line) = line else {
+                    break;
+                };
+                if let Some(message) = child::find_json(&line)
+                    && sender.send(message).is_err()
+                {
+                    break;
+                }
+            }
+        });
+
+        let mut worker = Worker {
+            stdin: child.stdin.take(),
+            child,
+            messages,
+            last_id: 0,
+        };
+
+        let ready = worker
+            .receive(timeout, |message| {
+                message.get("ready").is_some() || message.get("error").is_some()
+            })
+            .inspect_err(|_| worker.kill())?;
+        if let Some(error) = ready["error"].as_str() {
+            return Err(error.to_string());
+        }
+        Ok(worker)
+    }
+
+    // Every request gets an id, so the response is told apart from JSON
+    // lines the plugin prints itself
+    fn request(&mut self, mut request: Value, timeout: Duration) -> Result<Value, String> {
+        self.last_id += 1;
+        let id = json!(self.last_id);
+        request["id"] = id.clone();
+        let sent = self
+            .stdin
+            .as_mut()
+            .is_some_and(|stdin| writeln!(stdin, "{}", request).is_ok() && stdin.flush().is_ok());
+        if !sent {
+            return Err(self.crash_message());
+        }
+        self.receive(timeout, |message| message["id"] == id)
+    }
+
+    // Waits for the first message accepted by is_response, other messages
+    // are dropped
+    fn receive<F>(&mut self, timeout: Duration, is_response: F) -> Result<Value, String>
+    where
+        F: Fn(&Value) -> bool,
+    {
+        let deadline = Instant::now() + timeout;
+        loop {
+            match self
+                .messages
+                .recv_timeout(deadline.saturating_duration_since(Instant::now()))
+            {
+                Ok(message) if is_response(&message) => return Ok(message),
+                Ok(_) => {}
+                Err(RecvTimeoutError::Timeout) => {
+                    return Err(format!("Timed out after {} seconds", timeout.as_secs()));
+                }
+                Err(RecvTimeoutError::Disconnected) => return Err(self.crash_message()),
+            }
+        }
+    }
+
+    // For hanging or crashed workers, instead of waiting for them to end
+    fn kill(&mut self) {
+        let _ = self.child.kill();
+    }
+
+    fn crash_message(&mut self) -> String {
+        match self.child.wait() {
+            Ok(status) => format!("Plugin process failed ({})", status),
+            Err(_) => "Plugin process failed".to_string(),
+        }
+    }
+}
+
+impl Drop for Worker {
+    // Closing stdin ends the worker loop, so the plugin is closed properly
+    fn drop(&mut self) {
+        self.stdin.take();
+        if child::wait_with_timeout(&mut self.child, WORKER_EXIT_TIMEOUT).is_err() {
+            let _ = self.child.kill();
+        }
+    }
+}
+
+// Serves until stdin is closed or shutdown is requested. For every other
+// method, worker_args returns the worker arguments for the plugin of the
+// request or the error to respond with.
+pub fn run_serve<F>(timeout: Duration, mut worker_args: F)
+where
+    F: FnMut(&str, &Value) -> Result<WorkerArgs, RpcError>,
+{
+    let mut workers: HashMap<WorkerArgs, Worker> = HashMap::new();
+
+    for line in io::stdin().lock().lines() {
+        let Ok(line) = line else {
+            break;
+        };
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        let Ok(request) = serde_json::from_str::<Value>(&line) else {
+            respond(Value::Null, Err((PARSE_ERROR, "Invalid JSON".to_string())));
+            continue;
+        };
+
+        // Notifications without id get no response
+        let id = request["id"].clone();
+        let is_notification = request.get("id").is_none();
+
+        let result = match request["method"].as_str() {
+            Some("shutdown") => {
+                if !is_notification {
+                    respond(id, Ok(Value::Bool(true)));
+                }
+                break;
+            }
+            Some(method) => worker_args(method, &request["params"]).and_then(|args| {
+                plugin_request(&mut workers, args, method, &request["params"], timeout)
+            }),
+            None => Err((INVALID_REQUEST, "Method required".to_string())),
+        };
+
+        if !is_notification {
+            respond(id, result);
+        }
+    }
+}
+
+// The binary of the plugin given by "plugin", or by "renoiseDb" and
+// "documentId", resolve turns a plugin argument into its binary
+pub fn plugin_binary<R>(params: &Value, mut resolve: R) -> Result<PathBuf, RpcError>
+where
+    R: FnMut(&str) -> Result<PathBuf, String>,
+{
+    match (
+        params["plugin"].as_str(),
+        params["renoiseDb"].as_str(),
+        params["documentId"].as_str(),
+    ) {
+        (Some(spec), _, _) => resolve(spec),
+        (None, Some(db), Some(document_id)) => renoise_db::plugin_path(Path::new(db), document_id)
+            .and_then(|path| resolve(&path.display().to_string())),
+        _ => return Err((INVALID_PARAMS, "Plugin required".to_string())),
+    }
+    .map_err(|e| (PLUGIN_ERROR, e))
+}
+
+fn respond(id: Value, result: Result<Value, RpcError>) {
+    let response = match result {
+        Ok(result) => json!({
+            "jsonrpc": "2.0",
+            "id": id,
+            "result": result
+        }),
+        Err((code, message)) => json!({
+            "jsonrpc": "2.0",
+            "id": id,
+            "error": {
+                "message": message,
+                "code": code
+            }
+        }),
+    };
+    print_json(&response);
+}
+
+fn plugin_request(
+    workers: &mut HashMap<WorkerArgs, Worker>,
+    args: WorkerArgs,
+    method: &str,
+    params: &Value,
+    timeout: Duration,
+) -> Result<Value, RpcError> {
+    if !workers.contains_key(&args) {
+        let worker = Worker::start(&args, timeout).map_err(|e| (PLUGIN_ERROR, e))?;
+        workers.insert(args.clone(), worker);
+    }
+    let Some(worker) = workers.get_mut(&args) else {
+        return Err((PLUGIN_ERROR, "Plugin worker missing".to_string()));
+    };
+
+    let forwarded = json!({
+        "method": method,
+        "params": params
+    });
+    match worker.request(forwarded, timeout) {
+        Ok(mut response) => match response["error"].as_str() {
+            Some(error) => Err((PLUGIN_ERROR, error.to_string())),
+            None => Ok(response["result"].take()),
+        },
+        Err(error) => {
+            // the next request starts a fresh worker
+            if let Some(mut worker) = workers.remove(&args) {
+                worker.kill();
+            }
+            Err((PLUGIN_ERROR, error))
+        }
+    }
+}
+
+// Hosts a single plugin for the server, one request per line on stdin,
+// answered by one {"id": ..., "result": ...} or {"id": ..., "error": ...}
+// line on stdout with the id of the request. The handler is the loaded
+// plugin's request handler, or the load error.
+pub fn run_worker<F>(handler: Result<F, String>)
+where
+    F: FnMut(&str, &Value) -> Result<Value, String>,
+{
+    let mut handler = match handler {
+        Ok(handler) => handler,
+        Err(error) => {
+            print_json(&json!({ "error": error }));
+            return;
+        }
+    };
+    print_json(&json!({ "ready": true }));
+
+    for line in io::stdin().lock().lines() {
+        let Ok(line) = line else {
+            break;
+        };
+        let (id, response) = match serde_json::from_str::<Value>(&line) {
+            Ok(request) => (
+                request["id"].clone(),
+                match request["method"].as_str() {
+                    Some(method) => handler(method, &request["params"]),
+                    None => Err("Unknown method".to_string()),
+                },
+            ),
+            Err(_) => (Value::Null, Err("Invalid JSON".to_string())),
+        };
+        match response {
+            Ok(result) => print_json(&json!({ "id": id, "result": result })),
+            Err(error) => print_json(&json!({ "id": id, "error": error })),
+        }
+    }
+}
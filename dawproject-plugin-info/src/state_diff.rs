@@ -0,0 +1,122 @@
+/*!
+ * ------------------------------------------------------------------------
+ * Parameter diff of two plugin states
+ * by Jurek Raben
+ *
+ * Both states are loaded into the same plugin instance one after the
+ * other, the parameters read after each are compared by normalized value
+ * and display string. The opaque chunks are compared byte by byte.
+ *
+ * Licensed under CC Attribution-NonCommercial-ShareAlike 4.0 International
+ * Info here: https://creativecommons.org/licenses/by-nc-sa/4.0/
+ * ------------------------------------------------------------------------
+ */
+
+use std::fs;
+use std::path::Path;
+
+use base64::prelude::*;
+use serde::Serialize;
+use serde_json::{Value, json};
+
+use crate::ParameterInfo;
+
+// A state after loading it into the plugin
+#[derive(Debug, Clone)]
+pub struct LoadedState {
+    // e.g. "vstpreset", "fxp", "fxb" or "chunk"
+    pub kind: &'static str,
+    // The opaque plugin data, None for plain parameter values
+    pub chunk: Option<Vec<u8>>,
+    pub loaded: bool,
+    pub parameters: Vec<ParameterInfo>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ParameterDifference {
+    pub index: i32,
+    pub id: u32,
+    pub name: String,
+    pub from_display: String,
+    pub to_display: String,
+    pub from_value: f64,
+    pub to_value: f64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ChunkDifference {
+    pub size_difference: i64,
+    // None if one chunk is the start of the other
+    pub first_different_byte: Option<usize>,
+    pub identical: bool,
+}
+
+// A state argument of the tools: a file if it exists, otherwise base64 data
+pub fn read_state(spec: &str) -> Result<Vec<u8>, String> {
+    let path = Path::new(spec);
+    if path.is_file() {
+        return fs::read(path).map_err(|e| format!("Failed to read {}: {}", path.display(), e));
+    }
+    BASE64_STANDARD
+        .decode(spec.trim())
+        .map_err(|_| format!("{} is neither a file nor base64 state data", spec))
+}
+
+// Parameters are matched by ID, parameters missing in one of the states
+// are left out
+pub fn diff_parameters(from: &[ParameterInfo], to: &[ParameterInfo]) -> Vec<ParameterDifference> {
+    to.iter()
+        .filter_map(|to_param| {
+            let from_param = from.iter().find(|param| param.id == to_param.id)?;
+            if from_param.value == to_param.value && from_param.display == to_param.display {
+                return None;
+            }
+            Some(ParameterDifference {
+                index: to_param.index,
+                id: to_param.id,
+                name: to_param.name.clone(),
+                from_display: from_param.display.clone(),
+                to_display: to_param.display.clone(),
+                from_value: from_param.value,
+                to_value: to_param.value,
+            })
+        })
+        .collect()
+}
+
+pub fn diff_chunks(from: &[u8], to: &[u8]) -> ChunkDifference {
+    ChunkDifference {
+        size_difference: to.len() as i64 - from.len() as i64,
+        first_different_byte: from.iter().zip(to).position(|(from, to)| from != to),
+        identical: from == to,
+    }
+}
+
+// The diff as printed by the tools, without the "os" field
+pub fn diff_output(name: &str, from: &LoadedState, to: &LoadedState) -> Value {
+    let differences = diff_parameters(&from.parameters, &to.parameters);
+    let chunks = match (&from.chunk, &to.chunk) {
+        (Some(from), Some(to)) => json!(diff_chunks(from, to)),
+        _ => Value::Null,
+    };
+
+    json!({
+        "name": name,
+        "from": state_output(from),
+        "to": state_output(to),
+        "chunks": chunks,
+        "countParameters": to.parameters.len(),
+        "countDifferences": differences.len(),
+        "differences": differences
+    })
+}
+
+fn state_output(state: &LoadedState) -> Value {
+    json!({
+        "kind": state.kind,
+        "chunkSize": state.chunk.as_ref().map(Vec::len),
+        "loaded": state.loaded
+    })
+}
@@ -0,0 +1,204 @@
+/*!
+ * ------------------------------------------------------------------------
+ * Raw VST 2.4 ABI definitions, as far as the info tool needs them
+ * by Jurek Raben
+ *
+ * Licensed under CC Attribution-NonCommercial-ShareAlike 4.0 International
+ * Info here: https://creativecommons.org/licenses/by-nc-sa/4.0/
+ * ------------------------------------------------------------------------
+ */
+
+#![allow(dead_code)]
+
+use std::ffi::c_void;
+
+pub const VST_MAGIC: i32 = i32::from_be_bytes(*b"VstP");
+
+pub type HostCallbackProc = unsafe extern "C" fn(
+    effect: *mut AEffect,
+    opcode: i32,
+    index: i32,
+    value: isize,
+    ptr: *mut c_void,
+    opt: f32,
+) -> isize;
+
+pub type PluginMainProc = unsafe extern "C" fn(callback: HostCallbackProc) -> *mut AEffect;
+
+pub type DispatcherProc = unsafe extern "C" fn(
+    effect: *mut AEffect,
+    opcode: i32,
+    index: i32,
+    value: isize,
+    ptr: *mut c_void,
+    opt: f32,
+) -> isize;
+
+pub type ProcessProc = unsafe extern "C" fn(*mut AEffect, *mut *mut f32, *mut *mut f32, i32);
+pub type ProcessDoubleProc = unsafe extern "C" fn(*mut AEffect, *mut *mut f64, *mut *mut f64, i32);
+pub type SetParameterProc = unsafe extern "C" fn(*mut AEffect, i32, f32);
+pub type GetParameterProc = unsafe extern "C" fn(*mut AEffect, i32) -> f32;
+
+// Layout as in aeffect.h of the VST 2.4 SDK
+#[repr(C)]
+pub struct AEffect {
+    pub magic: i32,
+    pub dispatcher: DispatcherProc,
+    pub process: ProcessProc,
+    pub setParameter: SetParameterProc,
+    pub getParameter: GetParameterProc,
+    pub numPrograms: i32,
+    pub numParams: i32,
+    pub numInputs: i32,
+    pub numOutputs: i32,
+    pub flags: i32,
+    pub resvd1: isize,
+    pub resvd2: isize,
+    pub initialDelay: i32,
+    pub realQualities: i32,
+    pub offQualities: i32,
+    pub ioRatio: f32,
+    pub object: *mut c_void,
+    pub user: *mut c_void,
+    pub uniqueID: i32,
+    pub version: i32,
+    pub processReplacing: ProcessProc,
+    pub processDoubleReplacing: ProcessDoubleProc,
+    pub future: [u8; 56],
+}
+
+#[repr(C)]
+pub struct VstTimeInfo {
+    pub samplePos: f64,
+    pub sampleRate: f64,
+    pub nanoSeconds: f64,
+    pub ppqPos: f64,
+    pub tempo: f64,
+    pub barStartPos: f64,
+    pub cycleStartPos: f64,
+    pub cycleEndPos: f64,
+    pub timeSigNumerator: i32,
+    pub timeSigDenominator: i32,
+    pub smpteOffset: i32,
+    pub smpteFrameRate: i32,
+    pub samplesToNextClock: i32,
+    pub flags: i32,
+}
+
+// AEffect flags
+pub const effFlagsHasEditor: i32 = 1;
+pub const effFlagsCanReplacing: i32 = 1 << 4;
+pub const effFlagsProgramChunks: i32 = 1 << 5;
+pub const effFlagsIsSynth: i32 = 1 << 8;
+pub const effFlagsNoSoundInStop: i32 = 1 << 9;
+pub const effFlagsCanDoubleReplacing: i32 = 1 << 12;
+
+pub const kVstMaxNameLen: usize = 64;
+
+#[repr(C)]
+pub struct MidiProgramName {
+    pub thisProgramIndex: i32,
+    pub name: [u8; kVstMaxNameLen],
+    pub midiProgram: i8,
+    pub midiBankMsb: i8,
+    pub midiBankLsb: i8,
+    pub reserved: i8,
+    pub parentCategoryIndex: i32,
+    pub flags: i32,
+}
+
+#[repr(C)]
+pub struct MidiKeyName {
+    pub thisProgramIndex: i32,
+    pub thisKeyNumber: i32,
+    pub keyName: [u8; kVstMaxNameLen],
+    pub reserved: i32,
+    pub flags: i32,
+}
+
+// VstPlugCategory, indexed by the value returned from effGetPlugCategory
+pub const PLUG_CATEGORIES: [&str; 12] = [
+    "unknown",
+    "effect",
+    "synth",
+    "analysis",
+    "mastering",
+    "spacializer",
+    "roomFx",
+    "surroundFx",
+    "restoration",
+    "offlineProcess",
+    "shell",
+    "generator",
+];
+
+// VstTimeInfo flags
+pub const kVstTransportChanged: i32 = 1;
+pub const kVstNanosValid: i32 = 1 << 8;
+pub const kVstPpqPosValid: i32 = 1 << 9;
+pub const kVstTempoValid: i32 = 1 << 10;
+pub const kVstBarsValid: i32 = 1 << 11;
+pub const kVstTimeSigValid: i32 = 1 << 13;
+
+// Plugin opcodes (host -> plugin)
+pub const effOpen: i32 = 0;
+pub const effClose: i32 = 1;
+pub const effSetProgram: i32 = 2;
+pub const effGetProgram: i32 = 3;
+pub const effGetProgramName: i32 = 5;
+pub const effGetParamLabel: i32 = 6;
+pub const effGetParamDisplay: i32 = 7;
+pub const effGetParamName: i32 = 8;
+pub const effSetSampleRate: i32 = 10;
+pub const effSetBlockSize: i32 = 11;
+pub const effMainsChanged: i32 = 12;
+pub const effGetChunk: i32 = 23;
+pub const effSetChunk: i32 = 24;
+pub const effCanBeAutomated: i32 = 26;
+pub const effGetProgramNameIndexed: i32 = 29;
+pub const effGetPlugCategory: i32 = 35;
+pub const effGetVendorString: i32 = 47;
+pub const effGetProductString: i32 = 48;
+pub const effGetVendorVersion: i32 = 49;
+pub const effCanDo: i32 = 51;
+pub const effGetVstVersion: i32 = 58;
+pub const effGetMidiProgramName: i32 = 62;
+pub const effGetCurrentMidiProgram: i32 = 63;
+pub const effGetMidiKeyName: i32 = 66;
+pub const effBeginSetProgram: i32 = 67;
+pub const effEndSetProgram: i32 = 68;
+pub const effShellGetNextPlugin: i32 = 70;
+pub const effGetNumMidiInputChannels: i32 = 78;
+pub const effGetNumMidiOutputChannels: i32 = 79;
+
+// Host opcodes (plugin -> host)
+pub const audioMasterAutomate: i32 = 0;
+pub const audioMasterVersion: i32 = 1;
+pub const audioMasterCurrentId: i32 = 2;
+pub const audioMasterIdle: i32 = 3;
+pub const audioMasterGetTime: i32 = 7;
+pub const audioMasterProcessEvents: i32 = 8;
+pub const audioMasterIOChanged: i32 = 13;
+pub const audioMasterSizeWindow: i32 = 15;
+pub const audioMasterGetSampleRate: i32 = 16;
+pub const audioMasterGetBlockSize: i32 = 17;
+pub const audioMasterGetInputLatency: i32 = 18;
+pub const audioMasterGetOutputLatency: i32 = 19;
+pub const audioMasterGetCurrentProcessLevel: i32 = 23;
+pub const audioMasterGetAutomationState: i32 = 24;
+pub const audioMasterGetVendorString: i32 = 32;
+pub const audioMasterGetProductString: i32 = 33;
+pub const audioMasterGetVendorVersion: i32 = 34;
+pub const audioMasterCanDo: i32 = 37;
+pub const audioMasterGetLanguage: i32 = 38;
+pub const audioMasterUpdateDisplay: i32 = 42;
+pub const audioMasterBeginEdit: i32 = 43;
+pub const audioMasterEndEdit: i32 = 44;
+
+// String buffer sizes. The SDK limits are tiny and frequently ignored by
+// plugins, so the buffers handed out are larger than the spec requires.
+pub const kVstMaxProgNameLen: usize = 24;
+pub const kVstMaxParamStrLen: usize = 8;
+pub const kVstMaxVendorStrLen: usize = 64;
+pub const kVstMaxProductStrLen: usize = 64;
+pub const kStringBufferLen: usize = 256;
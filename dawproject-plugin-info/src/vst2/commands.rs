@@ -0,0 +1,340 @@
+/*!
+ * ------------------------------------------------------------------------
+ * VST2 command output
+ * by Jurek Raben
+ *
+ * The JSON output of the vst2info-tool commands. The commands work on an
+ * already loaded instance, they are shared by the subcommands and the
+ * workers of the serve mode.
+ *
+ * Licensed under CC Attribution-NonCommercial-ShareAlike 4.0 International
+ * Info here: https://creativecommons.org/licenses/by-nc-sa/4.0/
+ * ------------------------------------------------------------------------
+ */
+
+use std::fs;
+use std::path::Path;
+
+use base64::prelude::*;
+use serde_json::{Value, json};
+
+use super::api::*;
+use super::fxp::{self, FxContent, FxProgram};
+use super::host;
+use super::plugin::PluginInstance;
+use super::plugin_info;
+use crate::output::OS;
+use crate::state_diff::{self, LoadedState};
+
+const MIDI_CHANNELS: i32 = 16;
+const MIDI_KEYS: i32 = 128;
+
+pub fn decode_state(data: &str) -> Result<Vec<u8>, String> {
+    BASE64_STANDARD
+        .decode(data.trim())
+        .map_err(|e| format!("Invalid base64 state data: {}", e))
+}
+
+fn write_file(path: &Path, data: &[u8]) -> Result<(), String> {
+    fs::write(path, data).map_err(|e| format!("Failed to write {}: {}", path.display(), e))
+}
+
+pub fn info(instance: &PluginInstance, preset: Option<i32>, midi_names: bool) -> Value {
+    let mut output = info_output(instance);
+
+    if let Some(preset_num) = preset {
+        instance.change_preset(preset_num);
+        output["presetName"] = json!(instance.get_preset_name(preset_num));
+        output["presetData"] = json!(BASE64_STANDARD.encode(instance.get_preset_data()));
+    }
+
+    if midi_names {
+        output["midiNames"] = read_midi_names(instance);
+    }
+    output
+}
+
+pub fn params(instance: &PluginInstance, preset: Option<i32>) -> Value {
+    if let Some(preset_num) = preset {
+        instance.change_preset(preset_num);
+    }
+    json!({
+        "name": instance.name(),
+        "id": instance.unique_id(),
+        "presetName": instance.current_preset_name(),
+        "countParameters": instance.count_parameters(),
+        "parameters": parameters_output(instance)
+    })
+}
+
+pub fn preset(
+    instance: &PluginInstance,
+    preset: i32,
+    output_path: Option<&Path>,
+) -> Result<Value, String> {
+    let use_chunk = instance.has_flag(effFlagsProgramChunks);
+    instance.change_preset(preset);
+
+    let mut output = json!({
+        "name": instance.name(),
+        "id": instance.unique_id(),
+        "preset": preset,
+        "presetName": instance.get_preset_name(preset),
+        "presetData": BASE64_STANDARD.encode(instance.get_preset_data()),
+        "isChunk": use_chunk
+    });
+    if let Some(output_path) = output_path {
+        write_file(output_path, &fxp::write_program(instance, use_chunk))?;
+        output["file"] = json!(output_path.display().to_string());
+    }
+    Ok(output)
+}
+
+pub fn bank(instance: &PluginInstance, output_path: Option<&Path>) -> Result<Value, String> {
+    let use_chunk = instance.has_flag(effFlagsProgramChunks);
+
+    let mut output = json!({
+        "name": instance.name(),
+        "id": instance.unique_id(),
+        "countPresets": instance.count_presets(),
+        "bankData": BASE64_STANDARD.encode(instance.get_bank_data()),
+        "isChunk": use_chunk
+    });
+    if let Some(output_path) = output_path {
+        write_file(output_path, &fxp::write_bank(instance, use_chunk))?;
+        output["file"] = json!(output_path.display().to_string());
+    }
+    Ok(output)
+}
+
+pub fn apply_state(instance: &PluginInstance, state: &[u8], bank: bool) -> Value {
+    // only the calls caused by the state are reported, not the ones of loading
+    host::take_recorded_calls();
+    let applied = instance.set_chunk(state, !bank);
+
+    json!({
+        "name": instance.name(),
+        "id": instance.unique_id(),
+        "applied": applied,
+        "stateSize": state.len(),
+        "presetName": instance.current_preset_name(),
+        "countParameters": instance.count_parameters(),
+        "parameters": parameters_output(instance)
+    })
+}
+
+// Loads both states one after another and compares the parameters
+pub fn diff(instance: &PluginInstance, from: &[u8], to: &[u8], bank: bool) -> Value {
+    host::take_recorded_calls();
+    let from = load_state(instance, from, bank);
+    let to = load_state(instance, to, bank);
+    state_diff::diff_output(&instance.name(), &from, &to)
+}
+
+// Loads .fxp/.fxb data, or a raw program or bank chunk
+pub fn load_state(instance: &PluginInstance, state: &[u8], bank: bool) -> LoadedState {
+    let (kind, chunk, loaded) = if state.starts_with(b"CcnK") {
+        match fxp::parse_fx_file(state).map(|fx_file| fx_file.content) {
+            Ok(FxContent::Program(program)) => {
+                let loaded = load_fx_program(instance, &program);
+                ("fxp", program.is_chunk.then_some(program.chunk), loaded)
+            }
+            Ok(FxContent::Bank(fx_bank)) if fx_bank.is_chunk => {
+                let loaded = instance.set_chunk(&fx_bank.chunk, false);
+                ("fxb", Some(fx_bank.chunk), loaded)
+            }
+            Ok(FxContent::Bank(fx_bank)) => {
+                for (preset, program) in fx_bank.programs.iter().enumerate() {
+                    instance.change_preset(preset as i32);
+                    load_fx_program(instance, program);
+                }
+                instance.change_preset(fx_bank.current_program.unwrap_or(0));
+                ("fxb", None, true)
+            }
+            Err(_) => ("fxp", None, false),
+        }
+    } else {
+        (
+            "chunk",
+            Some(state.to_vec()),
+            instance.set_chunk(state, !bank),
+        )
+    };
+
+    LoadedState {
+        kind,
+        chunk,
+        loaded,
+        parameters: plugin_info(instance).parameters,
+    }
+}
+
+fn load_fx_program(instance: &PluginInstance, program: &FxProgram) -> bool {
+    if program.is_chunk {
+        return instance.set_chunk(&program.chunk, true);
+    }
+    for (index, value) in program.parameters.iter().enumerate() {
+        instance.set_parameter(index as i32, *value);
+    }
+    true
+}
+
+pub fn shell(instance: &PluginInstance) -> Value {
+    let mut plugins = Vec::new();
+    while let Some((id, name)) = instance.shell_next_plugin() {
+        plugins.push(json!({ "name": name, "id": id }));
+    }
+    json!({
+        "name": instance.name(),
+        "vendor": instance.vendor(),
+        "id": instance.unique_id(),
+        "category": instance.category(),
+        "plugins": plugins
+    })
+}
+
+// Requests of the serve mode, see serve::run_worker
+pub fn worker_request(
+    instance: &PluginInstance,
+    method: &str,
+    params: &Value,
+) -> Result<Value, String> {
+    // each response only reports the calls of its own request
+    host::take_recorded_calls();
+    let preset = params["preset"].as_i64().map(|preset| preset as i32);
+    let bank = params["bank"].as_bool().unwrap_or(false);
+    let output_path = params["output"].as_str().map(Path::new);
+
+    let output = match method {
+        "inspect" => info(
+            instance,
+            preset,
+            params["midiNames"].as_bool().unwrap_or(false),
+        ),
+        "params" => self::params(instance, preset),
+        "applyState" => {
+            let data = params["data"]
+                .as_str()
+                .ok_or_else(|| "State data required".to_string())?;
+            let state = decode_state(data)?;
+            apply_state(instance, &state, bank)
+        }
+        "makePreset" if bank => self::bank(instance, output_path)?,
+        "makePreset" => self::preset(instance, preset.unwrap_or(0), output_path)?,
+        _ => return Err("Unknown method".to_string()),
+    };
+    Ok(complete_output(instance, output))
+}
+
+fn info_output(instance: &PluginInstance) -> Value {
+    json!({
+        "name": instance.name(),
+        "vendor": instance.vendor(),
+        "countPresets": instance.count_presets(),
+        "countParameters": instance.count_parameters(),
+        "countInputs": instance.count_inputs(),
+        "countOutputs": instance.count_outputs(),
+        "id": instance.unique_id(),
+        "version": instance.version(),
+        "delay": instance.initial_delay(),
+        "category": instance.category(),
+        "flags": {
+            "hasEditor": instance.has_flag(effFlagsHasEditor),
+            "canReplacing": instance.has_flag(effFlagsCanReplacing),
+            "canDoubleReplacing": instance.has_flag(effFlagsCanDoubleReplacing),
+            "programChunks": instance.has_flag(effFlagsProgramChunks),
+            "isSynth": instance.has_flag(effFlagsIsSynth),
+            "noSoundInStop": instance.has_flag(effFlagsNoSoundInStop)
+        },
+        // The plugin's answer as is, 1 = yes, -1 = no, 0 = don't know
+        "canDo": {
+            "receiveVstMidiEvent": instance.can_do("receiveVstMidiEvent"),
+            "sendVstMidiEvent": instance.can_do("sendVstMidiEvent"),
+            "offline": instance.can_do("offline"),
+            "bypass": instance.can_do("bypass")
+        },
+        "countMidiInputs": instance.count_midi_inputs(),
+        "countMidiOutputs": instance.count_midi_outputs()
+    })
+}
+
+fn parameters_output(instance: &PluginInstance) -> Value {
+    instance
+        .parameters()
+        .into_iter()
+        .map(|param| {
+            json!({
+                "name": param.name,
+                "label": param.label,
+                "display": param.display,
+                "index": param.index,
+                "value": param.value,
+                "automatable": param.automatable
+            })
+        })
+        .collect()
+}
+
+// The loaded binary and the calls the plugin made to the host on its own,
+// e.g. parameter changes while opening, are added as diagnostics. Nested
+// objects keep a non string last field, the Lua side cuts the JSON at the
+// first quote followed by a closing brace.
+pub fn complete_output(instance: &PluginInstance, mut output: Value) -> Value {
+    output["loadedFile"] = json!(instance.path().display().to_string());
+    output["diagnostics"] = host::take_recorded_calls()
+        .into_iter()
+        .map(|call| {
+            json!({
+                "call": call.call,
+                "index": call.index,
+                "value": call.value
+            })
+        })
+        .collect();
+    output["os"] = json!(OS);
+    output
+}
+
+// Program and key names per MIDI channel, as published by drum plugins.
+// Key names are read for the channel's current program only.
+fn read_midi_names(instance: &PluginInstance) -> Value {
+    let mut channels = Vec::new();
+
+    for channel in 0..MIDI_CHANNELS {
+        let programs = instance.midi_programs(channel);
+        let current_program = instance.current_midi_program(channel);
+        let key_program = current_program.max(0);
+
+        let mut keys = Vec::new();
+        for key in 0..MIDI_KEYS {
+            if let Some(name) = instance.midi_key_name(channel, key_program, key) {
+                keys.push(json!({ "name": name, "key": key }));
+            }
+        }
+
+        if programs.is_empty() && keys.is_empty() {
+            continue;
+        }
+
+        let programs: Vec<Value> = programs
+            .into_iter()
+            .map(|program| {
+                json!({
+                    "name": program.name,
+                    "index": program.index,
+                    "program": program.program,
+                    "bankMsb": program.bank_msb,
+                    "bankLsb": program.bank_lsb
+                })
+            })
+            .collect();
+        channels.push(json!({
+            "channel": channel,
+            "currentProgram": current_program,
+            "programs": programs,
+            "keys": keys
+        }));
+    }
+
+    Value::Array(channels)
+}
@@ -0,0 +1,583 @@
+/*!
+ * ------------------------------------------------------------------------
+ * VST2 .fxp/.fxb preset and bank reader and writer
+ * by Jurek Raben
+ *
+ * All fields are big endian, as in vstfxstore.h of the VST 2.4 SDK. The
+ * reader keeps every field with its offset and collects problems as
+ * issues, so files of other hosts can be compared field by field.
+ *
+ * Licensed under CC Attribution-NonCommercial-ShareAlike 4.0 International
+ * Info here: https://creativecommons.org/licenses/by-nc-sa/4.0/
+ * ------------------------------------------------------------------------
+ */
+
+use std::fs;
+use std::path::Path;
+
+use serde::Serialize;
+use serde_json::{Value, json};
+
+use super::plugin::PluginInstance;
+
+pub const PROGRAM_NAME_LEN: usize = 28;
+pub const BANK_FUTURE_LEN: usize = 124;
+
+fn push_i32(buffer: &mut Vec<u8>, value: i32) {
+    buffer.extend_from_slice(&value.to_be_bytes());
+}
+
+fn push_name(buffer: &mut Vec<u8>, name: &str) {
+    let mut bytes = [0u8; PROGRAM_NAME_LEN];
+    let name = name.as_bytes();
+    // keep the terminating zero
+    let len = std::cmp::min(name.len(), PROGRAM_NAME_LEN - 1);
+    bytes[..len].copy_from_slice(&name[..len]);
+    buffer.extend_from_slice(&bytes);
+}
+
+// Prepends the 'CcnK' magic and the size of everything following it
+fn wrap_chunk(content: Vec<u8>) -> Vec<u8> {
+    let mut buffer = Vec::with_capacity(content.len() + 8);
+    buffer.extend_from_slice(b"CcnK");
+    push_i32(&mut buffer, content.len() as i32);
+    buffer.extend_from_slice(&content);
+    buffer
+}
+
+fn program_header(
+    magic: &[u8; 4],
+    unique_id: i32,
+    fx_version: i32,
+    num_params: i32,
+    name: &str,
+) -> Vec<u8> {
+    let mut content = Vec::new();
+    content.extend_from_slice(magic);
+    push_i32(&mut content, 1);
+    push_i32(&mut content, unique_id);
+    push_i32(&mut content, fx_version);
+    push_i32(&mut content, num_params);
+    push_name(&mut content, name);
+    content
+}
+
+fn bank_header(
+    magic: &[u8; 4],
+    unique_id: i32,
+    fx_version: i32,
+    num_programs: i32,
+    current_program: i32,
+) -> Vec<u8> {
+    let mut content = Vec::new();
+    content.extend_from_slice(magic);
+    push_i32(&mut content, 2);
+    push_i32(&mut content, unique_id);
+    push_i32(&mut content, fx_version);
+    push_i32(&mut content, num_programs);
+    push_i32(&mut content, current_program);
+    content.extend_from_slice(&[0u8; BANK_FUTURE_LEN]);
+    content
+}
+
+fn parameter_program(instance: &PluginInstance, name: &str) -> Vec<u8> {
+    let mut content = program_header(
+        b"FxCk",
+        instance.unique_id(),
+        instance.version(),
+        instance.count_parameters(),
+        name,
+    );
+    for index in 0..instance.count_parameters() {
+        content.extend_from_slice(&instance.get_parameter(index).to_be_bytes());
+    }
+    wrap_chunk(content)
+}
+
+// An opaque program chunk as .fxp, without loading the plugin
+pub fn write_chunk_program(
+    unique_id: i32,
+    fx_version: i32,
+    num_params: i32,
+    name: &str,
+    chunk: &[u8],
+) -> Vec<u8> {
+    let mut content = program_header(b"FPCh", unique_id, fx_version, num_params, name);
+    push_i32(&mut content, chunk.len() as i32);
+    content.extend_from_slice(chunk);
+    wrap_chunk(content)
+}
+
+// An opaque bank chunk as .fxb, without loading the plugin
+pub fn write_chunk_bank(
+    unique_id: i32,
+    fx_version: i32,
+    num_programs: i32,
+    current_program: i32,
+    chunk: &[u8],
+) -> Vec<u8> {
+    let mut content = bank_header(
+        b"FBCh",
+        unique_id,
+        fx_version,
+        num_programs,
+        current_program,
+    );
+    push_i32(&mut content, chunk.len() as i32);
+    content.extend_from_slice(chunk);
+    wrap_chunk(content)
+}
+
+// Current program of the plugin, as opaque chunk if the plugin uses chunks
+pub fn write_program(instance: &PluginInstance, use_chunk: bool) -> Vec<u8> {
+    let name = instance.current_preset_name();
+    if use_chunk {
+        write_chunk_program(
+            instance.unique_id(),
+            instance.version(),
+            instance.count_parameters(),
+            &name,
+            &instance.get_preset_data(),
+        )
+    } else {
+        parameter_program(instance, &name)
+    }
+}
+
+// All programs of the plugin. Parameter based banks step through every
+// program, the previously selected one is restored afterwards.
+pub fn write_bank(instance: &PluginInstance, use_chunk: bool) -> Vec<u8> {
+    let current_preset = instance.current_preset();
+    let count_presets = instance.count_presets();
+
+    if use_chunk {
+        return write_chunk_bank(
+            instance.unique_id(),
+            instance.version(),
+            count_presets,
+            current_preset,
+            &instance.get_bank_data(),
+        );
+    }
+
+    let mut content = bank_header(
+        b"FxBk",
+        instance.unique_id(),
+        instance.version(),
+        count_presets,
+        current_preset,
+    );
+    for preset in 0..count_presets {
+        instance.change_preset(preset);
+        let name = instance.current_preset_name();
+        content.extend_from_slice(&parameter_program(instance, &name));
+    }
+    instance.change_preset(current_preset);
+
+    wrap_chunk(content)
+}
+
+// A field as stored in the file, with the offset of its first byte
+#[derive(Debug, Clone, Serialize)]
+pub struct FxField {
+    pub name: String,
+    pub value: Value,
+    pub offset: usize,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FxProgram {
+    pub byte_size: i32,
+    // FPCh instead of FxCk
+    pub is_chunk: bool,
+    pub version: i32,
+    pub unique_id: i32,
+    pub fx_version: i32,
+    pub num_params: i32,
+    pub name: String,
+    pub parameters: Vec<f32>,
+    pub chunk_size: Option<i32>,
+    #[serde(skip)]
+    pub chunk: Vec<u8>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FxBank {
+    pub byte_size: i32,
+    // FBCh instead of FxBk
+    pub is_chunk: bool,
+    pub version: i32,
+    pub unique_id: i32,
+    pub fx_version: i32,
+    pub num_programs: i32,
+    // Only stored from bank version 2 on
+    pub current_program: Option<i32>,
+    pub programs: Vec<FxProgram>,
+    pub chunk_size: Option<i32>,
+    #[serde(skip)]
+    pub chunk: Vec<u8>,
+}
+
+#[derive(Debug, Clone)]
+pub enum FxContent {
+    Program(FxProgram),
+    Bank(FxBank),
+}
+
+#[derive(Debug, Clone)]
+pub struct FxFile {
+    pub content: FxContent,
+    pub fields: Vec<FxField>,
+    pub issues: Vec<String>,
+}
+
+// Reads the fields one after another, a missing field ends the file
+struct FieldReader<'a> {
+    data: &'a [u8],
+    position: usize,
+    fields: Vec<FxField>,
+    issues: Vec<String>,
+}
+
+impl<'a> FieldReader<'a> {
+    fn take(&mut self, name: &str, len: usize) -> Option<&'a [u8]> {
+        let data = self.data;
+        let Some(bytes) = data.get(self.position..self.position + len) else {
+            self.issues.push(format!(
+                "File ends inside {} at offset {}",
+                name, self.position
+            ));
+            self.position = self.data.len();
+            return None;
+        };
+        self.position += len;
+        Some(bytes)
+    }
+
+    fn push_field(&mut self, name: &str, value: Value, len: usize) {
+        self.fields.push(FxField {
+            name: name.to_string(),
+            value,
+            offset: self.position - len,
+        });
+    }
+
+    fn magic(&mut self, name: &str) -> Option<String> {
+        let magic = String::from_utf8_lossy(self.take(name, 4)?).to_string();
+        self.push_field(name, json!(magic), 4);
+        Some(magic)
+    }
+
+    fn i32(&mut self, name: &str) -> Option<i32> {
+        let value = i32::from_be_bytes(self.take(name, 4)?.try_into().ok()?);
+        self.push_field(name, json!(value), 4);
+        Some(value)
+    }
+
+    fn f32(&mut self, name: &str) -> Option<f32> {
+        let value = f32::from_be_bytes(self.take(name, 4)?.try_into().ok()?);
+        self.push_field(name, json!(value), 4);
+        Some(value)
+    }
+
+    fn program_name(&mut self, name: &str) -> Option<String> {
+        let bytes = self.take(name, PROGRAM_NAME_LEN)?;
+        let end = match bytes.iter().position(|&c| c == 0) {
+            Some(end) => end,
+            None => {
+                self.issues.push(format!("{} is not null terminated", name));
+                bytes.len()
+            }
+        };
+        let value = String::from_utf8_lossy(&bytes[..end]).to_string();
+        self.push_field(name, json!(value), PROGRAM_NAME_LEN);
+        Some(value)
+    }
+
+    // Opaque data, dumped with its size only
+    fn bytes(&mut self, name: &str, len: usize) -> Option<Vec<u8>> {
+        let bytes = self.take(name, len)?.to_vec();
+        self.push_field(name, json!({ "size": len }), len);
+        Some(bytes)
+    }
+
+    // Compares a byteSize field with the bytes actually read after it
+    fn check_byte_size(&mut self, name: &str, byte_size: i32, start: usize) {
+        let read = self.position - start;
+        if byte_size as i64 != read as i64 {
+            self.issues.push(format!(
+                "{} {} does not match the {} bytes following it",
+                name, byte_size, read
+            ));
+        }
+    }
+}
+
+pub fn read_fx_file(path: &Path) -> Result<FxFile, String> {
+    let data = fs::read(path).map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+    parse_fx_file(&data)
+}
+
+// Fails only if the data is no CcnK container at all
+pub fn parse_fx_file(data: &[u8]) -> Result<FxFile, String> {
+    if data.len() < 12 || !data.starts_with(b"CcnK") {
+        return Err("Not an .fxp or .fxb file".to_string());
+    }
+
+    let mut reader = FieldReader {
+        data,
+        position: 0,
+        fields: Vec::new(),
+        issues: Vec::new(),
+    };
+    let content = match &data[8..12] {
+        b"FxCk" | b"FPCh" => FxContent::Program(read_program(&mut reader, "")),
+        b"FxBk" | b"FBCh" => FxContent::Bank(read_bank(&mut reader)),
+        magic => {
+            return Err(format!(
+                "Unknown fxMagic {:?}",
+                String::from_utf8_lossy(magic)
+            ));
+        }
+    };
+
+    if reader.position < data.len() {
+        reader.issues.push(format!(
+            "{} bytes of trailing data",
+            data.len() - reader.position
+        ));
+    }
+    Ok(FxFile {
+        content,
+        fields: reader.fields,
+        issues: reader.issues,
+    })
+}
+
+// A CcnK program, on its own or inside a bank. Fields are named with the
+// given prefix, e.g. "programs[1]."
+fn read_program(reader: &mut FieldReader, prefix: &str) -> FxProgram {
+    let field = |name: &str| format!("{}{}", prefix, name);
+    let mut program = FxProgram {
+        byte_size: 0,
+        is_chunk: false,
+        version: 0,
+        unique_id: 0,
+        fx_version: 0,
+        num_params: 0,
+        name: String::new(),
+        parameters: Vec::new(),
+        chunk_size: None,
+        chunk: Vec::new(),
+    };
+
+    let Some(chunk_magic) = reader.magic(&field("chunkMagic")) else {
+        return program;
+    };
+    if chunk_magic != "CcnK" {
+        reader.issues.push(format!(
+            "{} is {:?} instead of \"CcnK\"",
+            field("chunkMagic"),
+            chunk_magic
+        ));
+    }
+    let Some(byte_size) = reader.i32(&field("byteSize")) else {
+        return program;
+    };
+    program.byte_size = byte_size;
+    let start = reader.position;
+
+    let Some(fx_magic) = reader.magic(&field("fxMagic")) else {
+        return program;
+    };
+    program.is_chunk = match fx_magic.as_str() {
+        "FxCk" => false,
+        "FPCh" => true,
+        _ => {
+            reader.issues.push(format!(
+                "{} is {:?} instead of \"FxCk\" or \"FPCh\"",
+                field("fxMagic"),
+                fx_magic
+            ));
+            return program;
+        }
+    };
+
+    let (Some(version), Some(unique_id), Some(fx_version), Some(num_params), Some(name)) = (
+        reader.i32(&field("version")),
+        reader.i32(&field("fxID")),
+        reader.i32(&field("fxVersion")),
+        reader.i32(&field("numParams")),
+        reader.program_name(&field("prgName")),
+    ) else {
+        return program;
+    };
+    program.version = version;
+    program.unique_id = unique_id;
+    program.fx_version = fx_version;
+    program.num_params = num_params;
+    program.name = name;
+    if version != 1 {
+        reader
+            .issues
+            .push(format!("{} {} instead of 1", field("version"), version));
+    }
+
+    if program.is_chunk {
+        let Some(chunk_size) = reader.i32(&field("size")) else {
+            return program;
+        };
+        program.chunk_size = Some(chunk_size);
+        program.chunk = reader
+            .bytes(&field("chunk"), chunk_size.max(0) as usize)
+            .unwrap_or_default();
+    } else {
+        for index in 0..num_params.max(0) {
+            match reader.f32(&field(&format!("params[{}]", index))) {
+                Some(value) => program.parameters.push(value),
+                None => return program,
+            }
+        }
+    }
+
+    reader.check_byte_size(&field("byteSize"), byte_size, start);
+    program
+}
+
+fn read_bank(reader: &mut FieldReader) -> FxBank {
+    let mut bank = FxBank {
+        byte_size: 0,
+        is_chunk: false,
+        version: 0,
+        unique_id: 0,
+        fx_version: 0,
+        num_programs: 0,
+        current_program: None,
+        programs: Vec::new(),
+        chunk_size: None,
+        chunk: Vec::new(),
+    };
+
+    let (Some(_), Some(byte_size), Some(fx_magic)) = (
+        reader.magic("chunkMagic"),
+        reader.i32("byteSize"),
+        reader.magic("fxMagic"),
+    ) else {
+        return bank;
+    };
+    bank.byte_size = byte_size;
+    bank.is_chunk = fx_magic == "FBCh";
+    let start = reader.position - 4;
+
+    let (Some(version), Some(unique_id), Some(fx_version), Some(num_programs)) = (
+        reader.i32("version"),
+        reader.i32("fxID"),
+        reader.i32("fxVersion"),
+        reader.i32("numPrograms"),
+    ) else {
+        return bank;
+    };
+    bank.version = version;
+    bank.unique_id = unique_id;
+    bank.fx_version = fx_version;
+    bank.num_programs = num_programs;
+    if !(1..=2).contains(&version) {
+        reader
+            .issues
+            .push(format!("version {} instead of 1 or 2", version));
+    }
+
+    // Part of the future bytes in version 1 banks
+    let Some(current_program) = reader.i32("currentProgram") else {
+        return bank;
+    };
+    if version >= 2 {
+        bank.current_program = Some(current_program);
+        if current_program < 0 || current_program >= num_programs {
+            reader.issues.push(format!(
+                "currentProgram {} is not one of the {} programs",
+                current_program, num_programs
+            ));
+        }
+    }
+    if reader.bytes("future", BANK_FUTURE_LEN).is_none() {
+        return bank;
+    }
+
+    if bank.is_chunk {
+        let Some(chunk_size) = reader.i32("size") else {
+            return bank;
+        };
+        bank.chunk_size = Some(chunk_size);
+        bank.chunk = reader
+            .bytes("chunk", chunk_size.max(0) as usize)
+            .unwrap_or_default();
+    } else {
+        for index in 0..num_programs.max(0) {
+            if reader.position >= reader.data.len() {
+                reader.issues.push(format!(
+                    "Bank ends after {} of {} programs",
+                    index, num_programs
+                ));
+                break;
+            }
+            let program = read_program(reader, &format!("programs[{}].", index));
+            if program.unique_id != unique_id {
+                reader.issues.push(format!(
+                    "programs[{}].fxID {} does not match the bank's {}",
+                    index, program.unique_id, unique_id
+                ));
+            }
+            bank.programs.push(program);
+        }
+    }
+
+    reader.check_byte_size("byteSize", byte_size, start);
+    bank
+}
+
+impl FxFile {
+    pub fn unique_id(&self) -> i32 {
+        match &self.content {
+            FxContent::Program(program) => program.unique_id,
+            FxContent::Bank(bank) => bank.unique_id,
+        }
+    }
+
+    // Compares the file with the plugin it should be loaded into
+    pub fn check_plugin(&mut self, unique_id: i32, num_params: i32, num_programs: i32) {
+        if self.unique_id() != unique_id {
+            self.issues.push(format!(
+                "fxID {} does not match the plugin's {}",
+                self.unique_id(),
+                unique_id
+            ));
+        }
+
+        let programs = match &self.content {
+            FxContent::Program(program) => vec![program],
+            FxContent::Bank(bank) => {
+                if bank.num_programs != num_programs {
+                    self.issues.push(format!(
+                        "numPrograms {} does not match the plugin's {}",
+                        bank.num_programs, num_programs
+                    ));
+                }
+                bank.programs.iter().collect()
+            }
+        };
+        let mismatches: Vec<String> = programs
+            .into_iter()
+            .filter(|program| program.num_params != num_params)
+            .map(|program| {
+                format!(
+                    "numParams {} of {:?} does not match the plugin's {}",
+                    program.num_params, program.name, num_params
+                )
+            })
+            .collect();
+        self.issues.extend(mismatches);
+    }
+}
@@ -0,0 +1,165 @@
+/*!
+ * ------------------------------------------------------------------------
+ * Minimal VST2 host callback
+ * by Jurek Raben
+ *
+ * Answers the host opcodes that plugins commonly query while being
+ * opened and records calls the plugin makes on its own initiative.
+ *
+ * Licensed under CC Attribution-NonCommercial-ShareAlike 4.0 International
+ * Info here: https://creativecommons.org/licenses/by-nc-sa/4.0/
+ * ------------------------------------------------------------------------
+ */
+
+use std::ffi::{CStr, c_char, c_void};
+use std::sync::Mutex;
+
+use super::api::*;
+
+pub const HOST_VENDOR: &str = "Jurek Raben";
+pub const HOST_PRODUCT: &str = "DawProject-Tool";
+pub const HOST_VERSION: isize = 1;
+pub const HOST_SAMPLE_RATE: f32 = 44100.0;
+pub const HOST_BLOCK_SIZE: isize = 512;
+pub const HOST_TEMPO: f64 = 120.0;
+
+const HOST_CAN_DO: [&str; 7] = [
+    "sendVstEvents",
+    "sendVstMidiEvent",
+    "sendVstTimeInfo",
+    "receiveVstEvents",
+    "receiveVstMidiEvent",
+    "supportShell",
+    "shellCategory",
+];
+
+// A call the plugin made to the host without being asked to
+#[derive(Debug, Clone)]
+pub struct HostCall {
+    pub call: &'static str,
+    pub index: i32,
+    pub value: f32,
+}
+
+struct HostState {
+    current_id: i32,
+    calls: Vec<HostCall>,
+}
+
+// Only a single plugin is hosted per process, and the callback has no
+// context pointer before the AEffect exists, so the state is global.
+static HOST_STATE: Mutex<HostState> = Mutex::new(HostState {
+    current_id: 0,
+    calls: Vec::new(),
+});
+
+// The transport never moves, plugins only get a consistent snapshot
+static mut TIME_INFO: VstTimeInfo = VstTimeInfo {
+    samplePos: 0.0,
+    sampleRate: HOST_SAMPLE_RATE as f64,
+    nanoSeconds: 0.0,
+    ppqPos: 0.0,
+    tempo: HOST_TEMPO,
+    barStartPos: 0.0,
+    cycleStartPos: 0.0,
+    cycleEndPos: 0.0,
+    timeSigNumerator: 4,
+    timeSigDenominator: 4,
+    smpteOffset: 0,
+    smpteFrameRate: 0,
+    samplesToNextClock: 0,
+    flags: kVstNanosValid | kVstPpqPosValid | kVstTempoValid | kVstBarsValid | kVstTimeSigValid,
+};
+
+fn record_call(call: &'static str, index: i32, value: f32) {
+    if let Ok(mut state) = HOST_STATE.lock() {
+        state.calls.push(HostCall { call, index, value });
+    }
+}
+
+// Sets the id a shell plugin should instantiate on the next VSTPluginMain call
+pub fn set_current_id(id: i32) {
+    if let Ok(mut state) = HOST_STATE.lock() {
+        state.current_id = id;
+    }
+}
+
+fn current_id() -> i32 {
+    HOST_STATE.lock().map(|state| state.current_id).unwrap_or(0)
+}
+
+// Returns the calls recorded so far and clears the list, so a loaded
+// plugin only reports the calls since the last output
+pub fn take_recorded_calls() -> Vec<HostCall> {
+    HOST_STATE
+        .lock()
+        .map(|mut state| std::mem::take(&mut state.calls))
+        .unwrap_or_default()
+}
+
+unsafe fn copy_string(ptr: *mut c_void, string: &str, max: usize) {
+    if ptr.is_null() {
+        return;
+    }
+    let bytes = string.as_bytes();
+    let len = std::cmp::min(bytes.len(), max - 1);
+    unsafe {
+        std::ptr::copy_nonoverlapping(bytes.as_ptr(), ptr as *mut u8, len);
+        *(ptr as *mut u8).add(len) = 0;
+    }
+}
+
+pub(crate) unsafe extern "C" fn host_callback(
+    _effect: *mut AEffect,
+    opcode: i32,
+    index: i32,
+    value: isize,
+    ptr: *mut c_void,
+    opt: f32,
+) -> isize {
+    match opcode {
+        audioMasterAutomate => {
+            record_call("automate", index, opt);
+            0
+        }
+        audioMasterVersion => 2400,
+        audioMasterCurrentId => current_id() as isize,
+        audioMasterIdle => 0,
+        audioMasterGetTime => (&raw mut TIME_INFO) as isize,
+        audioMasterIOChanged => {
+            record_call("ioChanged", index, value as f32);
+            1
+        }
+        audioMasterGetSampleRate => HOST_SAMPLE_RATE as isize,
+        audioMasterGetBlockSize => HOST_BLOCK_SIZE,
+        audioMasterGetInputLatency | audioMasterGetOutputLatency => 0,
+        // kVstProcessLevelUser, nothing is running in realtime
+        audioMasterGetCurrentProcessLevel => 1,
+        // kVstAutomationOff
+        audioMasterGetAutomationState => 1,
+        audioMasterGetVendorString => {
+            unsafe { copy_string(ptr, HOST_VENDOR, kVstMaxVendorStrLen) };
+            1
+        }
+        audioMasterGetProductString => {
+            unsafe { copy_string(ptr, HOST_PRODUCT, kVstMaxProductStrLen) };
+            1
+        }
+        audioMasterGetVendorVersion => HOST_VERSION,
+        audioMasterCanDo => {
+            if ptr.is_null() {
+                return 0;
+            }
+            let can_do = unsafe { CStr::from_ptr(ptr as *const c_char) }.to_string_lossy();
+            if HOST_CAN_DO.contains(&can_do.as_ref()) {
+                1
+            } else {
+                0
+            }
+        }
+        // kVstLangEnglish
+        audioMasterGetLanguage => 1,
+        audioMasterUpdateDisplay | audioMasterBeginEdit | audioMasterEndEdit => 1,
+        _ => 0,
+    }
+}
@@ -0,0 +1,82 @@
+/*!
+ * ------------------------------------------------------------------------
+ * VST2 backend
+ * by Jurek Raben
+ *
+ * Hosts VST2 plugins through their raw 2.4 ABI, without the SDK.
+ *
+ * Licensed under CC Attribution-NonCommercial-ShareAlike 4.0 International
+ * Info here: https://creativecommons.org/licenses/by-nc-sa/4.0/
+ * ------------------------------------------------------------------------
+ */
+
+// Names as in the VST 2.4 SDK
+#![allow(non_upper_case_globals)]
+#![allow(non_snake_case)]
+
+pub mod api;
+pub mod commands;
+pub mod fxp;
+pub mod host;
+pub mod plugin;
+pub mod resolve;
+
+use std::path::{Path, PathBuf};
+
+use crate::{Format, ParameterInfo, PluginError, PluginFormat, PluginInfo};
+
+pub struct Vst2;
+
+impl PluginFormat for Vst2 {
+    fn format(&self) -> Format {
+        Format::Vst2
+    }
+
+    fn search_paths(&self) -> Vec<PathBuf> {
+        resolve::search_paths()
+    }
+
+    fn is_plugin(&self, path: &Path) -> bool {
+        resolve::is_bundle(path) || resolve::is_plugin_binary(path)
+    }
+
+    fn resolve_binary(&self, path: &Path) -> Result<PathBuf, PluginError> {
+        resolve::resolve_binary(path).map_err(PluginError::PathError)
+    }
+
+    fn inspect(&self, binary: &Path) -> Result<PluginInfo, PluginError> {
+        let instance = plugin::load(binary).map_err(PluginError::LoadError)?;
+        Ok(plugin_info(&instance))
+    }
+}
+
+// VST2 parameters have no IDs and no default values, the index and the
+// value after loading stand in for them
+pub fn plugin_info(instance: &plugin::PluginInstance) -> PluginInfo {
+    PluginInfo {
+        format: Format::Vst2,
+        name: instance.name(),
+        vendor: instance.vendor(),
+        version: instance.version().to_string(),
+        id: instance.unique_id().to_string(),
+        category: instance.category().to_string(),
+        count_inputs: instance.count_inputs(),
+        count_outputs: instance.count_outputs(),
+        count_presets: instance.count_presets(),
+        parameters: instance
+            .parameters()
+            .into_iter()
+            .map(|param| ParameterInfo {
+                index: param.index,
+                id: param.index as u32,
+                name: param.name,
+                units: param.label,
+                display: param.display,
+                step_count: 0,
+                value: param.value as f64,
+                default_value: param.value as f64,
+                automatable: param.automatable,
+            })
+            .collect(),
+    }
+}
@@ -0,0 +1,344 @@
+/*!
+ * ------------------------------------------------------------------------
+ * VST2 plugin loading and instance wrapper
+ * by Jurek Raben
+ *
+ * Licensed under CC Attribution-NonCommercial-ShareAlike 4.0 International
+ * Info here: https://creativecommons.org/licenses/by-nc-sa/4.0/
+ * ------------------------------------------------------------------------
+ */
+
+use std::ffi::{CString, c_void};
+use std::path::{Path, PathBuf};
+use std::ptr;
+
+use libloading::Library;
+
+use super::api::*;
+use super::host::{self, HOST_BLOCK_SIZE, HOST_SAMPLE_RATE};
+
+pub struct MidiProgram {
+    pub index: i32,
+    pub name: String,
+    pub program: i8,
+    pub bank_msb: i8,
+    pub bank_lsb: i8,
+}
+
+pub struct ParameterInfo {
+    pub index: i32,
+    pub name: String,
+    pub label: String,
+    pub display: String,
+    pub value: f32,
+    pub automatable: bool,
+}
+
+pub struct PluginInstance {
+    effect: *mut AEffect,
+    path: PathBuf,
+    // Keeps the code of the dispatcher mapped for the instance lifetime
+    _lib: Library,
+}
+
+impl Drop for PluginInstance {
+    fn drop(&mut self) {
+        self.dispatch(effMainsChanged, 0, 0, ptr::null_mut(), 0.0);
+        self.dispatch(effClose, 0, 0, ptr::null_mut(), 0.0);
+    }
+}
+
+pub fn load(path: &Path) -> Result<PluginInstance, String> {
+    let lib =
+        unsafe { Library::new(path) }.map_err(|e| format!("Failed to load library: {}", e))?;
+
+    let main: PluginMainProc = unsafe {
+        *lib.get::<PluginMainProc>(b"VSTPluginMain")
+            .or_else(|_| lib.get::<PluginMainProc>(b"main_macho"))
+            .or_else(|_| lib.get::<PluginMainProc>(b"main"))
+            .map_err(|_| "VSTPluginMain not found".to_string())?
+    };
+
+    let effect = unsafe { main(host::host_callback) };
+    if effect.is_null() {
+        return Err("Failed to create a plugin instance".to_string());
+    }
+    if unsafe { (*effect).magic } != VST_MAGIC {
+        return Err("Not a VST2 plugin".to_string());
+    }
+
+    let instance = PluginInstance {
+        effect,
+        path: path.to_path_buf(),
+        _lib: lib,
+    };
+
+    // Open the plugin the way a regular host would, some plugins only
+    // report their real parameter layout afterwards
+    instance.dispatch(effOpen, 0, 0, ptr::null_mut(), 0.0);
+    instance.dispatch(effSetSampleRate, 0, 0, ptr::null_mut(), HOST_SAMPLE_RATE);
+    instance.dispatch(effSetBlockSize, 0, HOST_BLOCK_SIZE, ptr::null_mut(), 0.0);
+    instance.dispatch(effMainsChanged, 0, 1, ptr::null_mut(), 0.0);
+
+    Ok(instance)
+}
+
+impl PluginInstance {
+    pub(crate) fn dispatch(
+        &self,
+        opcode: i32,
+        index: i32,
+        value: isize,
+        ptr: *mut c_void,
+        opt: f32,
+    ) -> isize {
+        unsafe { ((*self.effect).dispatcher)(self.effect, opcode, index, value, ptr, opt) }
+    }
+
+    fn read_string(&self, opcode: i32, index: i32) -> String {
+        let mut buffer = vec![0u8; kStringBufferLen];
+        self.dispatch(opcode, index, 0, buffer.as_mut_ptr() as *mut c_void, 0.0);
+        c_buffer_to_string(&buffer)
+    }
+
+    // The binary the instance was loaded from
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    fn effect(&self) -> &AEffect {
+        unsafe { &*self.effect }
+    }
+
+    pub fn name(&self) -> String {
+        self.read_string(effGetProductString, 0)
+    }
+
+    pub fn vendor(&self) -> String {
+        self.read_string(effGetVendorString, 0)
+    }
+
+    pub fn count_presets(&self) -> i32 {
+        self.effect().numPrograms
+    }
+
+    pub fn count_parameters(&self) -> i32 {
+        self.effect().numParams
+    }
+
+    pub fn count_inputs(&self) -> i32 {
+        self.effect().numInputs
+    }
+
+    pub fn count_outputs(&self) -> i32 {
+        self.effect().numOutputs
+    }
+
+    pub fn unique_id(&self) -> i32 {
+        self.effect().uniqueID
+    }
+
+    pub fn version(&self) -> i32 {
+        self.effect().version
+    }
+
+    pub fn initial_delay(&self) -> i32 {
+        self.effect().initialDelay
+    }
+
+    pub fn flags(&self) -> i32 {
+        self.effect().flags
+    }
+
+    pub fn has_flag(&self, flag: i32) -> bool {
+        self.flags() & flag != 0
+    }
+
+    pub fn category(&self) -> &'static str {
+        let category = self.dispatch(effGetPlugCategory, 0, 0, ptr::null_mut(), 0.0);
+        PLUG_CATEGORIES
+            .get(category as usize)
+            .copied()
+            .unwrap_or(PLUG_CATEGORIES[0])
+    }
+
+    // 1 = yes, -1 = no, 0 = don't know
+    pub fn can_do(&self, feature: &str) -> isize {
+        let feature = CString::new(feature).unwrap_or_default();
+        self.dispatch(effCanDo, 0, 0, feature.as_ptr() as *mut c_void, 0.0)
+    }
+
+    pub fn count_midi_inputs(&self) -> isize {
+        self.dispatch(effGetNumMidiInputChannels, 0, 0, ptr::null_mut(), 0.0)
+    }
+
+    pub fn count_midi_outputs(&self) -> isize {
+        self.dispatch(effGetNumMidiOutputChannels, 0, 0, ptr::null_mut(), 0.0)
+    }
+
+    // Programs published for a MIDI channel, empty if not supported
+    pub fn midi_programs(&self, channel: i32) -> Vec<MidiProgram> {
+        let mut programs = Vec::new();
+        let mut program_name: MidiProgramName = unsafe { std::mem::zeroed() };
+        let count = self.dispatch(
+            effGetMidiProgramName,
+            channel,
+            0,
+            &mut program_name as *mut MidiProgramName as *mut c_void,
+            0.0,
+        );
+
+        for index in 0..count as i32 {
+            let mut program_name: MidiProgramName = unsafe { std::mem::zeroed() };
+            program_name.thisProgramIndex = index;
+            self.dispatch(
+                effGetMidiProgramName,
+                channel,
+                0,
+                &mut program_name as *mut MidiProgramName as *mut c_void,
+                0.0,
+            );
+            programs.push(MidiProgram {
+                index,
+                name: c_buffer_to_string(&program_name.name),
+                program: program_name.midiProgram,
+                bank_msb: program_name.midiBankMsb,
+                bank_lsb: program_name.midiBankLsb,
+            });
+        }
+        programs
+    }
+
+    pub fn current_midi_program(&self, channel: i32) -> i32 {
+        let mut program_name: MidiProgramName = unsafe { std::mem::zeroed() };
+        self.dispatch(
+            effGetCurrentMidiProgram,
+            channel,
+            0,
+            &mut program_name as *mut MidiProgramName as *mut c_void,
+            0.0,
+        ) as i32
+    }
+
+    pub fn midi_key_name(&self, channel: i32, program: i32, key: i32) -> Option<String> {
+        let mut key_name: MidiKeyName = unsafe { std::mem::zeroed() };
+        key_name.thisProgramIndex = program;
+        key_name.thisKeyNumber = key;
+        let supported = self.dispatch(
+            effGetMidiKeyName,
+            channel,
+            0,
+            &mut key_name as *mut MidiKeyName as *mut c_void,
+            0.0,
+        );
+        let name = c_buffer_to_string(&key_name.keyName);
+        if supported == 0 || name.is_empty() {
+            return None;
+        }
+        Some(name)
+    }
+
+    pub fn get_parameter(&self, index: i32) -> f32 {
+        unsafe { ((*self.effect).getParameter)(self.effect, index) }
+    }
+
+    pub fn set_parameter(&self, index: i32, value: f32) {
+        unsafe { ((*self.effect).setParameter)(self.effect, index, value) }
+    }
+
+    pub fn parameter_info(&self, index: i32) -> ParameterInfo {
+        ParameterInfo {
+            index,
+            name: self.read_string(effGetParamName, index),
+            label: self.read_string(effGetParamLabel, index),
+            display: self.read_string(effGetParamDisplay, index),
+            value: self.get_parameter(index),
+            automatable: self.dispatch(effCanBeAutomated, index, 0, ptr::null_mut(), 0.0) != 0,
+        }
+    }
+
+    pub fn parameters(&self) -> Vec<ParameterInfo> {
+        (0..self.count_parameters())
+            .map(|index| self.parameter_info(index))
+            .collect()
+    }
+
+    pub fn change_preset(&self, preset: i32) {
+        self.dispatch(effBeginSetProgram, 0, 0, ptr::null_mut(), 0.0);
+        self.dispatch(effSetProgram, 0, preset as isize, ptr::null_mut(), 0.0);
+        self.dispatch(effEndSetProgram, 0, 0, ptr::null_mut(), 0.0);
+    }
+
+    pub fn current_preset(&self) -> i32 {
+        self.dispatch(effGetProgram, 0, 0, ptr::null_mut(), 0.0) as i32
+    }
+
+    pub fn current_preset_name(&self) -> String {
+        self.read_string(effGetProgramName, 0)
+    }
+
+    pub fn get_preset_name(&self, preset: i32) -> String {
+        self.read_string(effGetProgramNameIndexed, preset)
+    }
+
+    pub fn get_preset_data(&self) -> Vec<u8> {
+        self.get_chunk(true)
+    }
+
+    pub fn get_bank_data(&self) -> Vec<u8> {
+        self.get_chunk(false)
+    }
+
+    fn get_chunk(&self, is_preset: bool) -> Vec<u8> {
+        let mut data: *mut u8 = ptr::null_mut();
+        let len = self.dispatch(
+            effGetChunk,
+            is_preset as i32,
+            0,
+            &mut data as *mut *mut u8 as *mut c_void,
+            0.0,
+        );
+        if data.is_null() || len <= 0 {
+            return Vec::new();
+        }
+        unsafe { std::slice::from_raw_parts(data, len as usize) }.to_vec()
+    }
+
+    pub fn set_chunk(&self, data: &[u8], is_preset: bool) -> bool {
+        if is_preset {
+            self.dispatch(effBeginSetProgram, 0, 0, ptr::null_mut(), 0.0);
+        }
+        let result = self.dispatch(
+            effSetChunk,
+            is_preset as i32,
+            data.len() as isize,
+            data.as_ptr() as *mut c_void,
+            0.0,
+        );
+        if is_preset {
+            self.dispatch(effEndSetProgram, 0, 0, ptr::null_mut(), 0.0);
+        }
+        result != 0
+    }
+
+    // Next sub plugin of a shell plugin as (id, name), None when done
+    pub fn shell_next_plugin(&self) -> Option<(i32, String)> {
+        let mut buffer = vec![0u8; kStringBufferLen];
+        let id = self.dispatch(
+            effShellGetNextPlugin,
+            0,
+            0,
+            buffer.as_mut_ptr() as *mut c_void,
+            0.0,
+        );
+        if id == 0 {
+            return None;
+        }
+        Some((id as i32, c_buffer_to_string(&buffer)))
+    }
+}
+
+fn c_buffer_to_string(buffer: &[u8]) -> String {
+    let end = buffer.iter().position(|&c| c == 0).unwrap_or(buffer.len());
+    String::from_utf8_lossy(&buffer[..end]).to_string()
+}
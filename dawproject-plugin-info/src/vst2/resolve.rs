@@ -0,0 +1,205 @@
+/*!
+ * ------------------------------------------------------------------------
+ * VST2 plugin file resolution
+ * by Jurek Raben
+ *
+ * Turns a plugin argument into the binary that has to be loaded. The
+ * argument can be a binary, a .vst bundle, a unique ID or a plugin name,
+ * the latter two are searched in the standard VST2 folders. Unique IDs
+ * are looked up in the plugin info cache first, only the other plugins are
+ * loaded to read their IDs.
+ *
+ * Licensed under CC Attribution-NonCommercial-ShareAlike 4.0 International
+ * Info here: https://creativecommons.org/licenses/by-nc-sa/4.0/
+ * ------------------------------------------------------------------------
+ */
+
+use std::env;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use serde_json::Value;
+
+use super::Vst2;
+use crate::cache::PluginCache;
+use crate::child;
+use crate::search;
+
+const PROBE_TIMEOUT: Duration = Duration::from_secs(30);
+
+pub fn resolve_plugin(spec: &str, cache: Option<&mut PluginCache>) -> Result<PathBuf, String> {
+    let path = PathBuf::from(spec);
+    if path.exists() {
+        return resolve_binary(&path);
+    }
+
+    let candidates = search::find_plugins(&Vst2, &search_paths());
+
+    if let Some(candidate) = candidates.iter().find(|candidate| {
+        plugin_name(candidate).is_some_and(|name| name.eq_ignore_ascii_case(spec))
+    }) {
+        return resolve_binary(candidate);
+    }
+
+    if let Some(unique_id) = parse_unique_id(spec)
+        && let Some(binary) = find_unique_id(&candidates, unique_id, cache)
+    {
+        return Ok(binary);
+    }
+
+    Err(spec.to_string() + " does not exist and was not found in the VST2 search paths")
+}
+
+// The binary inside a .vst bundle, or the given path if it is a file
+pub fn resolve_binary(path: &Path) -> Result<PathBuf, String> {
+    if path.is_file() {
+        return Ok(path.to_path_buf());
+    }
+
+    if is_bundle(path) {
+        let macos_path = path.join("Contents").join("MacOS");
+        let executable = search::bundle_executable(path).or_else(|| {
+            path.file_stem()
+                .map(|stem| stem.to_string_lossy().to_string())
+        });
+        if let Some(executable) = executable {
+            let binary = macos_path.join(executable);
+            if binary.is_file() {
+                return Ok(binary);
+            }
+        }
+    }
+
+    Err(path.display().to_string() + " does not exist")
+}
+
+pub fn is_bundle(path: &Path) -> bool {
+    path.is_dir()
+        && path
+            .extension()
+            .is_some_and(|extension| extension.eq_ignore_ascii_case("vst"))
+}
+
+fn plugin_name(path: &Path) -> Option<String> {
+    path.file_stem()
+        .map(|stem| stem.to_string_lossy().to_string())
+}
+
+// Accepts a decimal ID or a four character code like "Stub"
+pub fn parse_unique_id(spec: &str) -> Option<i32> {
+    if let Ok(unique_id) = spec.parse::<i32>() {
+        return Some(unique_id);
+    }
+    let bytes: [u8; 4] = spec.as_bytes().try_into().ok()?;
+    Some(i32::from_be_bytes(bytes))
+}
+
+// The cached infos are checked first, the other plugins are loaded one by
+// one and their infos added to the cache
+fn find_unique_id(
+    candidates: &[PathBuf],
+    unique_id: i32,
+    mut cache: Option<&mut PluginCache>,
+) -> Option<PathBuf> {
+    let mut uncached = Vec::new();
+    for binary in candidates
+        .iter()
+        .filter_map(|candidate| resolve_binary(candidate).ok())
+    {
+        match cache.as_deref().and_then(|cache| cache.get(&binary)) {
+            Some(info) if info_unique_id(&info) == Some(unique_id) => return Some(binary),
+            Some(_) => {}
+            None => uncached.push(binary),
+        }
+    }
+
+    let mut found = None;
+    for binary in uncached {
+        let Some(info) = probe_info(&binary) else {
+            continue;
+        };
+        let matches = info_unique_id(&info) == Some(unique_id);
+        if let Some(cache) = cache.as_deref_mut() {
+            cache.insert(&binary, info);
+        }
+        if matches {
+            found = Some(binary);
+            break;
+        }
+    }
+    if let Some(cache) = cache {
+        // the search result is still fine, even if the cache can't be written
+        let _ = cache.save();
+    }
+    found
+}
+
+fn info_unique_id(info: &Value) -> Option<i32> {
+    info["id"].as_i64().map(|id| id as i32)
+}
+
+// Reads the info in a child process, so a crashing plugin does not take
+// the search down with it
+fn probe_info(binary: &Path) -> Option<Value> {
+    child::run_child(&["info".as_ref(), binary.as_os_str()], PROBE_TIMEOUT).ok()
+}
+
+pub fn search_paths() -> Vec<PathBuf> {
+    search::env_search_paths("VST_PATH", default_search_paths())
+}
+
+#[cfg(target_os = "macos")]
+fn default_search_paths() -> Vec<PathBuf> {
+    let mut paths = Vec::new();
+    if let Some(home) = env::var_os("HOME").map(PathBuf::from) {
+        paths.push(home.join("Library/Audio/Plug-Ins/VST"));
+    }
+    paths.push(PathBuf::from("/Library/Audio/Plug-Ins/VST"));
+    paths
+}
+
+#[cfg(target_os = "linux")]
+fn default_search_paths() -> Vec<PathBuf> {
+    let mut paths = Vec::new();
+    if let Some(home) = env::var_os("HOME").map(PathBuf::from) {
+        paths.push(home.join(".vst"));
+        paths.push(home.join(".lxvst"));
+    }
+    for path in [
+        "/usr/local/lib/vst",
+        "/usr/lib/vst",
+        "/usr/local/lib/lxvst",
+        "/usr/lib/lxvst",
+    ] {
+        paths.push(PathBuf::from(path));
+    }
+    paths
+}
+
+#[cfg(target_os = "windows")]
+fn default_search_paths() -> Vec<PathBuf> {
+    let mut paths = Vec::new();
+    for variable in ["ProgramFiles", "CommonProgramFiles"] {
+        if let Some(base) = env::var_os(variable).map(PathBuf::from) {
+            paths.push(base.join("VSTPlugins"));
+            paths.push(base.join("Steinberg").join("VSTPlugins"));
+            paths.push(base.join("VST2"));
+            paths.push(base.join("Steinberg").join("VST2"));
+        }
+    }
+    paths
+}
+
+#[cfg(not(any(target_os = "macos", target_os = "linux", target_os = "windows")))]
+fn default_search_paths() -> Vec<PathBuf> {
+    Vec::new()
+}
+
+pub fn is_plugin_binary(path: &Path) -> bool {
+    let expected = if cfg!(target_os = "windows") {
+        "dll"
+    } else {
+        "so"
+    };
+    path.is_file() && search::has_extension(path, expected)
+}
@@ -0,0 +1,46 @@
+/*!
+ * ------------------------------------------------------------------------
+ * VST3 host context
+ * by Jurek Raben
+ *
+ * The IHostApplication handed to the component and controller on
+ * initialize. It only reports a name, plugins get no host objects.
+ *
+ * Licensed under CC Attribution-NonCommercial-ShareAlike 4.0 International
+ * Info here: https://creativecommons.org/licenses/by-nc-sa/4.0/
+ * ------------------------------------------------------------------------
+ */
+
+use std::ffi::c_void;
+
+use vst3::Class;
+use vst3::Steinberg::Vst::{IHostApplication, IHostApplicationTrait, String128};
+use vst3::Steinberg::{TUID, kNoInterface, kResultOk, tresult};
+
+const HOST_NAME: &str = "DawProject Plugin Info";
+
+pub struct HostApplication;
+
+impl Class for HostApplication {
+    type Interfaces = (IHostApplication,);
+}
+
+impl IHostApplicationTrait for HostApplication {
+    unsafe fn getName(&self, name: *mut String128) -> tresult {
+        let host_name: Vec<u16> = HOST_NAME.encode_utf16().chain(std::iter::once(0)).collect();
+        let name = unsafe { &mut *name };
+        for (target, source) in name.iter_mut().zip(host_name) {
+            *target = source;
+        }
+        kResultOk
+    }
+
+    unsafe fn createInstance(
+        &self,
+        _cid: *mut TUID,
+        _iid: *mut TUID,
+        _obj: *mut *mut c_void,
+    ) -> tresult {
+        kNoInterface
+    }
+}
@@ -0,0 +1,376 @@
+/*!
+ * ------------------------------------------------------------------------
+ * VST3 plugin inspection
+ * by Jurek Raben
+ *
+ * Creates the Audio Module class of a plugin with its edit controller,
+ * reads the buses and parameters, and takes everything down again.
+ *
+ * Licensed under CC Attribution-NonCommercial-ShareAlike 4.0 International
+ * Info here: https://creativecommons.org/licenses/by-nc-sa/4.0/
+ * ------------------------------------------------------------------------
+ */
+
+use std::ffi::{c_char, c_void};
+use std::path::Path;
+use std::ptr;
+
+use libloading::Library;
+use vst3::Steinberg::Vst::BusDirections_::{kInput, kOutput};
+use vst3::Steinberg::Vst::MediaTypes_::{kAudio, kEvent};
+use vst3::Steinberg::Vst::ParameterInfo_::ParameterFlags_::kCanAutomate;
+use vst3::Steinberg::Vst::{
+    IComponent, IComponentTrait, IConnectionPoint, IConnectionPointTrait, IEditController,
+    IEditControllerTrait, String128,
+};
+use vst3::Steinberg::{
+    FUnknown, IPluginBaseTrait, IPluginFactory, IPluginFactory2, IPluginFactory2Trait,
+    IPluginFactoryTrait, TUID, kResultOk,
+};
+use vst3::{ComPtr, ComWrapper, Interface};
+
+use super::host::HostApplication;
+use crate::{Format, ParameterInfo, PluginError, PluginInfo};
+
+const AUDIO_MODULE_CLASS: &str = "Audio Module Class";
+const DEFAULT_VERSION: &str = "1.0.0";
+
+type GetFactoryProc = unsafe extern "system" fn() -> *mut IPluginFactory;
+
+// The Audio Module class as listed by the factory
+pub(super) struct AudioClass {
+    pub cid: TUID,
+    pub name: String,
+    pub version: String,
+    pub category: String,
+}
+
+// The factory of a plugin library. Declared before the library, so the
+// factory is released before the library is unloaded.
+pub(super) struct PluginModule {
+    pub factory: ComPtr<IPluginFactory>,
+    _library: Library,
+}
+
+pub(super) fn load_module(binary: &Path) -> Result<PluginModule, PluginError> {
+    let library = unsafe { Library::new(binary) }
+        .map_err(|e| PluginError::LoadError(format!("Failed to load library: {}", e)))?;
+
+    let get_factory = unsafe { library.get::<GetFactoryProc>(b"GetPluginFactory") }
+        .map_err(|e| PluginError::LoadError(format!("GetPluginFactory not found: {}", e)))?;
+
+    let factory = unsafe { ComPtr::from_raw(get_factory()) }
+        .ok_or_else(|| PluginError::FactoryError("GetPluginFactory returned NULL".to_string()))?;
+
+    Ok(PluginModule {
+        factory,
+        _library: library,
+    })
+}
+
+pub fn inspect_plugin(binary: &Path) -> Result<PluginInfo, PluginError> {
+    let module = load_module(binary)?;
+    read_plugin_info(&module.factory)
+}
+
+fn read_plugin_info(factory: &ComPtr<IPluginFactory>) -> Result<PluginInfo, PluginError> {
+    let vendor = factory_vendor(factory)?;
+    let audio_class = find_audio_class(factory)?;
+    with_plugin(factory, &audio_class.cid, |component, controller| {
+        plugin_info(&audio_class, &vendor, component, controller)
+    })
+}
+
+pub(super) fn factory_vendor(factory: &ComPtr<IPluginFactory>) -> Result<String, PluginError> {
+    let mut factory_info = unsafe { std::mem::zeroed() };
+    let result = unsafe { factory.getFactoryInfo(&mut factory_info) };
+    if result != kResultOk {
+        return Err(PluginError::FactoryError(format!(
+            "Failed to get factory info: {}",
+            result
+        )));
+    }
+    Ok(c_str_to_string(&factory_info.vendor))
+}
+
+// The buses are counted while the component is active
+pub(super) fn plugin_info(
+    audio_class: &AudioClass,
+    vendor: &str,
+    component: &ComPtr<IComponent>,
+    controller: &ComPtr<IEditController>,
+) -> PluginInfo {
+    unsafe { component.setActive(1) };
+    let info = PluginInfo {
+        format: Format::Vst3,
+        name: audio_class.name.clone(),
+        vendor: vendor.to_string(),
+        version: audio_class.version.clone(),
+        id: class_id_string(&audio_class.cid),
+        category: audio_class.category.clone(),
+        count_inputs: bus_count(component, kInput as i32),
+        count_outputs: bus_count(component, kOutput as i32),
+        count_presets: 0,
+        parameters: read_parameters(controller),
+    };
+    unsafe { component.setActive(0) };
+    info
+}
+
+// An initialized component with its controller, connected for the
+// callbacks. Dropping it takes everything down again.
+pub(super) struct OpenPlugin {
+    pub component: ComPtr<IComponent>,
+    pub controller: ComPtr<IEditController>,
+    connection: Option<(ComPtr<IConnectionPoint>, ComPtr<IConnectionPoint>)>,
+    separate: bool,
+    // Kept alive until the plugin is terminated
+    _host: ComWrapper<HostApplication>,
+}
+
+impl OpenPlugin {
+    pub(super) fn open(
+        factory: &ComPtr<IPluginFactory>,
+        cid: &TUID,
+    ) -> Result<OpenPlugin, PluginError> {
+        let component = create_instance::<IComponent>(factory, cid)
+            .ok_or_else(|| PluginError::ComponentError("Failed to create component".to_string()))?;
+
+        let host = ComWrapper::new(HostApplication);
+        let host_ptr = host
+            .as_com_ref::<FUnknown>()
+            .map_or(ptr::null_mut(), |host| host.as_ptr());
+
+        let result = unsafe { component.initialize(host_ptr) };
+        if result != kResultOk {
+            return Err(PluginError::InitError(format!(
+                "Failed to initialize component: {:#x}",
+                result
+            )));
+        }
+
+        let Some((controller, separate)) = open_controller(factory, &component, host_ptr) else {
+            unsafe { component.terminate() };
+            return Err(PluginError::ComponentError(
+                "No controller available".to_string(),
+            ));
+        };
+        let connection = if separate {
+            connect(&component, &controller)
+        } else {
+            None
+        };
+
+        Ok(OpenPlugin {
+            component,
+            controller,
+            connection,
+            separate,
+            _host: host,
+        })
+    }
+}
+
+impl Drop for OpenPlugin {
+    fn drop(&mut self) {
+        if let Some((component_point, controller_point)) = &self.connection {
+            unsafe {
+                component_point.disconnect(controller_point.as_ptr());
+                controller_point.disconnect(component_point.as_ptr());
+            }
+        }
+        if self.separate {
+            unsafe { self.controller.terminate() };
+        }
+        unsafe { self.component.terminate() };
+    }
+}
+
+// Creates and initializes the component with its controller, connects
+// them for the callback and takes everything down again afterwards
+pub(super) fn with_plugin<R>(
+    factory: &ComPtr<IPluginFactory>,
+    cid: &TUID,
+    f: impl FnOnce(&ComPtr<IComponent>, &ComPtr<IEditController>) -> R,
+) -> Result<R, PluginError> {
+    let plugin = OpenPlugin::open(factory, cid)?;
+    Ok(f(&plugin.component, &plugin.controller))
+}
+
+// Version and sub categories are only known to IPluginFactory2
+pub(super) fn find_audio_class(
+    factory: &ComPtr<IPluginFactory>,
+) -> Result<AudioClass, PluginError> {
+    let factory2 = factory.cast::<IPluginFactory2>();
+
+    for index in 0..unsafe { factory.countClasses() } {
+        let mut class_info = unsafe { std::mem::zeroed() };
+        if unsafe { factory.getClassInfo(index, &mut class_info) } != kResultOk
+            || c_str_to_string(&class_info.category) != AUDIO_MODULE_CLASS
+        {
+            continue;
+        }
+
+        let mut audio_class = AudioClass {
+            cid: class_info.cid,
+            name: c_str_to_string(&class_info.name),
+            version: DEFAULT_VERSION.to_string(),
+            category: String::new(),
+        };
+        if let Some(factory2) = &factory2 {
+            let mut class_info2 = unsafe { std::mem::zeroed() };
+            if unsafe { factory2.getClassInfo2(index, &mut class_info2) } == kResultOk {
+                let version = c_str_to_string(&class_info2.version);
+                if !version.is_empty() {
+                    audio_class.version = version;
+                }
+                audio_class.category = c_str_to_string(&class_info2.subCategories);
+            }
+        }
+        return Ok(audio_class);
+    }
+
+    Err(PluginError::ComponentError(
+        "No Audio Module class found".to_string(),
+    ))
+}
+
+fn create_instance<I: Interface>(
+    factory: &ComPtr<IPluginFactory>,
+    cid: &TUID,
+) -> Option<ComPtr<I>> {
+    let mut instance: *mut I = ptr::null_mut();
+    let result = unsafe {
+        factory.createInstance(
+            cid.as_ptr(),
+            I::IID.as_ptr() as *const c_char,
+            &mut instance as *mut *mut I as *mut *mut c_void,
+        )
+    };
+    if result != kResultOk {
+        return None;
+    }
+    unsafe { ComPtr::from_raw(instance) }
+}
+
+// The component itself for single component plugins, otherwise the
+// separate controller class, initialized. The flag tells them apart.
+fn open_controller(
+    factory: &ComPtr<IPluginFactory>,
+    component: &ComPtr<IComponent>,
+    host_ptr: *mut FUnknown,
+) -> Option<(ComPtr<IEditController>, bool)> {
+    if let Some(controller) = component.cast::<IEditController>() {
+        return Some((controller, false));
+    }
+
+    let mut controller_cid: TUID = [0; 16];
+    if unsafe { component.getControllerClassId(&mut controller_cid) } != kResultOk {
+        return None;
+    }
+    let controller = create_instance::<IEditController>(factory, &controller_cid)?;
+    if unsafe { controller.initialize(host_ptr) } != kResultOk {
+        return None;
+    }
+    Some((controller, true))
+}
+
+fn connect(
+    component: &ComPtr<IComponent>,
+    controller: &ComPtr<IEditController>,
+) -> Option<(ComPtr<IConnectionPoint>, ComPtr<IConnectionPoint>)> {
+    let component_point = component.cast::<IConnectionPoint>()?;
+    let controller_point = controller.cast::<IConnectionPoint>()?;
+    unsafe {
+        component_point.connect(controller_point.as_ptr());
+        controller_point.connect(component_point.as_ptr());
+    }
+    Some((component_point, controller_point))
+}
+
+// Audio and event buses together
+fn bus_count(component: &ComPtr<IComponent>, direction: i32) -> i32 {
+    unsafe {
+        component.getBusCount(kAudio as i32, direction)
+            + component.getBusCount(kEvent as i32, direction)
+    }
+}
+
+pub(super) fn read_parameters(controller: &ComPtr<IEditController>) -> Vec<ParameterInfo> {
+    let mut parameters = Vec::new();
+
+    for index in 0..unsafe { controller.getParameterCount() } {
+        let mut param_info = unsafe { std::mem::zeroed() };
+        if unsafe { controller.getParameterInfo(index, &mut param_info) } != kResultOk {
+            continue;
+        }
+
+        let value = unsafe { controller.getParamNormalized(param_info.id) };
+        let mut display: String128 = [0; 128];
+        let display =
+            if unsafe { controller.getParamStringByValue(param_info.id, value, &mut display) }
+                == kResultOk
+            {
+                utf16_to_string(&display)
+            } else {
+                String::new()
+            };
+
+        parameters.push(ParameterInfo {
+            index,
+            id: param_info.id,
+            name: utf16_to_string(&param_info.title),
+            units: utf16_to_string(&param_info.units),
+            display,
+            step_count: param_info.stepCount,
+            value,
+            default_value: param_info.defaultNormalizedValue,
+            automatable: param_info.flags & kCanAutomate != 0,
+        });
+    }
+
+    parameters
+}
+
+// As written by FUID::toString. Windows plugins store their IDs in the
+// COM GUID layout, with the first three fields in little endian.
+pub(super) fn class_id_string(cid: &TUID) -> String {
+    let mut bytes = cid.map(|byte| byte as u8);
+    if cfg!(target_os = "windows") {
+        bytes[0..4].reverse();
+        bytes[4..6].reverse();
+        bytes[6..8].reverse();
+    }
+    bytes.iter().map(|byte| format!("{:02X}", byte)).collect()
+}
+
+// The other way around, None if the ID is not 32 hex digits
+pub(super) fn class_id_tuid(class_id: &str) -> Option<TUID> {
+    if class_id.len() != 32 || !class_id.is_ascii() {
+        return None;
+    }
+    let mut bytes = [0u8; 16];
+    for (index, byte) in bytes.iter_mut().enumerate() {
+        *byte = u8::from_str_radix(&class_id[index * 2..index * 2 + 2], 16).ok()?;
+    }
+    if cfg!(target_os = "windows") {
+        bytes[0..4].reverse();
+        bytes[4..6].reverse();
+        bytes[6..8].reverse();
+    }
+    Some(bytes.map(|byte| byte as c_char))
+}
+
+fn c_str_to_string(chars: &[c_char]) -> String {
+    let bytes: Vec<u8> = chars
+        .iter()
+        .take_while(|&&c| c != 0)
+        .map(|&c| c as u8)
+        .collect();
+    String::from_utf8_lossy(&bytes).to_string()
+}
+
+fn utf16_to_string(chars: &[u16]) -> String {
+    let chars: Vec<u16> = chars.iter().copied().take_while(|&c| c != 0).collect();
+    String::from_utf16_lossy(&chars)
+}
@@ -0,0 +1,118 @@
+/*!
+ * ------------------------------------------------------------------------
+ * Loaded VST3 plugin
+ * by Jurek Raben
+ *
+ * Keeps the module, the component and the controller of a plugin loaded,
+ * e.g. for the workers of the serve mode, so states can be loaded and read
+ * back one request after another.
+ *
+ * Licensed under CC Attribution-NonCommercial-ShareAlike 4.0 International
+ * Info here: https://creativecommons.org/licenses/by-nc-sa/4.0/
+ * ------------------------------------------------------------------------
+ */
+
+use std::path::Path;
+
+use vst3::ComWrapper;
+use vst3::Steinberg::IBStream;
+use vst3::Steinberg::Vst::{IComponentTrait, IEditControllerTrait};
+use vst3::Steinberg::kResultOk;
+
+use super::inspect::{
+    AudioClass, OpenPlugin, PluginModule, class_id_string, factory_vendor, find_audio_class,
+    load_module, plugin_info, read_parameters,
+};
+use super::preset;
+use super::state::{load_component_state, split_state};
+use super::stream::MemoryStream;
+use crate::{ParameterInfo, PluginError, PluginInfo};
+
+// Declared before the module, so the plugin is taken down before the
+// library is unloaded
+pub struct PluginInstance {
+    plugin: OpenPlugin,
+    audio_class: AudioClass,
+    vendor: String,
+    _module: PluginModule,
+}
+
+impl PluginInstance {
+    pub fn load(binary: &Path) -> Result<PluginInstance, PluginError> {
+        let module = load_module(binary)?;
+        let vendor = factory_vendor(&module.factory)?;
+        let audio_class = find_audio_class(&module.factory)?;
+        let plugin = OpenPlugin::open(&module.factory, &audio_class.cid)?;
+        Ok(PluginInstance {
+            plugin,
+            audio_class,
+            vendor,
+            _module: module,
+        })
+    }
+
+    pub fn name(&self) -> &str {
+        &self.audio_class.name
+    }
+
+    pub fn class_id(&self) -> String {
+        class_id_string(&self.audio_class.cid)
+    }
+
+    pub fn info(&self) -> PluginInfo {
+        plugin_info(
+            &self.audio_class,
+            &self.vendor,
+            &self.plugin.component,
+            &self.plugin.controller,
+        )
+    }
+
+    pub fn parameters(&self) -> Vec<ParameterInfo> {
+        read_parameters(&self.plugin.controller)
+    }
+
+    // Loads a .vstpreset, .fxp/.fxb data or a plain component state, see
+    // state::load_states. Returns the kind of the data and whether the
+    // component took it.
+    pub fn load_state(&self, state: &[u8]) -> (&'static str, bool) {
+        let (kind, component_state, controller_state) = split_state(state);
+        let loaded = load_component_state(
+            &self.plugin.component,
+            &self.plugin.controller,
+            &component_state,
+            controller_state.as_deref(),
+        )
+        .is_some();
+        (kind, loaded)
+    }
+
+    // The current state as .vstpreset, with the controller state if the
+    // controller has one. None if the component fails to write its state.
+    pub fn preset(&self) -> Option<Vec<u8>> {
+        let component_state = ComWrapper::new(MemoryStream::default());
+        let result = unsafe {
+            self.plugin
+                .component
+                .getState(component_state.as_com_ref::<IBStream>()?.as_ptr())
+        };
+        if result != kResultOk {
+            return None;
+        }
+        let component_state = component_state.data();
+
+        let controller_state = ComWrapper::new(MemoryStream::default());
+        let result = unsafe {
+            self.plugin
+                .controller
+                .getState(controller_state.as_com_ref::<IBStream>()?.as_ptr())
+        };
+        let controller_state = controller_state.data();
+
+        let mut chunks = vec![("Comp", component_state.as_slice())];
+        if result == kResultOk && !controller_state.is_empty() {
+            chunks.push(("Cont", controller_state.as_slice()));
+        }
+        Some(preset::write_preset(&self.class_id(), &chunks))
+    }
+}
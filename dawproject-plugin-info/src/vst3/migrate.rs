@@ -0,0 +1,183 @@
+/*!
+ * ------------------------------------------------------------------------
+ * VST2 to VST3 state migration
+ * by Jurek Raben
+ *
+ * VST3 hosts replacing a VST2 plugin by its VST3 version hand the VST2
+ * state to setState of the component, wrapped into a "VstW" header (size,
+ * version and bypass, big endian) followed by the .fxp or .fxb data. The
+ * plugin is loaded with such a state, its own state is read back and the
+ * VST2 parameter indices are mapped to VST3 parameter IDs.
+ *
+ * Licensed under CC Attribution-NonCommercial-ShareAlike 4.0 International
+ * Info here: https://creativecommons.org/licenses/by-nc-sa/4.0/
+ * ------------------------------------------------------------------------
+ */
+
+use std::path::Path;
+
+use serde::Serialize;
+use serde_json::Value;
+use vst3::Steinberg::Vst::{IEditController, IRemapParamID, IRemapParamIDTrait};
+use vst3::Steinberg::{
+    IBStream, IPluginCompatibility, IPluginCompatibilityTrait, IPluginFactory, TUID, kResultOk,
+    kResultTrue,
+};
+use vst3::{ComPtr, ComWrapper};
+
+use super::inspect::{
+    class_id_string, class_id_tuid, find_audio_class, load_module, read_parameters, with_plugin,
+};
+use super::state::load_component_state;
+use super::stdio;
+use super::stream::MemoryStream;
+use crate::{ParameterInfo, PluginError};
+
+pub const VSTW_MAGIC: &[u8; 4] = b"VstW";
+const VSTW_HEADER_SIZE: i32 = 8;
+const VSTW_VERSION: i32 = 1;
+
+const VST2_NAME_LEN: usize = 9;
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ParameterMapping {
+    pub vst2_index: i32,
+    pub vst3_id: u32,
+    pub title: String,
+    // Normalized, after loading the migrated state
+    pub value: f64,
+    // Mapped by the plugin itself via IRemapParamID
+    pub remapped: bool,
+}
+
+#[derive(Debug, Clone)]
+pub struct Migration {
+    pub name: String,
+    pub class_id: String,
+    // Class IDs the plugin declares to replace via IPluginCompatibility
+    pub replaces: Vec<String>,
+    // The component state after loading, None if the plugin refused it
+    pub state: Option<Vec<u8>>,
+    pub mapping: Vec<ParameterMapping>,
+}
+
+// convertVST2UID_To_FUID of the VST3 SDK: "VST", the unique ID and the
+// first nine characters of the lower case plugin name
+pub fn vst2_class_id(unique_id: i32, name: &str) -> String {
+    let mut bytes = b"VST".to_vec();
+    bytes.extend(unique_id.to_be_bytes());
+    let name = name.to_lowercase();
+    bytes.extend(name.bytes().chain(std::iter::repeat(0)).take(VST2_NAME_LEN));
+    bytes.iter().map(|byte| format!("{:02X}", byte)).collect()
+}
+
+// The .fxp or .fxb data behind the "VstW" header, not bypassed
+pub fn wrap_vst2_state(fx_data: &[u8]) -> Vec<u8> {
+    let mut state = VSTW_MAGIC.to_vec();
+    state.extend(VSTW_HEADER_SIZE.to_be_bytes());
+    state.extend(VSTW_VERSION.to_be_bytes());
+    state.extend(0i32.to_be_bytes());
+    state.extend(fx_data);
+    state
+}
+
+// Loads the wrapped state into the plugin. The class ID of the VST2
+// plugin is needed for the parameter remapping of the plugin, without it
+// the VST2 indices are taken as VST3 parameter indices.
+pub fn migrate_state(
+    binary: &Path,
+    wrapped_state: &[u8],
+    vst2_class_id: Option<&str>,
+    count_vst2_parameters: Option<i32>,
+) -> Result<Migration, PluginError> {
+    stdio::suppress_output(|| {
+        let module = load_module(binary)?;
+        let audio_class = find_audio_class(&module.factory)?;
+        let class_id = class_id_string(&audio_class.cid);
+        let replaces = replaced_class_ids(&module.factory, &class_id);
+        let vst2_cid = vst2_class_id.and_then(class_id_tuid);
+
+        with_plugin(
+            &module.factory,
+            &audio_class.cid,
+            |component, controller| {
+                let state = load_component_state(component, controller, wrapped_state, None);
+                let parameters = read_parameters(controller);
+                let count = count_vst2_parameters.unwrap_or(parameters.len() as i32);
+                Migration {
+                    name: audio_class.name,
+                    class_id,
+                    replaces,
+                    state,
+                    mapping: map_parameters(controller, &parameters, vst2_cid.as_ref(), count),
+                }
+            },
+        )
+    })
+}
+
+// VST2 automation addresses the parameters by index. Plugins may name the
+// VST3 ID for an index via IRemapParamID, otherwise the index is kept, as
+// by the VST2 wrapper of the SDK.
+fn map_parameters(
+    controller: &ComPtr<IEditController>,
+    parameters: &[ParameterInfo],
+    vst2_cid: Option<&TUID>,
+    count: i32,
+) -> Vec<ParameterMapping> {
+    let remap = vst2_cid.zip(controller.cast::<IRemapParamID>());
+
+    (0..count)
+        .filter_map(|index| {
+            let remapped_id = remap.as_ref().and_then(|(cid, remap)| {
+                let mut id = 0;
+                let result = unsafe { remap.getCompatibleParamID(*cid, index as u32, &mut id) };
+                (result == kResultTrue).then_some(id)
+            });
+            let param = match remapped_id {
+                Some(id) => parameters.iter().find(|param| param.id == id),
+                None => parameters.get(index as usize),
+            }?;
+            Some(ParameterMapping {
+                vst2_index: index,
+                vst3_id: param.id,
+                title: param.name.clone(),
+                value: param.value,
+                remapped: remapped_id.is_some(),
+            })
+        })
+        .collect()
+}
+
+// The "Old" class IDs listed for the plugin in the compatibility JSON
+fn replaced_class_ids(factory: &ComPtr<IPluginFactory>, class_id: &str) -> Vec<String> {
+    let Some(compatibility) = factory.cast::<IPluginCompatibility>() else {
+        return Vec::new();
+    };
+    let stream = ComWrapper::new(MemoryStream::default());
+    let Some(stream_ptr) = stream
+        .as_com_ref::<IBStream>()
+        .map(|stream| stream.as_ptr())
+    else {
+        return Vec::new();
+    };
+    if unsafe { compatibility.getCompatibilityJSON(stream_ptr) } != kResultOk {
+        return Vec::new();
+    }
+    let Ok(Value::Array(entries)) = serde_json::from_slice(&stream.data()) else {
+        return Vec::new();
+    };
+
+    entries
+        .iter()
+        .filter(|entry| {
+            entry["New"]
+                .as_str()
+                .is_some_and(|new| new.eq_ignore_ascii_case(class_id))
+        })
+        .filter_map(|entry| entry["Old"].as_array())
+        .flatten()
+        .filter_map(|old| old.as_str().map(str::to_uppercase))
+        .collect()
+}
@@ -0,0 +1,143 @@
+/*!
+ * ------------------------------------------------------------------------
+ * VST3 backend
+ * by Jurek Raben
+ *
+ * Inspection heavily based on rust-vst3-host by Helge Sverre
+ * [https://github.com/HelgeSverre/rust-vst3-host](https://github.com/HelgeSverre/rust-vst3-host)
+ *
+ * Licensed under CC Attribution-NonCommercial-ShareAlike 4.0 International
+ * Info here: https://creativecommons.org/licenses/by-nc-sa/4.0/
+ * ------------------------------------------------------------------------
+ */
+
+pub mod host;
+pub mod inspect;
+pub mod instance;
+pub mod migrate;
+pub mod preset;
+pub mod presets;
+pub mod state;
+pub mod stdio;
+pub mod stream;
+
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::search;
+use crate::{Format, PluginError, PluginFormat, PluginInfo};
+
+pub struct Vst3;
+
+impl PluginFormat for Vst3 {
+    fn format(&self) -> Format {
+        Format::Vst3
+    }
+
+    fn search_paths(&self) -> Vec<PathBuf> {
+        search::env_search_paths("VST3_PATH", default_search_paths())
+    }
+
+    fn is_plugin(&self, path: &Path) -> bool {
+        path.exists() && search::has_extension(path, "vst3")
+    }
+
+    fn resolve_binary(&self, path: &Path) -> Result<PathBuf, PluginError> {
+        binary_path(path)
+    }
+
+    fn inspect(&self, binary: &Path) -> Result<PluginInfo, PluginError> {
+        stdio::suppress_output(|| inspect::inspect_plugin(binary))
+    }
+}
+
+// The binary inside a .vst3 bundle, or the given path if it is a file
+pub fn binary_path(path: &Path) -> Result<PathBuf, PluginError> {
+    if path.is_file() {
+        return Ok(path.to_path_buf());
+    }
+    if !search::has_extension(path, "vst3") {
+        return Err(PluginError::PathError("Invalid VST3 path".to_string()));
+    }
+
+    let contents_path = path.join("Contents");
+    if cfg!(target_os = "macos") {
+        find_binary(&contents_path.join("MacOS"), |file| {
+            let name = file.file_name().unwrap_or_default().to_string_lossy();
+            !name.starts_with('.') && !name.ends_with(".plist") && !name.ends_with(".txt")
+        })
+    } else if cfg!(target_os = "windows") {
+        find_binary(&contents_path.join(bundle_arch("win")), |file| {
+            search::has_extension(file, "vst3")
+        })
+    } else if cfg!(target_os = "linux") {
+        find_binary(&contents_path.join(bundle_arch("linux")), |file| {
+            search::has_extension(file, "so")
+        })
+    } else {
+        Err(PluginError::PathError("Unsupported platform".to_string()))
+    }
+}
+
+// Architecture folder of the bundle, e.g. x86_64-linux
+fn bundle_arch(os: &str) -> String {
+    let arch = if cfg!(target_arch = "x86_64") {
+        "x86_64"
+    } else if cfg!(target_arch = "aarch64") {
+        "aarch64"
+    } else {
+        "x86"
+    };
+    format!("{}-{}", arch, os)
+}
+
+fn find_binary(dir: &Path, is_binary: impl Fn(&Path) -> bool) -> Result<PathBuf, PluginError> {
+    let entries =
+        fs::read_dir(dir).map_err(|_| PluginError::PathError("Directory not found".to_string()))?;
+
+    let mut files: Vec<PathBuf> = entries.flatten().map(|entry| entry.path()).collect();
+    files.sort();
+    files
+        .into_iter()
+        .find(|file| file.is_file() && is_binary(file))
+        .ok_or_else(|| PluginError::PathError("No binary found".to_string()))
+}
+
+#[cfg(target_os = "macos")]
+fn default_search_paths() -> Vec<PathBuf> {
+    let mut paths = Vec::new();
+    if let Some(home) = env::var_os("HOME").map(PathBuf::from) {
+        paths.push(home.join("Library/Audio/Plug-Ins/VST3"));
+    }
+    paths.push(PathBuf::from("/Library/Audio/Plug-Ins/VST3"));
+    paths
+}
+
+#[cfg(target_os = "linux")]
+fn default_search_paths() -> Vec<PathBuf> {
+    let mut paths = Vec::new();
+    if let Some(home) = env::var_os("HOME").map(PathBuf::from) {
+        paths.push(home.join(".vst3"));
+    }
+    paths.push(PathBuf::from("/usr/lib/vst3"));
+    paths.push(PathBuf::from("/usr/local/lib/vst3"));
+    paths
+}
+
+#[cfg(target_os = "windows")]
+fn default_search_paths() -> Vec<PathBuf> {
+    let mut paths = Vec::new();
+    if let Some(base) = env::var_os("LOCALAPPDATA").map(PathBuf::from) {
+        paths.push(base.join("Programs").join("Common").join("VST3"));
+    }
+    if let Some(base) = env::var_os("CommonProgramFiles").map(PathBuf::from) {
+        paths.push(base.join("VST3"));
+    }
+    paths
+}
+
+#[cfg(not(any(target_os = "macos", target_os = "linux", target_os = "windows")))]
+fn default_search_paths() -> Vec<PathBuf> {
+    Vec::new()
+}
@@ -0,0 +1,346 @@
+/*!
+ * ------------------------------------------------------------------------
+ * VST3 .vstpreset reader, writer and validator
+ * by Jurek Raben
+ *
+ * Layout as in vstpresetfile.h of the VST3 SDK, all little endian: the
+ * header ("VST3", version, class ID as 32 ASCII chars, offset of the chunk
+ * list), the chunk data and the chunk list ("List", entry count and per
+ * entry the chunk ID, offset and size). Problems are collected as issues
+ * instead of failing, so broken files can still be compared with working
+ * ones.
+ *
+ * Licensed under CC Attribution-NonCommercial-ShareAlike 4.0 International
+ * Info here: https://creativecommons.org/licenses/by-nc-sa/4.0/
+ * ------------------------------------------------------------------------
+ */
+
+use std::fs;
+use std::io::Read;
+use std::path::Path;
+
+use quick_xml::events::{BytesStart, Event};
+use quick_xml::{Reader, Writer};
+use serde::Serialize;
+
+pub const HEADER_MAGIC: &[u8; 4] = b"VST3";
+pub const LIST_MAGIC: &[u8; 4] = b"List";
+pub const FORMAT_VERSION: i32 = 1;
+pub const HEADER_SIZE: u64 = 48;
+
+const CLASS_ID_LEN: usize = 32;
+const LIST_HEADER_SIZE: u64 = 8;
+const LIST_ENTRY_SIZE: u64 = 20;
+
+// Component state, controller state, program list data and meta info
+pub const KNOWN_CHUNKS: [&str; 4] = ["Comp", "Cont", "Prog", "Info"];
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PresetChunk {
+    pub id: String,
+    pub known: bool,
+    pub offset: u64,
+    pub size: u64,
+}
+
+#[derive(Debug, Clone)]
+pub struct VstPreset {
+    pub version: i32,
+    pub class_id: String,
+    pub list_offset: u64,
+    pub chunks: Vec<PresetChunk>,
+    pub issues: Vec<String>,
+    data: Vec<u8>,
+}
+
+pub fn read_preset(path: &Path) -> Result<VstPreset, String> {
+    let data = fs::read(path).map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+    parse_preset(data)
+}
+
+// Fails only if the data is no .vstpreset at all
+pub fn parse_preset(data: Vec<u8>) -> Result<VstPreset, String> {
+    if data.len() < HEADER_SIZE as usize || !data.starts_with(HEADER_MAGIC) {
+        return Err("Not a .vstpreset file".to_string());
+    }
+
+    let class_id = String::from_utf8_lossy(&data[8..8 + CLASS_ID_LEN]).to_string();
+    let mut preset = VstPreset {
+        version: i32::from_le_bytes(data[4..8].try_into().unwrap()),
+        class_id,
+        list_offset: u64::from_le_bytes(data[40..48].try_into().unwrap()),
+        chunks: Vec::new(),
+        issues: Vec::new(),
+        data,
+    };
+    preset.read_chunk_list();
+    preset.validate();
+    Ok(preset)
+}
+
+impl VstPreset {
+    pub fn size(&self) -> u64 {
+        self.data.len() as u64
+    }
+
+    pub fn chunk(&self, id: &str) -> Option<&PresetChunk> {
+        self.chunks.iter().find(|chunk| chunk.id == id)
+    }
+
+    // None if the chunk lies outside of the file
+    pub fn chunk_data(&self, chunk: &PresetChunk) -> Option<&[u8]> {
+        let end = chunk.offset.checked_add(chunk.size)?;
+        self.data.get(chunk.offset as usize..end as usize)
+    }
+
+    // The Info chunk, indented for reading
+    pub fn info_xml(&self) -> Option<Result<String, String>> {
+        let data = self.chunk_data(self.chunk("Info")?)?;
+        Some(pretty_xml(data))
+    }
+
+    pub fn component_state(&self) -> Option<&[u8]> {
+        self.chunk_data(self.chunk("Comp")?)
+    }
+
+    // IDs and values of the Attr elements of the Info chunk, like "Name"
+    // or "MusicalCategory"
+    pub fn info_attributes(&self) -> Vec<(String, String)> {
+        let Some(data) = self.chunk("Info").and_then(|chunk| self.chunk_data(chunk)) else {
+            return Vec::new();
+        };
+        let mut reader = Reader::from_reader(data);
+        let mut attributes = Vec::new();
+        loop {
+            match reader.read_event() {
+                Ok(Event::Start(element) | Event::Empty(element))
+                    if element.name().as_ref() == b"Attr" =>
+                {
+                    if let (Some(id), Some(value)) =
+                        (attribute(&element, b"id"), attribute(&element, b"value"))
+                    {
+                        attributes.push((id, value));
+                    }
+                }
+                Ok(Event::Eof) | Err(_) => break,
+                _ => {}
+            }
+        }
+        attributes
+    }
+
+    pub fn info_attribute(&self, id: &str) -> Option<String> {
+        self.info_attributes()
+            .into_iter()
+            .find(|(attribute_id, _)| attribute_id == id)
+            .map(|(_, value)| value)
+    }
+
+    // Adds an issue if the preset belongs to another plugin
+    pub fn check_class_id(&mut self, class_id: &str) {
+        if !self.class_id.eq_ignore_ascii_case(class_id) {
+            self.issues.push(format!(
+                "Class ID {} does not match the plugin's {}",
+                self.class_id, class_id
+            ));
+        }
+    }
+
+    fn read_u64(&self, offset: u64) -> Option<u64> {
+        let bytes = self.data.get(offset as usize..offset as usize + 8)?;
+        Some(u64::from_le_bytes(bytes.try_into().ok()?))
+    }
+
+    fn read_chunk_list(&mut self) {
+        let list_offset = self.list_offset;
+        let Some(list_header) = list_offset
+            .checked_add(LIST_HEADER_SIZE)
+            .and_then(|end| self.data.get(list_offset as usize..end as usize))
+        else {
+            self.issues.push(format!(
+                "Chunk list offset {} is beyond the end of the file ({} bytes)",
+                list_offset,
+                self.size()
+            ));
+            return;
+        };
+        if &list_header[..4] != LIST_MAGIC {
+            self.issues.push(format!(
+                "No chunk list at offset {}, found {:?}",
+                list_offset,
+                String::from_utf8_lossy(&list_header[..4])
+            ));
+            return;
+        }
+
+        let count = i32::from_le_bytes(list_header[4..8].try_into().unwrap());
+        let entries_offset = list_offset + LIST_HEADER_SIZE;
+        for index in 0..count.max(0) as u64 {
+            let entry_offset = entries_offset + index * LIST_ENTRY_SIZE;
+            let (Some(id), Some(offset), Some(size)) = (
+                self.data
+                    .get(entry_offset as usize..entry_offset as usize + 4)
+                    .map(|id| String::from_utf8_lossy(id).to_string()),
+                self.read_u64(entry_offset + 4),
+                self.read_u64(entry_offset + 12),
+            ) else {
+                self.issues.push(format!(
+                    "Chunk list is truncated after {} of {} entries",
+                    index, count
+                ));
+                return;
+            };
+            self.chunks.push(PresetChunk {
+                known: KNOWN_CHUNKS.contains(&id.as_str()),
+                id,
+                offset,
+                size,
+            });
+        }
+
+        let list_end = entries_offset + count.max(0) as u64 * LIST_ENTRY_SIZE;
+        if self.size() > list_end {
+            self.issues.push(format!(
+                "{} bytes of trailing data after the chunk list",
+                self.size() - list_end
+            ));
+        }
+    }
+
+    fn validate(&mut self) {
+        let mut issues = Vec::new();
+
+        if self.version != FORMAT_VERSION {
+            issues.push(format!("Unexpected format version {}", self.version));
+        }
+        if self.class_id.len() != CLASS_ID_LEN
+            || !self.class_id.chars().all(|c| c.is_ascii_hexdigit())
+        {
+            issues.push(format!("Class ID {:?} is not 32 hex digits", self.class_id));
+        }
+        if self.list_offset < HEADER_SIZE {
+            issues.push(format!(
+                "Chunk list offset {} points into the header",
+                self.list_offset
+            ));
+        }
+
+        let list_end = self
+            .list_offset
+            .saturating_add(LIST_HEADER_SIZE + self.chunks.len() as u64 * LIST_ENTRY_SIZE);
+        let mut listed: Vec<&str> = Vec::new();
+        for chunk in &self.chunks {
+            let end = chunk.offset.saturating_add(chunk.size);
+            if !chunk.known {
+                issues.push(format!("Unknown chunk {:?}", chunk.id));
+            }
+            if chunk.size == 0 {
+                issues.push(format!("Chunk {} is empty", chunk.id));
+            }
+            if chunk.offset < HEADER_SIZE {
+                issues.push(format!(
+                    "Chunk {} at offset {} starts inside the header",
+                    chunk.id, chunk.offset
+                ));
+            }
+            if end > self.size() {
+                issues.push(format!(
+                    "Chunk {} ends at {}, beyond the end of the file ({} bytes)",
+                    chunk.id,
+                    end,
+                    self.size()
+                ));
+            } else if chunk.size > 0 && chunk.offset < list_end && end > self.list_offset {
+                issues.push(format!("Chunk {} overlaps the chunk list", chunk.id));
+            }
+            if listed.contains(&chunk.id.as_str()) {
+                issues.push(format!("Chunk {} is listed more than once", chunk.id));
+            }
+            listed.push(&chunk.id);
+        }
+
+        let mut ranges: Vec<&PresetChunk> =
+            self.chunks.iter().filter(|chunk| chunk.size > 0).collect();
+        ranges.sort_by_key(|chunk| chunk.offset);
+        for pair in ranges.windows(2) {
+            if pair[0].offset.saturating_add(pair[0].size) > pair[1].offset {
+                issues.push(format!("Chunks {} and {} overlap", pair[0].id, pair[1].id));
+            }
+        }
+
+        if self.chunk("Comp").is_none() {
+            issues.push("No component state (Comp)".to_string());
+        }
+        if let Some(Err(e)) = self.info_xml() {
+            issues.push(format!("Info chunk is no valid XML: {}", e));
+        }
+
+        self.issues.extend(issues);
+    }
+}
+
+// A .vstpreset in the layout of the VST3 SDK, the chunks one after another
+// followed by the chunk list
+pub fn write_preset(class_id: &str, chunks: &[(&str, &[u8])]) -> Vec<u8> {
+    let body_size: usize = chunks.iter().map(|(_, chunk)| chunk.len()).sum();
+    let mut data = HEADER_MAGIC.to_vec();
+    data.extend(FORMAT_VERSION.to_le_bytes());
+    data.extend(class_id.as_bytes());
+    data.extend((HEADER_SIZE + body_size as u64).to_le_bytes());
+
+    let mut list = LIST_MAGIC.to_vec();
+    list.extend((chunks.len() as i32).to_le_bytes());
+    for (id, chunk) in chunks {
+        list.extend(id.as_bytes());
+        list.extend((data.len() as u64).to_le_bytes());
+        list.extend((chunk.len() as u64).to_le_bytes());
+        data.extend(*chunk);
+    }
+    data.extend(list);
+    data
+}
+
+// The class ID of a .vstpreset file, read from its header only
+pub fn read_class_id(path: &Path) -> Option<String> {
+    let mut header = [0u8; HEADER_SIZE as usize];
+    fs::File::open(path).ok()?.read_exact(&mut header).ok()?;
+    if !header.starts_with(HEADER_MAGIC) {
+        return None;
+    }
+    Some(String::from_utf8_lossy(&header[8..8 + CLASS_ID_LEN]).to_string())
+}
+
+fn attribute(element: &BytesStart, name: &[u8]) -> Option<String> {
+    let attribute = element.try_get_attribute(name).ok()??;
+    Some(attribute.unescape_value().ok()?.to_string())
+}
+
+fn pretty_xml(xml: &[u8]) -> Result<String, String> {
+    let mut reader = Reader::from_reader(xml);
+    reader.config_mut().trim_text(true);
+    let mut writer = Writer::new_with_indent(Vec::new(), b' ', 2);
+    // Elements without content are written as empty elements, on one line
+    let mut pending_start = None;
+    loop {
+        let event = reader
+            .read_event()
+            .map_err(|e| format!("{} at position {}", e, reader.error_position()))?;
+        let event = match (pending_start.take(), event) {
+            (Some(start), Event::End(_)) => Event::Empty(start),
+            (Some(start), event) => {
+                writer
+                    .write_event(Event::Start(start))
+                    .map_err(|e| e.to_string())?;
+                event
+            }
+            (None, event) => event,
+        };
+        match event {
+            Event::Eof => break,
+            Event::Start(start) => pending_start = Some(start),
+            event => writer.write_event(event).map_err(|e| e.to_string())?,
+        }
+    }
+    String::from_utf8(writer.into_inner()).map_err(|e| e.to_string())
+}
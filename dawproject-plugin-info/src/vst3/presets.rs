@@ -0,0 +1,197 @@
+/*!
+ * ------------------------------------------------------------------------
+ * VST3 preset discovery
+ * by Jurek Raben
+ *
+ * Finds the .vstpreset files of a plugin in the preset folders of the
+ * VST3 SDK, below <Vendor>/<Plugin> if the plugin is known, and in the
+ * factory preset folder of its bundle. The class ID in the header of a
+ * file decides whether it belongs to the plugin.
+ *
+ * Licensed under CC Attribution-NonCommercial-ShareAlike 4.0 International
+ * Info here: https://creativecommons.org/licenses/by-nc-sa/4.0/
+ * ------------------------------------------------------------------------
+ */
+
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use serde::Serialize;
+
+use super::preset;
+use crate::search;
+
+const MAX_SEARCH_DEPTH: usize = 6;
+
+// Category attributes of the Info chunk, the first one found is taken
+const CATEGORY_ATTRIBUTES: [&str; 2] = ["MusicalCategory", "MusicalInstrument"];
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PresetFile {
+    pub name: String,
+    pub path: String,
+    // From the Info chunk, otherwise the subfolder of the preset
+    pub category: Option<String>,
+    // Only set if a state was given to compare with
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub matches_state: Option<bool>,
+    pub factory: bool,
+}
+
+// A folder to search, and whether it holds the factory presets
+#[derive(Debug, Clone)]
+pub struct PresetFolder {
+    pub path: PathBuf,
+    pub factory: bool,
+}
+
+// Folders of VST3_PRESET_PATH, followed by the user and system folders
+pub fn preset_search_paths() -> Vec<PathBuf> {
+    search::env_search_paths("VST3_PRESET_PATH", default_preset_paths())
+}
+
+// The plugin's folders below the preset folders, or the whole preset
+// folders if vendor and plugin name are unknown. The factory presets of
+// the bundle come last.
+pub fn preset_folders(
+    vendor_and_name: Option<(&str, &str)>,
+    bundle: Option<&Path>,
+) -> Vec<PresetFolder> {
+    let mut folders: Vec<PresetFolder> = preset_search_paths()
+        .into_iter()
+        .map(|path| PresetFolder {
+            path: match vendor_and_name {
+                Some((vendor, name)) => path.join(vendor).join(name),
+                None => path,
+            },
+            factory: false,
+        })
+        .collect();
+    if let Some(bundle) = bundle.filter(|bundle| bundle.is_dir()) {
+        folders.push(PresetFolder {
+            path: bundle.join("Contents").join("Resources").join("Presets"),
+            factory: true,
+        });
+    }
+    folders
+}
+
+// All presets of the class ID below the folders. With a component state
+// every preset tells whether its Comp chunk is that state.
+pub fn find_presets(
+    class_id: &str,
+    folders: &[PresetFolder],
+    state: Option<&[u8]>,
+) -> Vec<PresetFile> {
+    let mut presets = Vec::new();
+    for folder in folders {
+        let mut files = Vec::new();
+        collect_presets(&folder.path, 0, &mut files);
+
+        for file in files {
+            let Some(file_class_id) = preset::read_class_id(&file) else {
+                continue;
+            };
+            if !file_class_id.eq_ignore_ascii_case(class_id) {
+                continue;
+            }
+            let Ok(vst_preset) = preset::read_preset(&file) else {
+                continue;
+            };
+
+            let name = vst_preset
+                .info_attribute("Name")
+                .filter(|name| !name.is_empty())
+                .unwrap_or_else(|| {
+                    file.file_stem()
+                        .unwrap_or_default()
+                        .to_string_lossy()
+                        .to_string()
+                });
+            let category = CATEGORY_ATTRIBUTES
+                .iter()
+                .find_map(|id| vst_preset.info_attribute(id))
+                .filter(|category| !category.is_empty())
+                .or_else(|| subfolder(&folder.path, &file));
+            presets.push(PresetFile {
+                name,
+                path: file.display().to_string(),
+                category,
+                matches_state: state.map(|state| vst_preset.component_state() == Some(state)),
+                factory: folder.factory,
+            });
+        }
+    }
+    presets
+}
+
+fn collect_presets(dir: &Path, depth: usize, files: &mut Vec<PathBuf>) {
+    if depth > MAX_SEARCH_DEPTH {
+        return;
+    }
+    let Ok(entries) = fs::read_dir(dir) else {
+        return;
+    };
+
+    let mut entries: Vec<PathBuf> = entries.flatten().map(|entry| entry.path()).collect();
+    entries.sort();
+
+    for path in entries {
+        if path.is_dir() {
+            collect_presets(&path, depth + 1, files);
+        } else if search::has_extension(&path, "vstpreset") {
+            files.push(path);
+        }
+    }
+}
+
+// Category folders between the searched folder and the file, e.g. "Bass/Sub"
+fn subfolder(folder: &Path, file: &Path) -> Option<String> {
+    let relative = file.parent()?.strip_prefix(folder).ok()?;
+    let parts: Vec<String> = relative
+        .components()
+        .map(|part| part.as_os_str().to_string_lossy().to_string())
+        .collect();
+    (!parts.is_empty()).then(|| parts.join("/"))
+}
+
+#[cfg(target_os = "macos")]
+fn default_preset_paths() -> Vec<PathBuf> {
+    let mut paths = Vec::new();
+    if let Some(home) = env::var_os("HOME").map(PathBuf::from) {
+        paths.push(home.join("Library/Audio/Presets"));
+    }
+    paths.push(PathBuf::from("/Library/Audio/Presets"));
+    paths.push(PathBuf::from("/Network/Library/Audio/Presets"));
+    paths
+}
+
+#[cfg(target_os = "linux")]
+fn default_preset_paths() -> Vec<PathBuf> {
+    let mut paths = Vec::new();
+    if let Some(home) = env::var_os("HOME").map(PathBuf::from) {
+        paths.push(home.join(".vst3").join("presets"));
+    }
+    paths.push(PathBuf::from("/usr/share/vst3/presets"));
+    paths.push(PathBuf::from("/usr/local/share/vst3/presets"));
+    paths
+}
+
+#[cfg(target_os = "windows")]
+fn default_preset_paths() -> Vec<PathBuf> {
+    let mut paths = Vec::new();
+    if let Some(home) = env::var_os("USERPROFILE").map(PathBuf::from) {
+        paths.push(home.join("Documents").join("VST3 Presets"));
+    }
+    if let Some(base) = env::var_os("PROGRAMDATA").map(PathBuf::from) {
+        paths.push(base.join("VST3 Presets"));
+    }
+    paths
+}
+
+#[cfg(not(any(target_os = "macos", target_os = "linux", target_os = "windows")))]
+fn default_preset_paths() -> Vec<PathBuf> {
+    Vec::new()
+}
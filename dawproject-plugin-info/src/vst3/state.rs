@@ -0,0 +1,118 @@
+/*!
+ * ------------------------------------------------------------------------
+ * VST3 plugin state loading
+ * by Jurek Raben
+ *
+ * Loads states into the component and controller of a plugin: .vstpreset
+ * files by their Comp and Cont chunks, VST2 .fxp/.fxb data wrapped like
+ * for a migration, anything else as plain component state, e.g. a Renoise
+ * ParameterChunk.
+ *
+ * Licensed under CC Attribution-NonCommercial-ShareAlike 4.0 International
+ * Info here: https://creativecommons.org/licenses/by-nc-sa/4.0/
+ * ------------------------------------------------------------------------
+ */
+
+use std::path::Path;
+
+use vst3::Steinberg::IBStream;
+use vst3::Steinberg::Vst::{IComponent, IComponentTrait, IEditController, IEditControllerTrait};
+use vst3::Steinberg::kResultOk;
+use vst3::{ComPtr, ComWrapper};
+
+use super::inspect::{find_audio_class, load_module, read_parameters, with_plugin};
+use super::migrate;
+use super::preset;
+use super::stdio;
+use super::stream::MemoryStream;
+use crate::PluginError;
+use crate::state_diff::LoadedState;
+
+// Loads the states one after another into the same instance, returns the
+// plugin name and the parameters after each state
+pub fn load_states(
+    binary: &Path,
+    states: &[Vec<u8>],
+) -> Result<(String, Vec<LoadedState>), PluginError> {
+    stdio::suppress_output(|| {
+        let module = load_module(binary)?;
+        let audio_class = find_audio_class(&module.factory)?;
+
+        with_plugin(
+            &module.factory,
+            &audio_class.cid,
+            |component, controller| {
+                let loaded_states = states
+                    .iter()
+                    .map(|state| {
+                        let (kind, component_state, controller_state) = split_state(state);
+                        let loaded = load_component_state(
+                            component,
+                            controller,
+                            &component_state,
+                            controller_state.as_deref(),
+                        )
+                        .is_some();
+                        LoadedState {
+                            kind,
+                            chunk: Some(component_state),
+                            loaded,
+                            parameters: read_parameters(controller),
+                        }
+                    })
+                    .collect();
+                (audio_class.name, loaded_states)
+            },
+        )
+    })
+}
+
+// Kind, component state and controller state of the data
+pub(super) fn split_state(state: &[u8]) -> (&'static str, Vec<u8>, Option<Vec<u8>>) {
+    if let Ok(vst_preset) = preset::parse_preset(state.to_vec()) {
+        let controller_state = vst_preset
+            .chunk("Cont")
+            .and_then(|chunk| vst_preset.chunk_data(chunk))
+            .filter(|data| !data.is_empty())
+            .map(<[u8]>::to_vec);
+        let component_state = vst_preset.component_state().unwrap_or_default().to_vec();
+        return ("vstpreset", component_state, controller_state);
+    }
+    if state.starts_with(b"CcnK") {
+        let kind = match state.get(8..12) {
+            Some(b"FxBk" | b"FBCh") => "fxb",
+            _ => "fxp",
+        };
+        return (kind, migrate::wrap_vst2_state(state), None);
+    }
+    ("chunk", state.to_vec(), None)
+}
+
+// Loads the component state and returns the state the component reports
+// afterwards, None if it refused it. The controller gets it like after
+// loading a project, followed by its own state if there is one.
+pub(super) fn load_component_state(
+    component: &ComPtr<IComponent>,
+    controller: &ComPtr<IEditController>,
+    component_state: &[u8],
+    controller_state: Option<&[u8]>,
+) -> Option<Vec<u8>> {
+    let input = ComWrapper::new(MemoryStream::new(component_state.to_vec()));
+    if unsafe { component.setState(input.as_com_ref::<IBStream>()?.as_ptr()) } != kResultOk {
+        return None;
+    }
+
+    let output = ComWrapper::new(MemoryStream::default());
+    if unsafe { component.getState(output.as_com_ref::<IBStream>()?.as_ptr()) } != kResultOk {
+        return None;
+    }
+    let state = output.data();
+
+    let controller_input = ComWrapper::new(MemoryStream::new(state.clone()));
+    unsafe { controller.setComponentState(controller_input.as_com_ref::<IBStream>()?.as_ptr()) };
+    if let Some(controller_state) = controller_state {
+        let controller_input = ComWrapper::new(MemoryStream::new(controller_state.to_vec()));
+        unsafe { controller.setState(controller_input.as_com_ref::<IBStream>()?.as_ptr()) };
+    }
+    Some(state)
+}
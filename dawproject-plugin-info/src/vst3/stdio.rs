@@ -0,0 +1,102 @@
+/*!
+ * ------------------------------------------------------------------------
+ * Output suppression while plugins are loaded
+ * by Jurek Raben
+ *
+ * Some plugins print to stdout or stderr while loading, which would end
+ * up in the JSON output of the tool.
+ *
+ * Licensed under CC Attribution-NonCommercial-ShareAlike 4.0 International
+ * Info here: https://creativecommons.org/licenses/by-nc-sa/4.0/
+ * ------------------------------------------------------------------------
+ */
+
+#[cfg(unix)]
+pub fn suppress_output<F: FnOnce() -> R, R>(f: F) -> R {
+    use std::fs::File;
+    use std::io;
+    use std::os::unix::io::AsRawFd;
+
+    let stdout_fd = io::stdout().as_raw_fd();
+    let stderr_fd = io::stderr().as_raw_fd();
+
+    let dev_null = match File::create("/dev/null") {
+        Ok(file) => file,
+        Err(_) => return f(),
+    };
+    let null_fd = dev_null.as_raw_fd();
+
+    let saved_stdout = unsafe { libc::dup(stdout_fd) };
+    let saved_stderr = unsafe { libc::dup(stderr_fd) };
+    if saved_stdout == -1 || saved_stderr == -1 {
+        return f();
+    }
+
+    unsafe {
+        libc::dup2(null_fd, stdout_fd);
+        libc::dup2(null_fd, stderr_fd);
+    }
+
+    let result = f();
+
+    unsafe {
+        libc::dup2(saved_stdout, stdout_fd);
+        libc::dup2(saved_stderr, stderr_fd);
+        libc::close(saved_stdout);
+        libc::close(saved_stderr);
+    }
+
+    result
+}
+
+#[cfg(windows)]
+pub fn suppress_output<F: FnOnce() -> R, R>(f: F) -> R {
+    use winapi::um::fileapi::{CreateFileW, OPEN_EXISTING};
+    use winapi::um::handleapi::{CloseHandle, INVALID_HANDLE_VALUE};
+    use winapi::um::processenv::{GetStdHandle, SetStdHandle};
+    use winapi::um::winbase::{STD_ERROR_HANDLE, STD_OUTPUT_HANDLE};
+    use winapi::um::winnt::{FILE_ATTRIBUTE_NORMAL, FILE_SHARE_WRITE, GENERIC_WRITE};
+
+    let wide_nul: Vec<u16> = "NUL".encode_utf16().chain(std::iter::once(0)).collect();
+
+    let stdout_handle = unsafe { GetStdHandle(STD_OUTPUT_HANDLE) };
+    let stderr_handle = unsafe { GetStdHandle(STD_ERROR_HANDLE) };
+    if stdout_handle == INVALID_HANDLE_VALUE || stderr_handle == INVALID_HANDLE_VALUE {
+        return f();
+    }
+
+    let nul_handle = unsafe {
+        CreateFileW(
+            wide_nul.as_ptr(),
+            GENERIC_WRITE,
+            FILE_SHARE_WRITE,
+            std::ptr::null_mut(),
+            OPEN_EXISTING,
+            FILE_ATTRIBUTE_NORMAL,
+            std::ptr::null_mut(),
+        )
+    };
+    if nul_handle == INVALID_HANDLE_VALUE {
+        return f();
+    }
+
+    unsafe {
+        SetStdHandle(STD_OUTPUT_HANDLE, nul_handle);
+        SetStdHandle(STD_ERROR_HANDLE, nul_handle);
+    }
+
+    let result = f();
+
+    unsafe {
+        SetStdHandle(STD_OUTPUT_HANDLE, stdout_handle);
+        SetStdHandle(STD_ERROR_HANDLE, stderr_handle);
+        CloseHandle(nul_handle);
+    }
+
+    result
+}
+
+#[cfg(not(any(unix, windows)))]
+pub fn suppress_output<F: FnOnce() -> R, R>(f: F) -> R {
+    f()
+}
@@ -0,0 +1,128 @@
+/*!
+ * ------------------------------------------------------------------------
+ * VST3 memory stream
+ * by Jurek Raben
+ *
+ * The IBStream handed to plugins for their states, and for the JSON of
+ * IPluginCompatibility. Reads and writes at the current position, the
+ * data grows on writes past the end.
+ *
+ * Licensed under CC Attribution-NonCommercial-ShareAlike 4.0 International
+ * Info here: https://creativecommons.org/licenses/by-nc-sa/4.0/
+ * ------------------------------------------------------------------------
+ */
+
+use std::cell::{Cell, RefCell};
+use std::ffi::c_void;
+
+use vst3::Class;
+use vst3::Steinberg::IBStream_::IStreamSeekMode;
+use vst3::Steinberg::IBStream_::IStreamSeekMode_::{kIBSeekCur, kIBSeekEnd, kIBSeekSet};
+use vst3::Steinberg::{
+    IBStream, IBStreamTrait, int32, int64, kInvalidArgument, kResultFalse, kResultOk, tresult,
+};
+
+#[derive(Default)]
+pub struct MemoryStream {
+    data: RefCell<Vec<u8>>,
+    position: Cell<usize>,
+}
+
+impl MemoryStream {
+    // Positioned at the start, for plugins reading the data
+    pub fn new(data: Vec<u8>) -> Self {
+        MemoryStream {
+            data: RefCell::new(data),
+            position: Cell::new(0),
+        }
+    }
+
+    pub fn data(&self) -> Vec<u8> {
+        self.data.borrow().clone()
+    }
+}
+
+impl Class for MemoryStream {
+    type Interfaces = (IBStream,);
+}
+
+impl IBStreamTrait for MemoryStream {
+    unsafe fn read(
+        &self,
+        buffer: *mut c_void,
+        num_bytes: int32,
+        num_bytes_read: *mut int32,
+    ) -> tresult {
+        if buffer.is_null() || num_bytes < 0 {
+            return kInvalidArgument;
+        }
+        let data = self.data.borrow();
+        let start = self.position.get().min(data.len());
+        let end = start.saturating_add(num_bytes as usize).min(data.len());
+        unsafe {
+            std::ptr::copy_nonoverlapping(
+                data[start..end].as_ptr(),
+                buffer as *mut u8,
+                end - start,
+            );
+            if !num_bytes_read.is_null() {
+                *num_bytes_read = (end - start) as int32;
+            }
+        }
+        self.position.set(end);
+        kResultOk
+    }
+
+    unsafe fn write(
+        &self,
+        buffer: *mut c_void,
+        num_bytes: int32,
+        num_bytes_written: *mut int32,
+    ) -> tresult {
+        if buffer.is_null() || num_bytes < 0 {
+            return kInvalidArgument;
+        }
+        let bytes = unsafe { std::slice::from_raw_parts(buffer as *const u8, num_bytes as usize) };
+        let mut data = self.data.borrow_mut();
+        let start = self.position.get();
+        let end = start + bytes.len();
+        if data.len() < end {
+            data.resize(end, 0);
+        }
+        data[start..end].copy_from_slice(bytes);
+        self.position.set(end);
+        if !num_bytes_written.is_null() {
+            unsafe { *num_bytes_written = num_bytes };
+        }
+        kResultOk
+    }
+
+    unsafe fn seek(&self, pos: int64, mode: int32, result: *mut int64) -> tresult {
+        let mode = mode as IStreamSeekMode;
+        let base = if mode == kIBSeekSet {
+            0
+        } else if mode == kIBSeekCur {
+            self.position.get() as int64
+        } else if mode == kIBSeekEnd {
+            self.data.borrow().len() as int64
+        } else {
+            return kInvalidArgument;
+        };
+        let Some(position) = base.checked_add(pos).filter(|position| *position >= 0) else {
+            return kResultFalse;
+        };
+        self.position.set(position as usize);
+        if !result.is_null() {
+            unsafe { *result = position };
+        }
+        kResultOk
+    }
+
+    unsafe fn tell(&self, pos: *mut int64) -> tresult {
+        if pos.is_null() {
+            return kInvalidArgument;
+        }
+        unsafe { *pos = self.position.get() as int64 };
+        kResultOk
+    }
+}
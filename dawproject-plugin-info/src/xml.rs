@@ -0,0 +1,197 @@
+/*!
+ * ------------------------------------------------------------------------
+ * Element tree of XML documents
+ * by Jurek Raben
+ *
+ * Renoise songs and DAWproject files are read into a plain tree of
+ * elements, attributes and text, CDATA sections like the ParameterChunk
+ * of plugins become text. The DAWproject files are written from such a
+ * tree, too.
+ *
+ * Licensed under CC Attribution-NonCommercial-ShareAlike 4.0 International
+ * Info here: https://creativecommons.org/licenses/by-nc-sa/4.0/
+ * ------------------------------------------------------------------------
+ */
+
+use std::fmt::Display;
+
+use quick_xml::Reader;
+use quick_xml::escape::{escape, resolve_xml_entity};
+use quick_xml::events::{BytesStart, Event};
+
+#[derive(Debug, Clone, Default)]
+pub struct Element {
+    pub name: String,
+    pub attributes: Vec<(String, String)>,
+    pub text: String,
+    pub children: Vec<Element>,
+}
+
+const DECLARATION: &str = "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n";
+const INDENT: &str = "  ";
+
+impl Element {
+    pub fn new(name: &str) -> Element {
+        Element {
+            name: name.to_string(),
+            ..Default::default()
+        }
+    }
+
+    pub fn with_attribute(mut self, name: &str, value: impl Display) -> Element {
+        self.attributes.push((name.to_string(), value.to_string()));
+        self
+    }
+
+    pub fn with_child(mut self, child: Element) -> Element {
+        self.children.push(child);
+        self
+    }
+
+    pub fn with_children(mut self, children: impl IntoIterator<Item = Element>) -> Element {
+        self.children.extend(children);
+        self
+    }
+
+    pub fn with_text(mut self, text: impl Display) -> Element {
+        self.text = text.to_string();
+        self
+    }
+
+    // The document with XML declaration, indented by two spaces
+    pub fn to_xml(&self) -> String {
+        let mut xml = DECLARATION.to_string();
+        self.write(&mut xml, 0);
+        xml
+    }
+
+    fn write(&self, xml: &mut String, depth: usize) {
+        let indent = INDENT.repeat(depth);
+        xml.push_str(&indent);
+        xml.push('<');
+        xml.push_str(&self.name);
+        for (name, value) in &self.attributes {
+            xml.push_str(&format!(" {}=\"{}\"", name, escape(value.as_str())));
+        }
+
+        if self.children.is_empty() && self.text.is_empty() {
+            xml.push_str("/>\n");
+            return;
+        }
+        xml.push('>');
+        if self.children.is_empty() {
+            xml.push_str(&escape(self.text.as_str()));
+        } else {
+            xml.push('\n');
+            for child in &self.children {
+                child.write(xml, depth + 1);
+            }
+            xml.push_str(&indent);
+        }
+        xml.push_str(&format!("</{}>\n", self.name));
+    }
+
+    pub fn attribute(&self, name: &str) -> Option<&str> {
+        self.attributes
+            .iter()
+            .find(|(attribute, _)| attribute == name)
+            .map(|(_, value)| value.as_str())
+    }
+
+    pub fn child(&self, name: &str) -> Option<&Element> {
+        self.children.iter().find(|child| child.name == name)
+    }
+
+    pub fn children_named<'a>(&'a self, name: &'a str) -> impl Iterator<Item = &'a Element> {
+        self.children.iter().filter(move |child| child.name == name)
+    }
+
+    // Descendant along the element names, e.g. ["FilterDevices", "Devices"]
+    pub fn find(&self, path: &[&str]) -> Option<&Element> {
+        path.iter()
+            .try_fold(self, |element, name| element.child(name))
+    }
+
+    // Trimmed text of a child element
+    pub fn child_text(&self, name: &str) -> Option<&str> {
+        self.child(name).map(|child| child.text.trim())
+    }
+
+    pub fn child_number(&self, name: &str) -> Option<f64> {
+        self.child_text(name)?.parse().ok()
+    }
+
+    // Renoise stores parameters as <Name><Value>..</Value></Name>
+    pub fn child_value(&self, name: &str) -> Option<f64> {
+        self.child(name)?.child_number("Value")
+    }
+}
+
+pub fn parse(xml: &str) -> Result<Element, String> {
+    let mut reader = Reader::from_str(xml);
+    let mut stack: Vec<Element> = Vec::new();
+
+    loop {
+        let event = reader
+            .read_event()
+            .map_err(|e| format!("Invalid XML at {}: {}", reader.buffer_position(), e))?;
+        match event {
+            Event::Start(start) => stack.push(element(&start)?),
+            Event::Empty(start) => {
+                let element = element(&start)?;
+                match stack.last_mut() {
+                    Some(parent) => parent.children.push(element),
+                    None => return Ok(element),
+                }
+            }
+            Event::End(_) => {
+                let element = stack.pop().ok_or("Invalid XML, unexpected end tag")?;
+                match stack.last_mut() {
+                    Some(parent) => parent.children.push(element),
+                    None => return Ok(element),
+                }
+            }
+            Event::Text(text) => push_text(&mut stack, &text.decode().map_err(|e| e.to_string())?),
+            Event::CData(data) => push_text(&mut stack, &String::from_utf8_lossy(&data)),
+            Event::GeneralRef(reference) => {
+                let resolved = match reference.resolve_char_ref().map_err(|e| e.to_string())? {
+                    Some(c) => c.to_string(),
+                    None => {
+                        let name = reference.decode().map_err(|e| e.to_string())?;
+                        resolve_xml_entity(&name)
+                            .ok_or_else(|| format!("Unknown XML entity &{};", name))?
+                            .to_string()
+                    }
+                };
+                push_text(&mut stack, &resolved);
+            }
+            Event::Eof => return Err("Invalid XML, no root element".to_string()),
+            _ => {}
+        }
+    }
+}
+
+fn element(start: &BytesStart) -> Result<Element, String> {
+    let attributes = start
+        .attributes()
+        .map(|attribute| {
+            let attribute = attribute.map_err(|e| e.to_string())?;
+            let value = attribute.unescape_value().map_err(|e| e.to_string())?;
+            Ok((
+                String::from_utf8_lossy(attribute.key.as_ref()).to_string(),
+                value.to_string(),
+            ))
+        })
+        .collect::<Result<Vec<_>, String>>()?;
+    Ok(Element {
+        name: String::from_utf8_lossy(start.name().as_ref()).to_string(),
+        attributes,
+        ..Default::default()
+    })
+}
+
+fn push_text(stack: &mut [Element], text: &str) {
+    if let Some(element) = stack.last_mut() {
+        element.text.push_str(text);
+    }
+}
@@ -0,0 +1,61 @@
+/*!
+ * ------------------------------------------------------------------------
+ * Renoise .xrns song files
+ * by Jurek Raben
+ *
+ * A .xrns is a zip archive with the whole song in Song.xml, including the
+ * data the Lua API hides, like the linked instrument of automation
+ * devices. Extracted Song.xml files are read as well.
+ *
+ * Licensed under CC Attribution-NonCommercial-ShareAlike 4.0 International
+ * Info here: https://creativecommons.org/licenses/by-nc-sa/4.0/
+ * ------------------------------------------------------------------------
+ */
+
+pub mod pattern;
+pub mod song;
+
+use std::fs::{self, File};
+use std::io::Read;
+use std::path::Path;
+
+use zip::ZipArchive;
+
+use crate::xml::{self, Element};
+
+pub const SONG_XML: &str = "Song.xml";
+pub const ROOT_ELEMENT: &str = "RenoiseSong";
+
+const ZIP_MAGIC: &[u8; 4] = b"PK\x03\x04";
+
+// Song.xml of the archive, or the file itself if it is no zip archive
+pub fn read_song_xml(path: &Path) -> Result<String, String> {
+    let read_error = |e: std::io::Error| format!("Failed to read {}: {}", path.display(), e);
+
+    let mut magic = [0; 4];
+    let is_zip = File::open(path)
+        .and_then(|mut file| file.read_exact(&mut magic))
+        .is_ok()
+        && &magic == ZIP_MAGIC;
+    if !is_zip {
+        return fs::read_to_string(path).map_err(read_error);
+    }
+
+    let file = File::open(path).map_err(read_error)?;
+    let mut archive =
+        ZipArchive::new(file).map_err(|e| format!("Failed to open {}: {}", path.display(), e))?;
+    let mut song = archive
+        .by_name(SONG_XML)
+        .map_err(|_| format!("{} contains no {}", path.display(), SONG_XML))?;
+    let mut xml = String::new();
+    song.read_to_string(&mut xml).map_err(read_error)?;
+    Ok(xml)
+}
+
+pub fn read_song(path: &Path) -> Result<Element, String> {
+    let root = xml::parse(&read_song_xml(path)?)?;
+    if root.name != ROOT_ELEMENT {
+        return Err(format!("{} is no Renoise song", path.display()));
+    }
+    Ok(root)
+}
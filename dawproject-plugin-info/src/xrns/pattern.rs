@@ -0,0 +1,300 @@
+/*!
+ * ------------------------------------------------------------------------
+ * Patterns and pattern sequence of a Renoise song
+ * by Jurek Raben
+ *
+ * The pattern pool with the note and effect columns of each line, only
+ * non-empty lines are saved, and the graphical automation envelopes of
+ * the pattern tracks. Line, pattern, track and device indices are zero
+ * based, as in Song.xml.
+ *
+ * Licensed under CC Attribution-NonCommercial-ShareAlike 4.0 International
+ * Info here: https://creativecommons.org/licenses/by-nc-sa/4.0/
+ * ------------------------------------------------------------------------
+ */
+
+use crate::xml::Element;
+
+pub const NOTE_OFF: &str = "OFF";
+pub const EMPTY_VALUE: &str = "..";
+
+// Highest note column volume, also the default velocity
+pub const MAX_VOLUME: u8 = 0x80;
+
+const NOTE_NAMES: [&str; 12] = [
+    "C-", "C#", "D-", "D#", "E-", "F-", "F#", "G-", "G#", "A-", "A#", "B-",
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Note {
+    // 0 is C-0
+    On(u8),
+    Off,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct NoteColumn {
+    pub note: Option<Note>,
+    pub instrument: Option<u8>,
+    // Numeric volume, None if empty or a volume effect
+    pub volume: Option<u8>,
+    pub volume_string: String,
+    // e.g. "M0" for MIDI control messages
+    pub panning_string: String,
+    pub delay: u8,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct EffectColumn {
+    pub number: String,
+    pub amount: String,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Line {
+    pub index: usize,
+    pub note_columns: Vec<NoteColumn>,
+    pub effect_columns: Vec<EffectColumn>,
+}
+
+#[derive(Debug, Clone)]
+pub struct Envelope {
+    // Index in the device chain of the track, 0 is the pre-mixer
+    pub device_index: usize,
+    pub parameter_index: usize,
+    // "Points", "Lines" or "Curves"
+    pub play_mode: String,
+    // Time in lines from the pattern start, value and curve scaling
+    pub points: Vec<EnvelopePoint>,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct EnvelopePoint {
+    pub time: f64,
+    pub value: f64,
+    pub scaling: f64,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct PatternTrack {
+    pub lines: Vec<Line>,
+    pub envelopes: Vec<Envelope>,
+}
+
+#[derive(Debug, Clone)]
+pub struct Pattern {
+    pub number_of_lines: usize,
+    pub name: String,
+    // In the order of the song tracks
+    pub tracks: Vec<PatternTrack>,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct SequenceEntry {
+    pub pattern: usize,
+    pub section_start: bool,
+    pub section_name: String,
+    pub muted_tracks: Vec<usize>,
+}
+
+impl NoteColumn {
+    pub fn is_empty(&self) -> bool {
+        self.note.is_none()
+            && self.instrument.is_none()
+            && self.volume_string.is_empty()
+            && self.panning_string.is_empty()
+            && self.delay == 0
+    }
+}
+
+impl Pattern {
+    pub fn line(&self, track: usize, line: usize) -> Option<&Line> {
+        self.tracks
+            .get(track)?
+            .lines
+            .iter()
+            .find(|pattern_line| pattern_line.index == line)
+    }
+}
+
+pub fn patterns(song: &Element) -> Vec<Pattern> {
+    song.find(&["PatternPool", "Patterns"])
+        .map(|patterns| patterns.children_named("Pattern").map(pattern).collect())
+        .unwrap_or_default()
+}
+
+pub fn sequence(song: &Element) -> Vec<SequenceEntry> {
+    song.find(&["PatternSequence", "SequenceEntries"])
+        .map(|entries| {
+            entries
+                .children_named("SequenceEntry")
+                .map(|entry| SequenceEntry {
+                    pattern: entry
+                        .child_text("Pattern")
+                        .and_then(|pattern| pattern.parse().ok())
+                        .unwrap_or(0),
+                    section_start: entry.child_text("IsSectionStart") == Some("true"),
+                    section_name: entry
+                        .child_text("SectionName")
+                        .unwrap_or_default()
+                        .to_string(),
+                    muted_tracks: entry
+                        .find(&["MutedTracks"])
+                        .map(|muted| {
+                            muted
+                                .children_named("MutedTrack")
+                                .filter_map(|track| track.text.trim().parse().ok())
+                                .collect()
+                        })
+                        .unwrap_or_default(),
+                })
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+// "C-4" is 48, "OFF" a note-off
+pub fn parse_note(note: &str) -> Option<Note> {
+    if note == NOTE_OFF {
+        return Some(Note::Off);
+    }
+    let (name, octave) = note.split_at_checked(2)?;
+    let semitone = NOTE_NAMES.iter().position(|known| *known == name)?;
+    let octave: u8 = octave.parse().ok()?;
+    Some(Note::On(octave * 12 + semitone as u8))
+}
+
+// 48 is "C-4", keys above B-9 have no name
+pub fn note_string(key: u8) -> Option<String> {
+    let octave = key / 12;
+    (octave <= 9).then(|| format!("{}{}", NOTE_NAMES[key as usize % 12], octave))
+}
+
+fn pattern(pattern: &Element) -> Pattern {
+    let number_of_lines = pattern
+        .child_text("NumberOfLines")
+        .and_then(|lines| lines.parse().ok())
+        .unwrap_or(0);
+    Pattern {
+        number_of_lines,
+        name: pattern.child_text("Name").unwrap_or_default().to_string(),
+        tracks: pattern
+            .find(&["Tracks"])
+            .map(|tracks| {
+                tracks
+                    .children
+                    .iter()
+                    .map(|track| pattern_track(track, number_of_lines))
+                    .collect()
+            })
+            .unwrap_or_default(),
+    }
+}
+
+fn pattern_track(track: &Element, number_of_lines: usize) -> PatternTrack {
+    let mut lines: Vec<Line> = track
+        .find(&["Lines"])
+        .map(|lines| lines.children_named("Line").map(line).collect())
+        .unwrap_or_default();
+    lines.sort_by_key(|line| line.index);
+
+    PatternTrack {
+        lines,
+        envelopes: track
+            .find(&["Automations", "Envelopes"])
+            .map(|envelopes| {
+                envelopes
+                    .children_named("Envelope")
+                    .filter_map(|envelope| self::envelope(envelope, number_of_lines))
+                    .collect()
+            })
+            .unwrap_or_default(),
+    }
+}
+
+fn line(line: &Element) -> Line {
+    Line {
+        index: line
+            .attribute("index")
+            .and_then(|index| index.parse().ok())
+            .unwrap_or(0),
+        note_columns: line
+            .find(&["NoteColumns"])
+            .map(|columns| {
+                columns
+                    .children_named("NoteColumn")
+                    .map(note_column)
+                    .collect()
+            })
+            .unwrap_or_default(),
+        effect_columns: line
+            .find(&["EffectColumns"])
+            .map(|columns| {
+                columns
+                    .children_named("EffectColumn")
+                    .map(|column| EffectColumn {
+                        number: column.child_text("Number").unwrap_or_default().to_string(),
+                        amount: column.child_text("Value").unwrap_or_default().to_string(),
+                    })
+                    .collect()
+            })
+            .unwrap_or_default(),
+    }
+}
+
+fn note_column(column: &Element) -> NoteColumn {
+    let value = |name| {
+        column
+            .child_text(name)
+            .filter(|value| !value.is_empty() && *value != EMPTY_VALUE)
+    };
+    let volume_string = value("Volume").unwrap_or_default().to_string();
+
+    NoteColumn {
+        note: value("Note").and_then(parse_note),
+        instrument: value("Instrument").and_then(parse_hex),
+        volume: parse_hex(&volume_string).filter(|volume| *volume <= MAX_VOLUME),
+        volume_string,
+        panning_string: value("Panning").unwrap_or_default().to_string(),
+        delay: value("Delay").and_then(parse_hex).unwrap_or(0),
+    }
+}
+
+// Point times are saved in units of the envelope length, which spans the
+// whole pattern
+fn envelope(envelope: &Element, number_of_lines: usize) -> Option<Envelope> {
+    let data = envelope.child("Envelope")?;
+    let length = data
+        .child_number("Length")
+        .filter(|length| *length > 0.0)
+        .unwrap_or(number_of_lines as f64);
+    let lines_per_unit = number_of_lines as f64 / length;
+
+    let points = data
+        .find(&["Points"])
+        .map(|points| {
+            points
+                .children_named("Point")
+                .filter_map(|point| {
+                    let mut values = point.text.trim().split(',').map(str::parse::<f64>);
+                    Some(EnvelopePoint {
+                        time: values.next()?.ok()? * lines_per_unit,
+                        value: values.next()?.ok()?,
+                        scaling: values.next().and_then(Result::ok).unwrap_or(0.0),
+                    })
+                })
+                .collect()
+        })
+        .unwrap_or_default();
+
+    Some(Envelope {
+        device_index: envelope.child_text("DeviceIndex")?.parse().ok()?,
+        parameter_index: envelope.child_text("ParameterIndex")?.parse().ok()?,
+        play_mode: data.child_text("PlayMode").unwrap_or_default().to_string(),
+        points,
+    })
+}
+
+pub fn parse_hex(value: &str) -> Option<u8> {
+    u8::from_str_radix(value, 16).ok()
+}
@@ -0,0 +1,243 @@
+/*!
+ * ------------------------------------------------------------------------
+ * Tracks, devices and instruments of a Renoise song
+ * by Jurek Raben
+ *
+ * The device chains as saved in Song.xml: the plugin identifier and the
+ * complete ParameterChunk of plugin devices, the linked instrument of
+ * automation devices and the parameter values of native devices like
+ * the pre-mixer and the Gainer. Indices are zero based, as in Song.xml.
+ *
+ * Licensed under CC Attribution-NonCommercial-ShareAlike 4.0 International
+ * Info here: https://creativecommons.org/licenses/by-nc-sa/4.0/
+ * ------------------------------------------------------------------------
+ */
+
+use serde::Serialize;
+use serde_json::{Map, Value, json};
+
+use crate::xml::Element;
+
+// Element names of the tracks and their Lua API track type
+const TRACK_TYPES: [(&str, &str); 4] = [
+    ("SequencerTrack", "sequencer"),
+    ("SequencerGroupTrack", "group"),
+    ("SequencerMasterTrack", "master"),
+    ("SequencerSendTrack", "send"),
+];
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SongInfo {
+    pub name: String,
+    pub artist: String,
+    pub doc_version: i32,
+    pub tracks: Vec<TrackInfo>,
+    pub instruments: Vec<InstrumentInfo>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TrackInfo {
+    pub name: String,
+    #[serde(rename = "type")]
+    pub track_type: String,
+    pub devices: Vec<DeviceInfo>,
+    pub index: usize,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DeviceInfo {
+    #[serde(rename = "type")]
+    pub device_type: String,
+    // "mixer", "gainer", "plugin", "automation" or "native"
+    pub role: &'static str,
+    pub plugin: Option<PluginDevice>,
+    pub linked_instrument: Option<i32>,
+    // Parameters stored as <Name><Value>..</Value></Name>
+    pub values: Map<String, Value>,
+    pub index: usize,
+    pub active: bool,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PluginDevice {
+    // e.g. "VST3", "VST" or "AU"
+    pub plugin_type: String,
+    pub identifier: String,
+    pub name: String,
+    pub short_name: String,
+    // The active preset of the device, as named in the device's preset menu
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub preset_name: Option<String>,
+    pub parameter_chunk_type: String,
+    // Base64, as in Song.xml
+    pub parameter_chunk: Option<String>,
+    pub parameters: Vec<f64>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct InstrumentInfo {
+    pub name: String,
+    pub plugin: Option<PluginDevice>,
+    pub index: usize,
+}
+
+pub fn song_info(song: &Element) -> SongInfo {
+    let global = song.child("GlobalSongData");
+    let global_text = |name| {
+        global
+            .and_then(|global| global.child_text(name))
+            .unwrap_or_default()
+            .to_string()
+    };
+
+    SongInfo {
+        name: global_text("SongName"),
+        artist: global_text("Artist"),
+        doc_version: song
+            .attribute("doc_version")
+            .and_then(|version| version.parse().ok())
+            .unwrap_or(0),
+        tracks: tracks(song)
+            .enumerate()
+            .map(|(index, track)| track_info(index, track))
+            .collect(),
+        instruments: song
+            .find(&["Instruments"])
+            .map(|instruments| {
+                instruments
+                    .children_named("Instrument")
+                    .enumerate()
+                    .map(|(index, instrument)| instrument_info(index, instrument))
+                    .collect()
+            })
+            .unwrap_or_default(),
+    }
+}
+
+// Track elements in song order, the master and send tracks come last
+pub fn tracks(song: &Element) -> impl Iterator<Item = &Element> {
+    song.find(&["Tracks"])
+        .into_iter()
+        .flat_map(|tracks| tracks.children.iter())
+        .filter(|track| track_type(track).is_some())
+}
+
+pub fn track_type(track: &Element) -> Option<&'static str> {
+    TRACK_TYPES
+        .iter()
+        .find(|(name, _)| *name == track.name)
+        .map(|(_, track_type)| *track_type)
+}
+
+// Device elements of a track, the first one is the pre-mixer
+pub fn devices(track: &Element) -> impl Iterator<Item = &Element> {
+    track
+        .find(&["FilterDevices", "Devices"])
+        .into_iter()
+        .flat_map(|devices| devices.children.iter())
+}
+
+pub fn device_type(device: &Element) -> &str {
+    device.attribute("type").unwrap_or(&device.name)
+}
+
+fn track_info(index: usize, track: &Element) -> TrackInfo {
+    TrackInfo {
+        name: track.child_text("Name").unwrap_or_default().to_string(),
+        track_type: track_type(track).unwrap_or_default().to_string(),
+        devices: devices(track)
+            .enumerate()
+            .map(|(index, device)| device_info(index, device))
+            .collect(),
+        index,
+    }
+}
+
+fn device_info(index: usize, device: &Element) -> DeviceInfo {
+    let device_type = device_type(device);
+    let plugin = plugin_device(device);
+    let role = if device_type.ends_with("TrackDevice") {
+        "mixer"
+    } else if device_type.contains("Gainer") {
+        "gainer"
+    } else if plugin.is_some() {
+        "plugin"
+    } else if device_type == "InstrumentAutomationDevice" {
+        "automation"
+    } else {
+        "native"
+    };
+
+    let values = device
+        .children
+        .iter()
+        .filter(|child| child.name != "IsActive")
+        .filter_map(|child| Some((child.name.clone(), json!(child.child_number("Value")?))))
+        .collect();
+
+    DeviceInfo {
+        device_type: device_type.to_string(),
+        role,
+        plugin,
+        linked_instrument: device
+            .child_text("LinkedInstrument")
+            .and_then(|instrument| instrument.parse().ok()),
+        values,
+        index,
+        active: device
+            .child_value("IsActive")
+            .is_none_or(|active| active != 0.0),
+    }
+}
+
+pub fn plugin_device(device: &Element) -> Option<PluginDevice> {
+    let identifier = device.child_text("PluginIdentifier")?;
+    let text = |name| device.child_text(name).unwrap_or_default().to_string();
+
+    Some(PluginDevice {
+        plugin_type: text("PluginType"),
+        identifier: identifier.to_string(),
+        name: text("PluginDisplayName"),
+        short_name: text("PluginShortDisplayName"),
+        preset_name: device.child_text("SelectedPresetName").map(str::to_string),
+        parameter_chunk_type: text("ParameterChunkType"),
+        parameter_chunk: device
+            .child_text("ParameterChunk")
+            .filter(|chunk| !chunk.is_empty())
+            .map(str::to_string),
+        parameters: device
+            .find(&["Parameters"])
+            .map(|parameters| {
+                parameters
+                    .children_named("Parameter")
+                    .filter_map(|parameter| parameter.child_number("Value"))
+                    .collect()
+            })
+            .unwrap_or_default(),
+    })
+}
+
+fn instrument_info(index: usize, instrument: &Element) -> InstrumentInfo {
+    InstrumentInfo {
+        name: instrument
+            .child_text("Name")
+            .unwrap_or_default()
+            .to_string(),
+        plugin: instrument_plugin(instrument).and_then(plugin_device),
+        index,
+    }
+}
+
+// The plugin device of a plugin instrument
+pub fn instrument_plugin(instrument: &Element) -> Option<&Element> {
+    instrument
+        .child("PluginProperties")?
+        .children
+        .iter()
+        .find(|child| child.child("PluginIdentifier").is_some())
+}
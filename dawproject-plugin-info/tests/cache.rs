@@ -0,0 +1,72 @@
+// Several processes saving into the same cache file
+
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use serde_json::{Value, json};
+
+use dawproject_plugin_info::cache::PluginCache;
+
+const CACHE_FILE: &str = "test-cache.json";
+
+// A folder with the cache and two fake plugin binaries
+fn cache_dir(name: &str) -> PathBuf {
+    let dir = env::temp_dir().join(format!("plugin-cache-{}-{}", name, std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    for binary in ["First.so", "Second.so"] {
+        fs::write(dir.join(binary), binary).unwrap();
+    }
+    dir
+}
+
+fn cached_names(dir: &Path) -> Vec<Value> {
+    let cache = PluginCache::open(dir, CACHE_FILE).unwrap();
+    cache
+        .list()
+        .into_iter()
+        .map(|entry| entry["name"].clone())
+        .collect()
+}
+
+#[test]
+fn keeps_the_entries_saved_by_others() {
+    let dir = cache_dir("merge");
+    let mut first = PluginCache::open(&dir, CACHE_FILE).unwrap();
+    let mut second = PluginCache::open(&dir, CACHE_FILE).unwrap();
+
+    first.insert(&dir.join("First.so"), json!({ "name": "First" }));
+    second.insert(&dir.join("Second.so"), json!({ "name": "Second" }));
+    first.save().unwrap();
+    second.save().unwrap();
+
+    assert_eq!(cached_names(&dir), [json!("First"), json!("Second")]);
+    let temp_files: Vec<PathBuf> = fs::read_dir(&dir)
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| path.extension().is_some_and(|ext| ext == "tmp"))
+        .collect();
+    assert_eq!(temp_files, Vec::<PathBuf>::new());
+}
+
+#[test]
+fn prune_removes_only_the_stale_entries() {
+    let dir = cache_dir("prune");
+    let mut cache = PluginCache::open(&dir, CACHE_FILE).unwrap();
+    cache.insert(&dir.join("First.so"), json!({ "name": "First" }));
+    cache.insert(&dir.join("Second.so"), json!({ "name": "Second" }));
+    cache.save().unwrap();
+
+    // Another process updates the first entry meanwhile
+    let mut pruning = PluginCache::open(&dir, CACHE_FILE).unwrap();
+    let mut other = PluginCache::open(&dir, CACHE_FILE).unwrap();
+    other.insert(&dir.join("First.so"), json!({ "name": "First again" }));
+    other.save().unwrap();
+
+    fs::remove_file(dir.join("Second.so")).unwrap();
+    assert_eq!(pruning.prune().len(), 1);
+    pruning.save().unwrap();
+
+    assert_eq!(cached_names(&dir), [json!("First again")]);
+}
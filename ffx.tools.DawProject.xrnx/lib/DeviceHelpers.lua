@@ -173,7 +173,9 @@ function DeviceHelpers:readPluginInfo(device)
     return nil
   end
 
-  local sql = "SELECT LocalFilePath FROM CachedPlugins WHERE DocumentIdentifier = '" .. pluginId .. "';"
+  -- quotes inside the id would end the SQL string otherwise
+  local quotedPluginId = pluginId:gsub("'", "''")
+  local sql = "SELECT LocalFilePath FROM CachedPlugins WHERE DocumentIdentifier = '" .. quotedPluginId .. "';"
   local result = {}
   for a in db:rows(sql) do
     for _, v in ipairs(a) do
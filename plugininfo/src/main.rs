@@ -0,0 +1,399 @@
+/*!
+ * ------------------------------------------------------------------------
+ * Plugin Info Tool
+ * by Jurek Raben
+ *
+ * Takes any plugin path, detects its format and prints the plugin info in
+ * the same schema for all formats. Loads and saves the state of CLAP
+ * plugins for the DawProject ClapPlugin export, and converts .aupreset
+ * files and AudioUnit deviceIDs for the AuPlugin export. Reads the
+ * tracks and devices of Renoise songs.
+ *
+ * Licensed under CC Attribution-NonCommercial-ShareAlike 4.0 International
+ * Info here: https://creativecommons.org/licenses/by-nc-sa/4.0/
+ * ------------------------------------------------------------------------
+ */
+
+use std::env;
+use std::fs;
+use std::panic;
+use std::path::{Path, PathBuf};
+
+use base64::prelude::*;
+use clap::{Args, Parser, Subcommand, ValueEnum};
+use serde_json::{Value, json};
+
+use dawproject_plugin_info::au::identifier::{self as au_identifier, DeviceIdForm};
+use dawproject_plugin_info::au::preset as au_preset;
+use dawproject_plugin_info::clap::inspect as clap_inspect;
+use dawproject_plugin_info::clap::state as clap_state;
+use dawproject_plugin_info::output::{error_exit, print_json, with_os};
+use dawproject_plugin_info::xrns::{self, song as xrns_song};
+use dawproject_plugin_info::{Format, PluginError, detect};
+
+const SUBCOMMANDS: [&str; 7] = [
+    "info",
+    "apply-state",
+    "preset",
+    "aupreset",
+    "au-id",
+    "xrns",
+    "help",
+];
+
+#[derive(Clone, Copy, ValueEnum)]
+enum FormatArg {
+    Vst2,
+    Vst3,
+    Clap,
+}
+
+impl From<FormatArg> for Format {
+    fn from(format: FormatArg) -> Format {
+        match format {
+            FormatArg::Vst2 => Format::Vst2,
+            FormatArg::Vst3 => Format::Vst3,
+            FormatArg::Clap => Format::Clap,
+        }
+    }
+}
+
+#[derive(Parser)]
+#[command(
+    name = "plugininfo",
+    version,
+    about = "Extracts information from VST2, VST3 and CLAP plugins",
+    after_help = "Calling the tool with only a plugin path behaves like `info <PLUGIN>`."
+)]
+struct Cli {
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Args)]
+struct PluginArgs {
+    /// Plugin bundle (.vst3, .vst, .clap) or binary (.so, .dll, .dylib, .vst3, .clap)
+    plugin: PathBuf,
+
+    /// Skips the detection, e.g. for binaries exporting several entry points
+    #[arg(long, value_enum)]
+    format: Option<FormatArg>,
+
+    /// Plugin of a CLAP file containing several plugins, the first one by default
+    #[arg(long, value_name = "ID")]
+    plugin_id: Option<String>,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Plugin info and parameters
+    Info {
+        #[command(flatten)]
+        plugin: PluginArgs,
+
+        /// Prints all CLAP descriptors and the ports, parameter ranges and
+        /// flags and latency of the plugin instead
+        #[arg(long)]
+        details: bool,
+    },
+    /// Loads a state into a CLAP plugin and reports the resulting parameters
+    ApplyState {
+        #[command(flatten)]
+        plugin: PluginArgs,
+
+        /// Base64 encoded state, e.g. a Renoise ParameterChunk
+        #[arg(long, value_name = "BASE64", required_unless_present = "file")]
+        data: Option<String>,
+
+        /// File containing the raw state
+        #[arg(long, value_name = "FILE", conflicts_with = "data")]
+        file: Option<PathBuf>,
+    },
+    /// State of a CLAP plugin as saved by the plugin, optionally written as
+    /// .clap-preset file for a DawProject ClapPlugin
+    Preset {
+        #[command(flatten)]
+        plugin: PluginArgs,
+
+        /// Base64 encoded state to load first, e.g. a Renoise ParameterChunk
+        #[arg(long, value_name = "BASE64")]
+        data: Option<String>,
+
+        /// File containing the raw state to load first
+        #[arg(long, value_name = "FILE", conflicts_with = "data")]
+        file: Option<PathBuf>,
+
+        /// Writes the state as .clap-preset file
+        #[arg(long, value_name = "FILE")]
+        output: Option<PathBuf>,
+    },
+    /// Reads a binary or XML .aupreset, optionally replaces its keys and
+    /// writes it as XML plist
+    Aupreset {
+        /// .aupreset file
+        file: PathBuf,
+
+        /// Writes the preset as XML plist, may be the input file
+        #[arg(long, value_name = "FILE")]
+        output: Option<PathBuf>,
+
+        /// Replaces the plugin state by base64 encoded data
+        #[arg(long, value_name = "BASE64")]
+        data: Option<String>,
+
+        /// Replaces the plugin state by the content of a file
+        #[arg(long, value_name = "FILE", conflicts_with = "data")]
+        data_file: Option<PathBuf>,
+
+        /// Replaces the preset name
+        #[arg(long)]
+        name: Option<String>,
+
+        /// Replaces the component type, e.g. aufx
+        #[arg(long = "type", value_name = "CODE")]
+        component_type: Option<String>,
+
+        /// Replaces the component subtype
+        #[arg(long, value_name = "CODE")]
+        subtype: Option<String>,
+
+        /// Replaces the manufacturer code
+        #[arg(long, value_name = "CODE")]
+        manufacturer: Option<String>,
+    },
+    /// Decodes an AudioUnit deviceID and prints it in the forms of all DAWs
+    AuId {
+        /// e.g. "aufx:MyId:Manu" as in Renoise, or a Bitwig or Studio One deviceID
+        device_id: String,
+    },
+    /// Tracks, devices and instruments of a Renoise song, with the plugin
+    /// states and the data the Lua API hides
+    Xrns {
+        /// .xrns song or extracted Song.xml
+        song: PathBuf,
+    },
+}
+
+// Keeps `plugininfo <plugin>` working
+fn legacy_args(mut args: Vec<String>) -> Vec<String> {
+    if args.len() >= 2 && !args[1].starts_with('-') && !SUBCOMMANDS.contains(&args[1].as_str()) {
+        args.insert(1, "info".to_string());
+    }
+    args
+}
+
+fn main() {
+    panic::set_hook(Box::new(|_info| {
+        // do nothing
+    }));
+
+    let cli = Cli::parse_from(legacy_args(env::args().collect()));
+
+    let output = match cli.command {
+        Command::Info { plugin, details } => {
+            let (format, binary) = resolve_plugin(&plugin);
+            if format != Format::Clap && (plugin.plugin_id.is_some() || details) {
+                error_exit("--plugin-id and --details are only supported for CLAP plugins");
+            }
+
+            let info = if format == Format::Clap {
+                inspect_clap(&binary, plugin.plugin_id.as_deref(), details)
+            } else {
+                detect::backend(format)
+                    .and_then(|backend| backend.inspect(&binary))
+                    .map(|info| json!(info))
+            };
+            let mut output = info.unwrap_or_else(|e| error_exit(e.message()));
+            output["loadedFile"] = json!(binary.display().to_string());
+            output
+        }
+        Command::ApplyState { plugin, data, file } => {
+            let binary = resolve_clap_plugin(&plugin);
+            let state = read_state(data, file);
+            let clap_state =
+                clap_state::apply_state(&binary, plugin.plugin_id.as_deref(), state.as_deref())
+                    .unwrap_or_else(|e| error_exit(e.message()));
+
+            json!({
+                "name": clap_state.plugin.name,
+                "id": clap_state.plugin.id,
+                "applied": clap_state.applied,
+                "stateSize": state.map_or(0, |state| state.len()),
+                "countParameters": clap_state.parameters.len(),
+                "parameters": clap_state.parameters,
+                "loadedFile": binary.display().to_string()
+            })
+        }
+        Command::Preset {
+            plugin,
+            data,
+            file,
+            output: output_path,
+        } => {
+            let binary = resolve_clap_plugin(&plugin);
+            let state = read_state(data, file);
+            let clap_state =
+                clap_state::apply_state(&binary, plugin.plugin_id.as_deref(), state.as_deref())
+                    .unwrap_or_else(|e| error_exit(e.message()));
+
+            let mut output = json!({
+                "name": clap_state.plugin.name,
+                "id": clap_state.plugin.id,
+                "applied": clap_state.applied,
+                "presetData": BASE64_STANDARD.encode(&clap_state.data)
+            });
+            if let Some(output_path) = output_path {
+                write_preset(&output_path, &clap_state.data);
+                output["file"] = json!(output_path.display().to_string());
+            }
+            output["loadedFile"] = json!(binary.display().to_string());
+            output
+        }
+        Command::Aupreset {
+            file,
+            output: output_path,
+            data,
+            data_file,
+            name,
+            component_type,
+            subtype,
+            manufacturer,
+        } => {
+            let mut preset = au_preset::read_preset(&file).unwrap_or_else(|e| error_exit(e));
+            let state = read_state(data, data_file);
+            let codes = [
+                (au_preset::TYPE_KEY, component_type),
+                (au_preset::SUBTYPE_KEY, subtype),
+                (au_preset::MANUFACTURER_KEY, manufacturer),
+            ];
+            let changed =
+                state.is_some() || name.is_some() || codes.iter().any(|(_, code)| code.is_some());
+            if changed && output_path.is_none() {
+                error_exit("--output is needed to write the changed preset");
+            }
+
+            if let Some(state) = state {
+                preset.set_data(state);
+            }
+            if let Some(name) = name {
+                preset.set_name(&name);
+            }
+            for (key, code) in codes {
+                if let Some(code) = code {
+                    let code = au_preset::parse_four_char_code(&code).unwrap_or_else(|| {
+                        error_exit(format!("{} is no four-char code or number", code))
+                    });
+                    preset.set_code(key, code);
+                }
+            }
+
+            let mut output = aupreset_output(&preset);
+            if let Some(output_path) = output_path {
+                let xml = preset.to_xml().unwrap_or_else(|e| error_exit(e));
+                write_preset(&output_path, &xml);
+                output["file"] = json!(output_path.display().to_string());
+            }
+            output["loadedFile"] = json!(file.display().to_string());
+            output
+        }
+        Command::AuId { device_id } => {
+            let (component, form) = au_identifier::parse_device_id(&device_id)
+                .unwrap_or_else(|| error_exit(format!("{} is no AudioUnit deviceID", device_id)));
+            json!({
+                "form": form,
+                "type": component.component_type,
+                "subtype": component.subtype,
+                "manufacturer": component.manufacturer,
+                "plain": component.device_id(DeviceIdForm::Plain),
+                "bitwig": component.device_id(DeviceIdForm::Bitwig),
+                "studioOne": component.device_id(DeviceIdForm::StudioOne)
+            })
+        }
+        Command::Xrns { song } => {
+            let song_xml = xrns::read_song(&song).unwrap_or_else(|e| error_exit(e));
+            let info = xrns_song::song_info(&song_xml);
+            let mut output = json!({
+                "name": info.name,
+                "artist": info.artist,
+                "docVersion": info.doc_version,
+                "countTracks": info.tracks.len(),
+                "countInstruments": info.instruments.len(),
+                "tracks": info.tracks,
+                "instruments": info.instruments
+            });
+            output["loadedFile"] = json!(song.display().to_string());
+            output
+        }
+    };
+    print_json(&with_os(output));
+}
+
+// The detected or given format and the binary of the plugin
+fn resolve_plugin(args: &PluginArgs) -> (Format, PathBuf) {
+    // a bare file name would be looked up in the library search paths
+    let plugin = args.plugin.canonicalize().unwrap_or(args.plugin.clone());
+
+    let format = match args.format {
+        Some(format) => format.into(),
+        None => detect::detect_format(&plugin).unwrap_or_else(|e| error_exit(e.message())),
+    };
+    let binary = detect::backend(format)
+        .and_then(|backend| backend.resolve_binary(&plugin))
+        .unwrap_or_else(|e| error_exit(e.message()));
+    (format, binary)
+}
+
+fn resolve_clap_plugin(args: &PluginArgs) -> PathBuf {
+    let (format, binary) = resolve_plugin(args);
+    if format != Format::Clap {
+        error_exit("apply-state and preset are only supported for CLAP plugins, see vst2info-tool");
+    }
+    binary
+}
+
+fn read_state(data: Option<String>, file: Option<PathBuf>) -> Option<Vec<u8>> {
+    match (data, file) {
+        (Some(data), _) => Some(
+            BASE64_STANDARD
+                .decode(data.trim())
+                .unwrap_or_else(|e| error_exit(format!("Invalid base64 state data: {}", e))),
+        ),
+        (None, Some(file)) => {
+            Some(fs::read(&file).unwrap_or_else(|e| {
+                error_exit(format!("Failed to read {}: {}", file.display(), e))
+            }))
+        }
+        (None, None) => None,
+    }
+}
+
+fn write_preset(path: &Path, state: &[u8]) {
+    fs::write(path, state)
+        .unwrap_or_else(|e| error_exit(format!("Failed to write {}: {}", path.display(), e)));
+}
+
+fn aupreset_output(preset: &au_preset::AuPreset) -> Value {
+    let code = |key| preset.code(key).map(|code| code.to_string());
+    json!({
+        "format": preset.format,
+        "name": preset.name(),
+        "type": code(au_preset::TYPE_KEY),
+        "subtype": code(au_preset::SUBTYPE_KEY),
+        "manufacturer": code(au_preset::MANUFACTURER_KEY),
+        "data": preset.data().map(|data| BASE64_STANDARD.encode(data)),
+        "dataSize": preset.data().map_or(0, <[u8]>::len)
+    })
+}
+
+fn inspect_clap(
+    binary: &Path,
+    plugin_id: Option<&str>,
+    details: bool,
+) -> Result<Value, PluginError> {
+    let details_info = clap_inspect::inspect_details(binary, plugin_id)?;
+    if details {
+        Ok(json!(details_info))
+    } else {
+        Ok(json!(clap_inspect::plugin_info(details_info)))
+    }
+}
@@ -0,0 +1,129 @@
+// AudioUnit deviceID forms and their round trips
+
+mod common;
+
+use std::process::Output;
+
+use serde_json::{Value, json};
+
+use common::{error_message, plugininfo, stdout_json};
+
+fn run(device_id: &str) -> Output {
+    plugininfo().args(["au-id", device_id]).output().unwrap()
+}
+
+fn decode(device_id: &str) -> Value {
+    stdout_json(run(device_id))
+}
+
+#[test]
+fn encodes_renoise_identifiers() {
+    let output = decode("aufx:MyId:Manu");
+
+    assert_eq!(output["form"], "plain");
+    assert_eq!(output["type"], "aufx");
+    assert_eq!(output["subtype"], "MyId");
+    assert_eq!(output["manufacturer"], "Manu");
+    assert_eq!(output["bitwig"], "aufxMyIdManu");
+    // As built by the Lua export so far
+    assert_eq!(output["studioOne"], "6175667849644D79756E614D42414C4B");
+}
+
+#[test]
+fn round_trips_all_forms() {
+    let plain_ids = [
+        "aufx:MyId:Manu",
+        "aumu:DPt1:Ffxt",
+        // space padded and Mac Roman codes
+        "aumf:Fx  :\u{A9}Ffx",
+        "aufc:\u{C4}\u{2206}\u{F8FF} :a:bc",
+    ];
+
+    for plain in plain_ids {
+        let output = decode(plain);
+        assert_eq!(output["plain"], plain);
+
+        for form in ["plain", "bitwig", "studioOne"] {
+            let device_id = output[form].as_str().unwrap();
+            let round_trip = decode(device_id);
+            assert_eq!(
+                round_trip["form"],
+                match form {
+                    "studioOne" => "studio-one",
+                    form => form,
+                }
+            );
+            for field in [
+                "type",
+                "subtype",
+                "manufacturer",
+                "plain",
+                "bitwig",
+                "studioOne",
+            ] {
+                assert_eq!(
+                    round_trip[field], output[field],
+                    "{} of {}",
+                    field, device_id
+                );
+            }
+        }
+    }
+}
+
+#[test]
+fn decodes_trimmed_and_non_ascii_codes() {
+    let output = decode("aumu:DP:\u{A9}Fx");
+
+    assert_eq!(output["subtype"], "DP  ");
+    assert_eq!(output["manufacturer"], "\u{A9}Fx ");
+    assert_eq!(output["plain"], "aumu:DP  :\u{A9}Fx ");
+    // 0xA9 in Mac Roman
+    assert_eq!(output["studioOne"], "61756D7520204450207846A942414C4B");
+}
+
+#[test]
+fn decodes_trimmed_ids_of_bitwig_length_as_plain() {
+    let output = decode("aumu:Syn:Man");
+
+    assert_eq!(output["form"], "plain");
+    assert_eq!(output["type"], "aumu");
+    assert_eq!(output["subtype"], "Syn ");
+    assert_eq!(output["manufacturer"], "Man ");
+    assert_eq!(output["bitwig"], "aumuSyn Man ");
+}
+
+#[test]
+fn decodes_studio_one_ids_case_insensitive() {
+    assert_eq!(
+        decode("6175667849644d79756e614d42414c4b"),
+        json!({
+            "form": "studio-one",
+            "type": "aufx",
+            "subtype": "MyId",
+            "manufacturer": "Manu",
+            "plain": "aufx:MyId:Manu",
+            "bitwig": "aufxMyIdManu",
+            "studioOne": "6175667849644D79756E614D42414C4B",
+            "os": std::env::consts::OS
+        })
+    );
+}
+
+#[test]
+fn rejects_other_ids() {
+    for device_id in [
+        "aufx:MyId",
+        "aumu:a:b:Manu",
+        "aufx:MyIdX:Manu",
+        // no "BALK" suffix
+        "6175667849644D79756E614D00000000",
+        "{5DA1E3B2-7C0F-4E21-9A3B-0C44D17E5F60}",
+        "aufx:MyId:\u{1F600}",
+    ] {
+        assert_eq!(
+            error_message(&run(device_id)),
+            format!("{} is no AudioUnit deviceID", device_id)
+        );
+    }
+}
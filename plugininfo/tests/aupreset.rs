@@ -0,0 +1,152 @@
+// .aupreset conversion of binary and XML plist fixtures
+
+mod common;
+
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+use std::process::Output;
+
+use serde_json::json;
+
+use common::{error_message, fixture, plugininfo, stdout_json, temp_dir};
+
+fn output_file(name: &str) -> PathBuf {
+    temp_dir("aupreset").join(name)
+}
+
+fn run(args: &[&str]) -> Output {
+    plugininfo().arg("aupreset").args(args).output().unwrap()
+}
+
+#[test]
+fn reads_binary_presets() {
+    let file = fixture("Binary.aupreset");
+    assert_eq!(
+        stdout_json(run(&[&file])),
+        json!({
+            "format": "binary",
+            "name": "Init",
+            "type": "aufx",
+            "subtype": "DPt1",
+            "manufacturer": "Ffxt",
+            "data": "AAECAwQFBgcICQoLDA0ODw==",
+            "dataSize": 16,
+            "loadedFile": file,
+            "os": env::consts::OS
+        })
+    );
+}
+
+#[test]
+fn reads_xml_presets_with_non_ascii_codes() {
+    let output = stdout_json(run(&[&fixture("Xml.aupreset")]));
+
+    assert_eq!(output["format"], "xml");
+    assert_eq!(output["type"], "aumu");
+    // 0xA9 "Fx " is stored as negative integer
+    assert_eq!(output["manufacturer"], "\u{A9}Fx ");
+    assert_eq!(output["dataSize"], 7);
+}
+
+#[test]
+fn converts_binary_presets_to_xml() {
+    let file = output_file("Converted.aupreset");
+    let output = stdout_json(run(&[
+        &fixture("Binary.aupreset"),
+        "--output",
+        file.to_str().unwrap(),
+    ]));
+    assert_eq!(output["file"], file.to_str().unwrap());
+
+    assert_eq!(
+        fs::read_to_string(&file).unwrap(),
+        r#"<?xml version="1.0" encoding="UTF-8"?>
+<!DOCTYPE plist PUBLIC "-//Apple//DTD PLIST 1.0//EN" "http://www.apple.com/DTDs/PropertyList-1.0.dtd">
+<plist version="1.0">
+<dict>
+	<key>data</key>
+	<data>
+	AAECAwQFBgcICQoLDA0ODw==
+	</data>
+	<key>manufacturer</key>
+	<integer>1181120628</integer>
+	<key>name</key>
+	<string>Init</string>
+	<key>subtype</key>
+	<integer>1146123313</integer>
+	<key>type</key>
+	<integer>1635083896</integer>
+	<key>version</key>
+	<integer>0</integer>
+</dict>
+</plist>
+"#
+    );
+}
+
+#[test]
+fn rewrites_preset_keys() {
+    let file = output_file("Rewritten.aupreset");
+    let file = file.to_str().unwrap();
+    stdout_json(run(&[
+        &fixture("Binary.aupreset"),
+        "--output",
+        file,
+        "--data",
+        "UmVub2lzZQ==",
+        "--name",
+        "Renoise",
+        "--type",
+        "aumf",
+        "--subtype",
+        "0x44507432",
+        "--manufacturer",
+        "\u{A9}Fx ",
+    ]));
+
+    assert_eq!(
+        stdout_json(run(&[file])),
+        json!({
+            "format": "xml",
+            "name": "Renoise",
+            "type": "aumf",
+            "subtype": "DPt2",
+            "manufacturer": "\u{A9}Fx ",
+            "data": "UmVub2lzZQ==",
+            "dataSize": 7,
+            "loadedFile": file,
+            "os": env::consts::OS
+        })
+    );
+    assert!(
+        fs::read_to_string(file)
+            .unwrap()
+            .contains("<integer>-1454999520</integer>")
+    );
+}
+
+#[test]
+fn needs_an_output_for_changes() {
+    assert_eq!(
+        error_message(&run(&[&fixture("Binary.aupreset"), "--name", "Other"])),
+        "--output is needed to write the changed preset"
+    );
+    assert_eq!(
+        error_message(&run(&[
+            &fixture("Binary.aupreset"),
+            "--output",
+            output_file("Invalid.aupreset").to_str().unwrap(),
+            "--type",
+            "toolong"
+        ])),
+        "toolong is no four-char code or number"
+    );
+}
+
+#[test]
+fn rejects_files_that_are_no_plists() {
+    let file = output_file("Text.aupreset");
+    fs::write(&file, "no plist").unwrap();
+    assert!(error_message(&run(&[file.to_str().unwrap()])).starts_with("Not a plist file: "));
+}
@@ -0,0 +1,231 @@
+// CLAP inspection and state handling against the clap-test-plugin of the
+// workspace
+
+mod common;
+
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+
+use base64::prelude::*;
+use serde_json::{Value, json};
+
+use common::{plugininfo, stdout_json, temp_dir, test_library};
+
+// The test plugin, built next to the plugininfo binary and copied to a
+// .clap file as it would be installed
+fn test_plugin() -> &'static Path {
+    static PLUGIN: OnceLock<PathBuf> = OnceLock::new();
+    PLUGIN.get_or_init(|| {
+        let plugin = temp_dir("clap").join("TestPlugin.clap");
+        fs::copy(test_library("clap-test-plugin"), &plugin).unwrap();
+        plugin
+    })
+}
+
+fn run(args: &[&str]) -> Value {
+    stdout_json(plugininfo().args(args).output().unwrap())
+}
+
+fn plugin_arg() -> &'static str {
+    test_plugin().to_str().unwrap()
+}
+
+// The state format of the test plugin
+fn state(values: &[(u32, f64)]) -> Vec<u8> {
+    let mut state = b"DPTS".to_vec();
+    state.extend((values.len() as u32).to_le_bytes());
+    for (param_id, value) in values {
+        state.extend(param_id.to_le_bytes());
+        state.extend(value.to_le_bytes());
+    }
+    state
+}
+
+fn parameter_values(output: &Value) -> Vec<(Value, Value, Value)> {
+    output["parameters"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|param| {
+            (
+                param["id"].clone(),
+                param["value"].clone(),
+                param["display"].clone(),
+            )
+        })
+        .collect()
+}
+
+#[test]
+fn info_reports_the_first_plugin() {
+    let output = run(&[plugin_arg()]);
+
+    assert_eq!(output["format"], "clap");
+    assert_eq!(output["id"], "com.dawproject.test-gain");
+    assert_eq!(output["name"], "Test Gain");
+    assert_eq!(output["vendor"], "DawProject");
+    assert_eq!(output["version"], "1.2.3");
+    assert_eq!(output["category"], "audio-effect|stereo");
+    assert_eq!(output["countInputs"], 1);
+    assert_eq!(output["countOutputs"], 1);
+    assert_eq!(
+        output["parameters"][1],
+        json!({
+            "index": 1,
+            "id": 20,
+            "name": "Mode",
+            "units": "",
+            "display": "Warm",
+            "stepCount": 3,
+            "value": 1.0 / 3.0,
+            "defaultValue": 1.0 / 3.0,
+            "automatable": true
+        })
+    );
+    assert_eq!(output["os"], env::consts::OS);
+}
+
+#[test]
+fn details_list_descriptors_ports_and_latency() {
+    let output = run(&["info", plugin_arg(), "--details"]);
+
+    let ids: Vec<&str> = output["plugins"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|plugin| plugin["id"].as_str().unwrap())
+        .collect();
+    assert_eq!(
+        ids,
+        ["com.dawproject.test-gain", "com.dawproject.test-synth"]
+    );
+    assert_eq!(
+        output["plugin"]["features"],
+        json!(["audio-effect", "stereo"])
+    );
+    assert_eq!(
+        output["audioInputs"],
+        json!([{
+            "id": 0,
+            "name": "Main In",
+            "portType": "stereo",
+            "channelCount": 2,
+            "inPlacePair": 1,
+            "flags": ["main"]
+        }])
+    );
+    assert_eq!(output["noteInputs"], json!([]));
+    assert_eq!(
+        output["parameters"][0],
+        json!({
+            "id": 10,
+            "name": "Gain",
+            "module": "Main",
+            "display": "0.0 dB",
+            "minValue": -60.0,
+            "maxValue": 12.0,
+            "defaultValue": 0.0,
+            "value": 0.0,
+            "flags": ["automatable", "modulatable"]
+        })
+    );
+    assert_eq!(output["latency"], 64);
+
+    let output = run(&[
+        plugin_arg(),
+        "--details",
+        "--plugin-id",
+        "com.dawproject.test-synth",
+    ]);
+    assert_eq!(output["plugin"]["name"], "Test Synth");
+    assert_eq!(output["audioInputs"], json!([]));
+    assert_eq!(
+        output["noteInputs"],
+        json!([{
+            "id": 0,
+            "name": "Notes",
+            "preferredDialect": "clap",
+            "supportedDialects": ["clap", "midi"]
+        }])
+    );
+    assert!(output["latency"].is_null());
+}
+
+#[test]
+fn apply_state_reads_back_the_parameters() {
+    let data = BASE64_STANDARD.encode(state(&[(10, -6.0), (20, 3.0), (30, 1.0)]));
+    let output = run(&["apply-state", plugin_arg(), "--data", &data]);
+
+    assert_eq!(output["id"], "com.dawproject.test-gain");
+    assert_eq!(output["applied"], true);
+    assert_eq!(output["stateSize"], 44);
+    assert_eq!(output["countParameters"], 3);
+    assert_eq!(
+        parameter_values(&output),
+        [
+            (json!(10), json!(-6.0), json!("-6.0 dB")),
+            (json!(20), json!(3.0), json!("Broken")),
+            (json!(30), json!(1.0), json!("On")),
+        ]
+    );
+}
+
+#[test]
+fn apply_state_reports_a_rejected_state() {
+    let data = BASE64_STANDARD.encode(b"no state");
+    let output = run(&["apply-state", plugin_arg(), "--data", &data]);
+
+    assert_eq!(output["applied"], false);
+    assert_eq!(
+        parameter_values(&output)[0],
+        (json!(10), json!(0.0), json!("0.0 dB"))
+    );
+}
+
+#[test]
+fn preset_writes_the_saved_state() {
+    let loaded = state(&[(20, 2.0), (10, 3.5)]);
+    let preset_path = test_plugin().with_file_name("synth.clap-preset");
+    let output = run(&[
+        "preset",
+        plugin_arg(),
+        "--plugin-id",
+        "com.dawproject.test-synth",
+        "--data",
+        &BASE64_STANDARD.encode(&loaded),
+        "--output",
+        preset_path.to_str().unwrap(),
+    ]);
+
+    // Saved by the plugin with all parameters in its own order
+    let saved = state(&[(10, 3.5), (20, 2.0), (30, 0.0)]);
+    assert_eq!(output["id"], "com.dawproject.test-synth");
+    assert_eq!(output["applied"], true);
+    assert_eq!(output["presetData"], BASE64_STANDARD.encode(&saved));
+    assert_eq!(output["file"], preset_path.to_str().unwrap());
+    assert_eq!(fs::read(&preset_path).unwrap(), saved);
+
+    // and loads again
+    let output = run(&[
+        "apply-state",
+        plugin_arg(),
+        "--file",
+        preset_path.to_str().unwrap(),
+    ]);
+    assert_eq!(output["applied"], true);
+    assert_eq!(parameter_values(&output)[1].1, json!(2.0));
+}
+
+#[test]
+fn preset_without_state_saves_the_defaults() {
+    let output = run(&["preset", plugin_arg()]);
+
+    assert_eq!(output["applied"], true);
+    assert_eq!(
+        output["presetData"],
+        BASE64_STANDARD.encode(state(&[(10, 0.0), (20, 1.0), (30, 0.0)]))
+    );
+    assert!(output.get("file").is_none());
+}
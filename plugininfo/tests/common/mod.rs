@@ -0,0 +1,61 @@
+// Helpers shared by the plugininfo tests, each test crate uses only some
+
+#![allow(dead_code)]
+
+use std::env;
+use std::env::consts::{DLL_PREFIX, DLL_SUFFIX};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Output};
+
+use serde_json::Value;
+
+pub fn fixture(name: &str) -> String {
+    format!("{}/tests/fixtures/{}", env!("CARGO_MANIFEST_DIR"), name)
+}
+
+// A folder per test inside the temporary folder
+pub fn temp_dir(name: &str) -> PathBuf {
+    let dir = env::temp_dir().join(format!("plugininfo-{}-{}", name, std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+// Builds a test plugin of the workspace, the library ends up next to the
+// plugininfo binary
+pub fn test_library(package: &str) -> PathBuf {
+    let status = Command::new(env::var("CARGO").unwrap_or_else(|_| "cargo".to_string()))
+        .args(["build", "-p", package])
+        .status()
+        .unwrap();
+    assert!(status.success(), "{} builds", package);
+
+    let target_dir = Path::new(env!("CARGO_BIN_EXE_plugininfo"))
+        .parent()
+        .unwrap();
+    target_dir.join(format!(
+        "{}{}{}",
+        DLL_PREFIX,
+        package.replace('-', "_"),
+        DLL_SUFFIX
+    ))
+}
+
+pub fn plugininfo() -> Command {
+    Command::new(env!("CARGO_BIN_EXE_plugininfo"))
+}
+
+pub fn stdout_json(output: Output) -> Value {
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    serde_json::from_slice(&output.stdout).unwrap()
+}
+
+pub fn error_message(output: &Output) -> String {
+    assert!(!output.status.success());
+    let error: Value = serde_json::from_slice(&output.stderr).unwrap();
+    error["error"].as_str().unwrap().to_string()
+}
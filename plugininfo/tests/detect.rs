@@ -0,0 +1,60 @@
+// Format detection of the test plugins and of paths that are no loadable
+// plugins
+
+mod common;
+
+use std::fs;
+use std::path::PathBuf;
+use std::process::Output;
+
+use common::{error_message, plugininfo, stdout_json, temp_dir, test_library};
+
+fn run(plugin: &PathBuf) -> Output {
+    plugininfo().arg(plugin).output().unwrap()
+}
+
+#[test]
+fn detects_the_test_plugins_by_their_exports() {
+    let dir = temp_dir("exports");
+    for (package, format) in [
+        ("vst2-test-plugin", "vst2"),
+        ("vst3-test-plugin", "vst3"),
+        ("clap-test-plugin", "clap"),
+    ] {
+        // Without a plugin extension, the exports decide
+        let plugin = dir.join(package);
+        fs::copy(test_library(package), &plugin).unwrap();
+        let output = stdout_json(run(&plugin));
+        assert_eq!(output["format"], format, "{}", package);
+    }
+}
+
+#[test]
+fn reports_missing_paths() {
+    let plugin = PathBuf::from("/does/not/exist/Missing.vst3");
+    assert_eq!(
+        error_message(&run(&plugin)),
+        "/does/not/exist/Missing.vst3 does not exist"
+    );
+}
+
+#[test]
+fn rejects_files_that_are_no_libraries() {
+    let plugin = temp_dir("text").join("Text.so");
+    fs::write(&plugin, "no plugin").unwrap();
+    assert!(error_message(&run(&plugin)).starts_with("Failed to load library: "));
+}
+
+#[test]
+fn detects_bundles_by_extension() {
+    let dir = temp_dir("bundles");
+
+    // detected as VST3, but the bundle has no binary for this platform
+    let vst3 = dir.join("Empty.vst3");
+    fs::create_dir_all(vst3.join("Contents")).unwrap();
+    assert_eq!(error_message(&run(&vst3)), "Directory not found");
+
+    let folder = dir.join("Folder");
+    fs::create_dir_all(&folder).unwrap();
+    assert!(error_message(&run(&folder)).ends_with("is no plugin bundle"));
+}
@@ -0,0 +1,116 @@
+// Renoise song fixtures, as .xrns archive and as extracted Song.xml
+
+mod common;
+
+use std::fs;
+use std::process::Output;
+
+use serde_json::{Value, json};
+
+use common::{error_message, fixture, plugininfo, stdout_json, temp_dir};
+
+fn run(song: &str) -> Output {
+    plugininfo().args(["xrns", song]).output().unwrap()
+}
+
+#[test]
+fn reads_tracks_and_devices() {
+    let output = stdout_json(run(&fixture("Song.xrns")));
+
+    assert_eq!(output["name"], "Fixture & Test");
+    assert_eq!(output["artist"], "Jurek Raben");
+    assert_eq!(output["docVersion"], 63);
+    assert_eq!(output["countTracks"], 2);
+
+    let lead = &output["tracks"][0];
+    assert_eq!(lead["name"], "Lead");
+    assert_eq!(lead["type"], "sequencer");
+    assert_eq!(
+        lead["devices"][0],
+        json!({
+            "type": "SequencerTrackDevice",
+            "role": "mixer",
+            "plugin": null,
+            "linkedInstrument": null,
+            "values": { "Volume": 0.5, "Panning": 0.25, "Width": 1.0 },
+            "index": 0,
+            "active": true
+        })
+    );
+    assert_eq!(
+        lead["devices"][1],
+        json!({
+            "type": "AudioPluginDevice",
+            "role": "plugin",
+            "plugin": {
+                "pluginType": "VST",
+                "identifier": "Test Chunks",
+                "name": "VST: Test: Test Chunks",
+                "shortName": "Test Chunks",
+                "parameterChunkType": "Chunk",
+                "parameterChunk": "RFBQMgAAAD8AAAAAAACAPw==",
+                "parameters": [0.5, 0.0, 1.0]
+            },
+            "linkedInstrument": null,
+            "values": {},
+            "index": 1,
+            "active": false
+        })
+    );
+    // Missing in active_preset_data of the Lua API
+    assert_eq!(lead["devices"][2]["role"], "automation");
+    assert_eq!(lead["devices"][2]["linkedInstrument"], 0);
+    assert_eq!(lead["devices"][3]["role"], "gainer");
+    assert_eq!(
+        lead["devices"][3]["values"],
+        json!({ "Volume": 0.707946, "Panning": 0.5 })
+    );
+
+    let master = &output["tracks"][1];
+    assert_eq!(master["type"], "master");
+    assert_eq!(master["devices"][0]["role"], "mixer");
+}
+
+#[test]
+fn reads_instrument_plugins() {
+    let output = stdout_json(run(&fixture("Song.xrns")));
+
+    assert_eq!(output["countInstruments"], 2);
+    assert_eq!(output["instruments"][0]["name"], "Test Synth");
+    assert_eq!(
+        output["instruments"][0]["plugin"]["identifier"],
+        "5DA1E3B27C0F4E219A3B0C44D17E5F60"
+    );
+    assert_eq!(
+        output["instruments"][0]["plugin"]["parameterChunk"],
+        "RFBUMwAAAAA="
+    );
+    assert_eq!(output["instruments"][1]["plugin"], Value::Null);
+}
+
+#[test]
+fn reads_extracted_song_xml() {
+    let song = fixture("song/Song.xml");
+    let mut output = stdout_json(run(&song));
+    let mut archive_output = stdout_json(run(&fixture("Song.xrns")));
+
+    assert_eq!(output["loadedFile"], song);
+    output["loadedFile"] = Value::Null;
+    archive_output["loadedFile"] = Value::Null;
+    assert_eq!(output, archive_output);
+}
+
+#[test]
+fn rejects_files_that_are_no_songs() {
+    let file = temp_dir("xrns").join("Other.xml");
+    fs::write(&file, "<?xml version=\"1.0\"?><FilterDevicePreset/>").unwrap();
+    let file = file.to_str().unwrap();
+    assert_eq!(
+        error_message(&run(file)),
+        format!("{} is no Renoise song", file)
+    );
+
+    // Neither a zip archive nor XML
+    let aupreset = fixture("Binary.aupreset");
+    assert!(error_message(&run(&aupreset)).starts_with(&format!("Failed to read {}", aupreset)));
+}
@@ -0,0 +1,551 @@
+/*!
+ * ------------------------------------------------------------------------
+ * VST2 test plugin
+ * by Jurek Raben
+ *
+ * A VST2 plugin for the tests of vst2info-tool, written against the raw
+ * 2.4 ABI and without any audio processing. It has three parameters and
+ * three named programs. The environment variable VST2_TEST_PLUGIN picks
+ * the variant while loading:
+ *
+ * - unset or "chunks": programs are saved as opaque chunks (programChunks)
+ * - "params": no chunks, hosts have to save the parameter values
+ * - "shell": a shell plugin containing both variants as sub plugins
+ *
+ * A program chunk is the magic "DPP2" followed by the parameter values.
+ * A bank chunk is "DPB2", the program count, the current program and per
+ * program a 24 byte name and the values. All little endian. A program
+ * chunk "DPX2" aborts the process, for the crash handling of the tool.
+ * The values of a loaded program chunk are reported to the host through
+ * audioMasterAutomate, as plugins do when their parameters change. With
+ * VST2_TEST_PLUGIN_PRINT set, the plugin also prints a JSON line to stdout
+ * while loading and for every loaded chunk, as chatty plugins do.
+ *
+ * Licensed under CC Attribution-NonCommercial-ShareAlike 4.0 International
+ * Info here: https://creativecommons.org/licenses/by-nc-sa/4.0/
+ * ------------------------------------------------------------------------
+ */
+
+#![allow(non_upper_case_globals)]
+#![allow(non_snake_case)]
+
+use std::env;
+use std::ffi::{CStr, c_char, c_void};
+use std::ptr;
+
+const CHUNKS_ID: i32 = i32::from_be_bytes(*b"DPc2");
+const PARAMS_ID: i32 = i32::from_be_bytes(*b"DPp2");
+const SHELL_ID: i32 = i32::from_be_bytes(*b"DPs2");
+
+const VERSION: i32 = 1100;
+const INITIAL_DELAY: i32 = 32;
+const PROGRAM_MAGIC: &[u8; 4] = b"DPP2";
+const BANK_MAGIC: &[u8; 4] = b"DPB2";
+const CRASH_MAGIC: &[u8; 4] = b"DPX2";
+const PROGRAM_NAME_LEN: usize = 24;
+const MODES: [&str; 3] = ["Soft", "Hard", "Fold"];
+
+const VST_MAGIC: i32 = i32::from_be_bytes(*b"VstP");
+
+type HostCallbackProc =
+    unsafe extern "C" fn(*mut AEffect, i32, i32, isize, *mut c_void, f32) -> isize;
+type DispatcherProc =
+    unsafe extern "C" fn(*mut AEffect, i32, i32, isize, *mut c_void, f32) -> isize;
+type ProcessProc = unsafe extern "C" fn(*mut AEffect, *mut *mut f32, *mut *mut f32, i32);
+type ProcessDoubleProc = unsafe extern "C" fn(*mut AEffect, *mut *mut f64, *mut *mut f64, i32);
+type SetParameterProc = unsafe extern "C" fn(*mut AEffect, i32, f32);
+type GetParameterProc = unsafe extern "C" fn(*mut AEffect, i32) -> f32;
+
+// Layout as in aeffect.h of the VST 2.4 SDK
+#[repr(C)]
+pub struct AEffect {
+    magic: i32,
+    dispatcher: DispatcherProc,
+    process: ProcessProc,
+    setParameter: SetParameterProc,
+    getParameter: GetParameterProc,
+    numPrograms: i32,
+    numParams: i32,
+    numInputs: i32,
+    numOutputs: i32,
+    flags: i32,
+    resvd1: isize,
+    resvd2: isize,
+    initialDelay: i32,
+    realQualities: i32,
+    offQualities: i32,
+    ioRatio: f32,
+    object: *mut c_void,
+    user: *mut c_void,
+    uniqueID: i32,
+    version: i32,
+    processReplacing: ProcessProc,
+    processDoubleReplacing: ProcessDoubleProc,
+    future: [u8; 56],
+}
+
+const effFlagsCanReplacing: i32 = 1 << 4;
+const effFlagsProgramChunks: i32 = 1 << 5;
+
+const kPlugCategEffect: isize = 1;
+const kPlugCategShell: isize = 10;
+
+const effOpen: i32 = 0;
+const effClose: i32 = 1;
+const effSetProgram: i32 = 2;
+const effGetProgram: i32 = 3;
+const effGetProgramName: i32 = 5;
+const effGetParamLabel: i32 = 6;
+const effGetParamDisplay: i32 = 7;
+const effGetParamName: i32 = 8;
+const effGetChunk: i32 = 23;
+const effSetChunk: i32 = 24;
+const effCanBeAutomated: i32 = 26;
+const effGetProgramNameIndexed: i32 = 29;
+const effGetPlugCategory: i32 = 35;
+const effGetVendorString: i32 = 47;
+const effGetProductString: i32 = 48;
+const effGetVendorVersion: i32 = 49;
+const effCanDo: i32 = 51;
+const effGetVstVersion: i32 = 58;
+const effShellGetNextPlugin: i32 = 70;
+
+const audioMasterAutomate: i32 = 0;
+const audioMasterCurrentId: i32 = 2;
+
+// The string limits of the SDK, the tool hands out larger buffers
+const kVstMaxNameLen: usize = 64;
+
+#[derive(Clone, Copy, PartialEq)]
+enum Variant {
+    Chunks,
+    Params,
+    Shell,
+}
+
+impl Variant {
+    fn unique_id(self) -> i32 {
+        match self {
+            Variant::Chunks => CHUNKS_ID,
+            Variant::Params => PARAMS_ID,
+            Variant::Shell => SHELL_ID,
+        }
+    }
+
+    fn product(self) -> &'static str {
+        match self {
+            Variant::Chunks => "Test Chunks",
+            Variant::Params => "Test Params",
+            Variant::Shell => "Test Shell",
+        }
+    }
+}
+
+// The sub plugins of the shell variant
+const SHELL_PLUGINS: [Variant; 2] = [Variant::Chunks, Variant::Params];
+
+struct Param {
+    name: &'static str,
+    label: &'static str,
+    automatable: bool,
+}
+
+const PARAMS: [Param; 3] = [
+    Param {
+        name: "Gain",
+        label: "dB",
+        automatable: true,
+    },
+    Param {
+        name: "Mode",
+        label: "",
+        automatable: true,
+    },
+    Param {
+        name: "Mix",
+        label: "%",
+        automatable: false,
+    },
+];
+
+struct Program {
+    name: String,
+    values: [f32; 3],
+}
+
+fn factory_programs() -> Vec<Program> {
+    [
+        ("Init", [0.5, 0.0, 1.0]),
+        ("Bright", [0.75, 0.5, 0.25]),
+        ("Dark", [0.25, 1.0, 0.5]),
+    ]
+    .into_iter()
+    .map(|(name, values)| Program {
+        name: name.to_string(),
+        values,
+    })
+    .collect()
+}
+
+struct TestPlugin {
+    effect: AEffect,
+    host: HostCallbackProc,
+    variant: Variant,
+    programs: Vec<Program>,
+    current: usize,
+    // Handed out by effGetChunk, valid until the next call
+    chunk: Vec<u8>,
+    next_shell_plugin: usize,
+}
+
+unsafe fn plugin<'a>(effect: *mut AEffect) -> &'a mut TestPlugin {
+    unsafe { &mut *((*effect).object as *mut TestPlugin) }
+}
+
+unsafe fn copy_string(ptr: *mut c_void, string: &str) {
+    if ptr.is_null() {
+        return;
+    }
+    let bytes = string.as_bytes();
+    let len = bytes.len().min(kVstMaxNameLen - 1);
+    unsafe {
+        ptr::copy_nonoverlapping(bytes.as_ptr(), ptr as *mut u8, len);
+        *(ptr as *mut u8).add(len) = 0;
+    }
+}
+
+fn display(index: usize, value: f32) -> String {
+    match index {
+        0 => format!("{:.1}", value * 24.0 - 12.0),
+        1 => MODES[((value * 2.0).round() as usize).min(MODES.len() - 1)].to_string(),
+        _ => format!("{:.0}", value * 100.0),
+    }
+}
+
+// The variant is chosen once per instance, a shell only turns into one of
+// its sub plugins when the host asks for it through audioMasterCurrentId
+fn variant(host: HostCallbackProc) -> Option<Variant> {
+    let current_id = unsafe {
+        host(
+            ptr::null_mut(),
+            audioMasterCurrentId,
+            0,
+            0,
+            ptr::null_mut(),
+            0.0,
+        )
+    } as i32;
+    match env::var("VST2_TEST_PLUGIN").as_deref() {
+        Ok("shell") => match current_id {
+            0 => Some(Variant::Shell),
+            id => SHELL_PLUGINS
+                .into_iter()
+                .find(|variant| variant.unique_id() == id),
+        },
+        Ok("params") => Some(Variant::Params),
+        _ => Some(Variant::Chunks),
+    }
+}
+
+fn print_json_line() {
+    if env::var_os("VST2_TEST_PLUGIN_PRINT").is_some() {
+        println!(r#"{{"id":1,"result":"printed by the plugin"}}"#);
+    }
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn VSTPluginMain(host: HostCallbackProc) -> *mut AEffect {
+    print_json_line();
+    let Some(variant) = variant(host) else {
+        return ptr::null_mut();
+    };
+    let is_shell = variant == Variant::Shell;
+    let programs = if is_shell {
+        Vec::new()
+    } else {
+        factory_programs()
+    };
+
+    let mut flags = effFlagsCanReplacing;
+    if variant == Variant::Chunks {
+        flags |= effFlagsProgramChunks;
+    }
+
+    let plugin = Box::into_raw(Box::new(TestPlugin {
+        effect: AEffect {
+            magic: VST_MAGIC,
+            dispatcher,
+            process,
+            setParameter: set_parameter,
+            getParameter: get_parameter,
+            numPrograms: programs.len() as i32,
+            numParams: if is_shell { 0 } else { PARAMS.len() as i32 },
+            numInputs: if is_shell { 0 } else { 2 },
+            numOutputs: if is_shell { 0 } else { 2 },
+            flags,
+            resvd1: 0,
+            resvd2: 0,
+            initialDelay: if is_shell { 0 } else { INITIAL_DELAY },
+            realQualities: 0,
+            offQualities: 0,
+            ioRatio: 1.0,
+            object: ptr::null_mut(),
+            user: ptr::null_mut(),
+            uniqueID: variant.unique_id(),
+            version: VERSION,
+            processReplacing: process,
+            processDoubleReplacing: process_double,
+            future: [0; 56],
+        },
+        host,
+        variant,
+        programs,
+        current: 0,
+        chunk: Vec::new(),
+        next_shell_plugin: 0,
+    }));
+    unsafe {
+        (*plugin).effect.object = plugin as *mut c_void;
+        &mut (*plugin).effect
+    }
+}
+
+impl TestPlugin {
+    fn program_chunk(&self) -> Vec<u8> {
+        let mut chunk = PROGRAM_MAGIC.to_vec();
+        for value in self.programs[self.current].values {
+            chunk.extend(value.to_le_bytes());
+        }
+        chunk
+    }
+
+    fn bank_chunk(&self) -> Vec<u8> {
+        let mut chunk = BANK_MAGIC.to_vec();
+        chunk.extend((self.programs.len() as u32).to_le_bytes());
+        chunk.extend((self.current as u32).to_le_bytes());
+        for program in &self.programs {
+            let mut name = [0u8; PROGRAM_NAME_LEN];
+            let len = program.name.len().min(PROGRAM_NAME_LEN - 1);
+            name[..len].copy_from_slice(&program.name.as_bytes()[..len]);
+            chunk.extend(name);
+            for value in program.values {
+                chunk.extend(value.to_le_bytes());
+            }
+        }
+        chunk
+    }
+
+    fn load_program_chunk(&mut self, chunk: &[u8]) -> bool {
+        if chunk == CRASH_MAGIC {
+            std::process::abort();
+        }
+        let Some(values) = chunk.strip_prefix(PROGRAM_MAGIC).and_then(read_values) else {
+            return false;
+        };
+        self.programs[self.current].values = values;
+        print_json_line();
+        for (index, value) in values.into_iter().enumerate() {
+            unsafe {
+                (self.host)(
+                    &mut self.effect,
+                    audioMasterAutomate,
+                    index as i32,
+                    0,
+                    ptr::null_mut(),
+                    value,
+                )
+            };
+        }
+        true
+    }
+
+    fn load_bank_chunk(&mut self, chunk: &[u8]) -> bool {
+        let Some(data) = chunk.strip_prefix(BANK_MAGIC) else {
+            return false;
+        };
+        let program_len = PROGRAM_NAME_LEN + PARAMS.len() * 4;
+        let (Some(count), Some(current)) = (read_u32(data, 0), read_u32(data, 4)) else {
+            return false;
+        };
+        let programs = &data[8..];
+        if count != self.programs.len() || current >= count || programs.len() != count * program_len
+        {
+            return false;
+        }
+
+        for (program, data) in self.programs.iter_mut().zip(programs.chunks(program_len)) {
+            let name = &data[..PROGRAM_NAME_LEN];
+            let end = name.iter().position(|&c| c == 0).unwrap_or(name.len());
+            program.name = String::from_utf8_lossy(&name[..end]).to_string();
+            program.values = read_values(&data[PROGRAM_NAME_LEN..]).unwrap_or(program.values);
+        }
+        self.current = current;
+        true
+    }
+}
+
+fn read_u32(data: &[u8], offset: usize) -> Option<usize> {
+    let bytes = data.get(offset..offset + 4)?;
+    Some(u32::from_le_bytes(bytes.try_into().ok()?) as usize)
+}
+
+fn read_values(data: &[u8]) -> Option<[f32; 3]> {
+    if data.len() != PARAMS.len() * 4 {
+        return None;
+    }
+    let mut values = [0.0; 3];
+    for (value, bytes) in values.iter_mut().zip(data.chunks(4)) {
+        *value = f32::from_le_bytes(bytes.try_into().ok()?);
+    }
+    Some(values)
+}
+
+unsafe extern "C" fn dispatcher(
+    effect: *mut AEffect,
+    opcode: i32,
+    index: i32,
+    value: isize,
+    ptr: *mut c_void,
+    _opt: f32,
+) -> isize {
+    if opcode == effClose {
+        drop(unsafe { Box::from_raw((*effect).object as *mut TestPlugin) });
+        return 1;
+    }
+
+    let plugin = unsafe { plugin(effect) };
+    let param = usize::try_from(index)
+        .ok()
+        .and_then(|index| PARAMS.get(index));
+    match opcode {
+        effOpen => 0,
+        effSetProgram => {
+            if let Ok(program) = usize::try_from(value)
+                && program < plugin.programs.len()
+            {
+                plugin.current = program;
+            }
+            0
+        }
+        effGetProgram => plugin.current as isize,
+        effGetProgramName => {
+            if let Some(program) = plugin.programs.get(plugin.current) {
+                unsafe { copy_string(ptr, &program.name) };
+            }
+            0
+        }
+        effGetProgramNameIndexed => {
+            match usize::try_from(index)
+                .ok()
+                .and_then(|index| plugin.programs.get(index))
+            {
+                Some(program) => {
+                    unsafe { copy_string(ptr, &program.name) };
+                    1
+                }
+                None => 0,
+            }
+        }
+        effGetParamName | effGetParamLabel | effGetParamDisplay => {
+            let (Some(param), Some(program)) = (param, plugin.programs.get(plugin.current)) else {
+                return 0;
+            };
+            let string = match opcode {
+                effGetParamName => param.name.to_string(),
+                effGetParamLabel => param.label.to_string(),
+                _ => display(index as usize, program.values[index as usize]),
+            };
+            unsafe { copy_string(ptr, &string) };
+            0
+        }
+        effCanBeAutomated => param.is_some_and(|param| param.automatable) as isize,
+        effGetChunk if plugin.variant == Variant::Chunks && !ptr.is_null() => {
+            plugin.chunk = if index != 0 {
+                plugin.program_chunk()
+            } else {
+                plugin.bank_chunk()
+            };
+            unsafe { *(ptr as *mut *mut u8) = plugin.chunk.as_mut_ptr() };
+            plugin.chunk.len() as isize
+        }
+        effSetChunk if plugin.variant == Variant::Chunks && !ptr.is_null() => {
+            let chunk = unsafe { std::slice::from_raw_parts(ptr as *const u8, value as usize) };
+            let loaded = if index != 0 {
+                plugin.load_program_chunk(chunk)
+            } else {
+                plugin.load_bank_chunk(chunk)
+            };
+            loaded as isize
+        }
+        effGetPlugCategory if plugin.variant == Variant::Shell => kPlugCategShell,
+        effGetPlugCategory => kPlugCategEffect,
+        effGetVendorString => {
+            unsafe { copy_string(ptr, "DawProject") };
+            1
+        }
+        effGetProductString => {
+            unsafe { copy_string(ptr, plugin.variant.product()) };
+            1
+        }
+        effGetVendorVersion => VERSION as isize,
+        effCanDo => {
+            if ptr.is_null() {
+                return 0;
+            }
+            let feature = unsafe { CStr::from_ptr(ptr as *const c_char) };
+            match feature.to_bytes() {
+                b"bypass" => 1,
+                b"receiveVstMidiEvent" => -1,
+                _ => 0,
+            }
+        }
+        effGetVstVersion => 2400,
+        effShellGetNextPlugin if plugin.variant == Variant::Shell => {
+            let Some(variant) = SHELL_PLUGINS.get(plugin.next_shell_plugin) else {
+                return 0;
+            };
+            plugin.next_shell_plugin += 1;
+            unsafe { copy_string(ptr, variant.product()) };
+            variant.unique_id() as isize
+        }
+        _ => 0,
+    }
+}
+
+unsafe extern "C" fn set_parameter(effect: *mut AEffect, index: i32, value: f32) {
+    let plugin = unsafe { plugin(effect) };
+    let current = plugin.current;
+    if let Some(program) = plugin.programs.get_mut(current)
+        && let Some(param) = usize::try_from(index)
+            .ok()
+            .and_then(|index| program.values.get_mut(index))
+    {
+        *param = value.clamp(0.0, 1.0);
+    }
+}
+
+unsafe extern "C" fn get_parameter(effect: *mut AEffect, index: i32) -> f32 {
+    let plugin = unsafe { plugin(effect) };
+    plugin
+        .programs
+        .get(plugin.current)
+        .zip(usize::try_from(index).ok())
+        .and_then(|(program, index)| program.values.get(index).copied())
+        .unwrap_or(0.0)
+}
+
+unsafe extern "C" fn process(
+    _effect: *mut AEffect,
+    _inputs: *mut *mut f32,
+    _outputs: *mut *mut f32,
+    _frames: i32,
+) {
+}
+
+unsafe extern "C" fn process_double(
+    _effect: *mut AEffect,
+    _inputs: *mut *mut f64,
+    _outputs: *mut *mut f64,
+    _frames: i32,
+) {
+}
@@ -8,28 +8,272 @@
  * ------------------------------------------------------------------------
  */
 
-extern crate vst2;
-
 use std::env;
+use std::ffi::OsString;
+use std::fs;
 use std::panic;
-use std::path::PathBuf;
-use std::process::exit;
-use std::sync::{Arc, Mutex};
-
-use vst2::host::{Host, PluginLoader};
-use vst2::plugin::Plugin;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
 
 use base64::prelude::*;
+use clap::{Args, Parser, Subcommand};
+use serde_json::json;
+
+use dawproject_plugin_info::cache::PluginCache;
+use dawproject_plugin_info::output::{OS, error_exit, print_json};
+use dawproject_plugin_info::serve::{self, METHOD_NOT_FOUND};
+use dawproject_plugin_info::vst2::fxp::{self, FxContent};
+use dawproject_plugin_info::vst2::plugin::{self, PluginInstance};
+use dawproject_plugin_info::vst2::{Vst2, commands, host, resolve};
+use dawproject_plugin_info::vst3::stdio;
+use dawproject_plugin_info::{renoise_db, scan, state_diff};
+
+const CACHE_FILE: &str = "vst2info-cache.json";
+const SERVE_METHODS: [&str; 4] = ["inspect", "params", "applyState", "makePreset"];
+
+const SUBCOMMANDS: [&str; 14] = [
+    "info",
+    "params",
+    "preset",
+    "bank",
+    "apply-state",
+    "diff",
+    "shell",
+    "fxp",
+    "scan",
+    "cache",
+    "renoise-db",
+    "serve",
+    "worker",
+    "help",
+];
+
+#[derive(Parser)]
+#[command(
+    name = "vst2info-tool",
+    version,
+    about = "Extracts information and preset data from VST2 plugins",
+    after_help = "Calling the tool with only a plugin path and an optional preset number \
+                  behaves like `info <PLUGIN> --preset <NUMBER>`."
+)]
+struct Cli {
+    #[command(subcommand)]
+    command: Command,
+
+    /// Folder of the persistent plugin info cache, used by `info`, `scan`
+    /// and the search by unique ID
+    #[arg(long, global = true, value_name = "DIR")]
+    cache: Option<PathBuf>,
+
+    /// Inspects the plugins again, even if their cache entries are up to date
+    #[arg(long, global = true)]
+    refresh: bool,
+}
+
+#[derive(Args)]
+struct PluginArgs {
+    /// Plugin binary, .vst bundle, unique ID or name to search in the VST2 folders
+    #[arg(required_unless_present = "document_id")]
+    plugin: Option<String>,
+
+    /// Sub plugin id to instantiate from a shell plugin
+    #[arg(long, value_name = "ID")]
+    shell_id: Option<i32>,
+
+    /// Renoise plugin database (CachedVSTs_<arch>.db) to look up --document-id in
+    #[arg(long, value_name = "DB", requires = "document_id")]
+    renoise_db: Option<PathBuf>,
+
+    /// DocumentIdentifier of the plugin in the Renoise database, instead of PLUGIN
+    #[arg(
+        long,
+        value_name = "ID",
+        requires = "renoise_db",
+        conflicts_with = "plugin"
+    )]
+    document_id: Option<String>,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Plugin info, capabilities and optionally the data of a preset
+    Info {
+        #[command(flatten)]
+        plugin: PluginArgs,
+
+        /// Preset to select, adds its name and base64 chunk data
+        #[arg(long, value_name = "NUMBER")]
+        preset: Option<i32>,
+
+        /// Adds MIDI program and key names per channel
+        #[arg(long)]
+        midi_names: bool,
+    },
+    /// Parameter names, labels, display strings and values
+    Params {
+        #[command(flatten)]
+        plugin: PluginArgs,
+
+        /// Preset to select before reading the parameters
+        #[arg(long, value_name = "NUMBER")]
+        preset: Option<i32>,
+    },
+    /// Data of a single preset, optionally written as .fxp file
+    Preset {
+        #[command(flatten)]
+        plugin: PluginArgs,
+
+        /// Preset to select
+        #[arg(long, value_name = "NUMBER", default_value_t = 0)]
+        preset: i32,
+
+        /// Writes the preset as .fxp file
+        #[arg(long, value_name = "FILE")]
+        output: Option<PathBuf>,
+    },
+    /// Data of all presets, optionally written as .fxb file
+    Bank {
+        #[command(flatten)]
+        plugin: PluginArgs,
+
+        /// Writes the bank as .fxb file
+        #[arg(long, value_name = "FILE")]
+        output: Option<PathBuf>,
+    },
+    /// Loads a state chunk into the plugin and reports the resulting parameters
+    ApplyState {
+        #[command(flatten)]
+        plugin: PluginArgs,
+
+        /// Base64 encoded chunk, e.g. a Renoise ParameterChunk
+        #[arg(long, value_name = "BASE64", required_unless_present = "file")]
+        data: Option<String>,
+
+        /// File containing the raw chunk
+        #[arg(long, value_name = "FILE", conflicts_with = "data")]
+        file: Option<PathBuf>,
+
+        /// Loads the chunk as bank instead of program chunk
+        #[arg(long)]
+        bank: bool,
+    },
+    /// Loads two states one after another and lists the parameters that differ
+    Diff {
+        #[command(flatten)]
+        plugin: PluginArgs,
+
+        /// First state: an .fxp/.fxb file, a file with a raw chunk or base64 data
+        #[arg(long, value_name = "STATE")]
+        from: String,
+
+        /// Second state, like --from
+        #[arg(long, value_name = "STATE")]
+        to: String,
+
+        /// Loads raw chunks as bank instead of program chunks
+        #[arg(long)]
+        bank: bool,
+    },
+    /// Lists the sub plugins of a shell plugin
+    Shell {
+        #[command(flatten)]
+        plugin: PluginArgs,
+    },
+    /// Reads .fxp and .fxb files
+    Fxp {
+        #[command(subcommand)]
+        action: FxpAction,
+    },
+    /// Inspects all plugins below the given folders, one JSON object per line
+    Scan {
+        /// Folders or plugins to scan, defaults to VST_PATH and the standard VST2 folders
+        paths: Vec<PathBuf>,
+
+        /// Seconds after which a hanging plugin is given up
+        #[arg(long, value_name = "SECONDS", default_value_t = 30)]
+        timeout: u64,
+    },
+    /// Lists or prunes the entries of the plugin info cache given by --cache
+    Cache {
+        #[command(subcommand)]
+        action: CacheAction,
+    },
+    /// Reads the plugin database of Renoise
+    RenoiseDb {
+        #[command(subcommand)]
+        action: RenoiseDbAction,
+    },
+    /// Answers JSON-RPC requests on stdin/stdout, keeps the plugins loaded
+    Serve {
+        /// Seconds after which a hanging plugin is given up
+        #[arg(long, value_name = "SECONDS", default_value_t = 30)]
+        timeout: u64,
+    },
+    /// Hosts a single plugin for the serve mode
+    #[command(hide = true)]
+    Worker {
+        binary: PathBuf,
+
+        #[arg(long, value_name = "ID")]
+        shell_id: Option<i32>,
+    },
+}
+
+#[derive(Subcommand)]
+enum FxpAction {
+    /// Header fields, program names, parameter values and chunk sizes of
+    /// an .fxp or .fxb file, and the problems found in it
+    Inspect {
+        /// The .fxp or .fxb file
+        file: PathBuf,
+
+        /// Plugin binary, .vst bundle, unique ID or name the file should fit
+        #[arg(long, value_name = "PLUGIN")]
+        plugin: Option<String>,
+
+        /// Sub plugin id to instantiate from a shell plugin
+        #[arg(long, value_name = "ID", requires = "plugin")]
+        shell_id: Option<i32>,
+
+        /// Adds every field of the file with its offset
+        #[arg(long)]
+        fields: bool,
+    },
+}
 
-use json::object;
+#[derive(Subcommand)]
+enum RenoiseDbAction {
+    /// All plugins of the database with their DocumentIdentifier and file
+    List {
+        /// CachedVSTs_<arch>.db of Renoise
+        db: PathBuf,
+    },
+}
 
-struct SampleHost;
+#[derive(Subcommand)]
+enum CacheAction {
+    /// All cached plugins and whether their entries are still valid
+    List,
+    /// Removes entries of deleted or updated plugins
+    Prune,
+}
 
-impl Host for SampleHost {}
+// Keeps `vst2info-tool <plugin> [preset]`, as called by the Lua side, working
+fn legacy_args(args: Vec<String>) -> Vec<String> {
+    if args.len() < 2 || args[1].starts_with('-') || SUBCOMMANDS.contains(&args[1].as_str()) {
+        return args;
+    }
 
-fn error_exit(error_message: String) {
-    eprintln!("{}", json::stringify(object! { error: error_message }));
-    exit(1);
+    let mut legacy = vec![args[0].clone(), "info".to_string(), args[1].clone()];
+    let mut rest = args.into_iter().skip(2).peekable();
+    if let Some(preset) = rest.next_if(|arg| !arg.starts_with('-'))
+        && !preset.is_empty()
+    {
+        legacy.push("--preset".to_string());
+        legacy.push(preset);
+    }
+    legacy.extend(rest);
+    legacy
 }
 
 fn main() {
@@ -37,57 +281,269 @@ fn main() {
         // do nothing
     }));
 
-    let args: Vec<String> = env::args().collect();
+    let cli = Cli::parse_from(legacy_args(env::args().collect()));
+    let mut cache = cli.cache.as_deref().map(open_cache);
 
-    if args.len() < 2 {
-        error_exit(String::from("Plugin path required as first argument"));
-    }
+    // Binary whose info output gets cached, plain info calls only
+    let mut cache_binary = None;
+
+    let (instance, output) = match cli.command {
+        Command::Info {
+            plugin,
+            preset,
+            midi_names,
+        } => {
+            let binary = resolve_plugin(&plugin, cache.as_mut());
+            if cache.is_some() && preset.is_none() && !midi_names && plugin.shell_id.is_none() {
+                if !cli.refresh
+                    && let Some(info) = cache.as_ref().and_then(|cache| cache.get(&binary))
+                {
+                    print_json(&info);
+                    return;
+                }
+                cache_binary = Some(binary.clone());
+            }
+
+            let instance = load_binary(&binary, &plugin);
+            let output = commands::info(&instance, preset, midi_names);
+            (instance, output)
+        }
+        Command::Params { plugin, preset } => {
+            let instance = load_plugin(&plugin, cache.as_mut());
+            let output = commands::params(&instance, preset);
+            (instance, output)
+        }
+        Command::Preset {
+            plugin,
+            preset,
+            output: output_path,
+        } => {
+            let instance = load_plugin(&plugin, cache.as_mut());
+            let output = commands::preset(&instance, preset, output_path.as_deref())
+                .unwrap_or_else(|e| error_exit(e));
+            (instance, output)
+        }
+        Command::Bank {
+            plugin,
+            output: output_path,
+        } => {
+            let instance = load_plugin(&plugin, cache.as_mut());
+            let output =
+                commands::bank(&instance, output_path.as_deref()).unwrap_or_else(|e| error_exit(e));
+            (instance, output)
+        }
+        Command::ApplyState {
+            plugin,
+            data,
+            file,
+            bank,
+        } => {
+            let state = match (data, file) {
+                (Some(data), _) => commands::decode_state(&data).unwrap_or_else(|e| error_exit(e)),
+                (None, Some(file)) => fs::read(&file).unwrap_or_else(|e| {
+                    error_exit(format!("Failed to read {}: {}", file.display(), e))
+                }),
+                (None, None) => error_exit("State data or file required"),
+            };
 
-    let mut path = PathBuf::from(&args[1]);
-    let path_string: String = path.display().to_string();
+            let instance = load_plugin(&plugin, cache.as_mut());
+            let output = commands::apply_state(&instance, &state, bank);
+            (instance, output)
+        }
+        Command::Diff {
+            plugin,
+            from,
+            to,
+            bank,
+        } => {
+            let from = state_diff::read_state(&from).unwrap_or_else(|e| error_exit(e));
+            let to = state_diff::read_state(&to).unwrap_or_else(|e| error_exit(e));
 
-    if env::consts::OS == "macos" && path.is_dir() && path_string.ends_with(".vst") {
-        let file_name = path.file_name().unwrap().to_str().unwrap_or("");
-        if file_name != "" {
-            path = PathBuf::from(
-                path_string + "/Contents/MacOS/" + file_name.replace(".vst", "").as_str(),
+            let instance = load_plugin(&plugin, cache.as_mut());
+            let output = commands::diff(&instance, &from, &to, bank);
+            (instance, output)
+        }
+        Command::Shell { plugin } => {
+            let instance = load_plugin(&plugin, cache.as_mut());
+            let output = commands::shell(&instance);
+            (instance, output)
+        }
+        Command::Fxp {
+            action:
+                FxpAction::Inspect {
+                    file,
+                    plugin,
+                    shell_id,
+                    fields,
+                },
+        } => {
+            let mut fx_file = fxp::read_fx_file(&file).unwrap_or_else(|e| error_exit(e));
+            if let Some(plugin) = plugin {
+                let binary = resolve::resolve_plugin(&plugin, cache.as_mut())
+                    .unwrap_or_else(|e| error_exit(e));
+                if let Some(shell_id) = shell_id {
+                    host::set_current_id(shell_id);
+                }
+                let instance = plugin::load(&binary).unwrap_or_else(|e| error_exit(e));
+                fx_file.check_plugin(
+                    instance.unique_id(),
+                    instance.count_parameters(),
+                    instance.count_presets(),
+                );
+            }
+
+            let mut output = json!({
+                "file": file.display().to_string(),
+                "size": fs::metadata(&file).map_or(0, |metadata| metadata.len())
+            });
+            let chunk = match &fx_file.content {
+                FxContent::Program(program) => {
+                    output["program"] = json!(program);
+                    program.is_chunk.then_some(&program.chunk)
+                }
+                FxContent::Bank(bank) => {
+                    output["bank"] = json!(bank);
+                    bank.is_chunk.then_some(&bank.chunk)
+                }
+            };
+            if let Some(chunk) = chunk {
+                output["chunkData"] = json!(BASE64_STANDARD.encode(chunk));
+            }
+            if fields {
+                output["fields"] = json!(fx_file.fields);
+            }
+            output["issues"] = json!(fx_file.issues);
+            output["valid"] = json!(fx_file.issues.is_empty());
+            output["os"] = json!(OS);
+            print_json(&output);
+            return;
+        }
+        Command::Scan { paths, timeout } => {
+            scan::run_scan(
+                &Vst2,
+                paths,
+                Duration::from_secs(timeout),
+                cache.as_mut(),
+                cli.refresh,
             );
+            return;
+        }
+        Command::Cache { action } => {
+            let Some(cache) = cache.as_mut() else {
+                error_exit("Cache folder required, see --cache");
+            };
+            let output = match action {
+                CacheAction::List => {
+                    let entries = cache.list();
+                    json!({
+                        "count": entries.len(),
+                        "entries": entries,
+                        "os": OS
+                    })
+                }
+                CacheAction::Prune => {
+                    let removed = cache.prune();
+                    save_cache(cache);
+                    json!({
+                        "removed": removed,
+                        "os": OS
+                    })
+                }
+            };
+            print_json(&output);
+            return;
+        }
+        Command::RenoiseDb {
+            action: RenoiseDbAction::List { db },
+        } => {
+            let plugins = renoise_db::list_plugins(&db).unwrap_or_else(|e| error_exit(e));
+            print_json(&json!({
+                "count": plugins.len(),
+                "plugins": plugins,
+                "os": OS
+            }));
+            return;
         }
+        Command::Serve { timeout } => {
+            serve::run_serve(Duration::from_secs(timeout), |method, params| {
+                worker_args(method, params, cache.as_mut())
+            });
+            return;
+        }
+        Command::Worker { binary, shell_id } => {
+            if let Some(shell_id) = shell_id {
+                host::set_current_id(shell_id);
+            }
+            // Plugins printing to stdout can't be taken for a response
+            let instance = stdio::suppress_output(|| plugin::load(&binary));
+            serve::run_worker(instance.map(|instance| {
+                move |method: &str, params: &serde_json::Value| {
+                    stdio::suppress_output(|| commands::worker_request(&instance, method, params))
+                }
+            }));
+            return;
+        }
+    };
+
+    let output = commands::complete_output(&instance, output);
+    if let (Some(cache), Some(binary)) = (cache.as_mut(), cache_binary) {
+        cache.insert(&binary, output.clone());
+        // the info itself is still fine, even if the cache can't be written
+        let _ = cache.save();
     }
+    print_json(&output);
+}
 
-    if !path.exists() || path.is_dir() {
-        error_exit(path.display().to_string() + " does not exist");
+fn open_cache(dir: &Path) -> PluginCache {
+    PluginCache::open(dir, CACHE_FILE).unwrap_or_else(|e| error_exit(e))
+}
+
+fn save_cache(cache: &PluginCache) {
+    if let Err(e) = cache.save() {
+        error_exit(e);
     }
+}
 
-    let host = Arc::new(Mutex::new(SampleHost));
-    let mut loader =
-        PluginLoader::load(&path, host.clone()).unwrap_or_else(|e| panic!("{}", e.to_string()));
-
-    let mut instance = loader.instance().unwrap();
-    let info = instance.get_info();
-
-    let mut output = object! {
-        name: info.name,
-        vendor: info.vendor,
-        countPresets: info.presets,
-        countParameters: info.parameters,
-        countInputs: info.inputs,
-        countOutputs: info.outputs,
-        id: info.unique_id,
-        version: info.version,
-        delay: info.initial_delay,
-        os: env::consts::OS
+fn resolve_plugin(args: &PluginArgs, cache: Option<&mut PluginCache>) -> PathBuf {
+    let resolved = match (&args.renoise_db, &args.document_id, &args.plugin) {
+        (Some(db), Some(document_id), _) => {
+            renoise_db::plugin_path(db, document_id).and_then(|path| resolve::resolve_binary(&path))
+        }
+        (_, _, Some(plugin)) => resolve::resolve_plugin(plugin, cache),
+        _ => Err("Plugin required".to_string()),
     };
+    resolved.unwrap_or_else(|e| error_exit(e))
+}
+
+// Workers of the serve mode host one plugin, or one sub plugin of a shell
+fn worker_args(
+    method: &str,
+    params: &serde_json::Value,
+    mut cache: Option<&mut PluginCache>,
+) -> Result<Vec<OsString>, serve::RpcError> {
+    if !SERVE_METHODS.contains(&method) {
+        return Err((METHOD_NOT_FOUND, format!("Unknown method {}", method)));
+    }
+    let binary = serve::plugin_binary(params, |spec| {
+        resolve::resolve_plugin(spec, cache.as_deref_mut())
+    })?;
+
+    let mut args = vec![OsString::from("worker"), binary.into_os_string()];
+    if let Some(shell_id) = params["shellId"].as_i64() {
+        args.push(OsString::from("--shell-id"));
+        args.push(OsString::from(shell_id.to_string()));
+    }
+    Ok(args)
+}
+
+fn load_plugin(args: &PluginArgs, cache: Option<&mut PluginCache>) -> PluginInstance {
+    load_binary(&resolve_plugin(args, cache), args)
+}
 
-    if (args.len() > 2) && (&args[2].to_string()).len() > 0 {
-        let preset_num = (&args[2].to_string()).parse().unwrap();
-        instance.change_preset(preset_num);
-        output.insert("presetName", instance.get_preset_name(preset_num));
-        output.insert(
-            "presetData",
-            BASE64_STANDARD.encode(instance.get_preset_data()),
-        );
+fn load_binary(binary: &Path, args: &PluginArgs) -> PluginInstance {
+    if let Some(shell_id) = args.shell_id {
+        host::set_current_id(shell_id);
     }
 
-    println!("{}", json::stringify(output));
+    plugin::load(binary).unwrap_or_else(|e| error_exit(e))
 }
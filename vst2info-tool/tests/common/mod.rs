@@ -0,0 +1,57 @@
+// Helpers shared by the vst2info-tool tests, each test crate uses only some
+
+#![allow(dead_code)]
+
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Output};
+use std::sync::OnceLock;
+
+use serde_json::Value;
+
+// The vst2-test-plugin of the workspace, copied into a fresh temporary
+// folder
+pub fn test_plugin() -> &'static Path {
+    static PLUGIN: OnceLock<PathBuf> = OnceLock::new();
+    PLUGIN.get_or_init(|| {
+        let status = Command::new(env::var("CARGO").unwrap_or_else(|_| "cargo".to_string()))
+            .args(["build", "-p", "vst2-test-plugin"])
+            .status()
+            .unwrap();
+        assert!(status.success(), "vst2-test-plugin builds");
+
+        let target_dir = Path::new(env!("CARGO_BIN_EXE_vst2info-tool"))
+            .parent()
+            .unwrap();
+        let dir = env::temp_dir().join(format!("vst2info-mock-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let plugin = dir.join("TestPlugin.so");
+        fs::copy(target_dir.join("libvst2_test_plugin.so"), &plugin).unwrap();
+        plugin
+    })
+}
+
+pub fn plugin_arg() -> &'static str {
+    test_plugin().to_str().unwrap()
+}
+
+// A program chunk of the test plugin, little endian
+pub fn program_chunk(values: [f32; 3]) -> Vec<u8> {
+    let mut chunk = b"DPP2".to_vec();
+    for value in values {
+        chunk.extend(value.to_le_bytes());
+    }
+    chunk
+}
+
+pub fn vst2info_tool() -> Command {
+    Command::new(env!("CARGO_BIN_EXE_vst2info-tool"))
+}
+
+pub fn error_message(output: &Output) -> String {
+    assert!(!output.status.success());
+    let error: Value = serde_json::from_slice(&output.stderr).unwrap();
+    error["error"].as_str().unwrap().to_string()
+}
@@ -0,0 +1,558 @@
+// Info output, preset data and .fxp/.fxb files of the vst2-test-plugin of
+// the workspace, in all its variants
+
+#![cfg(target_os = "linux")]
+
+mod common;
+
+use std::fs;
+use std::path::PathBuf;
+
+use base64::prelude::*;
+use serde_json::{Value, json};
+
+use common::{plugin_arg, program_chunk, test_plugin, vst2info_tool};
+
+const CHUNKS_ID: i32 = i32::from_be_bytes(*b"DPc2");
+const PARAMS_ID: i32 = i32::from_be_bytes(*b"DPp2");
+const SHELL_ID: i32 = i32::from_be_bytes(*b"DPs2");
+const VERSION: i32 = 1100;
+
+// Values of the factory programs Init, Bright and Dark
+const PROGRAMS: [(&str, [f32; 3]); 3] = [
+    ("Init", [0.5, 0.0, 1.0]),
+    ("Bright", [0.75, 0.5, 0.25]),
+    ("Dark", [0.25, 1.0, 0.5]),
+];
+
+fn output_file(name: &str) -> PathBuf {
+    test_plugin().with_file_name(name)
+}
+
+// Runs the tool with the given variant of the test plugin
+fn run_text(variant: &str, args: &[&str]) -> String {
+    let output = vst2info_tool()
+        .env("VST2_TEST_PLUGIN", variant)
+        .args(args)
+        .output()
+        .unwrap();
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    String::from_utf8(output.stdout).unwrap()
+}
+
+fn run(variant: &str, args: &[&str]) -> Value {
+    serde_json::from_str(&run_text(variant, args)).unwrap()
+}
+
+// The bank chunk format of the test plugin, little endian
+fn bank_chunk() -> Vec<u8> {
+    let mut chunk = b"DPB2".to_vec();
+    chunk.extend(3u32.to_le_bytes());
+    chunk.extend(0u32.to_le_bytes());
+    for (name, values) in PROGRAMS {
+        let mut name_bytes = [0u8; 24];
+        name_bytes[..name.len()].copy_from_slice(name.as_bytes());
+        chunk.extend(name_bytes);
+        for value in values {
+            chunk.extend(value.to_le_bytes());
+        }
+    }
+    chunk
+}
+
+// The fxp/fxb layout of vstfxstore.h, big endian
+fn fx_chunk(content: Vec<u8>) -> Vec<u8> {
+    let mut chunk = b"CcnK".to_vec();
+    chunk.extend((content.len() as i32).to_be_bytes());
+    chunk.extend(content);
+    chunk
+}
+
+fn fx_program_header(magic: &[u8; 4], unique_id: i32, name: &str) -> Vec<u8> {
+    let mut content = magic.to_vec();
+    for value in [1, unique_id, VERSION, 3] {
+        content.extend(i32::to_be_bytes(value));
+    }
+    let mut name_bytes = [0u8; 28];
+    name_bytes[..name.len()].copy_from_slice(name.as_bytes());
+    content.extend(name_bytes);
+    content
+}
+
+fn fx_bank_header(magic: &[u8; 4], unique_id: i32) -> Vec<u8> {
+    let mut content = magic.to_vec();
+    for value in [2, unique_id, VERSION, 3, 0] {
+        content.extend(i32::to_be_bytes(value));
+    }
+    content.extend([0u8; 124]);
+    content
+}
+
+fn fx_parameter_program(unique_id: i32, (name, values): (&str, [f32; 3])) -> Vec<u8> {
+    let mut content = fx_program_header(b"FxCk", unique_id, name);
+    for value in values {
+        content.extend(value.to_be_bytes());
+    }
+    fx_chunk(content)
+}
+
+#[test]
+fn info_of_the_chunk_variant() {
+    let output = run_text("chunks", &[plugin_arg()]);
+
+    let expected = json!({
+        "name": "Test Chunks",
+        "vendor": "DawProject",
+        "countPresets": 3,
+        "countParameters": 3,
+        "countInputs": 2,
+        "countOutputs": 2,
+        "id": CHUNKS_ID,
+        "version": VERSION,
+        "delay": 32,
+        "category": "effect",
+        "flags": {
+            "hasEditor": false,
+            "canReplacing": true,
+            "canDoubleReplacing": false,
+            "programChunks": true,
+            "isSynth": false,
+            "noSoundInStop": false
+        },
+        "canDo": {
+            "receiveVstMidiEvent": -1,
+            "sendVstMidiEvent": 0,
+            "offline": 0,
+            "bypass": 1
+        },
+        "countMidiInputs": 0,
+        "countMidiOutputs": 0,
+        "loadedFile": plugin_arg(),
+        "diagnostics": [],
+        "os": "linux"
+    });
+    // Compared as text, the field order matters to the Lua side
+    assert_eq!(output, expected.to_string() + "\n");
+}
+
+#[test]
+fn legacy_preset_argument_adds_the_program_chunk() {
+    let output = run("chunks", &[plugin_arg(), "1"]);
+
+    assert_eq!(output["presetName"], "Bright");
+    assert_eq!(
+        output["presetData"],
+        BASE64_STANDARD.encode(program_chunk(PROGRAMS[1].1))
+    );
+}
+
+#[test]
+fn params_of_a_selected_program() {
+    let output = run("params", &["params", plugin_arg(), "--preset", "2"]);
+
+    assert_eq!(output["id"], PARAMS_ID);
+    assert_eq!(output["presetName"], "Dark");
+    assert_eq!(
+        output["parameters"],
+        json!([
+            { "name": "Gain", "label": "dB", "display": "-6.0", "index": 0, "value": 0.25, "automatable": true },
+            { "name": "Mode", "label": "", "display": "Fold", "index": 1, "value": 1.0, "automatable": true },
+            { "name": "Mix", "label": "%", "display": "50", "index": 2, "value": 0.5, "automatable": false }
+        ])
+    );
+}
+
+#[test]
+fn fxp_of_a_chunk_program() {
+    let file = output_file("chunks.fxp");
+    let output = run(
+        "chunks",
+        &[
+            "preset",
+            plugin_arg(),
+            "--preset",
+            "1",
+            "--output",
+            file.to_str().unwrap(),
+        ],
+    );
+
+    let chunk = program_chunk(PROGRAMS[1].1);
+    assert_eq!(output["isChunk"], true);
+    assert_eq!(output["presetName"], "Bright");
+    assert_eq!(output["presetData"], BASE64_STANDARD.encode(&chunk));
+    assert_eq!(output["file"], file.to_str().unwrap());
+
+    let mut content = fx_program_header(b"FPCh", CHUNKS_ID, "Bright");
+    content.extend((chunk.len() as i32).to_be_bytes());
+    content.extend(chunk);
+    assert_eq!(fs::read(&file).unwrap(), fx_chunk(content));
+}
+
+#[test]
+fn fxp_of_a_parameter_program() {
+    let file = output_file("params.fxp");
+    let output = run(
+        "params",
+        &[
+            "preset",
+            plugin_arg(),
+            "--preset",
+            "2",
+            "--output",
+            file.to_str().unwrap(),
+        ],
+    );
+
+    // Without chunks there is no preset data, only the parameter values
+    assert_eq!(output["isChunk"], false);
+    assert_eq!(output["presetName"], "Dark");
+    assert_eq!(output["presetData"], "");
+    assert_eq!(
+        fs::read(&file).unwrap(),
+        fx_parameter_program(PARAMS_ID, PROGRAMS[2])
+    );
+}
+
+#[test]
+fn fxb_of_a_chunk_bank() {
+    let file = output_file("chunks.fxb");
+    let output = run(
+        "chunks",
+        &["bank", plugin_arg(), "--output", file.to_str().unwrap()],
+    );
+
+    let chunk = bank_chunk();
+    assert_eq!(output["isChunk"], true);
+    assert_eq!(output["countPresets"], 3);
+    assert_eq!(output["bankData"], BASE64_STANDARD.encode(&chunk));
+
+    let mut content = fx_bank_header(b"FBCh", CHUNKS_ID);
+    content.extend((chunk.len() as i32).to_be_bytes());
+    content.extend(chunk);
+    assert_eq!(fs::read(&file).unwrap(), fx_chunk(content));
+}
+
+#[test]
+fn fxb_of_a_parameter_bank() {
+    let file = output_file("params.fxb");
+    let output = run(
+        "params",
+        &["bank", plugin_arg(), "--output", file.to_str().unwrap()],
+    );
+
+    assert_eq!(output["isChunk"], false);
+    assert_eq!(output["bankData"], "");
+
+    let mut content = fx_bank_header(b"FxBk", PARAMS_ID);
+    for program in PROGRAMS {
+        content.extend(fx_parameter_program(PARAMS_ID, program));
+    }
+    assert_eq!(fs::read(&file).unwrap(), fx_chunk(content));
+}
+
+#[test]
+fn apply_state_loads_a_program_chunk() {
+    let data = BASE64_STANDARD.encode(program_chunk([1.0, 0.5, 0.0]));
+    let output = run("chunks", &["apply-state", plugin_arg(), "--data", &data]);
+
+    assert_eq!(output["applied"], true);
+    assert_eq!(output["stateSize"], 16);
+    assert_eq!(output["parameters"][0]["display"], "12.0");
+    assert_eq!(output["parameters"][1]["display"], "Hard");
+    // Only the calls of loading the state, as it reported its values
+    assert_eq!(
+        output["diagnostics"],
+        json!([
+            { "call": "automate", "index": 0, "value": 1.0 },
+            { "call": "automate", "index": 1, "value": 0.5 },
+            { "call": "automate", "index": 2, "value": 0.0 }
+        ])
+    );
+
+    let data = BASE64_STANDARD.encode(b"no chunk");
+    let output = run("chunks", &["apply-state", plugin_arg(), "--data", &data]);
+    assert_eq!(output["applied"], false);
+    assert_eq!(output["parameters"][0]["value"], 0.5);
+}
+
+#[test]
+fn shell_lists_and_loads_its_sub_plugins() {
+    let output = run("shell", &["shell", plugin_arg()]);
+
+    assert_eq!(output["id"], SHELL_ID);
+    assert_eq!(output["category"], "shell");
+    assert_eq!(
+        output["plugins"],
+        json!([
+            { "name": "Test Chunks", "id": CHUNKS_ID },
+            { "name": "Test Params", "id": PARAMS_ID }
+        ])
+    );
+
+    let shell_id = PARAMS_ID.to_string();
+    let output = run(
+        "shell",
+        &[
+            "info",
+            plugin_arg(),
+            "--shell-id",
+            &shell_id,
+            "--preset",
+            "1",
+        ],
+    );
+    assert_eq!(output["name"], "Test Params");
+    assert_eq!(output["flags"]["programChunks"], false);
+    assert_eq!(output["presetName"], "Bright");
+    assert_eq!(output["presetData"], "");
+}
+
+#[test]
+fn fxp_inspect_reads_the_written_files() {
+    let file = output_file("inspect.fxp");
+    run(
+        "chunks",
+        &[
+            "preset",
+            plugin_arg(),
+            "--preset",
+            "2",
+            "--output",
+            file.to_str().unwrap(),
+        ],
+    );
+    let output = run(
+        "chunks",
+        &[
+            "fxp",
+            "inspect",
+            file.to_str().unwrap(),
+            "--plugin",
+            plugin_arg(),
+        ],
+    );
+
+    assert_eq!(
+        output["program"],
+        json!({
+            "byteSize": 68,
+            "isChunk": true,
+            "version": 1,
+            "uniqueId": CHUNKS_ID,
+            "fxVersion": VERSION,
+            "numParams": 3,
+            "name": "Dark",
+            "parameters": [],
+            "chunkSize": 16
+        })
+    );
+    assert_eq!(
+        output["chunkData"],
+        BASE64_STANDARD.encode(program_chunk(PROGRAMS[2].1))
+    );
+    assert_eq!(output["issues"], json!([]));
+    assert_eq!(output["valid"], true);
+
+    let file = output_file("inspect.fxb");
+    run(
+        "params",
+        &["bank", plugin_arg(), "--output", file.to_str().unwrap()],
+    );
+    let output = run(
+        "params",
+        &[
+            "fxp",
+            "inspect",
+            file.to_str().unwrap(),
+            "--plugin",
+            plugin_arg(),
+        ],
+    );
+
+    assert_eq!(output["bank"]["numPrograms"], 3);
+    assert_eq!(output["bank"]["currentProgram"], 0);
+    assert_eq!(output["bank"]["programs"][1]["name"], "Bright");
+    assert_eq!(
+        output["bank"]["programs"][1]["parameters"],
+        json!(PROGRAMS[1].1)
+    );
+    assert!(output.get("chunkData").is_none());
+    assert_eq!(output["valid"], true);
+}
+
+#[test]
+fn fxp_inspect_reports_problems() {
+    // 28 characters leave no room for the terminating zero
+    let name = "A program name of 28 chars..";
+    let mut content = b"FxCk".to_vec();
+    for value in [1, PARAMS_ID, VERSION, 2] {
+        content.extend(i32::to_be_bytes(value));
+    }
+    content.extend(name.as_bytes());
+    content.extend(0.5f32.to_be_bytes());
+    content.extend(0.25f32.to_be_bytes());
+    let mut data = b"CcnK".to_vec();
+    data.extend(100i32.to_be_bytes());
+    data.extend(content);
+    data.extend(b"junk");
+    let file = output_file("broken.fxp");
+    fs::write(&file, data).unwrap();
+
+    let output = run(
+        "chunks",
+        &[
+            "fxp",
+            "inspect",
+            file.to_str().unwrap(),
+            "--plugin",
+            plugin_arg(),
+            "--fields",
+        ],
+    );
+
+    assert_eq!(output["program"]["name"], name);
+    assert_eq!(output["program"]["parameters"], json!([0.5, 0.25]));
+    assert_eq!(
+        output["issues"],
+        json!([
+            "prgName is not null terminated",
+            "byteSize 100 does not match the 56 bytes following it",
+            "4 bytes of trailing data",
+            format!(
+                "fxID {} does not match the plugin's {}",
+                PARAMS_ID, CHUNKS_ID
+            ),
+            format!("numParams 2 of {:?} does not match the plugin's 3", name),
+        ])
+    );
+    assert_eq!(
+        output["fields"][8],
+        json!({ "name": "params[0]", "value": 0.5, "offset": 56 })
+    );
+    assert_eq!(
+        output["fields"][9],
+        json!({ "name": "params[1]", "value": 0.25, "offset": 60 })
+    );
+}
+
+#[test]
+fn fxp_inspect_rejects_other_files() {
+    let file = output_file("other.fxp");
+    fs::write(&file, b"VST3 and some more bytes").unwrap();
+
+    let output = vst2info_tool()
+        .args(["fxp", "inspect", file.to_str().unwrap()])
+        .output()
+        .unwrap();
+    assert_eq!(output.status.code(), Some(1));
+    assert_eq!(
+        String::from_utf8(output.stderr).unwrap(),
+        "{\"error\":\"Not an .fxp or .fxb file\"}\n"
+    );
+}
+
+#[test]
+fn diff_of_a_chunk_and_an_fxp() {
+    let from = BASE64_STANDARD.encode(program_chunk(PROGRAMS[0].1));
+    let file = output_file("diff-bright.fxp");
+    let chunk = program_chunk(PROGRAMS[1].1);
+    let mut content = fx_program_header(b"FPCh", CHUNKS_ID, "Bright");
+    content.extend((chunk.len() as i32).to_be_bytes());
+    content.extend(chunk);
+    fs::write(&file, fx_chunk(content)).unwrap();
+
+    let output = run(
+        "chunks",
+        &[
+            "diff",
+            plugin_arg(),
+            "--from",
+            &from,
+            "--to",
+            file.to_str().unwrap(),
+        ],
+    );
+
+    assert_eq!(
+        output["from"],
+        json!({ "kind": "chunk", "chunkSize": 16, "loaded": true })
+    );
+    assert_eq!(
+        output["to"],
+        json!({ "kind": "fxp", "chunkSize": 16, "loaded": true })
+    );
+    // The first differing byte is in the Gain value
+    assert_eq!(
+        output["chunks"],
+        json!({ "sizeDifference": 0, "firstDifferentByte": 6, "identical": false })
+    );
+    assert_eq!(output["countParameters"], 3);
+    assert_eq!(
+        output["differences"],
+        json!([
+            {
+                "index": 0, "id": 0, "name": "Gain",
+                "fromDisplay": "0.0", "toDisplay": "6.0", "fromValue": 0.5, "toValue": 0.75
+            },
+            {
+                "index": 1, "id": 1, "name": "Mode",
+                "fromDisplay": "Soft", "toDisplay": "Hard", "fromValue": 0.0, "toValue": 0.5
+            },
+            {
+                "index": 2, "id": 2, "name": "Mix",
+                "fromDisplay": "100", "toDisplay": "25", "fromValue": 1.0, "toValue": 0.25
+            }
+        ])
+    );
+}
+
+#[test]
+fn diff_of_equal_parameter_programs() {
+    let file = output_file("diff-dark.fxp");
+    fs::write(&file, fx_parameter_program(PARAMS_ID, PROGRAMS[2])).unwrap();
+    let file = file.to_str().unwrap();
+
+    let output = run(
+        "params",
+        &["diff", plugin_arg(), "--from", file, "--to", file],
+    );
+
+    assert_eq!(
+        output["to"],
+        json!({ "kind": "fxp", "chunkSize": null, "loaded": true })
+    );
+    assert!(output["chunks"].is_null());
+    assert_eq!(output["countDifferences"], 0);
+    assert_eq!(output["differences"], json!([]));
+}
+
+#[test]
+fn unique_ids_are_resolved_from_the_cache() {
+    let cache = output_file("resolve-cache");
+    let _ = fs::remove_dir_all(&cache);
+    let folder = test_plugin().parent().unwrap();
+    let resolve = |variant: &str| {
+        vst2info_tool()
+            .env("VST2_TEST_PLUGIN", variant)
+            .env("VST_PATH", folder)
+            .args(["--cache", cache.to_str().unwrap(), "info", "DPc2"])
+            .output()
+            .unwrap()
+    };
+
+    let output = resolve("chunks");
+    assert!(output.status.success());
+    let cached = fs::read_to_string(cache.join("vst2info-cache.json")).unwrap();
+    assert!(cached.contains("Test Chunks"));
+
+    // The params variant has another ID, found only through the cache
+    let output = resolve("params");
+    let info: Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(info["id"], CHUNKS_ID);
+    assert_eq!(info["loadedFile"], plugin_arg());
+}
@@ -0,0 +1,121 @@
+// Looks up plugins in a fixture standing in for Renoise's CachedVSTs_<arch>.db
+
+mod common;
+
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+use std::process::Output;
+
+use rusqlite::Connection;
+use serde_json::Value;
+
+use common::{error_message, vst2info_tool};
+
+fn fixture_db(name: &str) -> PathBuf {
+    let dir = env::temp_dir().join(format!("vst2info-tool-{}-{}", name, std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let db = dir.join("CachedVSTs_x64.db");
+    let _ = fs::remove_file(&db);
+
+    let connection = Connection::open(&db).unwrap();
+    connection
+        .execute_batch(
+            "CREATE TABLE CachedPlugins (
+                 DocumentIdentifier TEXT PRIMARY KEY,
+                 LocalFilePath TEXT,
+                 IsFavorite INTEGER
+             );",
+        )
+        .unwrap();
+    for (document_id, path) in [
+        ("Missing", Some("/does/not/exist/Missing.so".to_string())),
+        ("It's quoted", Some("/does/not/exist/Quoted.so".to_string())),
+        ("Existing", Some(dir.display().to_string())),
+        ("No File", None),
+    ] {
+        connection
+            .execute(
+                "INSERT INTO CachedPlugins (DocumentIdentifier, LocalFilePath, IsFavorite) \
+                 VALUES (?1, ?2, 0)",
+                (document_id, path),
+            )
+            .unwrap();
+    }
+    db
+}
+
+fn run(args: &[&str]) -> Output {
+    vst2info_tool().args(args).output().unwrap()
+}
+
+#[test]
+fn lists_all_plugins() {
+    let db = fixture_db("list");
+    let output = run(&["renoise-db", "list", db.to_str().unwrap()]);
+    assert!(output.status.success());
+
+    let list: Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(list["count"], 4);
+    assert_eq!(list["plugins"][0]["documentId"], "Existing");
+    assert_eq!(list["plugins"][0]["exists"], true);
+    assert_eq!(list["plugins"][1]["documentId"], "It's quoted");
+    assert_eq!(list["plugins"][1]["path"], "/does/not/exist/Quoted.so");
+    assert_eq!(list["plugins"][2]["exists"], false);
+    // Rows without a file are listed as well
+    assert_eq!(list["plugins"][3]["documentId"], "No File");
+    assert!(list["plugins"][3]["path"].is_null());
+    assert_eq!(list["plugins"][3]["exists"], false);
+}
+
+#[test]
+fn resolves_document_ids_with_quotes() {
+    let db = fixture_db("quoted");
+    let output = run(&[
+        "info",
+        "--renoise-db",
+        db.to_str().unwrap(),
+        "--document-id",
+        "It's quoted",
+    ]);
+    assert!(!output.status.success());
+    // found in the database, but the plugin file itself is missing
+    assert_eq!(
+        error_message(&output),
+        "/does/not/exist/Quoted.so does not exist"
+    );
+}
+
+#[test]
+fn reports_unknown_document_ids() {
+    let db = fixture_db("unknown");
+    let output = run(&[
+        "params",
+        "--renoise-db",
+        db.to_str().unwrap(),
+        "--document-id",
+        "' OR '1'='1",
+    ]);
+    assert!(!output.status.success());
+    assert!(error_message(&output).starts_with("' OR '1'='1 not found in "));
+}
+
+#[test]
+fn reports_document_ids_without_a_file() {
+    let db = fixture_db("no-file");
+    let output = run(&[
+        "info",
+        "--renoise-db",
+        db.to_str().unwrap(),
+        "--document-id",
+        "No File",
+    ]);
+    assert!(error_message(&output).starts_with("No File has no plugin file in "));
+}
+
+#[test]
+fn reports_missing_databases() {
+    let output = run(&["renoise-db", "list", "/does/not/exist/CachedVSTs_x64.db"]);
+    assert!(!output.status.success());
+    assert!(error_message(&output).starts_with("Failed to open /does/not/exist/CachedVSTs_x64.db"));
+}
@@ -0,0 +1,238 @@
+// Drives the serve mode over pipes, the way the Lua side would
+
+mod common;
+
+use std::fs;
+use std::io::{BufRead, BufReader, Write};
+use std::process::{Child, ChildStdin, ChildStdout, Command, Stdio};
+
+use base64::prelude::*;
+use serde_json::{Value, json};
+
+use common::{plugin_arg, program_chunk, vst2info_tool};
+
+struct Client {
+    child: Child,
+    stdin: ChildStdin,
+    stdout: BufReader<ChildStdout>,
+}
+
+impl Client {
+    fn start() -> Client {
+        Client::spawn(vst2info_tool())
+    }
+
+    fn spawn(mut command: Command) -> Client {
+        let mut child = command
+            .args(["serve", "--timeout", "5"])
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .spawn()
+            .expect("serve mode starts");
+        Client {
+            stdin: child.stdin.take().unwrap(),
+            stdout: BufReader::new(child.stdout.take().unwrap()),
+            child,
+        }
+    }
+
+    fn send_line(&mut self, line: &str) -> Value {
+        writeln!(self.stdin, "{}", line).unwrap();
+        self.stdin.flush().unwrap();
+        let mut response = String::new();
+        self.stdout.read_line(&mut response).unwrap();
+        serde_json::from_str(&response).expect("response is JSON")
+    }
+
+    fn call(&mut self, id: i32, method: &str, params: Value) -> Value {
+        let request = json!({
+            "jsonrpc": "2.0",
+            "id": id,
+            "method": method,
+            "params": params
+        });
+        self.send_line(&request.to_string())
+    }
+}
+
+#[test]
+fn answers_protocol_errors_and_keeps_running() {
+    let mut client = Client::start();
+
+    let response = client.send_line("not json");
+    assert_eq!(response["error"]["code"], -32700);
+    assert!(response["id"].is_null());
+
+    let response = client.call(1, "unknownMethod", json!({}));
+    assert_eq!(response["id"], 1);
+    assert_eq!(response["error"]["code"], -32601);
+
+    let response = client.call(2, "inspect", json!({}));
+    assert_eq!(response["error"]["code"], -32602);
+
+    let response = client.call(
+        3,
+        "params",
+        json!({ "plugin": "/does/not/exist/Missing.so" }),
+    );
+    assert_eq!(response["id"], 3);
+    assert_eq!(response["error"]["code"], -32000);
+    assert!(
+        response["error"]["message"]
+            .as_str()
+            .unwrap()
+            .contains("Missing.so")
+    );
+
+    let response = client.call(4, "shutdown", json!({}));
+    assert_eq!(response["id"], 4);
+    assert_eq!(response["result"], true);
+    assert!(client.child.wait().unwrap().success());
+}
+
+#[cfg(target_os = "linux")]
+#[test]
+fn answers_from_the_loaded_test_plugin() {
+    let mut client = Client::start();
+    let plugin = json!({ "plugin": plugin_arg() });
+
+    // The same info as the info command
+    let info = vst2info_tool().arg(plugin_arg()).output().unwrap();
+    let info: Value = serde_json::from_slice(&info.stdout).unwrap();
+    let response = client.call(1, "inspect", plugin.clone());
+    assert_eq!(response["result"], info);
+
+    let response = client.call(2, "params", json!({ "plugin": plugin_arg(), "preset": 1 }));
+    assert_eq!(response["result"]["presetName"], "Bright");
+    assert_eq!(response["result"]["parameters"][0]["value"], 0.75);
+
+    let chunk = program_chunk([1.0, 0.5, 0.0]);
+    let data = BASE64_STANDARD.encode(&chunk);
+    let response = client.call(
+        3,
+        "applyState",
+        json!({ "plugin": plugin_arg(), "data": data }),
+    );
+    assert_eq!(response["result"]["applied"], true);
+    assert_eq!(response["result"]["stateSize"], 16);
+    assert_eq!(response["result"]["parameters"][0]["display"], "12.0");
+
+    // The applied state stays in the loaded plugin
+    let file = common::test_plugin().with_file_name("served.fxp");
+    let response = client.call(
+        4,
+        "makePreset",
+        json!({ "plugin": plugin_arg(), "preset": 1, "output": file }),
+    );
+    let preset = &response["result"];
+    assert_eq!(preset["presetName"], "Bright");
+    assert_eq!(preset["presetData"], data);
+    assert_eq!(preset["isChunk"], true);
+    assert_eq!(preset["file"], file.display().to_string());
+    let written = fs::read(&file).unwrap();
+    assert!(written.starts_with(b"CcnK"));
+    assert!(written.ends_with(&chunk));
+
+    let response = client.call(5, "shutdown", json!({}));
+    assert_eq!(response["result"], true);
+    assert!(client.child.wait().unwrap().success());
+}
+
+#[cfg(target_os = "linux")]
+#[test]
+fn keeps_running_after_a_plugin_crash() {
+    let mut client = Client::start();
+
+    // The test plugin aborts on this chunk
+    let response = client.call(
+        1,
+        "applyState",
+        json!({ "plugin": plugin_arg(), "data": BASE64_STANDARD.encode(b"DPX2") }),
+    );
+    assert_eq!(response["id"], 1);
+    assert_eq!(response["error"]["code"], -32000);
+    assert!(
+        response["error"]["message"]
+            .as_str()
+            .unwrap()
+            .starts_with("Plugin process failed")
+    );
+
+    // A fresh worker with the plugin's initial state
+    let response = client.call(2, "params", json!({ "plugin": plugin_arg() }));
+    assert_eq!(response["id"], 2);
+    assert_eq!(response["result"]["presetName"], "Init");
+    assert_eq!(response["result"]["parameters"][0]["value"], 0.5);
+
+    let response = client.call(3, "shutdown", json!({}));
+    assert_eq!(response["result"], true);
+    assert!(client.child.wait().unwrap().success());
+}
+
+#[cfg(target_os = "linux")]
+#[test]
+fn reports_the_host_calls_of_each_request_only() {
+    let mut client = Client::start();
+    let data = BASE64_STANDARD.encode(program_chunk([1.0, 0.5, 0.0]));
+    let automated = json!([
+        { "call": "automate", "index": 0, "value": 1.0 },
+        { "call": "automate", "index": 1, "value": 0.5 },
+        { "call": "automate", "index": 2, "value": 0.0 }
+    ]);
+
+    for id in 1..=2 {
+        let response = client.call(
+            id,
+            "applyState",
+            json!({ "plugin": plugin_arg(), "data": data }),
+        );
+        assert_eq!(response["result"]["diagnostics"], automated);
+    }
+    let response = client.call(3, "params", json!({ "plugin": plugin_arg() }));
+    assert_eq!(response["result"]["diagnostics"], json!([]));
+}
+
+#[cfg(target_os = "linux")]
+#[test]
+fn plugin_output_is_no_response() {
+    let mut command = vst2info_tool();
+    command.env("VST2_TEST_PLUGIN_PRINT", "1");
+    let mut client = Client::spawn(command);
+    let data = BASE64_STANDARD.encode(program_chunk([1.0, 0.5, 0.0]));
+
+    for id in 1..=3 {
+        let response = client.call(
+            id,
+            "applyState",
+            json!({ "plugin": plugin_arg(), "data": data }),
+        );
+        assert_eq!(response["id"], id);
+        assert_eq!(response["result"]["applied"], true);
+    }
+    let response = client.call(4, "params", json!({ "plugin": plugin_arg() }));
+    assert_eq!(response["id"], 4);
+    assert_eq!(response["result"]["parameters"][0]["value"], 1.0);
+}
+
+#[test]
+fn notifications_get_no_response() {
+    let mut client = Client::start();
+
+    writeln!(
+        client.stdin,
+        r#"{{"jsonrpc":"2.0","method":"unknownMethod"}}"#
+    )
+    .unwrap();
+    let response = client.call(1, "shutdown", json!({}));
+    assert_eq!(response["id"], 1);
+    assert_eq!(response["result"], true);
+}
+
+#[test]
+fn ends_when_stdin_closes() {
+    let Client {
+        mut child, stdin, ..
+    } = Client::start();
+    drop(stdin);
+    assert!(child.wait().unwrap().success());
+}
@@ -0,0 +1,824 @@
+/*!
+ * ------------------------------------------------------------------------
+ * VST3 test plugin
+ * by Jurek Raben
+ *
+ * A VST3 plugin for the tests of the helper tools, it doesn't process any
+ * audio. The component and the edit controller are separate classes,
+ * connected by the host. The parameters are defined by the PARAMETERS
+ * table, including a hidden one, MIDI CC proxies as added by plugin
+ * frameworks and one without a title. The state of the component and the
+ * controller is the magic "DPT3" followed by the parameter count and the
+ * IDs and normalized values, little endian.
+ *
+ * It is also the VST3 version of the "Test Chunks" plugin of the
+ * vst2-test-plugin: the component loads its VST2 states wrapped by the
+ * host into "VstW" and an .fxp or .fxb, the controller maps its VST2
+ * parameters via IRemapParamID and the factory declares the replacement
+ * via IPluginCompatibility.
+ *
+ * Based on the gain example of the vst3 crate.
+ *
+ * Licensed under CC Attribution-NonCommercial-ShareAlike 4.0 International
+ * Info here: https://creativecommons.org/licenses/by-nc-sa/4.0/
+ * ------------------------------------------------------------------------
+ */
+
+#![allow(non_snake_case)]
+
+use std::cell::RefCell;
+use std::ffi::{c_char, c_void};
+use std::ptr;
+
+use vst3::Steinberg::Vst::ParameterInfo_::ParameterFlags_::*;
+use vst3::Steinberg::Vst::*;
+use vst3::Steinberg::*;
+use vst3::{Class, ComRef, ComWrapper, uid};
+
+const PLUGIN_NAME: &str = "Test Plugin";
+const PLUGIN_VENDOR: &str = "DawProject";
+const PLUGIN_VERSION: &str = "1.4.0";
+const SUB_CATEGORIES: &str = "Fx|Dynamics";
+const SDK_VERSION: &str = "VST 3.7.12";
+
+const STATE_MAGIC: &[u8; 4] = b"DPT3";
+const STATE_ENTRY_SIZE: usize = 12;
+
+const ADVANCED_UNIT_ID: UnitID = 1;
+
+// The replaced VST2 plugin, its class ID as converted by the VST3 SDK
+// from the unique ID "DPc2" and the name "test chun"
+const VST2_UNIQUE_ID: &[u8; 4] = b"DPc2";
+const VST2_CLASS_ID: TUID = uid(0x56535444, 0x50633274, 0x65737420, 0x6368756E);
+const VST2_PROGRAM_MAGIC: &[u8; 4] = b"DPP2";
+const VST2_BANK_MAGIC: &[u8; 4] = b"DPB2";
+const VST2_PROGRAM_NAME_LEN: usize = 24;
+
+// VST3 parameter IDs of the VST2 parameters Gain, Mode and Mix, by index
+const VST2_PARAMETERS: [ParamID; 3] = [100, 101, 300];
+
+struct Parameter {
+    id: ParamID,
+    title: &'static str,
+    units: &'static str,
+    step_count: i32,
+    default_value: f64,
+    unit_id: UnitID,
+    flags: i32,
+}
+
+const PARAMETERS: [Parameter; 7] = [
+    Parameter {
+        id: 100,
+        title: "Gain",
+        units: "dB",
+        step_count: 0,
+        default_value: 0.5,
+        unit_id: kRootUnitId,
+        flags: kCanAutomate,
+    },
+    Parameter {
+        id: 101,
+        title: "Mode",
+        units: "",
+        step_count: 3,
+        default_value: 0.0,
+        unit_id: ADVANCED_UNIT_ID,
+        flags: kCanAutomate | kIsList,
+    },
+    Parameter {
+        id: 102,
+        title: "Bypass",
+        units: "",
+        step_count: 1,
+        default_value: 0.0,
+        unit_id: kRootUnitId,
+        flags: kCanAutomate | kIsBypass,
+    },
+    Parameter {
+        id: 200,
+        title: "Internal",
+        units: "",
+        step_count: 0,
+        default_value: 0.25,
+        unit_id: ADVANCED_UNIT_ID,
+        flags: kIsHidden | kIsReadOnly,
+    },
+    Parameter {
+        id: 1000,
+        title: "MIDI CC 0|1",
+        units: "",
+        step_count: 0,
+        default_value: 0.0,
+        unit_id: kRootUnitId,
+        flags: kCanAutomate | kIsHidden,
+    },
+    Parameter {
+        id: 1001,
+        title: "MIDI CC 0|74",
+        units: "",
+        step_count: 0,
+        default_value: 0.0,
+        unit_id: kRootUnitId,
+        flags: kCanAutomate | kIsHidden,
+    },
+    Parameter {
+        id: 300,
+        title: "",
+        units: "",
+        step_count: 0,
+        default_value: 1.0,
+        unit_id: kRootUnitId,
+        flags: kNoFlags,
+    },
+];
+
+const MODES: [&str; 4] = ["Clean", "Warm", "Hot", "Broken"];
+
+// Name and parent of the units, the root unit first
+const UNITS: [(UnitID, UnitID, &str); 2] = [
+    (kRootUnitId, kNoParentUnitId, "Root"),
+    (ADVANCED_UNIT_ID, kRootUnitId, "Advanced"),
+];
+
+fn default_values() -> Vec<f64> {
+    PARAMETERS.iter().map(|param| param.default_value).collect()
+}
+
+fn parameter_index(id: ParamID) -> Option<usize> {
+    PARAMETERS.iter().position(|param| param.id == id)
+}
+
+fn display_string(id: ParamID, value: f64) -> Option<String> {
+    match id {
+        100 => Some(format!("{:.1} dB", value * 72.0 - 60.0)),
+        101 => Some(MODES[(value * 3.0).round().clamp(0.0, 3.0) as usize].to_string()),
+        102 => Some(if value >= 0.5 { "On" } else { "Off" }.to_string()),
+        _ => parameter_index(id).map(|_| format!("{:.2}", value)),
+    }
+}
+
+fn copy_cstring(source: &str, target: &mut [c_char]) {
+    let Some(max_len) = target.len().checked_sub(1) else {
+        return;
+    };
+    let bytes = &source.as_bytes()[..source.len().min(max_len)];
+    for (target, byte) in target.iter_mut().zip(bytes) {
+        *target = *byte as c_char;
+    }
+    target[bytes.len()] = 0;
+}
+
+// As written by FUID::toString, in the COM GUID layout on Windows
+fn class_id_string(cid: &TUID) -> String {
+    let mut bytes = cid.map(|byte| byte as u8);
+    if cfg!(target_os = "windows") {
+        bytes[0..4].reverse();
+        bytes[4..6].reverse();
+        bytes[6..8].reverse();
+    }
+    bytes.iter().map(|byte| format!("{:02X}", byte)).collect()
+}
+
+fn copy_wstring(source: &str, target: &mut [TChar]) {
+    let Some(max_len) = target.len().checked_sub(1) else {
+        return;
+    };
+    let mut len = 0;
+    for (target, char) in target[..max_len].iter_mut().zip(source.encode_utf16()) {
+        *target = char as TChar;
+        len += 1;
+    }
+    target[len] = 0;
+}
+
+fn write_state(stream: *mut IBStream, values: &[f64]) -> tresult {
+    let Some(stream) = (unsafe { ComRef::from_raw(stream) }) else {
+        return kInvalidArgument;
+    };
+
+    let mut state = STATE_MAGIC.to_vec();
+    state.extend((values.len() as u32).to_le_bytes());
+    for (param, value) in PARAMETERS.iter().zip(values) {
+        state.extend(param.id.to_le_bytes());
+        state.extend(value.to_le_bytes());
+    }
+
+    let mut written = 0;
+    let result = unsafe {
+        stream.write(
+            state.as_mut_ptr() as *mut c_void,
+            state.len() as i32,
+            &mut written,
+        )
+    };
+    if result == kResultOk && written as usize == state.len() {
+        kResultOk
+    } else {
+        kResultFalse
+    }
+}
+
+// The values of a state, parameters missing in it keep their defaults
+fn read_state(stream: *mut IBStream) -> Option<Vec<f64>> {
+    let stream = unsafe { ComRef::from_raw(stream) }?;
+
+    let mut state = Vec::new();
+    let mut buffer = [0u8; 256];
+    loop {
+        let mut read = 0;
+        let result = unsafe {
+            stream.read(
+                buffer.as_mut_ptr() as *mut c_void,
+                buffer.len() as i32,
+                &mut read,
+            )
+        };
+        if result != kResultOk || read <= 0 {
+            break;
+        }
+        state.extend_from_slice(&buffer[..read as usize]);
+    }
+
+    if state.starts_with(b"VstW") {
+        return read_vst2_state(&state);
+    }
+
+    let rest = state.strip_prefix(STATE_MAGIC)?;
+    let count = u32::from_le_bytes(rest.get(0..4)?.try_into().ok()?) as usize;
+    let entries = rest.get(4..)?;
+    if entries.len() != count * STATE_ENTRY_SIZE {
+        return None;
+    }
+
+    let mut values = default_values();
+    for entry in entries.chunks(STATE_ENTRY_SIZE) {
+        let id = u32::from_le_bytes(entry[0..4].try_into().ok()?);
+        let value = f64::from_le_bytes(entry[4..12].try_into().ok()?);
+        if let Some(index) = parameter_index(id) {
+            values[index] = value.clamp(0.0, 1.0);
+        }
+    }
+    Some(values)
+}
+
+// The values of a VST2 state behind the "VstW" header, the program
+// chunk of an .fxp or the current program of an .fxb bank chunk
+fn read_vst2_state(state: &[u8]) -> Option<Vec<f64>> {
+    let header_size = u32::from_be_bytes(state.get(4..8)?.try_into().ok()?) as usize;
+    let fx = state.get(8 + header_size..)?;
+    if fx.get(0..4)? != b"CcnK" || fx.get(16..20)? != VST2_UNIQUE_ID {
+        return None;
+    }
+
+    let values = match fx.get(8..12)? {
+        b"FPCh" => fx.get(60..)?.strip_prefix(VST2_PROGRAM_MAGIC)?,
+        b"FBCh" => {
+            let bank = fx.get(160..)?.strip_prefix(VST2_BANK_MAGIC)?;
+            let current = u32::from_le_bytes(bank.get(4..8)?.try_into().ok()?) as usize;
+            let program_len = VST2_PROGRAM_NAME_LEN + VST2_PARAMETERS.len() * 4;
+            bank.get(8 + current * program_len + VST2_PROGRAM_NAME_LEN..)?
+        }
+        _ => return None,
+    };
+
+    let mut state_values = default_values();
+    for (id, value) in VST2_PARAMETERS.iter().zip(values.chunks_exact(4)) {
+        state_values[parameter_index(*id)?] = f32::from_le_bytes(value.try_into().ok()?) as f64;
+    }
+    Some(state_values)
+}
+
+// Component
+
+struct TestComponent {
+    values: RefCell<Vec<f64>>,
+}
+
+impl Class for TestComponent {
+    type Interfaces = (IComponent, IConnectionPoint);
+}
+
+impl TestComponent {
+    const CID: TUID = uid(0x5DA1E3B2, 0x7C0F4E21, 0x9A3B0C44, 0xD17E5F60);
+}
+
+impl IPluginBaseTrait for TestComponent {
+    unsafe fn initialize(&self, _context: *mut FUnknown) -> tresult {
+        kResultOk
+    }
+
+    unsafe fn terminate(&self) -> tresult {
+        kResultOk
+    }
+}
+
+impl IComponentTrait for TestComponent {
+    unsafe fn getControllerClassId(&self, class_id: *mut TUID) -> tresult {
+        unsafe { *class_id = TestController::CID };
+        kResultOk
+    }
+
+    unsafe fn setIoMode(&self, _mode: IoMode) -> tresult {
+        kResultOk
+    }
+
+    // A stereo audio input and output and a MIDI event input
+    unsafe fn getBusCount(&self, media_type: MediaType, dir: BusDirection) -> i32 {
+        match (media_type as MediaTypes, dir as BusDirections) {
+            (MediaTypes_::kAudio, _) => 1,
+            (MediaTypes_::kEvent, BusDirections_::kInput) => 1,
+            _ => 0,
+        }
+    }
+
+    unsafe fn getBusInfo(
+        &self,
+        media_type: MediaType,
+        dir: BusDirection,
+        index: i32,
+        bus: *mut BusInfo,
+    ) -> tresult {
+        if index >= unsafe { self.getBusCount(media_type, dir) } {
+            return kInvalidArgument;
+        }
+
+        let bus = unsafe { &mut *bus };
+        bus.mediaType = media_type;
+        bus.direction = dir;
+        bus.busType = BusTypes_::kMain as BusType;
+        bus.flags = BusInfo_::BusFlags_::kDefaultActive;
+        let (name, channel_count) = match (media_type as MediaTypes, dir as BusDirections) {
+            (MediaTypes_::kAudio, BusDirections_::kInput) => ("Input", 2),
+            (MediaTypes_::kAudio, _) => ("Output", 2),
+            _ => ("MIDI In", 16),
+        };
+        bus.channelCount = channel_count;
+        copy_wstring(name, &mut bus.name);
+        kResultOk
+    }
+
+    unsafe fn getRoutingInfo(
+        &self,
+        _in_info: *mut RoutingInfo,
+        _out_info: *mut RoutingInfo,
+    ) -> tresult {
+        kNotImplemented
+    }
+
+    unsafe fn activateBus(
+        &self,
+        _media_type: MediaType,
+        _dir: BusDirection,
+        _index: i32,
+        _state: TBool,
+    ) -> tresult {
+        kResultOk
+    }
+
+    unsafe fn setActive(&self, _state: TBool) -> tresult {
+        kResultOk
+    }
+
+    unsafe fn setState(&self, state: *mut IBStream) -> tresult {
+        match read_state(state) {
+            Some(values) => {
+                *self.values.borrow_mut() = values;
+                kResultOk
+            }
+            None => kResultFalse,
+        }
+    }
+
+    unsafe fn getState(&self, state: *mut IBStream) -> tresult {
+        write_state(state, &self.values.borrow())
+    }
+}
+
+impl IConnectionPointTrait for TestComponent {
+    unsafe fn connect(&self, _other: *mut IConnectionPoint) -> tresult {
+        kResultOk
+    }
+
+    unsafe fn disconnect(&self, _other: *mut IConnectionPoint) -> tresult {
+        kResultOk
+    }
+
+    unsafe fn notify(&self, _message: *mut IMessage) -> tresult {
+        kResultOk
+    }
+}
+
+// Edit controller
+
+struct TestController {
+    values: RefCell<Vec<f64>>,
+}
+
+impl Class for TestController {
+    type Interfaces = (IEditController, IUnitInfo, IRemapParamID, IConnectionPoint);
+}
+
+impl TestController {
+    const CID: TUID = uid(0x0B9C2E61, 0x4F3A4D87, 0xB2C5E8A1, 0x3F6D7E90);
+}
+
+impl IPluginBaseTrait for TestController {
+    unsafe fn initialize(&self, _context: *mut FUnknown) -> tresult {
+        kResultOk
+    }
+
+    unsafe fn terminate(&self) -> tresult {
+        kResultOk
+    }
+}
+
+impl IEditControllerTrait for TestController {
+    unsafe fn setComponentState(&self, state: *mut IBStream) -> tresult {
+        match read_state(state) {
+            Some(values) => {
+                *self.values.borrow_mut() = values;
+                kResultOk
+            }
+            None => kResultFalse,
+        }
+    }
+
+    unsafe fn setState(&self, _state: *mut IBStream) -> tresult {
+        kResultOk
+    }
+
+    unsafe fn getState(&self, _state: *mut IBStream) -> tresult {
+        kResultOk
+    }
+
+    unsafe fn getParameterCount(&self) -> i32 {
+        PARAMETERS.len() as i32
+    }
+
+    unsafe fn getParameterInfo(&self, param_index: i32, info: *mut ParameterInfo) -> tresult {
+        let Some(param) = usize::try_from(param_index)
+            .ok()
+            .and_then(|index| PARAMETERS.get(index))
+        else {
+            return kInvalidArgument;
+        };
+
+        let info = unsafe { &mut *info };
+        info.id = param.id;
+        copy_wstring(param.title, &mut info.title);
+        copy_wstring(param.title, &mut info.shortTitle);
+        copy_wstring(param.units, &mut info.units);
+        info.stepCount = param.step_count;
+        info.defaultNormalizedValue = param.default_value;
+        info.unitId = param.unit_id;
+        info.flags = param.flags;
+        kResultOk
+    }
+
+    unsafe fn getParamStringByValue(
+        &self,
+        id: ParamID,
+        value_normalized: ParamValue,
+        string: *mut String128,
+    ) -> tresult {
+        match display_string(id, value_normalized) {
+            Some(display) => {
+                copy_wstring(&display, unsafe { &mut *string });
+                kResultOk
+            }
+            None => kInvalidArgument,
+        }
+    }
+
+    unsafe fn getParamValueByString(
+        &self,
+        _id: ParamID,
+        _string: *mut TChar,
+        _value_normalized: *mut ParamValue,
+    ) -> tresult {
+        kNotImplemented
+    }
+
+    unsafe fn normalizedParamToPlain(&self, _id: ParamID, value_normalized: ParamValue) -> f64 {
+        value_normalized
+    }
+
+    unsafe fn plainParamToNormalized(&self, _id: ParamID, plain_value: ParamValue) -> f64 {
+        plain_value
+    }
+
+    unsafe fn getParamNormalized(&self, id: ParamID) -> ParamValue {
+        parameter_index(id).map_or(0.0, |index| self.values.borrow()[index])
+    }
+
+    unsafe fn setParamNormalized(&self, id: ParamID, value: ParamValue) -> tresult {
+        match parameter_index(id) {
+            Some(index) => {
+                self.values.borrow_mut()[index] = value.clamp(0.0, 1.0);
+                kResultOk
+            }
+            None => kInvalidArgument,
+        }
+    }
+
+    unsafe fn setComponentHandler(&self, _handler: *mut IComponentHandler) -> tresult {
+        kResultOk
+    }
+
+    unsafe fn createView(&self, _name: FIDString) -> *mut IPlugView {
+        ptr::null_mut()
+    }
+}
+
+impl IUnitInfoTrait for TestController {
+    unsafe fn getUnitCount(&self) -> i32 {
+        UNITS.len() as i32
+    }
+
+    unsafe fn getUnitInfo(&self, unit_index: i32, info: *mut UnitInfo) -> tresult {
+        let Some((id, parent_id, name)) = usize::try_from(unit_index)
+            .ok()
+            .and_then(|index| UNITS.get(index))
+        else {
+            return kInvalidArgument;
+        };
+
+        let info = unsafe { &mut *info };
+        info.id = *id;
+        info.parentUnitId = *parent_id;
+        copy_wstring(name, &mut info.name);
+        info.programListId = kNoProgramListId;
+        kResultOk
+    }
+
+    unsafe fn getProgramListCount(&self) -> i32 {
+        0
+    }
+
+    unsafe fn getProgramListInfo(&self, _list_index: i32, _info: *mut ProgramListInfo) -> tresult {
+        kInvalidArgument
+    }
+
+    unsafe fn getProgramName(
+        &self,
+        _list_id: ProgramListID,
+        _program_index: i32,
+        _name: *mut String128,
+    ) -> tresult {
+        kInvalidArgument
+    }
+
+    unsafe fn getProgramInfo(
+        &self,
+        _list_id: ProgramListID,
+        _program_index: i32,
+        _attribute_id: vst3::Steinberg::Vst::CString,
+        _attribute_value: *mut String128,
+    ) -> tresult {
+        kInvalidArgument
+    }
+
+    unsafe fn hasProgramPitchNames(&self, _list_id: ProgramListID, _program_index: i32) -> tresult {
+        kResultFalse
+    }
+
+    unsafe fn getProgramPitchName(
+        &self,
+        _list_id: ProgramListID,
+        _program_index: i32,
+        _midi_pitch: i16,
+        _name: *mut String128,
+    ) -> tresult {
+        kResultFalse
+    }
+
+    unsafe fn getSelectedUnit(&self) -> UnitID {
+        kRootUnitId
+    }
+
+    unsafe fn selectUnit(&self, _unit_id: UnitID) -> tresult {
+        kResultOk
+    }
+
+    unsafe fn getUnitByBus(
+        &self,
+        _media_type: MediaType,
+        _dir: BusDirection,
+        _bus_index: i32,
+        _channel: i32,
+        unit_id: *mut UnitID,
+    ) -> tresult {
+        unsafe { *unit_id = kRootUnitId };
+        kResultOk
+    }
+
+    unsafe fn setUnitProgramData(
+        &self,
+        _list_or_unit_id: i32,
+        _program_index: i32,
+        _data: *mut IBStream,
+    ) -> tresult {
+        kNotImplemented
+    }
+}
+
+impl IRemapParamIDTrait for TestController {
+    unsafe fn getCompatibleParamID(
+        &self,
+        plugin_to_replace_uid: *const TUID,
+        old_param_id: ParamID,
+        new_param_id: *mut ParamID,
+    ) -> tresult {
+        if unsafe { *plugin_to_replace_uid } != VST2_CLASS_ID {
+            return kResultFalse;
+        }
+        match VST2_PARAMETERS.get(old_param_id as usize) {
+            Some(id) => {
+                unsafe { *new_param_id = *id };
+                kResultTrue
+            }
+            None => kResultFalse,
+        }
+    }
+}
+
+impl IConnectionPointTrait for TestController {
+    unsafe fn connect(&self, _other: *mut IConnectionPoint) -> tresult {
+        kResultOk
+    }
+
+    unsafe fn disconnect(&self, _other: *mut IConnectionPoint) -> tresult {
+        kResultOk
+    }
+
+    unsafe fn notify(&self, _message: *mut IMessage) -> tresult {
+        kResultOk
+    }
+}
+
+// Factory
+
+struct Factory;
+
+impl Class for Factory {
+    type Interfaces = (IPluginFactory2, IPluginCompatibility);
+}
+
+impl Factory {
+    // Category and class ID of the component and the controller
+    const CLASSES: [(&'static str, TUID); 2] = [
+        ("Audio Module Class", TestComponent::CID),
+        ("Component Controller Class", TestController::CID),
+    ];
+}
+
+impl IPluginFactoryTrait for Factory {
+    unsafe fn getFactoryInfo(&self, info: *mut PFactoryInfo) -> tresult {
+        let info = unsafe { &mut *info };
+        copy_cstring(PLUGIN_VENDOR, &mut info.vendor);
+        copy_cstring(
+            "https://github.com/Jurek-Raben/DawProject-Tool",
+            &mut info.url,
+        );
+        copy_cstring("", &mut info.email);
+        info.flags = PFactoryInfo_::FactoryFlags_::kUnicode as int32;
+        kResultOk
+    }
+
+    unsafe fn countClasses(&self) -> i32 {
+        Self::CLASSES.len() as i32
+    }
+
+    unsafe fn getClassInfo(&self, index: i32, info: *mut PClassInfo) -> tresult {
+        let Some((category, cid)) = usize::try_from(index)
+            .ok()
+            .and_then(|index| Self::CLASSES.get(index))
+        else {
+            return kInvalidArgument;
+        };
+
+        let info = unsafe { &mut *info };
+        info.cid = *cid;
+        info.cardinality = PClassInfo_::ClassCardinality_::kManyInstances as int32;
+        copy_cstring(category, &mut info.category);
+        copy_cstring(PLUGIN_NAME, &mut info.name);
+        kResultOk
+    }
+
+    unsafe fn createInstance(
+        &self,
+        cid: FIDString,
+        iid: FIDString,
+        obj: *mut *mut c_void,
+    ) -> tresult {
+        let instance = match unsafe { *(cid as *const TUID) } {
+            TestComponent::CID => ComWrapper::new(TestComponent {
+                values: RefCell::new(default_values()),
+            })
+            .to_com_ptr::<FUnknown>(),
+            TestController::CID => ComWrapper::new(TestController {
+                values: RefCell::new(default_values()),
+            })
+            .to_com_ptr::<FUnknown>(),
+            _ => None,
+        };
+
+        match instance {
+            Some(instance) => {
+                let instance = instance.as_ptr();
+                unsafe { ((*(*instance).vtbl).queryInterface)(instance, iid as *mut TUID, obj) }
+            }
+            None => kInvalidArgument,
+        }
+    }
+}
+
+impl IPluginFactory2Trait for Factory {
+    unsafe fn getClassInfo2(&self, index: i32, info: *mut PClassInfo2) -> tresult {
+        let Some((category, cid)) = usize::try_from(index)
+            .ok()
+            .and_then(|index| Self::CLASSES.get(index))
+        else {
+            return kInvalidArgument;
+        };
+
+        let info = unsafe { &mut *info };
+        info.cid = *cid;
+        info.cardinality = PClassInfo_::ClassCardinality_::kManyInstances as int32;
+        copy_cstring(category, &mut info.category);
+        copy_cstring(PLUGIN_NAME, &mut info.name);
+        info.classFlags = 0;
+        copy_cstring(SUB_CATEGORIES, &mut info.subCategories);
+        copy_cstring(PLUGIN_VENDOR, &mut info.vendor);
+        copy_cstring(PLUGIN_VERSION, &mut info.version);
+        copy_cstring(SDK_VERSION, &mut info.sdkVersion);
+        kResultOk
+    }
+}
+
+impl IPluginCompatibilityTrait for Factory {
+    unsafe fn getCompatibilityJSON(&self, stream: *mut IBStream) -> tresult {
+        let Some(stream) = (unsafe { ComRef::from_raw(stream) }) else {
+            return kInvalidArgument;
+        };
+        let mut json = format!(
+            r#"[{{"New":"{}","Old":["{}"]}}]"#,
+            class_id_string(&TestComponent::CID),
+            class_id_string(&VST2_CLASS_ID)
+        )
+        .into_bytes();
+
+        let mut written = 0;
+        unsafe {
+            stream.write(
+                json.as_mut_ptr() as *mut c_void,
+                json.len() as i32,
+                &mut written,
+            )
+        }
+    }
+}
+
+#[cfg(target_os = "windows")]
+#[unsafe(no_mangle)]
+extern "system" fn InitDll() -> bool {
+    true
+}
+
+#[cfg(target_os = "windows")]
+#[unsafe(no_mangle)]
+extern "system" fn ExitDll() -> bool {
+    true
+}
+
+#[cfg(target_os = "macos")]
+#[unsafe(no_mangle)]
+extern "system" fn BundleEntry(_bundle_ref: *mut c_void) -> bool {
+    true
+}
+
+#[cfg(target_os = "macos")]
+#[unsafe(no_mangle)]
+extern "system" fn BundleExit() -> bool {
+    true
+}
+
+#[cfg(target_os = "linux")]
+#[unsafe(no_mangle)]
+extern "system" fn ModuleEntry(_library_handle: *mut c_void) -> bool {
+    true
+}
+
+#[cfg(target_os = "linux")]
+#[unsafe(no_mangle)]
+extern "system" fn ModuleExit() -> bool {
+    true
+}
+
+#[unsafe(no_mangle)]
+extern "system" fn GetPluginFactory() -> *mut IPluginFactory {
+    ComWrapper::new(Factory)
+        .to_com_ptr::<IPluginFactory>()
+        .map_or(ptr::null_mut(), |factory| factory.into_raw())
+}
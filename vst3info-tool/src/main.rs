@@ -1,856 +1,694 @@
 /*!
-* ------------------------------------------------------------------------
-* VST3 Info Extractor Tool
-* heavily based on rust-vst3-host by Helge Sverre
-* [https://github.com/HelgeSverre/rust-vst3-host](https://github.com/HelgeSverre/rust-vst3-host)
-*
-* by Jurek Raben
-* ------------------------------------------------------------------------
-*/
-
-#![allow(deprecated)]
-#![allow(non_upper_case_globals)]
-#![allow(non_snake_case)]
-
-use json::object;
-use std::env;
-use std::path::PathBuf;
-use std::process::exit;
-use std::ptr;
-use std::sync::atomic::{AtomicU32, Ordering};
-use vst3::Steinberg::FUnknown;
-use vst3::Steinberg::IPluginFactoryTrait;
-use vst3::Steinberg::TUID;
-use vst3::Steinberg::Vst::String128;
-use vst3::Steinberg::Vst::{
-    BusDirections_::*, IAudioProcessor, IComponent, IComponentTrait, IConnectionPoint,
-    IConnectionPointTrait, IEditController, IEditControllerTrait, MediaTypes_::*,
-};
-use vst3::Steinberg::{IPluginBaseTrait, IPluginFactory, kNoInterface, kResultOk, tresult};
-use vst3::{ComPtr, Interface};
-
-// Platform-specific imports
-#[cfg(unix)]
-use libloading::os::unix::{Library, Symbol};
-#[cfg(windows)]
-use libloading::{Library, Symbol};
-#[cfg(windows)]
-use std::ffi::OsStr;
-#[cfg(windows)]
-use std::iter::once;
-
-// Custom error type for better error handling
-#[derive(Debug)]
-enum PluginError {
-    LoadError(String),
-    InitError(String),
-    FactoryError(String),
-    ComponentError(String),
-    PathError(String),
-}
-
-impl std::fmt::Display for PluginError {
-    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
-        match self {
-            PluginError::LoadError(msg) => write!(f, "Load error: {}", msg),
-            PluginError::InitError(msg) => write!(f, "Initialization error: {}", msg),
-            PluginError::FactoryError(msg) => write!(f, "Factory error: {}", msg),
-            PluginError::ComponentError(msg) => write!(f, "Component error: {}", msg),
-            PluginError::PathError(msg) => write!(f, "Path error: {}", msg),
-        }
-    }
-}
-
-impl std::error::Error for PluginError {}
-
-#[derive(Debug, Clone)]
-struct PluginInfo {
-    factory_info: FactoryInfo,
-    classes: Vec<ClassInfo>,
-    component_info: Option<ComponentInfo>,
-    controller_info: Option<ControllerInfo>,
-    name: String,
-    version: String,
-}
-
-#[derive(Debug, Clone)]
-struct FactoryInfo {
-    vendor: String,
-    url: String,
-    email: String,
-    flags: i32,
-}
-
-#[derive(Debug, Clone)]
-struct ClassInfo {
-    name: String,
-    category: String,
-    class_id: String,
-    cardinality: i32,
-}
-
-#[derive(Debug, Clone)]
-struct ComponentInfo {
-    bus_count_inputs: i32,
-    bus_count_outputs: i32,
-    audio_inputs: Vec<BusInfo>,
-    audio_outputs: Vec<BusInfo>,
-    event_inputs: Vec<BusInfo>,
-    event_outputs: Vec<BusInfo>,
-    supports_processing: bool,
-}
-
-#[derive(Debug, Clone)]
-struct BusInfo {
-    name: String,
-    bus_type: i32,
-    flags: i32,
-    channel_count: i32,
-}
-
-#[derive(Debug, Clone)]
-struct ControllerInfo {
-    parameter_count: i32,
-    parameters: Vec<ParameterInfo>,
-}
-
-#[derive(Debug, Clone)]
-struct ParameterInfo {
-    id: u32,
-    title: String,
-    short_title: String,
-    units: String,
-    step_count: i32,
-    default_normalized_value: f64,
-    unit_id: i32,
-    flags: i32,
-    current_value: f64,
-}
+ * ------------------------------------------------------------------------
+ * VST3 Info Extractor Tool
+ * by Jurek Raben
+ *
+ * Licensed under CC Attribution-NonCommercial-ShareAlike 4.0 International
+ * Info here: https://creativecommons.org/licenses/by-nc-sa/4.0/
+ * ------------------------------------------------------------------------
+ */
 
-// Host Application implementation
-struct HostApplication {
-    vtbl: *const IHostApplicationVtbl,
-    ref_count: AtomicU32,
-}
-
-#[repr(C)]
-struct IHostApplicationVtbl {
-    query_interface:
-        unsafe extern "C" fn(*mut FUnknown, *const TUID, *mut *mut std::ffi::c_void) -> tresult,
-    add_ref: unsafe extern "C" fn(*mut FUnknown) -> u32,
-    release: unsafe extern "C" fn(*mut FUnknown) -> u32,
-    get_name: unsafe extern "C" fn(*mut HostApplication, *mut String128) -> tresult,
-    create_instance: unsafe extern "C" fn(
-        *mut HostApplication,
-        *mut TUID,
-        *mut TUID,
-        *mut *mut std::ffi::c_void,
-    ) -> tresult,
-}
-
-unsafe extern "C" fn host_query_interface(
-    _this: *mut FUnknown,
-    _iid: *const TUID,
-    _obj: *mut *mut std::ffi::c_void,
-) -> tresult {
-    kNoInterface
-}
-
-unsafe extern "C" fn host_add_ref(this: *mut FUnknown) -> u32 {
-    let host = this as *mut HostApplication;
-    (*host).ref_count.fetch_add(1, Ordering::SeqCst) + 1
-}
-
-unsafe extern "C" fn host_release(this: *mut FUnknown) -> u32 {
-    let host = this as *mut HostApplication;
-    let count = (*host).ref_count.fetch_sub(1, Ordering::SeqCst) - 1;
-    if count == 0 {
-        drop(Box::from_raw(host));
-    }
-    count
-}
-
-unsafe extern "C" fn host_get_name(_this: *mut HostApplication, name: *mut String128) -> tresult {
-    let host_name: Vec<u16> = "RustHost"
-        .encode_utf16()
-        .chain(std::iter::once(0))
-        .collect();
-    let len = std::cmp::min(host_name.len(), 128);
-    for i in 0..len {
-        (*name)[i] = host_name[i] as i16;
-    }
-    kResultOk
-}
-
-unsafe extern "C" fn host_create_instance(
-    _this: *mut HostApplication,
-    _cid: *mut TUID,
-    _iid: *mut TUID,
-    _obj: *mut *mut std::ffi::c_void,
-) -> tresult {
-    kNoInterface
-}
-
-static HOST_VTBL: IHostApplicationVtbl = IHostApplicationVtbl {
-    query_interface: host_query_interface,
-    add_ref: host_add_ref,
-    release: host_release,
-    get_name: host_get_name,
-    create_instance: host_create_instance,
-};
-
-impl HostApplication {
-    fn new() -> *mut FUnknown {
-        let host = Box::new(HostApplication {
-            vtbl: &HOST_VTBL,
-            ref_count: AtomicU32::new(1),
-        });
-        Box::into_raw(host) as *mut FUnknown
-    }
-}
-
-// Platform-specific stdout suppression
-#[cfg(unix)]
-use std::fs::File;
-#[cfg(unix)]
-use std::io;
-
-#[cfg(unix)]
-unsafe fn suppress_stdout<F: FnOnce() -> R, R>(f: F) -> R {
-    use std::os::unix::io::AsRawFd;
-    let stdout_fd = io::stdout().as_raw_fd();
-    let stderr_fd = io::stderr().as_raw_fd();
-
-    let dev_null = match File::create("/dev/null") {
-        Ok(file) => file,
-        Err(_) => return f(), // Fallback if can't create /dev/null
-    };
-    let null_fd = dev_null.as_raw_fd();
-
-    let saved_stdout = libc::dup(stdout_fd);
-    let saved_stderr = libc::dup(stderr_fd);
-
-    if saved_stdout == -1 || saved_stderr == -1 {
-        return f(); // Fallback if dup fails
-    }
-
-    libc::dup2(null_fd, stdout_fd);
-    libc::dup2(null_fd, stderr_fd);
-
-    let result = f();
-
-    libc::dup2(saved_stdout, stdout_fd);
-    libc::dup2(saved_stderr, stderr_fd);
-    libc::close(saved_stdout);
-    libc::close(saved_stderr);
-
-    result
-}
-
-#[cfg(windows)]
-unsafe fn suppress_stdout<F: FnOnce() -> R, R>(f: F) -> R {
-    use std::os::windows::io::AsRawHandle;
-    use winapi::um::fileapi::CreateFileW;
-    use winapi::um::fileapi::OPEN_EXISTING; // <- HIER ist die Korrektur
-    use winapi::um::handleapi::{CloseHandle, INVALID_HANDLE_VALUE};
-    use winapi::um::processenv::{GetStdHandle, SetStdHandle};
-    use winapi::um::winbase::{STD_ERROR_HANDLE, STD_OUTPUT_HANDLE};
-    use winapi::um::winnt::{FILE_ATTRIBUTE_NORMAL, FILE_SHARE_WRITE, GENERIC_WRITE};
-
-    let wide_nul: Vec<u16> = "NUL".encode_utf16().chain(std::iter::once(0)).collect();
-
-    let stdout_handle = GetStdHandle(STD_OUTPUT_HANDLE);
-    let stderr_handle = GetStdHandle(STD_ERROR_HANDLE);
-
-    if stdout_handle == INVALID_HANDLE_VALUE || stderr_handle == INVALID_HANDLE_VALUE {
-        return f();
-    }
-
-    let nul_handle = CreateFileW(
-        wide_nul.as_ptr(),
-        GENERIC_WRITE,
-        FILE_SHARE_WRITE,
-        std::ptr::null_mut(),
-        OPEN_EXISTING, // <- Jetzt korrekt importiert
-        FILE_ATTRIBUTE_NORMAL,
-        std::ptr::null_mut(),
-    );
-
-    if nul_handle == INVALID_HANDLE_VALUE {
-        return f();
-    }
-
-    SetStdHandle(STD_OUTPUT_HANDLE, nul_handle);
-    SetStdHandle(STD_ERROR_HANDLE, nul_handle);
-
-    let result = f();
-
-    SetStdHandle(STD_OUTPUT_HANDLE, stdout_handle);
-    SetStdHandle(STD_ERROR_HANDLE, stderr_handle);
-    CloseHandle(nul_handle);
-
-    result
-}
-
-// Improved error handling
-fn error_exit(error_message: &str) -> ! {
-    eprintln!("{}", json::stringify(object! { error: error_message }));
-    exit(1);
-}
-
-// RAII guard for library lifetime management
-struct LibraryGuard {
-    _lib: Library,
-}
-
-impl LibraryGuard {
-    fn new(lib: Library) -> Self {
-        Self { _lib: lib }
-    }
+use std::env;
+use std::ffi::OsString;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use base64::prelude::*;
+use clap::{Args, Parser, Subcommand};
+use serde_json::{Value, json};
+
+use dawproject_plugin_info::cache::PluginCache;
+use dawproject_plugin_info::output::{OS, error_exit, print_json};
+use dawproject_plugin_info::serve::{self, METHOD_NOT_FOUND};
+use dawproject_plugin_info::vst2::fxp::{self, FxContent};
+use dawproject_plugin_info::vst2::{commands, resolve};
+use dawproject_plugin_info::vst3::Vst3;
+use dawproject_plugin_info::vst3::instance::PluginInstance;
+use dawproject_plugin_info::vst3::migrate;
+use dawproject_plugin_info::vst3::preset::{self as vst_preset, VstPreset};
+use dawproject_plugin_info::vst3::presets;
+use dawproject_plugin_info::vst3::state as vst3_state;
+use dawproject_plugin_info::vst3::stdio;
+use dawproject_plugin_info::{PluginFormat, PluginInfo, renoise_db, scan, state_diff};
+
+const CACHE_FILE: &str = "vst3info-cache.json";
+const SERVE_METHODS: [&str; 4] = ["inspect", "params", "applyState", "makePreset"];
+
+// Parameters the DawProject export can't map to Renoise devices
+const FORBIDDEN_PARAMETERS: [&str; 2] = ["midi", "cc "];
+
+// The legacy output always had this version, the Lua side relies on it
+const LEGACY_VERSION: &str = "1.0.0";
+
+const SUBCOMMANDS: [&str; 10] = [
+    "info",
+    "diff",
+    "scan",
+    "cache",
+    "renoise-db",
+    "vstpreset",
+    "migrate",
+    "serve",
+    "worker",
+    "help",
+];
+
+#[derive(Parser)]
+#[command(
+    name = "vst3info-tool",
+    version,
+    about = "Extracts information from VST3 plugins",
+    after_help = "Calling the tool with only a plugin path behaves like `info <PLUGIN>`."
+)]
+struct Cli {
+    #[command(subcommand)]
+    command: Command,
+
+    /// Folder of the persistent plugin info cache, used by `info` and `scan`
+    #[arg(long, global = true, value_name = "DIR")]
+    cache: Option<PathBuf>,
+
+    /// Inspects the plugins again, even if their cache entries are up to date
+    #[arg(long, global = true)]
+    refresh: bool,
+}
+
+#[derive(Args)]
+struct PluginArgs {
+    /// Plugin binary or .vst3 bundle
+    #[arg(required_unless_present = "document_id")]
+    plugin: Option<PathBuf>,
+
+    /// Renoise plugin database (CachedVSTs_<arch>.db) to look up --document-id in
+    #[arg(long, value_name = "DB", requires = "document_id")]
+    renoise_db: Option<PathBuf>,
+
+    /// DocumentIdentifier of the plugin in the Renoise database, instead of PLUGIN
+    #[arg(
+        long,
+        value_name = "ID",
+        requires = "renoise_db",
+        conflicts_with = "plugin"
+    )]
+    document_id: Option<String>,
+}
+
+#[derive(Args)]
+struct Vst2StateArgs {
+    /// Base64 encoded VST2 chunk, e.g. a Renoise ParameterChunk, or .fxp/.fxb data
+    #[arg(long, value_name = "BASE64", required_unless_present = "file")]
+    data: Option<String>,
+
+    /// File containing the raw chunk, or an .fxp/.fxb file
+    #[arg(long, value_name = "FILE", conflicts_with = "data")]
+    file: Option<PathBuf>,
+
+    /// Unique ID of the VST2 plugin, decimal or as four characters, required for raw chunks
+    #[arg(long, value_name = "ID")]
+    vst2_id: Option<String>,
+
+    /// Name of the VST2 plugin, needed for the parameter mapping of the plugin
+    #[arg(long, value_name = "NAME")]
+    vst2_name: Option<String>,
+
+    /// Version of the VST2 plugin, written into the .fxp/.fxb of raw chunks
+    #[arg(long, value_name = "VERSION", default_value_t = 1)]
+    vst2_version: i32,
+
+    /// Wraps a raw chunk as bank instead of program chunk
+    #[arg(long)]
+    bank: bool,
+
+    /// Number of VST2 parameters to map, defaults to the VST3 parameter count
+    #[arg(long, value_name = "COUNT")]
+    vst2_params: Option<i32>,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Plugin info and parameters
+    Info {
+        #[command(flatten)]
+        plugin: PluginArgs,
+    },
+    /// Loads two states one after another and lists the parameters that differ
+    Diff {
+        #[command(flatten)]
+        plugin: PluginArgs,
+
+        /// First state: a .vstpreset, an .fxp/.fxb of the VST2 version, a file with
+        /// a raw component state or base64 data
+        #[arg(long, value_name = "STATE")]
+        from: String,
+
+        /// Second state, like --from
+        #[arg(long, value_name = "STATE")]
+        to: String,
+    },
+    /// Inspects all plugins below the given folders, one JSON object per line
+    Scan {
+        /// Folders or plugins to scan, defaults to VST3_PATH and the standard VST3 folders
+        paths: Vec<PathBuf>,
+
+        /// Seconds after which a hanging plugin is given up
+        #[arg(long, value_name = "SECONDS", default_value_t = 30)]
+        timeout: u64,
+    },
+    /// Lists or prunes the entries of the plugin info cache given by --cache
+    Cache {
+        #[command(subcommand)]
+        action: CacheAction,
+    },
+    /// Reads the plugin database of Renoise
+    RenoiseDb {
+        #[command(subcommand)]
+        action: RenoiseDbAction,
+    },
+    /// Reads .vstpreset files
+    Vstpreset {
+        #[command(subcommand)]
+        action: VstPresetAction,
+    },
+    /// Loads a VST2 state into the VST3 version of the plugin, as hosts
+    /// migrating VST2 projects do, and maps the VST2 parameters to VST3 IDs
+    Migrate {
+        #[command(flatten)]
+        plugin: PluginArgs,
+
+        #[command(flatten)]
+        state: Vst2StateArgs,
+    },
+    /// Answers JSON-RPC requests on stdin/stdout, keeps the plugins loaded
+    Serve {
+        /// Seconds after which a hanging plugin is given up
+        #[arg(long, value_name = "SECONDS", default_value_t = 30)]
+        timeout: u64,
+    },
+    /// Hosts a single plugin for the serve mode
+    #[command(hide = true)]
+    Worker { binary: PathBuf },
+}
+
+#[derive(Subcommand)]
+enum RenoiseDbAction {
+    /// All plugins of the database with their DocumentIdentifier and file
+    List {
+        /// CachedVSTs_<arch>.db of Renoise
+        db: PathBuf,
+    },
+}
+
+#[derive(Subcommand)]
+enum VstPresetAction {
+    /// Header, chunk list and Info XML of a .vstpreset file, and the
+    /// problems found in its layout
+    Inspect {
+        /// The .vstpreset file
+        file: PathBuf,
+
+        /// Plugin binary or .vst3 bundle whose class ID the preset should have
+        #[arg(long, value_name = "PLUGIN", conflicts_with = "class_id")]
+        plugin: Option<PathBuf>,
+
+        /// Class ID the preset should have, as 32 hex digits
+        #[arg(long, value_name = "ID")]
+        class_id: Option<String>,
+
+        /// Writes the data of every chunk into the folder, e.g. for binary diffs
+        #[arg(long, value_name = "DIR")]
+        dump_chunks: Option<PathBuf>,
+    },
+    /// Lists the .vstpreset files of a plugin in the VST3 preset folders,
+    /// with name and category
+    Find {
+        /// Class ID of the plugin, as 32 hex digits
+        #[arg(required_unless_present = "plugin", conflicts_with = "plugin")]
+        class_id: Option<String>,
+
+        /// Plugin binary or .vst3 bundle, searches below <Vendor>/<Plugin> and in the bundle
+        #[arg(long, value_name = "PLUGIN")]
+        plugin: Option<PathBuf>,
+
+        /// Base64 encoded component state, e.g. a Renoise ParameterChunk, to find its preset
+        #[arg(long, value_name = "BASE64")]
+        state: Option<String>,
+
+        /// Further folder to search, can be given more than once
+        #[arg(long = "folder", value_name = "DIR")]
+        folders: Vec<PathBuf>,
+    },
+}
+
+#[derive(Subcommand)]
+enum CacheAction {
+    /// All cached plugins and whether their entries are still valid
+    List,
+    /// Removes entries of deleted or updated plugins
+    Prune,
+}
+
+// Keeps `vst3info-tool <plugin>`, as called by the Lua side, working
+fn legacy_args(mut args: Vec<String>) -> Vec<String> {
+    if args.len() >= 2 && !args[1].starts_with('-') && !SUBCOMMANDS.contains(&args[1].as_str()) {
+        args.insert(1, "info".to_string());
+    }
+    args
 }
 
 fn main() {
-    let args: Vec<String> = env::args().collect();
-
-    if args.len() < 2 {
-        error_exit("Plugin path required as first argument");
-    }
-
-    let path = PathBuf::from(&args[1]);
-    let path_string = path.display().to_string();
-
-    // Get the VST3 binary path
-    let binary_path = match get_vst3_binary_path(&path_string) {
-        Ok(path) => path,
-        Err(_) => error_exit("Path does not exist or is invalid"),
-    };
-
-    // Load and inspect the plugin
-    let info = match unsafe { suppress_stdout(|| inspect_vst3_plugin(&binary_path)) } {
-        Ok(info) => info,
-        Err(_) => error_exit("Failed to load or inspect plugin"),
-    };
-
-    let controller_info = match info.controller_info {
-        Some(value) => value,
-        None => error_exit("Controller not found"),
-    };
-
-    let component_info = match info.component_info {
-        Some(value) => value,
-        None => error_exit("Component not found"),
-    };
-
-    // Build output JSON
-    let mut output = object! {
-        name: info.name,
-        vendor: info.factory_info.vendor,
-        version: info.version,
-        countParameters: controller_info.parameter_count,
-        countInputs: component_info.bus_count_inputs,
-        countOutputs: component_info.bus_count_outputs,
-        parameters: [],
-        os: env::consts::OS
-    };
+    let cli = Cli::parse_from(legacy_args(env::args().collect()));
+    let mut cache = cli
+        .cache
+        .as_deref()
+        .map(|dir| PluginCache::open(dir, CACHE_FILE).unwrap_or_else(|e| error_exit(e)));
+
+    match cli.command {
+        Command::Info { plugin } => {
+            let binary = resolve_plugin(&plugin);
+            if !cli.refresh
+                && let Some(info) = cache.as_ref().and_then(|cache| cache.get(&binary))
+            {
+                print_json(&info);
+                return;
+            }
 
-    // Filter and add parameters
-    for (i, param) in controller_info.parameters.iter().enumerate() {
-        let title_lower = param.title.to_lowercase();
-        let forbidden = ["midi", "cc "];
-
-        if !title_lower.is_empty() && !forbidden.iter().any(|f| title_lower.contains(f)) {
-            output["parameters"].push(object! {
-                id: param.id,
-                index: i,
-                title: param.title.clone(),
-                stepCount: param.step_count
-            });
+            let output = inspect_output(&binary).unwrap_or_else(|e| error_exit(e));
+            if let Some(cache) = cache.as_mut() {
+                cache.insert(&binary, output.clone());
+                // the info itself is still fine, even if the cache can't be written
+                let _ = cache.save();
+            }
+            print_json(&output);
         }
-    }
-
-    println!("{}", json::stringify(output));
-}
-
-// Improved path handling
-fn get_vst3_binary_path(bundle_path: &str) -> Result<String, PluginError> {
-    let path = std::path::Path::new(bundle_path);
-
-    if path.is_file() {
-        return Ok(bundle_path.to_string());
-    }
-
-    if !bundle_path.ends_with(".vst3") {
-        return Err(PluginError::PathError("Invalid VST3 path".to_string()));
-    }
-
-    #[cfg(target_os = "macos")]
-    {
-        let contents_path = path.join("Contents").join("MacOS");
-        return find_binary_in_directory(&contents_path);
-    }
-
-    #[cfg(target_os = "windows")]
-    {
-        let contents_path = path.join("Contents");
-        let arch_path = if cfg!(target_arch = "x86_64") {
-            contents_path.join("x86_64-win")
-        } else {
-            contents_path.join("x86-win")
-        };
-        return find_vst3_binary_in_directory(&arch_path);
-    }
-
-    #[cfg(target_os = "linux")]
-    {
-        let contents_path = path.join("Contents");
-        let arch_path = if cfg!(target_arch = "x86_64") {
-            contents_path.join("x86_64-linux")
-        } else {
-            contents_path.join("x86-linux")
-        };
-        return find_so_binary_in_directory(&arch_path);
-    }
-
-    #[cfg(not(any(target_os = "macos", target_os = "windows", target_os = "linux")))]
-    {
-        Err(PluginError::PathError("Unsupported platform".to_string()))
-    }
-}
-
-#[cfg(target_os = "macos")]
-fn find_binary_in_directory(dir: &std::path::Path) -> Result<String, PluginError> {
-    let entries = std::fs::read_dir(dir)
-        .map_err(|_| PluginError::PathError("Directory not found".to_string()))?;
-
-    for entry in entries.flatten() {
-        let file_path = entry.path();
-        if file_path.is_file() {
-            if let Some(name) = file_path.file_name() {
-                if let Some(name_str) = name.to_str() {
-                    if !name_str.starts_with('.')
-                        && !name_str.ends_with(".plist")
-                        && !name_str.ends_with(".txt")
-                    {
-                        return Ok(file_path.to_string_lossy().to_string());
+        Command::Diff { plugin, from, to } => {
+            let from = state_diff::read_state(&from).unwrap_or_else(|e| error_exit(e));
+            let to = state_diff::read_state(&to).unwrap_or_else(|e| error_exit(e));
+            let binary = resolve_plugin(&plugin);
+
+            let (name, states) =
+                vst3_state::load_states(&binary, &[from, to]).unwrap_or_else(|e| error_exit(e));
+            let mut output = state_diff::diff_output(&name, &states[0], &states[1]);
+            output["os"] = json!(OS);
+            print_json(&output);
+        }
+        Command::Scan { paths, timeout } => {
+            scan::run_scan(
+                &Vst3,
+                paths,
+                Duration::from_secs(timeout),
+                cache.as_mut(),
+                cli.refresh,
+            );
+        }
+        Command::Cache { action } => {
+            let Some(cache) = cache.as_mut() else {
+                error_exit("Cache folder required, see --cache");
+            };
+            let output = match action {
+                CacheAction::List => {
+                    let entries = cache.list();
+                    json!({
+                        "count": entries.len(),
+                        "entries": entries,
+                        "os": OS
+                    })
+                }
+                CacheAction::Prune => {
+                    let removed = cache.prune();
+                    if let Err(e) = cache.save() {
+                        error_exit(e);
                     }
+                    json!({
+                        "removed": removed,
+                        "os": OS
+                    })
                 }
-            }
+            };
+            print_json(&output);
         }
-    }
-    Err(PluginError::PathError("No binary found".to_string()))
-}
-
-#[cfg(target_os = "windows")]
-fn find_vst3_binary_in_directory(dir: &std::path::Path) -> Result<String, PluginError> {
-    let entries = std::fs::read_dir(dir)
-        .map_err(|_| PluginError::PathError("Directory not found".to_string()))?;
-
-    for entry in entries.flatten() {
-        let file_path = entry.path();
-        if file_path.is_file() && file_path.extension() == Some(std::ffi::OsStr::new("vst3")) {
-            return Ok(file_path.to_string_lossy().to_string());
+        Command::RenoiseDb {
+            action: RenoiseDbAction::List { db },
+        } => {
+            let plugins = renoise_db::list_plugins(&db).unwrap_or_else(|e| error_exit(e));
+            print_json(&json!({
+                "count": plugins.len(),
+                "plugins": plugins,
+                "os": OS
+            }));
         }
-    }
-    Err(PluginError::PathError("No VST3 binary found".to_string()))
-}
-
-#[cfg(target_os = "linux")]
-fn find_so_binary_in_directory(dir: &std::path::Path) -> Result<String, PluginError> {
-    let entries = std::fs::read_dir(dir)
-        .map_err(|_| PluginError::PathError("Directory not found".to_string()))?;
+        Command::Vstpreset {
+            action:
+                VstPresetAction::Inspect {
+                    file,
+                    plugin,
+                    class_id,
+                    dump_chunks,
+                },
+        } => {
+            let mut preset = vst_preset::read_preset(&file).unwrap_or_else(|e| error_exit(e));
+            let expected_class_id = match (plugin, class_id) {
+                (Some(plugin), _) => Some(plugin_class_id(&plugin)),
+                (None, class_id) => class_id,
+            };
+            if let Some(expected_class_id) = &expected_class_id {
+                preset.check_class_id(expected_class_id);
+            }
 
-    for entry in entries.flatten() {
-        let file_path = entry.path();
-        if file_path.is_file() && file_path.extension() == Some(std::ffi::OsStr::new("so")) {
-            return Ok(file_path.to_string_lossy().to_string());
+            let mut output = json!({
+                "file": file.display().to_string(),
+                "size": preset.size(),
+                "version": preset.version,
+                "classId": preset.class_id,
+                "listOffset": preset.list_offset,
+                "chunks": preset.chunks,
+                "info": preset.info_xml().and_then(Result::ok)
+            });
+            if let Some(expected_class_id) = expected_class_id {
+                output["expectedClassId"] = json!(expected_class_id);
+            }
+            if let Some(dir) = dump_chunks {
+                output["dumpedFiles"] = json!(dump_chunks_to(&preset, &dir));
+            }
+            output["issues"] = json!(preset.issues);
+            output["valid"] = json!(preset.issues.is_empty());
+            output["os"] = json!(OS);
+            print_json(&output);
         }
-    }
-    Err(PluginError::PathError("No SO binary found".to_string()))
-}
-
-// Unified library loading
-unsafe fn load_vst3_library(path: &str) -> Result<Library, PluginError> {
-    Library::new(path).map_err(|e| PluginError::LoadError(format!("Failed to load library: {}", e)))
-}
-
-// Improved main inspection function with proper library management
-unsafe fn inspect_vst3_plugin(path: &str) -> Result<PluginInfo, PluginError> {
-    let lib = load_vst3_library(path)?;
-
-    let get_factory: Symbol<unsafe extern "C" fn() -> *mut IPluginFactory> = lib
-        .get(b"GetPluginFactory")
-        .map_err(|e| PluginError::LoadError(format!("GetPluginFactory not found: {}", e)))?;
-
-    let factory_ptr = get_factory();
-    if factory_ptr.is_null() {
-        return Err(PluginError::FactoryError(
-            "GetPluginFactory returned NULL".to_string(),
-        ));
-    }
-
-    let factory = ComPtr::<IPluginFactory>::from_raw(factory_ptr)
-        .ok_or_else(|| PluginError::FactoryError("Failed to wrap IPluginFactory".to_string()))?;
-
-    // Keep library alive for plugin lifetime
-    let _lib_guard = LibraryGuard::new(lib);
-
-    extract_plugin_info(&factory)
-}
-
-// Refactored plugin info extraction
-unsafe fn extract_plugin_info(factory: &ComPtr<IPluginFactory>) -> Result<PluginInfo, PluginError> {
-    let factory_info = get_factory_info(factory)?;
-    let classes = get_all_classes(factory)?;
-
-    let audio_class = classes
-        .iter()
-        .find(|c| c.category.contains("Audio Module Class"))
-        .ok_or_else(|| PluginError::ComponentError("No Audio Module class found".to_string()))?;
-
-    let (component_info, controller_info, name, version) = initialize_and_inspect_plugin(factory)?;
-
-    Ok(PluginInfo {
-        factory_info,
-        classes,
-        component_info,
-        controller_info,
-        name,
-        version,
-    })
-}
-
-unsafe fn initialize_and_inspect_plugin(
-    factory: &ComPtr<IPluginFactory>,
-) -> Result<
-    (
-        Option<ComponentInfo>,
-        Option<ControllerInfo>,
-        String,
-        String,
-    ),
-    PluginError,
-> {
-    let num_classes = factory.countClasses();
-    let mut component_ptr: *mut IComponent = ptr::null_mut();
-    let mut plugin_name = String::new();
-    let plugin_version = "1.0.0".to_string(); // Default version
-    let mut audio_class_id = None;
-
-    // Find and create the Audio Module component
-    for i in 0..num_classes {
-        let mut class_info = std::mem::zeroed();
-        if factory.getClassInfo(i, &mut class_info) == kResultOk {
-            let category = c_str_to_string(&class_info.category);
-
-            if category.contains("Audio Module Class") {
-                plugin_name = c_str_to_string(&class_info.name);
-                audio_class_id = Some(class_info.cid);
-
-                let result = factory.createInstance(
-                    class_info.cid.as_ptr() as *const i8,
-                    IComponent::IID.as_ptr() as *const i8,
-                    &mut component_ptr as *mut _ as *mut _,
-                );
-
-                if result == kResultOk && !component_ptr.is_null() {
-                    break;
+        Command::Vstpreset {
+            action:
+                VstPresetAction::Find {
+                    class_id,
+                    plugin,
+                    state,
+                    folders,
+                },
+        } => {
+            let state =
+                state.map(|state| commands::decode_state(&state).unwrap_or_else(|e| error_exit(e)));
+            let (class_id, mut preset_folders) = match (class_id, plugin) {
+                (_, Some(plugin)) => {
+                    let info = Vst3
+                        .resolve_binary(&plugin)
+                        .and_then(|binary| Vst3.inspect(&binary))
+                        .unwrap_or_else(|_| error_exit("Failed to load or inspect plugin"));
+                    let folders = presets::preset_folders(
+                        Some((info.vendor.as_str(), info.name.as_str())),
+                        Some(&plugin),
+                    );
+                    (info.id, folders)
                 }
+                (Some(class_id), None) => (class_id, presets::preset_folders(None, None)),
+                (None, None) => error_exit("Class ID or plugin required"),
+            };
+            preset_folders.extend(folders.into_iter().map(|path| presets::PresetFolder {
+                path,
+                factory: false,
+            }));
+
+            let found = presets::find_presets(&class_id, &preset_folders, state.as_deref());
+            let mut output = json!({
+                "classId": class_id,
+                "folders": preset_folders
+                    .iter()
+                    .map(|folder| folder.path.display().to_string())
+                    .collect::<Vec<_>>(),
+                "count": found.len(),
+                "presets": found
+            });
+            if state.is_some() {
+                output["matchedPreset"] = json!(
+                    found
+                        .iter()
+                        .find(|preset| preset.matches_state == Some(true))
+                        .map(|preset| &preset.name)
+                );
             }
+            output["os"] = json!(OS);
+            print_json(&output);
         }
-    }
-
-    let audio_class_id = audio_class_id
-        .ok_or_else(|| PluginError::ComponentError("No Audio Module class found".to_string()))?;
-
-    if component_ptr.is_null() {
-        return Err(PluginError::ComponentError(
-            "Failed to create component".to_string(),
-        ));
-    }
-
-    let component = ComPtr::<IComponent>::from_raw(component_ptr)
-        .ok_or_else(|| PluginError::ComponentError("Failed to wrap component".to_string()))?;
-
-    // Initialize component
-    let host_ptr = HostApplication::new();
-    let init_result = component.initialize(host_ptr);
-    if init_result != kResultOk {
-        return Err(PluginError::InitError(format!(
-            "Failed to initialize component: {:#x}",
-            init_result
-        )));
-    }
-
-    // Get or create controller
-    let controller = match get_or_create_controller(&component, factory, &audio_class_id)? {
-        Some(ctrl) => ctrl,
-        None => {
-            component.terminate();
-            return Err(PluginError::ComponentError(
-                "No controller available".to_string(),
-            ));
+        Command::Migrate { plugin, state } => {
+            let binary = resolve_plugin(&plugin);
+            print_json(&migrate_output(&binary, &state));
+        }
+        Command::Serve { timeout } => {
+            serve::run_serve(Duration::from_secs(timeout), worker_args);
+        }
+        Command::Worker { binary } => {
+            // The plugin stays loaded, every request is answered by it
+            let instance = stdio::suppress_output(|| PluginInstance::load(&binary));
+            serve::run_worker(instance.map_err(|e| e.to_string()).map(|instance| {
+                move |method: &str, params: &Value| {
+                    stdio::suppress_output(|| worker_request(&instance, method, params))
+                }
+            }));
         }
-    };
-
-    // Get component info
-    let component_info = get_component_info(&component)?;
-
-    // Connect components if they are separate
-    let _ = connect_component_and_controller(&component, &controller);
-
-    // Activate component
-    component.setActive(1);
-
-    // Get controller info
-    let controller_info = get_controller_info(&controller)?;
-
-    // Cleanup
-    component.terminate();
-    controller.terminate();
-
-    Ok((
-        Some(component_info),
-        Some(controller_info),
-        plugin_name,
-        plugin_version,
-    ))
-}
-
-unsafe fn get_or_create_controller(
-    component: &ComPtr<IComponent>,
-    factory: &ComPtr<IPluginFactory>,
-    _class_id: &TUID,
-) -> Result<Option<ComPtr<IEditController>>, PluginError> {
-    // First, try to cast component to IEditController (single component)
-    if let Some(controller) = component.cast::<IEditController>() {
-        return Ok(Some(controller));
-    }
-
-    // If not single component, try to get separate controller
-    let mut controller_cid = [0i8; 16];
-    let result = component.getControllerClassId(&mut controller_cid);
-
-    if result != kResultOk {
-        return Ok(None);
-    }
-
-    let mut controller_ptr: *mut IEditController = ptr::null_mut();
-    let create_result = factory.createInstance(
-        controller_cid.as_ptr(),
-        IEditController::IID.as_ptr() as *const i8,
-        &mut controller_ptr as *mut _ as *mut _,
-    );
-
-    if create_result != kResultOk || controller_ptr.is_null() {
-        return Ok(None);
-    }
-
-    let controller = ComPtr::<IEditController>::from_raw(controller_ptr)
-        .ok_or_else(|| PluginError::ComponentError("Failed to wrap controller".to_string()))?;
-
-    // Initialize controller
-    let init_result = controller.initialize(ptr::null_mut());
-    if init_result != kResultOk {
-        return Ok(None);
     }
-
-    Ok(Some(controller))
 }
 
-unsafe fn connect_component_and_controller(
-    component: &ComPtr<IComponent>,
-    controller: &ComPtr<IEditController>,
-) -> Result<(), PluginError> {
-    // Try to get connection points
-    let comp_cp = component.cast::<IConnectionPoint>();
-    let ctrl_cp = controller.cast::<IConnectionPoint>();
-
-    if let (Some(comp_cp), Some(ctrl_cp)) = (comp_cp, ctrl_cp) {
-        // Connect component to controller
-        let result1 = comp_cp.connect(ctrl_cp.as_ptr());
-        let result2 = ctrl_cp.connect(comp_cp.as_ptr());
-
-        if result1 == kResultOk && result2 == kResultOk {
-            Ok(())
-        } else {
-            Err(PluginError::ComponentError(format!(
-                "Connection failed: comp->ctrl={:#x}, ctrl->comp={:#x}",
-                result1, result2
-            )))
+fn resolve_plugin(args: &PluginArgs) -> PathBuf {
+    let path = match (&args.renoise_db, &args.document_id, &args.plugin) {
+        (Some(db), Some(document_id), _) => {
+            renoise_db::plugin_path(db, document_id).unwrap_or_else(|e| error_exit(e))
         }
-    } else {
-        Err(PluginError::ComponentError(
-            "No connection points available".to_string(),
-        ))
-    }
+        (_, _, Some(plugin)) => plugin.clone(),
+        _ => error_exit("Plugin required"),
+    };
+    Vst3.resolve_binary(&path)
+        .unwrap_or_else(|_| error_exit("Path does not exist or is invalid"))
 }
 
-unsafe fn get_component_info(component: &ComPtr<IComponent>) -> Result<ComponentInfo, PluginError> {
-    // Get bus information using the imported constants (cast to i32)
-    let audio_input_count = component.getBusCount(kAudio as i32, kInput as i32);
-    let audio_output_count = component.getBusCount(kAudio as i32, kOutput as i32);
-    let event_input_count = component.getBusCount(kEvent as i32, kInput as i32);
-    let event_output_count = component.getBusCount(kEvent as i32, kOutput as i32);
-
-    let mut audio_inputs = Vec::new();
-    let mut audio_outputs = Vec::new();
-    let mut event_inputs = Vec::new();
-    let mut event_outputs = Vec::new();
-
-    // Get audio input buses
-    for i in 0..audio_input_count {
-        if let Ok(bus_info) = get_bus_info(component, kAudio as i32, kInput as i32, i) {
-            audio_inputs.push(bus_info);
-        }
-    }
+fn plugin_class_id(plugin: &Path) -> String {
+    Vst3.resolve_binary(plugin)
+        .and_then(|binary| Vst3.inspect(&binary))
+        .map(|info| info.id)
+        .unwrap_or_else(|_| error_exit("Failed to load or inspect plugin"))
+}
 
-    // Get audio output buses
-    for i in 0..audio_output_count {
-        if let Ok(bus_info) = get_bus_info(component, kAudio as i32, kOutput as i32, i) {
-            audio_outputs.push(bus_info);
-        }
-    }
+// One file per chunk, named after its position and ID
+fn dump_chunks_to(preset: &VstPreset, dir: &Path) -> Vec<String> {
+    fs::create_dir_all(dir)
+        .unwrap_or_else(|e| error_exit(format!("Failed to create {}: {}", dir.display(), e)));
 
-    // Get event input buses
-    for i in 0..event_input_count {
-        if let Ok(bus_info) = get_bus_info(component, kEvent as i32, kInput as i32, i) {
-            event_inputs.push(bus_info);
+    let mut files = Vec::new();
+    for (index, chunk) in preset.chunks.iter().enumerate() {
+        let Some(data) = preset.chunk_data(chunk) else {
+            continue;
+        };
+        let id: String = chunk
+            .id
+            .chars()
+            .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+            .collect();
+        let path = dir.join(format!("{}-{}.bin", index, id));
+        fs::write(&path, data)
+            .unwrap_or_else(|e| error_exit(format!("Failed to write {}: {}", path.display(), e)));
+        files.push(path.display().to_string());
+    }
+    files
+}
+
+// The VST2 state as .fxp/.fxb data, with the unique ID and the parameter
+// count of the VST2 plugin, as far as known
+fn vst2_fx_data(args: &Vst2StateArgs, issues: &mut Vec<String>) -> (Vec<u8>, i32, Option<i32>) {
+    let state = match (&args.data, &args.file) {
+        (Some(data), _) => commands::decode_state(data).unwrap_or_else(|e| error_exit(e)),
+        (None, Some(file)) => fs::read(file)
+            .unwrap_or_else(|e| error_exit(format!("Failed to read {}: {}", file.display(), e))),
+        (None, None) => error_exit("State data or file required"),
+    };
+    let unique_id = args.vst2_id.as_deref().map(|id| {
+        resolve::parse_unique_id(id)
+            .unwrap_or_else(|| error_exit(format!("Invalid VST2 unique ID {}", id)))
+    });
+
+    if state.starts_with(b"CcnK") {
+        let fx_file = fxp::parse_fx_file(&state).unwrap_or_else(|e| error_exit(e));
+        if let Some(unique_id) = unique_id
+            && unique_id != fx_file.unique_id()
+        {
+            issues.push(format!(
+                "fxID {} does not match the VST2 unique ID {}",
+                fx_file.unique_id(),
+                unique_id
+            ));
         }
+        issues.extend(fx_file.issues.iter().cloned());
+        let count = match &fx_file.content {
+            FxContent::Program(program) if !program.is_chunk => Some(program.num_params),
+            _ => None,
+        };
+        return (state, fx_file.unique_id(), args.vst2_params.or(count));
     }
 
-    // Get event output buses
-    for i in 0..event_output_count {
-        if let Ok(bus_info) = get_bus_info(component, kEvent as i32, kOutput as i32, i) {
-            event_outputs.push(bus_info);
-        }
+    let Some(unique_id) = unique_id else {
+        error_exit("VST2 unique ID required for raw chunks, see --vst2-id");
+    };
+    let fx_data = if args.bank {
+        fxp::write_chunk_bank(unique_id, args.vst2_version, 1, 0, &state)
+    } else {
+        fxp::write_chunk_program(
+            unique_id,
+            args.vst2_version,
+            args.vst2_params.unwrap_or(0),
+            "",
+            &state,
+        )
+    };
+    (fx_data, unique_id, args.vst2_params)
+}
+
+fn migrate_output(binary: &Path, args: &Vst2StateArgs) -> Value {
+    let mut issues = Vec::new();
+    let (fx_data, unique_id, count_parameters) = vst2_fx_data(args, &mut issues);
+    let vst2_class_id = args
+        .vst2_name
+        .as_deref()
+        .map(|name| migrate::vst2_class_id(unique_id, name));
+    let wrapped_state = migrate::wrap_vst2_state(&fx_data);
+
+    let migration = migrate::migrate_state(
+        binary,
+        &wrapped_state,
+        vst2_class_id.as_deref(),
+        count_parameters,
+    )
+    .unwrap_or_else(|_| error_exit("Failed to load or inspect plugin"));
+
+    if migration.state.is_none() {
+        issues.push("The plugin did not load the wrapped VST2 state".to_string());
+    }
+    if let Some(vst2_class_id) = &vst2_class_id
+        && !migration.replaces.is_empty()
+        && !migration.replaces.contains(vst2_class_id)
+    {
+        issues.push(format!(
+            "The plugin declares to replace {}, not {}",
+            migration.replaces.join(", "),
+            vst2_class_id
+        ));
     }
 
-    // Check if component supports audio processing
-    let supports_processing = component.cast::<IAudioProcessor>().is_some();
-
-    Ok(ComponentInfo {
-        bus_count_inputs: audio_input_count + event_input_count,
-        bus_count_outputs: audio_output_count + event_output_count,
-        audio_inputs,
-        audio_outputs,
-        event_inputs,
-        event_outputs,
-        supports_processing,
+    json!({
+        "name": migration.name,
+        "classId": migration.class_id,
+        "vst2Id": unique_id,
+        "vst2ClassId": vst2_class_id,
+        "replaces": migration.replaces,
+        "wrappedState": BASE64_STANDARD.encode(&wrapped_state),
+        "state": migration.state.map(|state| BASE64_STANDARD.encode(state)),
+        "parameterMapping": migration.mapping,
+        "issues": issues,
+        "valid": issues.is_empty(),
+        "os": OS
     })
 }
 
-unsafe fn get_controller_info(
-    controller: &ComPtr<IEditController>,
-) -> Result<ControllerInfo, PluginError> {
-    let parameter_count = controller.getParameterCount();
-    let mut parameters = Vec::new();
-
-    // Get all parameter information
-    for i in 0..parameter_count {
-        let mut param_info = std::mem::zeroed();
-        if controller.getParameterInfo(i, &mut param_info) == kResultOk {
-            let current_value = controller.getParamNormalized(param_info.id);
-            let title = utf16_to_string_i16(&param_info.title);
-
-            parameters.push(ParameterInfo {
-                id: param_info.id,
-                title,
-                short_title: utf16_to_string_i16(&param_info.shortTitle),
-                units: utf16_to_string_i16(&param_info.units),
-                step_count: param_info.stepCount,
-                default_normalized_value: param_info.defaultNormalizedValue,
-                unit_id: param_info.unitId,
-                flags: param_info.flags,
-                current_value,
-            });
+fn worker_args(method: &str, params: &Value) -> Result<Vec<OsString>, serve::RpcError> {
+    if !SERVE_METHODS.contains(&method) {
+        return Err((METHOD_NOT_FOUND, format!("Unknown method {}", method)));
+    }
+    let binary = serve::plugin_binary(params, |plugin| {
+        Vst3.resolve_binary(Path::new(plugin))
+            .map_err(|_| "Path does not exist or is invalid".to_string())
+    })?;
+    Ok(vec![OsString::from("worker"), binary.into_os_string()])
+}
+
+// Requests of the serve mode, see serve::run_worker. States are loaded
+// like by diff, makePreset writes the current state as .vstpreset.
+fn worker_request(
+    instance: &PluginInstance,
+    method: &str,
+    params: &Value,
+) -> Result<Value, String> {
+    match method {
+        "inspect" => Ok(legacy_output(instance.info())),
+        "params" => {
+            let info = legacy_output(instance.info());
+            Ok(json!({
+                "name": info["name"],
+                "countParameters": info["countParameters"],
+                "parameters": info["parameters"],
+                "os": OS
+            }))
         }
-    }
-
-    Ok(ControllerInfo {
-        parameter_count,
-        parameters,
-    })
-}
-
-unsafe fn get_factory_info(factory: &ComPtr<IPluginFactory>) -> Result<FactoryInfo, PluginError> {
-    let mut factory_info = std::mem::zeroed();
-    let result = factory.getFactoryInfo(&mut factory_info);
-
-    if result != kResultOk {
-        return Err(PluginError::FactoryError(format!(
-            "Failed to get factory info: {}",
-            result
-        )));
-    }
-
-    Ok(FactoryInfo {
-        vendor: c_str_to_string(&factory_info.vendor),
-        url: c_str_to_string(&factory_info.url),
-        email: c_str_to_string(&factory_info.email),
-        flags: factory_info.flags,
-    })
-}
-
-unsafe fn get_all_classes(factory: &ComPtr<IPluginFactory>) -> Result<Vec<ClassInfo>, PluginError> {
-    let class_count = factory.countClasses();
-    let mut classes = Vec::new();
-
-    for i in 0..class_count {
-        let mut class_info = std::mem::zeroed();
-        if factory.getClassInfo(i, &mut class_info) == kResultOk {
-            classes.push(ClassInfo {
-                name: c_str_to_string(&class_info.name),
-                category: c_str_to_string(&class_info.category),
-                class_id: format!("{:?}", class_info.cid),
-                cardinality: class_info.cardinality,
+        "applyState" => {
+            let data = params["data"]
+                .as_str()
+                .ok_or_else(|| "State data required".to_string())?;
+            let state = commands::decode_state(data)?;
+            let (kind, applied) = instance.load_state(&state);
+            let parameters = instance.parameters();
+            Ok(json!({
+                "name": instance.name(),
+                "id": instance.class_id(),
+                "kind": kind,
+                "applied": applied,
+                "stateSize": state.len(),
+                "countParameters": parameters.len(),
+                "parameters": parameters,
+                "os": OS
+            }))
+        }
+        "makePreset" => {
+            let preset = instance
+                .preset()
+                .ok_or_else(|| "Failed to read the plugin state".to_string())?;
+            let mut output = json!({
+                "name": instance.name(),
+                "id": instance.class_id(),
+                "presetData": BASE64_STANDARD.encode(&preset)
             });
+            if let Some(path) = params["output"].as_str() {
+                fs::write(path, &preset).map_err(|e| format!("Failed to write {}: {}", path, e))?;
+                output["file"] = json!(path);
+            }
+            output["os"] = json!(OS);
+            Ok(output)
         }
+        _ => Err("Unknown method".to_string()),
     }
-
-    Ok(classes)
 }
 
-unsafe fn get_bus_info(
-    component: &ComPtr<IComponent>,
-    media_type: i32,
-    direction: i32,
-    index: i32,
-) -> Result<BusInfo, PluginError> {
-    let mut bus_info = std::mem::zeroed();
-    let result = component.getBusInfo(media_type, direction, index, &mut bus_info);
-
-    if result != kResultOk {
-        return Err(PluginError::ComponentError(format!(
-            "Failed to get bus info: {}",
-            result
-        )));
-    }
-
-    Ok(BusInfo {
-        name: utf16_to_string_i16(&bus_info.name),
-        bus_type: bus_info.busType,
-        flags: bus_info.flags as i32,
-        channel_count: bus_info.channelCount,
-    })
+// Loads and inspects the plugin for the info command
+fn inspect_output(binary: &Path) -> Result<Value, String> {
+    let info = Vst3
+        .inspect(binary)
+        .map_err(|_| "Failed to load or inspect plugin".to_string())?;
+    Ok(legacy_output(info))
 }
 
-// Helper functions
-unsafe fn c_str_to_string(ptr: &[i8]) -> String {
-    let bytes: Vec<u8> = ptr
+// The output the Lua side reads, parameters without a title and MIDI
+// mapping parameters are left out. The index counts the parameters whose
+// info could be read, not the controller's parameter indices.
+fn legacy_output(info: PluginInfo) -> Value {
+    let parameters: Vec<Value> = info
+        .parameters
         .iter()
-        .take_while(|&&c| c != 0)
-        .map(|&c| c as u8)
+        .enumerate()
+        .filter(|(_, param)| {
+            let title = param.name.to_lowercase();
+            !title.is_empty() && !FORBIDDEN_PARAMETERS.iter().any(|f| title.contains(f))
+        })
+        .map(|(index, param)| {
+            json!({
+                "id": param.id,
+                "index": index,
+                "title": param.name,
+                "stepCount": param.step_count
+            })
+        })
         .collect();
-    String::from_utf8_lossy(&bytes)
-        .trim_matches('\0')
-        .to_string()
-}
 
-unsafe fn utf16_to_string_i16(ptr: &[i16]) -> String {
-    let u16_slice: Vec<u16> = ptr
-        .iter()
-        .take_while(|&&c| c != 0)
-        .map(|&c| c as u16)
-        .collect();
-    String::from_utf16_lossy(&u16_slice)
+    json!({
+        "name": info.name,
+        "vendor": info.vendor,
+        "version": LEGACY_VERSION,
+        "countParameters": info.parameters.len(),
+        "countInputs": info.count_inputs,
+        "countOutputs": info.count_outputs,
+        "parameters": parameters,
+        "os": OS
+    })
 }
@@ -0,0 +1,701 @@
+// Output of the tool for the vst3-test-plugin of the workspace, packaged
+// as .vst3 bundle like an installed Linux plugin
+
+#![cfg(target_os = "linux")]
+
+use std::env;
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::sync::OnceLock;
+
+use base64::prelude::*;
+use serde_json::{Value, json};
+
+// The bundle folder inside a fresh temporary folder
+fn test_bundle() -> &'static Path {
+    static BUNDLE: OnceLock<PathBuf> = OnceLock::new();
+    BUNDLE.get_or_init(|| {
+        let status = Command::new(env::var("CARGO").unwrap_or_else(|_| "cargo".to_string()))
+            .args(["build", "-p", "vst3-test-plugin"])
+            .status()
+            .unwrap();
+        assert!(status.success(), "vst3-test-plugin builds");
+
+        let target_dir = Path::new(env!("CARGO_BIN_EXE_vst3info-tool"))
+            .parent()
+            .unwrap();
+        let dir = env::temp_dir().join(format!("vst3info-mock-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        let bundle = dir.join("TestPlugin.vst3");
+        let arch_dir = bundle
+            .join("Contents")
+            .join(format!("{}-linux", env::consts::ARCH));
+        fs::create_dir_all(&arch_dir).unwrap();
+        fs::copy(
+            target_dir.join("libvst3_test_plugin.so"),
+            arch_dir.join("TestPlugin.so"),
+        )
+        .unwrap();
+        bundle
+    })
+}
+
+fn binary() -> PathBuf {
+    test_bundle()
+        .join("Contents")
+        .join(format!("{}-linux", env::consts::ARCH))
+        .join("TestPlugin.so")
+}
+
+fn run(args: &[&str]) -> String {
+    let output = Command::new(env!("CARGO_BIN_EXE_vst3info-tool"))
+        .args(args)
+        .output()
+        .unwrap();
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    String::from_utf8(output.stdout).unwrap()
+}
+
+// Titled parameters without the MIDI CC proxies, the hidden one included
+fn expected_parameters() -> Value {
+    json!([
+        { "id": 100, "index": 0, "title": "Gain", "stepCount": 0 },
+        { "id": 101, "index": 1, "title": "Mode", "stepCount": 3 },
+        { "id": 102, "index": 2, "title": "Bypass", "stepCount": 1 },
+        { "id": 200, "index": 3, "title": "Internal", "stepCount": 0 }
+    ])
+}
+
+fn expected_info() -> Value {
+    json!({
+        "name": "Test Plugin",
+        "vendor": "DawProject",
+        "version": "1.0.0",
+        "countParameters": 7,
+        "countInputs": 2,
+        "countOutputs": 1,
+        "parameters": expected_parameters(),
+        "os": "linux"
+    })
+}
+
+#[test]
+fn info_of_the_bundle() {
+    let output = run(&[test_bundle().to_str().unwrap()]);
+
+    // Compared as text, the field order matters to the Lua side
+    assert_eq!(output, expected_info().to_string() + "\n");
+}
+
+#[test]
+fn info_of_the_binary() {
+    let output = run(&["info", binary().to_str().unwrap()]);
+
+    assert_eq!(output, expected_info().to_string() + "\n");
+}
+
+#[test]
+fn scan_finds_the_bundle() {
+    let folder = test_bundle().parent().unwrap();
+    let output = run(&["scan", folder.to_str().unwrap()]);
+    let lines: Vec<Value> = output
+        .lines()
+        .map(|line| serde_json::from_str(line).unwrap())
+        .collect();
+
+    assert_eq!(lines.len(), 2);
+    assert_eq!(
+        lines[0],
+        json!({
+            "path": test_bundle().to_str().unwrap(),
+            "ok": true,
+            "cached": false,
+            "info": expected_info()
+        })
+    );
+    assert_eq!(lines[1]["summary"]["ok"], 1);
+}
+
+#[test]
+fn scan_saves_the_cache_and_reports_save_errors() {
+    let folder = test_bundle().parent().unwrap().to_str().unwrap();
+    let cache = env::temp_dir().join(format!("vst3info-scan-cache-{}", std::process::id()));
+    let _ = fs::remove_dir_all(&cache);
+    let cache_arg = cache.to_str().unwrap();
+    let lines = |output: String| -> Vec<Value> {
+        output
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect()
+    };
+
+    let first = lines(run(&["--cache", cache_arg, "scan", folder]));
+    assert_eq!(first[0]["cached"], false);
+    assert_eq!(first[1]["summary"].get("cacheError"), None);
+    let second = lines(run(&["--cache", cache_arg, "scan", folder]));
+    assert_eq!(second[0]["cached"], true);
+
+    // The cache file is replaced by a folder
+    let cache_file = cache.join("vst3info-cache.json");
+    fs::remove_file(&cache_file).unwrap();
+    fs::create_dir_all(&cache_file).unwrap();
+    let failed = lines(run(&["--cache", cache_arg, "--refresh", "scan", folder]));
+    assert_eq!(failed[0]["ok"], true);
+    assert_eq!(
+        failed[1]["summary"]["cacheError"]
+            .as_str()
+            .unwrap()
+            .split(": ")
+            .next(),
+        Some(format!("Failed to write {}", cache_file.display()).as_str())
+    );
+}
+
+#[test]
+fn serve_answers_from_the_loaded_plugin() {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_vst3info-tool"))
+        .arg("serve")
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .unwrap();
+    let plugin = test_bundle().to_str().unwrap();
+    let mut stdin = child.stdin.take().unwrap();
+    for (id, method) in [(1, "inspect"), (2, "params")] {
+        let request = json!({
+            "jsonrpc": "2.0",
+            "id": id,
+            "method": method,
+            "params": { "plugin": plugin }
+        });
+        writeln!(stdin, "{}", request).unwrap();
+    }
+    writeln!(stdin, r#"{{"jsonrpc":"2.0","id":3,"method":"shutdown"}}"#).unwrap();
+    drop(stdin);
+
+    let output = child.wait_with_output().unwrap();
+    let lines: Vec<String> = String::from_utf8(output.stdout)
+        .unwrap()
+        .lines()
+        .map(String::from)
+        .collect();
+    assert_eq!(
+        lines,
+        [
+            json!({ "jsonrpc": "2.0", "id": 1, "result": expected_info() }).to_string(),
+            json!({
+                "jsonrpc": "2.0",
+                "id": 2,
+                "result": {
+                    "name": "Test Plugin",
+                    "countParameters": 7,
+                    "parameters": expected_parameters(),
+                    "os": "linux"
+                }
+            })
+            .to_string(),
+            json!({ "jsonrpc": "2.0", "id": 3, "result": true }).to_string(),
+        ]
+    );
+}
+
+// Sends the requests for the bundle to a server and returns the responses
+fn serve(requests: &[(&str, Value)]) -> Vec<Value> {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_vst3info-tool"))
+        .arg("serve")
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .unwrap();
+    let mut stdin = child.stdin.take().unwrap();
+    for (id, (method, params)) in requests.iter().enumerate() {
+        let mut params = params.clone();
+        params["plugin"] = json!(test_bundle().to_str().unwrap());
+        let request = json!({
+            "jsonrpc": "2.0",
+            "id": id + 1,
+            "method": method,
+            "params": params
+        });
+        writeln!(stdin, "{}", request).unwrap();
+    }
+    drop(stdin);
+
+    let output = child.wait_with_output().unwrap();
+    String::from_utf8(output.stdout)
+        .unwrap()
+        .lines()
+        .map(|line| serde_json::from_str(line).unwrap())
+        .collect()
+}
+
+#[test]
+fn serve_applies_states_and_makes_presets() {
+    let gain = vst3_state(&[(100, 0.75)]);
+    let file = test_bundle().with_file_name("Served.vstpreset");
+    let output = file.to_str().unwrap();
+
+    let responses = serve(&[
+        ("applyState", json!({ "data": gain })),
+        ("makePreset", json!({ "output": output })),
+        ("applyState", json!({ "data": vst3_state(&[]) })),
+        ("params", json!({})),
+    ]);
+
+    let applied = &responses[0]["result"];
+    assert_eq!(applied["kind"], "chunk");
+    assert_eq!(applied["applied"], true);
+    assert_eq!(applied["id"], "5DA1E3B27C0F4E219A3B0C44D17E5F60");
+    assert_eq!(applied["parameters"][0]["id"], 100);
+    assert_eq!(applied["parameters"][0]["value"], 0.75);
+
+    // The state of the loaded plugin as Comp chunk right after the header
+    let preset = &responses[1]["result"];
+    assert_eq!(preset["file"], output);
+    let preset_data = BASE64_STANDARD
+        .decode(preset["presetData"].as_str().unwrap())
+        .unwrap();
+    assert_eq!(fs::read(&file).unwrap(), preset_data);
+    let state = BASE64_STANDARD.decode(&gain).unwrap();
+    assert_eq!(&preset_data[48..48 + state.len()], state.as_slice());
+    let inspected: Value = serde_json::from_str(&run(&[
+        "vstpreset",
+        "inspect",
+        output,
+        "--plugin",
+        test_bundle().to_str().unwrap(),
+    ]))
+    .unwrap();
+    assert_eq!(inspected["valid"], true, "{}", inspected["issues"]);
+
+    assert_eq!(responses[2]["result"]["parameters"][0]["value"], 0.5);
+    assert_eq!(responses[3]["result"]["parameters"], expected_parameters());
+
+    // The written preset loads the value again
+    let responses = serve(&[("applyState", json!({ "data": preset["presetData"] }))]);
+    assert_eq!(responses[0]["result"]["kind"], "vstpreset");
+    assert_eq!(responses[0]["result"]["applied"], true);
+    assert_eq!(responses[0]["result"]["parameters"][0]["value"], 0.75);
+}
+
+#[test]
+fn serve_reports_states_the_plugin_refuses() {
+    let responses = serve(&[
+        (
+            "applyState",
+            json!({ "data": BASE64_STANDARD.encode(b"junk") }),
+        ),
+        ("applyState", json!({})),
+    ]);
+
+    assert_eq!(responses[0]["result"]["applied"], false);
+    assert_eq!(
+        responses[1]["error"],
+        json!({ "message": "State data required", "code": -32000 })
+    );
+}
+
+#[test]
+fn vstpreset_class_id_of_the_plugin() {
+    // Header, a Comp chunk of four bytes and the chunk list
+    let preset = |class_id: &str| {
+        let mut data = b"VST3".to_vec();
+        data.extend(1i32.to_le_bytes());
+        data.extend(class_id.as_bytes());
+        data.extend(52u64.to_le_bytes());
+        data.extend(b"data");
+        data.extend(b"List");
+        data.extend(1i32.to_le_bytes());
+        data.extend(b"Comp");
+        data.extend(48u64.to_le_bytes());
+        data.extend(4u64.to_le_bytes());
+        data
+    };
+    let file = test_bundle().with_file_name("Test.vstpreset");
+    let plugin = test_bundle().to_str().unwrap();
+
+    fs::write(&file, preset("5DA1E3B27C0F4E219A3B0C44D17E5F60")).unwrap();
+    let output: Value = serde_json::from_str(&run(&[
+        "vstpreset",
+        "inspect",
+        file.to_str().unwrap(),
+        "--plugin",
+        plugin,
+    ]))
+    .unwrap();
+    assert_eq!(
+        output["expectedClassId"],
+        "5DA1E3B27C0F4E219A3B0C44D17E5F60"
+    );
+    assert_eq!(output["valid"], true);
+
+    fs::write(&file, preset("0B9C2E6100000000000000000000000A")).unwrap();
+    let output: Value = serde_json::from_str(&run(&[
+        "vstpreset",
+        "inspect",
+        file.to_str().unwrap(),
+        "--plugin",
+        plugin,
+    ]))
+    .unwrap();
+    assert_eq!(output["valid"], false);
+}
+
+// VST2 unique ID and class ID of the "Test Chunks" plugin of the
+// vst2-test-plugin, whose VST3 version the test plugin is
+const VST2_ID: i32 = i32::from_be_bytes(*b"DPc2");
+const VST2_CLASS_ID: &str = "5653544450633274657374206368756E";
+
+// Program chunk of the vst2-test-plugin with the values Gain, Mode, Mix
+fn vst2_program_chunk(values: [f32; 3]) -> Vec<u8> {
+    let mut chunk = b"DPP2".to_vec();
+    for value in values {
+        chunk.extend(value.to_le_bytes());
+    }
+    chunk
+}
+
+// Component state of the test plugin with the given values by ID, the
+// others at their defaults
+fn vst3_state(values: &[(u32, f64)]) -> String {
+    let defaults = [
+        (100, 0.5),
+        (101, 0.0),
+        (102, 0.0),
+        (200, 0.25),
+        (1000, 0.0),
+        (1001, 0.0),
+        (300, 1.0),
+    ];
+    let mut state = b"DPT3".to_vec();
+    state.extend(7u32.to_le_bytes());
+    for (id, default) in defaults {
+        let value = values
+            .iter()
+            .find(|(value_id, _)| *value_id == id)
+            .map_or(default, |(_, value)| *value);
+        state.extend(id.to_le_bytes());
+        state.extend(value.to_le_bytes());
+    }
+    BASE64_STANDARD.encode(state)
+}
+
+fn migrate(args: &[&str]) -> Value {
+    let mut all_args = vec!["migrate", test_bundle().to_str().unwrap()];
+    all_args.extend(args);
+    serde_json::from_str(&run(&all_args)).unwrap()
+}
+
+#[test]
+fn migrate_remaps_the_vst2_parameters() {
+    let data = BASE64_STANDARD.encode(vst2_program_chunk([0.75, 0.5, 0.25]));
+    let output = migrate(&[
+        "--data",
+        &data,
+        "--vst2-id",
+        "DPc2",
+        "--vst2-name",
+        "Test Chunks",
+        "--vst2-params",
+        "3",
+    ]);
+
+    assert_eq!(output["name"], "Test Plugin");
+    assert_eq!(output["classId"], "5DA1E3B27C0F4E219A3B0C44D17E5F60");
+    assert_eq!(output["vst2Id"], VST2_ID);
+    assert_eq!(output["vst2ClassId"], VST2_CLASS_ID);
+    assert_eq!(output["replaces"], json!([VST2_CLASS_ID]));
+
+    // "VstW", header size, version and bypass, then the .fxp
+    let wrapped = BASE64_STANDARD
+        .decode(output["wrappedState"].as_str().unwrap())
+        .unwrap();
+    assert_eq!(wrapped[..16], *b"VstW\0\0\0\x08\0\0\0\x01\0\0\0\0");
+    assert_eq!(wrapped[16..20], *b"CcnK");
+    assert_eq!(wrapped[24..28], *b"FPCh");
+
+    assert_eq!(
+        output["state"],
+        vst3_state(&[(100, 0.75), (101, 0.5), (300, 0.25)])
+    );
+    assert_eq!(
+        output["parameterMapping"],
+        json!([
+            { "vst2Index": 0, "vst3Id": 100, "title": "Gain", "value": 0.75, "remapped": true },
+            { "vst2Index": 1, "vst3Id": 101, "title": "Mode", "value": 0.5, "remapped": true },
+            { "vst2Index": 2, "vst3Id": 300, "title": "", "value": 0.25, "remapped": true }
+        ])
+    );
+    assert_eq!(output["issues"], json!([]));
+    assert_eq!(output["valid"], true);
+}
+
+#[test]
+fn migrate_a_bank_chunk_by_index() {
+    // All three programs, the third one selected
+    let mut chunk = b"DPB2".to_vec();
+    chunk.extend(3u32.to_le_bytes());
+    chunk.extend(2u32.to_le_bytes());
+    for (name, values) in [
+        ("Init", [0.5, 0.0, 1.0]),
+        ("Bright", [0.75, 0.5, 0.25]),
+        ("Dark", [0.25, 1.0, 0.5]),
+    ] {
+        let mut name_bytes = [0u8; 24];
+        name_bytes[..name.len()].copy_from_slice(name.as_bytes());
+        chunk.extend(name_bytes);
+        for value in values {
+            chunk.extend((value as f32).to_le_bytes());
+        }
+    }
+    let file = test_bundle().with_file_name("bank.chunk");
+    fs::write(&file, chunk).unwrap();
+
+    let id = VST2_ID.to_string();
+    let output = migrate(&[
+        "--file",
+        file.to_str().unwrap(),
+        "--vst2-id",
+        &id,
+        "--bank",
+        "--vst2-params",
+        "3",
+    ]);
+
+    // Without the VST2 name the plugin can't be asked for the mapping
+    assert!(output["vst2ClassId"].is_null());
+    assert_eq!(
+        output["state"],
+        vst3_state(&[(100, 0.25), (101, 1.0), (300, 0.5)])
+    );
+    assert_eq!(
+        output["parameterMapping"],
+        json!([
+            { "vst2Index": 0, "vst3Id": 100, "title": "Gain", "value": 0.25, "remapped": false },
+            { "vst2Index": 1, "vst3Id": 101, "title": "Mode", "value": 1.0, "remapped": false },
+            { "vst2Index": 2, "vst3Id": 102, "title": "Bypass", "value": 0.0, "remapped": false }
+        ])
+    );
+    assert_eq!(output["valid"], true);
+}
+
+#[test]
+fn migrate_reports_a_state_of_another_plugin() {
+    // An .fxp of another VST2 plugin
+    let chunk = vst2_program_chunk([0.75, 0.5, 0.25]);
+    let mut fxp = b"CcnK".to_vec();
+    fxp.extend((52 + chunk.len() as i32).to_be_bytes());
+    fxp.extend(b"FPCh");
+    fxp.extend(1i32.to_be_bytes());
+    fxp.extend(b"Othr");
+    fxp.extend(1i32.to_be_bytes());
+    fxp.extend(3i32.to_be_bytes());
+    fxp.extend([0u8; 28]);
+    fxp.extend((chunk.len() as i32).to_be_bytes());
+    fxp.extend(chunk);
+    let file = test_bundle().with_file_name("Other.fxp");
+    fs::write(&file, fxp).unwrap();
+
+    let output = migrate(&[
+        "--file",
+        file.to_str().unwrap(),
+        "--vst2-name",
+        "Other",
+        "--vst2-params",
+        "1",
+    ]);
+
+    let other_class_id = "5653544F7468726F7468657200000000";
+    assert_eq!(output["vst2Id"], i32::from_be_bytes(*b"Othr"));
+    assert!(output["state"].is_null());
+    assert_eq!(
+        output["issues"],
+        json!([
+            "The plugin did not load the wrapped VST2 state",
+            format!(
+                "The plugin declares to replace {}, not {}",
+                VST2_CLASS_ID, other_class_id
+            )
+        ])
+    );
+    assert_eq!(output["valid"], false);
+}
+
+#[test]
+fn vstpreset_find_of_the_plugin() {
+    // Header, a Comp chunk of four bytes and the chunk list
+    let preset = |class_id: &str| {
+        let mut data = b"VST3".to_vec();
+        data.extend(1i32.to_le_bytes());
+        data.extend(class_id.as_bytes());
+        data.extend(52u64.to_le_bytes());
+        data.extend(b"data");
+        data.extend(b"List");
+        data.extend(1i32.to_le_bytes());
+        data.extend(b"Comp");
+        data.extend(48u64.to_le_bytes());
+        data.extend(4u64.to_le_bytes());
+        data
+    };
+    let class_id = "5DA1E3B27C0F4E219A3B0C44D17E5F60";
+
+    let factory_dir = test_bundle()
+        .join("Contents")
+        .join("Resources")
+        .join("Presets");
+    fs::create_dir_all(&factory_dir).unwrap();
+    fs::write(factory_dir.join("Init.vstpreset"), preset(class_id)).unwrap();
+
+    // The user presets below <Vendor>/<Plugin>, others are not searched
+    let root = test_bundle().with_file_name("presets");
+    let user_dir = root.join("DawProject").join("Test Plugin").join("Keys");
+    fs::create_dir_all(&user_dir).unwrap();
+    fs::write(user_dir.join("Soft.vstpreset"), preset(class_id)).unwrap();
+    fs::write(root.join("Loose.vstpreset"), preset(class_id)).unwrap();
+
+    let output = Command::new(env!("CARGO_BIN_EXE_vst3info-tool"))
+        .args([
+            "vstpreset",
+            "find",
+            "--plugin",
+            test_bundle().to_str().unwrap(),
+        ])
+        .env("VST3_PRESET_PATH", &root)
+        .env("HOME", root.join("home"))
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    let output: Value = serde_json::from_slice(&output.stdout).unwrap();
+
+    assert_eq!(output["classId"], class_id);
+    assert_eq!(
+        output["presets"],
+        json!([
+            {
+                "name": "Soft",
+                "path": user_dir.join("Soft.vstpreset").to_str().unwrap(),
+                "category": "Keys",
+                "factory": false
+            },
+            {
+                "name": "Init",
+                "path": factory_dir.join("Init.vstpreset").to_str().unwrap(),
+                "category": null,
+                "factory": true
+            }
+        ])
+    );
+    assert!(output.get("matchedPreset").is_none());
+}
+
+#[test]
+fn diff_of_a_vstpreset_and_a_vst2_fxp() {
+    // Preset with the default state of the plugin
+    let state = BASE64_STANDARD.decode(vst3_state(&[])).unwrap();
+    let mut preset = b"VST3".to_vec();
+    preset.extend(1i32.to_le_bytes());
+    preset.extend(b"5DA1E3B27C0F4E219A3B0C44D17E5F60");
+    preset.extend((48 + state.len() as u64).to_le_bytes());
+    preset.extend(&state);
+    preset.extend(b"List");
+    preset.extend(1i32.to_le_bytes());
+    preset.extend(b"Comp");
+    preset.extend(48u64.to_le_bytes());
+    preset.extend((state.len() as u64).to_le_bytes());
+    let preset_file = test_bundle().with_file_name("Default.vstpreset");
+    fs::write(&preset_file, preset).unwrap();
+
+    // Program chunk of the VST2 version as .fxp
+    let chunk = vst2_program_chunk([0.75, 0.5, 0.25]);
+    let mut fxp = b"CcnK".to_vec();
+    fxp.extend((52 + chunk.len() as i32).to_be_bytes());
+    fxp.extend(b"FPCh");
+    fxp.extend(1i32.to_be_bytes());
+    fxp.extend(b"DPc2");
+    fxp.extend(1i32.to_be_bytes());
+    fxp.extend(3i32.to_be_bytes());
+    fxp.extend([0u8; 28]);
+    fxp.extend((chunk.len() as i32).to_be_bytes());
+    fxp.extend(chunk);
+    let fxp = BASE64_STANDARD.encode(fxp);
+
+    let output: Value = serde_json::from_str(&run(&[
+        "diff",
+        test_bundle().to_str().unwrap(),
+        "--from",
+        preset_file.to_str().unwrap(),
+        "--to",
+        &fxp,
+    ]))
+    .unwrap();
+
+    assert_eq!(output["name"], "Test Plugin");
+    assert_eq!(
+        output["from"],
+        json!({ "kind": "vstpreset", "chunkSize": 92, "loaded": true })
+    );
+    // The .fxp is loaded behind the "VstW" header
+    assert_eq!(
+        output["to"],
+        json!({ "kind": "fxp", "chunkSize": 92, "loaded": true })
+    );
+    assert_eq!(
+        output["chunks"],
+        json!({ "sizeDifference": 0, "firstDifferentByte": 0, "identical": false })
+    );
+    assert_eq!(output["countParameters"], 7);
+    assert_eq!(
+        output["differences"],
+        json!([
+            {
+                "index": 0, "id": 100, "name": "Gain",
+                "fromDisplay": "-24.0 dB", "toDisplay": "-6.0 dB", "fromValue": 0.5, "toValue": 0.75
+            },
+            {
+                "index": 1, "id": 101, "name": "Mode",
+                "fromDisplay": "Clean", "toDisplay": "Hot", "fromValue": 0.0, "toValue": 0.5
+            },
+            {
+                "index": 6, "id": 300, "name": "",
+                "fromDisplay": "1.00", "toDisplay": "0.25", "fromValue": 1.0, "toValue": 0.25
+            }
+        ])
+    );
+    assert_eq!(output["os"], "linux");
+}
+
+#[test]
+fn diff_reports_why_the_plugin_failed_to_load() {
+    let not_a_plugin = test_bundle().with_file_name("NotAPlugin.so");
+    fs::write(&not_a_plugin, b"no shared library").unwrap();
+    let state = BASE64_STANDARD.encode(b"state");
+
+    let output = Command::new(env!("CARGO_BIN_EXE_vst3info-tool"))
+        .args([
+            "diff",
+            not_a_plugin.to_str().unwrap(),
+            "--from",
+            &state,
+            "--to",
+            &state,
+        ])
+        .output()
+        .unwrap();
+
+    assert!(!output.status.success());
+    let error: Value = serde_json::from_slice(&output.stderr).unwrap();
+    assert!(
+        error["error"]
+            .as_str()
+            .unwrap()
+            .starts_with("Load error: Failed to load library: "),
+        "{}",
+        error
+    );
+}
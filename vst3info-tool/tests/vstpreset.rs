@@ -0,0 +1,300 @@
+// vstpreset inspect against hand built preset files, in the layout of the
+// VST3 SDK and in the one the Lua side writes
+
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Output};
+
+use serde_json::{Value, json};
+
+const CLASS_ID: &str = "5DA1E3B27C0F4E219A3B0C44D17E5F60";
+const INFO_XML: &str = r#"<?xml version="1.0" encoding="utf-8"?><MetaInfo><Attr id="MediaType" value="VstPreset" type="string" flags="writeProtected"></Attr><Attr id="PlugInName" value="Test Plugin" type="string" flags="writeProtected"></Attr></MetaInfo>"#;
+
+// Chunk ID, offset and size, as listed in the chunk list
+type Entry<'a> = (&'a [u8; 4], u64, u64);
+
+fn header(class_id: &str, list_offset: u64) -> Vec<u8> {
+    let mut data = b"VST3".to_vec();
+    data.extend(1i32.to_le_bytes());
+    data.extend(class_id.as_bytes());
+    data.extend(list_offset.to_le_bytes());
+    data
+}
+
+fn chunk_list(entries: &[Entry]) -> Vec<u8> {
+    let mut data = b"List".to_vec();
+    data.extend((entries.len() as i32).to_le_bytes());
+    for (id, offset, size) in entries {
+        data.extend(*id);
+        data.extend(offset.to_le_bytes());
+        data.extend(size.to_le_bytes());
+    }
+    data
+}
+
+// Header, the chunks one after another and the chunk list
+fn sdk_preset(chunks: &[(&[u8; 4], &[u8])]) -> Vec<u8> {
+    let body_size: usize = chunks.iter().map(|(_, data)| data.len()).sum();
+    let mut data = header(CLASS_ID, 48 + body_size as u64);
+    let mut entries = Vec::new();
+    for (id, chunk) in chunks {
+        entries.push((*id, data.len() as u64, chunk.len() as u64));
+        data.extend(*chunk);
+    }
+    data.extend(chunk_list(&entries));
+    data
+}
+
+fn write_preset(name: &str, data: &[u8]) -> PathBuf {
+    let dir = env::temp_dir().join(format!("vst3info-vstpreset-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let path = dir.join(name);
+    fs::write(&path, data).unwrap();
+    path
+}
+
+fn run_tool(args: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_vst3info-tool"))
+        .args(args)
+        .output()
+        .unwrap()
+}
+
+fn inspect(path: &Path, args: &[&str]) -> Value {
+    let mut all_args = vec!["vstpreset", "inspect", path.to_str().unwrap()];
+    all_args.extend(args);
+    let output = run_tool(&all_args);
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    serde_json::from_slice(&output.stdout).unwrap()
+}
+
+#[test]
+fn sdk_layout_is_valid() {
+    let path = write_preset(
+        "sdk.vstpreset",
+        &sdk_preset(&[
+            (b"Comp", b"component"),
+            (b"Cont", b"ctrl"),
+            (b"Info", INFO_XML.as_bytes()),
+        ]),
+    );
+    let output = inspect(&path, &[]);
+
+    assert_eq!(output["version"], 1);
+    assert_eq!(output["classId"], CLASS_ID);
+    assert_eq!(output["listOffset"], 48 + 13 + INFO_XML.len());
+    assert_eq!(
+        output["chunks"],
+        json!([
+            { "id": "Comp", "known": true, "offset": 48, "size": 9 },
+            { "id": "Cont", "known": true, "offset": 57, "size": 4 },
+            { "id": "Info", "known": true, "offset": 61, "size": INFO_XML.len() }
+        ])
+    );
+    assert_eq!(
+        output["info"],
+        [
+            r#"<?xml version="1.0" encoding="utf-8"?>"#,
+            "<MetaInfo>",
+            r#"  <Attr id="MediaType" value="VstPreset" type="string" flags="writeProtected"/>"#,
+            r#"  <Attr id="PlugInName" value="Test Plugin" type="string" flags="writeProtected"/>"#,
+            "</MetaInfo>",
+        ]
+        .join("\n")
+    );
+    assert_eq!(output["issues"], json!([]));
+    assert_eq!(output["valid"], true);
+    assert_eq!(output["os"], env::consts::OS);
+}
+
+// As written by DeviceHelpers:convertBinaryToVst3Preset
+#[test]
+fn lua_layout_has_an_empty_controller_state() {
+    let state = b"parameter chunk";
+    let list_offset = 48 + state.len() as u64;
+    let mut data = header(CLASS_ID, list_offset);
+    data.extend(state);
+    data.extend(chunk_list(&[
+        (b"Comp", 48, state.len() as u64),
+        (b"Cont", list_offset, 0),
+    ]));
+    let output = inspect(&write_preset("lua.vstpreset", &data), &[]);
+
+    assert_eq!(output["issues"], json!(["Chunk Cont is empty"]));
+    assert_eq!(output["valid"], false);
+    assert!(output["info"].is_null());
+}
+
+#[test]
+fn broken_layout_lists_all_problems() {
+    let mut data = header("not a class id at all, but long!", 68);
+    data.extend(b"0123456789abcdefghij");
+    data.extend(chunk_list(&[
+        (b"Comp", 48, 12),
+        (b"Cont", 56, 12),
+        (b"Xtra", 40, 4),
+        (b"Prog", 60, 101),
+    ]));
+    data.extend(b"junk");
+    let output = inspect(&write_preset("broken.vstpreset", &data), &[]);
+
+    assert_eq!(
+        output["issues"],
+        json!([
+            "4 bytes of trailing data after the chunk list",
+            "Class ID \"not a class id at all, but long!\" is not 32 hex digits",
+            "Unknown chunk \"Xtra\"",
+            "Chunk Xtra at offset 40 starts inside the header",
+            "Chunk Prog ends at 161, beyond the end of the file (160 bytes)",
+            "Chunks Comp and Cont overlap",
+            "Chunks Cont and Prog overlap",
+        ])
+    );
+    assert_eq!(output["valid"], false);
+
+    // The chunk list itself is only taken from the list offset
+    let mut data = header(CLASS_ID, 48);
+    data.extend(chunk_list(&[(b"Comp", 48, 12)]));
+    let output = inspect(&write_preset("list.vstpreset", &data), &[]);
+    assert_eq!(
+        output["issues"],
+        json!(["Chunk Comp overlaps the chunk list"])
+    );
+}
+
+#[test]
+fn class_id_is_compared() {
+    let path = write_preset("class.vstpreset", &sdk_preset(&[(b"Comp", b"state")]));
+
+    let output = inspect(&path, &["--class-id", &CLASS_ID.to_lowercase()]);
+    assert_eq!(output["expectedClassId"], CLASS_ID.to_lowercase());
+    assert_eq!(output["valid"], true);
+
+    let other = "00000000000000000000000000000001";
+    let output = inspect(&path, &["--class-id", other]);
+    assert_eq!(
+        output["issues"],
+        json!([format!(
+            "Class ID {} does not match the plugin's {}",
+            CLASS_ID, other
+        )])
+    );
+}
+
+#[test]
+fn chunks_are_dumped() {
+    let path = write_preset(
+        "dump.vstpreset",
+        &sdk_preset(&[(b"Comp", b"component"), (b"Cont", b"ctrl")]),
+    );
+    let dir = path.with_file_name("dump");
+    let output = inspect(&path, &["--dump-chunks", dir.to_str().unwrap()]);
+
+    let files = [dir.join("0-Comp.bin"), dir.join("1-Cont.bin")];
+    assert_eq!(
+        output["dumpedFiles"],
+        json!(
+            files
+                .iter()
+                .map(|file| file.to_str().unwrap())
+                .collect::<Vec<_>>()
+        )
+    );
+    assert_eq!(fs::read(&files[0]).unwrap(), b"component");
+    assert_eq!(fs::read(&files[1]).unwrap(), b"ctrl");
+}
+
+#[test]
+fn other_files_are_rejected() {
+    let path = write_preset(
+        "other.fxp",
+        b"CcnK and some more bytes, but no vstpreset header",
+    );
+    let output = run_tool(&["vstpreset", "inspect", path.to_str().unwrap()]);
+
+    assert_eq!(output.status.code(), Some(1));
+    assert_eq!(
+        String::from_utf8(output.stderr).unwrap(),
+        "{\"error\":\"Not a .vstpreset file\"}\n"
+    );
+}
+
+fn info_xml(attributes: &[(&str, &str)]) -> String {
+    let mut xml = r#"<?xml version="1.0" encoding="utf-8"?><MetaInfo>"#.to_string();
+    for (id, value) in attributes {
+        xml += &format!(
+            r#"<Attr id="{}" value="{}" type="string" flags="writeProtected"/>"#,
+            id, value
+        );
+    }
+    xml + "</MetaInfo>"
+}
+
+#[test]
+fn presets_are_found_by_class_id() {
+    let root = env::temp_dir().join(format!("vst3info-presets-{}", std::process::id()));
+    let _ = fs::remove_dir_all(&root);
+    let plugin_dir = root.join("DawProject").join("Test Plugin");
+    fs::create_dir_all(plugin_dir.join("Pads")).unwrap();
+    fs::create_dir_all(root.join("Other").join("Plugin")).unwrap();
+
+    let lead_info = info_xml(&[("Name", "Bright Lead"), ("MusicalCategory", "Lead")]);
+    fs::write(
+        plugin_dir.join("Lead.vstpreset"),
+        sdk_preset(&[(b"Comp", b"state-a"), (b"Info", lead_info.as_bytes())]),
+    )
+    .unwrap();
+    fs::write(
+        plugin_dir.join("Pads").join("Warm.vstpreset"),
+        sdk_preset(&[(b"Comp", b"state-b")]),
+    )
+    .unwrap();
+    let mut other = header("00000000000000000000000000000001", 53);
+    other.extend(b"other");
+    other.extend(chunk_list(&[(b"Comp", 48, 5)]));
+    fs::write(
+        root.join("Other").join("Plugin").join("Other.vstpreset"),
+        other,
+    )
+    .unwrap();
+    fs::write(plugin_dir.join("readme.txt"), "no preset").unwrap();
+
+    // HOME is moved away, so only the given folder has presets
+    let output = Command::new(env!("CARGO_BIN_EXE_vst3info-tool"))
+        .args(["vstpreset", "find", CLASS_ID, "--state", "c3RhdGUtYg=="])
+        .env("VST3_PRESET_PATH", &root)
+        .env("HOME", root.join("home"))
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    let output: Value = serde_json::from_slice(&output.stdout).unwrap();
+
+    assert_eq!(output["classId"], CLASS_ID);
+    assert_eq!(output["folders"][0], root.to_str().unwrap());
+    assert_eq!(output["count"], 2);
+    assert_eq!(
+        output["presets"],
+        json!([
+            {
+                "name": "Bright Lead",
+                "path": plugin_dir.join("Lead.vstpreset").to_str().unwrap(),
+                "category": "Lead",
+                "matchesState": false,
+                "factory": false
+            },
+            {
+                "name": "Warm",
+                "path": plugin_dir.join("Pads").join("Warm.vstpreset").to_str().unwrap(),
+                "category": "DawProject/Test Plugin/Pads",
+                "matchesState": true,
+                "factory": false
+            }
+        ])
+    );
+    assert_eq!(output["matchedPreset"], "Warm");
+}